@@ -0,0 +1,10 @@
+fn main() {
+    println!("cargo:rerun-if-changed=proto");
+
+    // Only the gRPC feature needs protoc and the generated service
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("No bundled protoc for this platform"));
+        tonic_build::compile_protos("proto/updater.proto").expect("Failed to compile proto/updater.proto");
+    }
+}
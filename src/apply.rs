@@ -0,0 +1,230 @@
+//! Converging the installed packages to a declared manifest.
+
+use anyhow::{bail, Result};
+use colored::*;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::error::Error;
+use crate::fs_snapshot;
+use crate::lock;
+use crate::manifest::{self, Manifest};
+use crate::package::{self, InstallOptions, InstallReason, Package};
+use crate::prompt;
+use crate::version;
+use crate::verify;
+
+/// One way the installed packages differ from a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Change {
+    /// Declared but not installed
+    Missing { name: String, version: Option<String> },
+    /// Installed, but the active version isn't the declared one
+    Mismatched { name: String, active: Option<String>, wanted: String },
+    /// Installed explicitly without being declared; only removed with `--prune`
+    Extra { name: String },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Missing { name, version: Some(version) } => write!(f, "install {} {}", name, version),
+            Change::Missing { name, version: None } => write!(f, "install {}", name),
+            Change::Mismatched { name, active, wanted } => {
+                write!(f, "switch {} from {} to {}", name, active.as_deref().unwrap_or("none"), wanted)
+            }
+            Change::Extra { name } => write!(f, "remove {}", name),
+        }
+    }
+}
+
+/// The installed version of a package that satisfies a declared version or
+/// constraint, preferring the active one and then the newest.
+pub(crate) fn installed_match(package: &Package, wanted: &str) -> Option<String> {
+    if !version::is_constraint(wanted) {
+        return package.versions.contains_key(wanted).then(|| wanted.to_string());
+    }
+    let req = version::parse_constraint(wanted).ok()?;
+    let matches = |v: &String| version::parse(v).is_some_and(|parsed| req.matches(&parsed));
+    if let Some(active) = package.active_version.as_ref().filter(|active| matches(active)) {
+        return Some(active.clone());
+    }
+    package.versions.keys().filter(|v| matches(v)).max_by(|a, b| version::compare(a, b)).cloned()
+}
+
+/// Everything that differs between the installed packages and `manifest`,
+/// declared packages first.
+pub fn plan(manifest: &Manifest, packages: &HashMap<String, Package>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (name, entry) in &manifest.packages {
+        let spec = entry.spec();
+        let package = match packages.get(name) {
+            Some(package) => package,
+            None => {
+                changes.push(Change::Missing { name: name.clone(), version: spec.version.clone() });
+                continue;
+            }
+        };
+        let wanted = match &spec.version {
+            Some(wanted) => wanted,
+            None => continue,
+        };
+        let satisfied = installed_match(package, wanted);
+        if satisfied.is_none() || satisfied != package.active_version {
+            changes.push(Change::Mismatched { name: name.clone(), active: package.active_version.clone(), wanted: wanted.clone() });
+        }
+    }
+
+    // Dependencies come and go with the packages that need them
+    let mut extra: Vec<&String> = packages
+        .iter()
+        .filter(|(name, package)| package.reason == InstallReason::Explicit && !manifest.packages.contains_key(*name))
+        .map(|(name, _)| name)
+        .collect();
+    extra.sort();
+    changes.extend(extra.into_iter().map(|name| Change::Extra { name: name.clone() }));
+    changes
+}
+
+fn make_change(manifest: &Manifest, change: &Change) -> Result<()> {
+    match change {
+        Change::Missing { name, .. } => package::install(name, manifest.packages[name].spec().install_options()).map(|_| ()),
+        Change::Mismatched { name, wanted, .. } => {
+            let installed = package::load_packages()?.get(name).and_then(|package| installed_match(package, wanted));
+            let version = match installed {
+                Some(version) => version,
+                None => package::install(name, manifest.packages[name].spec().install_options())?,
+            };
+            package::switch(name, &version)
+        }
+        Change::Extra { name } => package::remove_package(name, None, false, false, false),
+    }
+}
+
+/// Install, switch and, with `prune`, remove packages until the installed
+/// ones are exactly the ones declared in the manifest at `path`. With
+/// `locked`, install the versions in its lockfile and verify them against it.
+pub fn apply(path: &Path, prune: bool, dry_run: bool, locked: bool) -> Result<()> {
+    let mut manifest = manifest::load_manifest(path)?;
+    let lockfile = if locked {
+        let lockfile = lock::load(&lock::lock_path(path))?;
+        manifest = lock::pin(&manifest, &lockfile)?;
+        // Downloads are compared with the lockfile before they are installed, not after
+        for (name, locked) in &lockfile.packages {
+            if let Some(sha256) = &locked.sha256 {
+                verify::expect_artifact(name, sha256);
+            }
+        }
+        Some(lockfile)
+    } else {
+        None
+    };
+    let mut changes = plan(&manifest, &package::load_packages()?);
+    let kept = changes.iter().filter(|change| matches!(change, Change::Extra { .. })).count();
+    if !prune {
+        changes.retain(|change| !matches!(change, Change::Extra { .. }));
+    }
+    if !prune && kept > 0 {
+        info!("{} {}", kept.to_string().yellow().bold(), "undeclared packages are kept, pass --prune to remove them".yellow());
+    }
+    if changes.is_empty() {
+        println!("{} {}", "Already matches".green(), path.display().to_string().yellow().bold());
+        return lockfile.as_ref().map_or(Ok(()), lock::verify);
+    }
+    for change in &changes {
+        println!("  {}", change);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !prompt::confirm(&format!("Apply {} change(s) from {}?", changes.len(), path.display()))? {
+        return Err(Error::Aborted(format!("Aborted applying {}", path.display())).into());
+    }
+    // The changes were confirmed as a whole
+    prompt::set_assume_yes();
+    fs_snapshot::before(&format!("apply {}", path.display()))?;
+    // Mismatched packages that have the wanted version already only switch to it
+    let packages = package::load_packages()?;
+    let installs: Vec<(&str, InstallOptions)> = changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::Missing { name, .. } => Some(name),
+            Change::Mismatched { name, wanted, .. } => packages.get(name).and_then(|p| installed_match(p, wanted)).is_none().then_some(name),
+            Change::Extra { .. } => None,
+        })
+        .map(|name| (name.as_str(), manifest.packages[name].spec().install_options()))
+        .collect();
+    package::prefetch(installs.iter().filter_map(|(name, options)| {
+        let backend = options.backend.as_deref().or(options.url.as_ref().map(|_| "url"))?;
+        let version = options.version.as_deref().filter(|v| !version::is_constraint(v));
+        Some((backend, *name, version, options.url.as_ref()))
+    }))?;
+
+    let mut failed = 0;
+    for change in &changes {
+        if let Err(e) = make_change(&manifest, change) {
+            warn!("{} {}: {:#}", "Failed to".red(), change, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} change(s) failed, rerun `updater apply {}` to retry", failed, changes.len(), path.display());
+    }
+    info!("{} {}", "Applied".green(), path.display().to_string().yellow().bold());
+    lockfile.as_ref().map_or(Ok(()), lock::verify)
+}
+
+/// Print what `apply --prune` would change, grouped into missing, extra and
+/// mismatched packages, and fail when there is anything so CI can gate on drift.
+pub fn diff(path: &Path, json: bool) -> Result<()> {
+    let manifest = manifest::load_manifest(path)?;
+    let changes = plan(&manifest, &package::load_packages()?);
+
+    if json {
+        let document = json!({
+            "manifest": path,
+            "in_sync": changes.is_empty(),
+            "changes": changes,
+        });
+        println!("{}", serde_json::to_string_pretty(&document)?);
+    } else if changes.is_empty() {
+        println!("{} {}", "Installed packages match".green(), path.display().to_string().yellow().bold());
+    } else {
+        let missing: Vec<&Change> = changes.iter().filter(|change| matches!(change, Change::Missing { .. })).collect();
+        let extra: Vec<&Change> = changes.iter().filter(|change| matches!(change, Change::Extra { .. })).collect();
+        let mismatched: Vec<&Change> = changes.iter().filter(|change| matches!(change, Change::Mismatched { .. })).collect();
+        if !missing.is_empty() {
+            println!("{}", "Missing:".bold());
+            for change in missing {
+                if let Change::Missing { name, version } = change {
+                    println!("  {} {} {}", "+".green(), name.green().bold(), version.as_deref().unwrap_or_default().cyan());
+                }
+            }
+        }
+        if !extra.is_empty() {
+            println!("{}", "Extra:".bold());
+            for change in extra {
+                if let Change::Extra { name } = change {
+                    println!("  {} {}", "-".red(), name.red().bold());
+                }
+            }
+        }
+        if !mismatched.is_empty() {
+            println!("{}", "Mismatched:".bold());
+            for change in mismatched {
+                if let Change::Mismatched { name, active, wanted } = change {
+                    println!("  {} {} {} -> {}", "~".yellow(), name.yellow().bold(), active.as_deref().unwrap_or("none").yellow(), wanted.cyan());
+                }
+            }
+        }
+    }
+
+    if !changes.is_empty() {
+        return Err(Error::Drift(changes.len()).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use crate::backends::{collect_binaries, run_command};
+use crate::landlock;
+
+/// Unpack an archive into the install dir, or install a bare binary as `bin/<name>`.
+pub fn extract(artifact: &Path, name: &str, install_dir: &Path) -> Result<()> {
+    let file_name = artifact.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+    let is_tar = [".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tar.zst"]
+        .iter()
+        .any(|ext| file_name.ends_with(ext));
+
+    if is_tar || file_name.ends_with(".zip") {
+        let mut cmd = if is_tar {
+            let mut cmd = Command::new("tar");
+            cmd.arg("-xf").arg(artifact).arg("-C").arg(install_dir);
+            cmd
+        } else {
+            let mut cmd = Command::new("unzip");
+            cmd.args(["-q", "-o"]).arg(artifact).arg("-d").arg(install_dir);
+            cmd
+        };
+        // Members pointing outside the install dir fail instead of landing there
+        let _ruleset = landlock::confine_writes(&mut cmd, install_dir)?;
+        run_command(&mut cmd).context("Failed to extract archive")?;
+    } else {
+        let bin_dir = install_dir.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+        let binary = bin_dir.join(name.rsplit('/').next().unwrap_or(name));
+        fs::copy(artifact, &binary)?;
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755))?;
+        if file_name.to_lowercase().ends_with(".appimage") {
+            extract_appimage_desktop_files(&binary, install_dir);
+        }
+        return Ok(());
+    }
+
+    // Archives often keep their binaries at the top level instead of in bin/
+    if collect_binaries(install_dir).is_empty() {
+        link_top_level_binaries(install_dir)?;
+    }
+    Ok(())
+}
+
+/// Unpack an AppImage's desktop entries and icons into `squashfs-root/`, so
+/// they can be installed for the desktop. Not every AppImage supports this,
+/// and the app works without them, so failures are only logged.
+fn extract_appimage_desktop_files(appimage: &Path, install_dir: &Path) {
+    for pattern in ["*.desktop", ".DirIcon", "*.png", "*.svg", "usr/share/icons/*"] {
+        let status = Command::new(appimage)
+            .args(["--appimage-extract", pattern])
+            .current_dir(install_dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            debug!("Could not extract {} from {}", pattern, appimage.display());
+            return;
+        }
+    }
+}
+
+fn link_top_level_binaries(install_dir: &Path) -> Result<()> {
+    let bin_dir = install_dir.join("bin");
+    let mut dirs = vec![install_dir.to_path_buf()];
+    // Also look one level down for archives wrapping everything in `tool-1.2.3/`,
+    // skipping hidden dirs such as the kept artifact
+    for entry in fs::read_dir(install_dir)?.flatten() {
+        if entry.path().is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+            dirs.push(entry.path());
+        }
+    }
+
+    for dir in dirs {
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            let executable = fs::metadata(&path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            if executable {
+                fs::create_dir_all(&bin_dir)?;
+                std::os::unix::fs::symlink(&path, bin_dir.join(entry.file_name())).ok();
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::config;
+use crate::error::Error;
+use crate::net::{self, SendRetrying};
+use crate::package::{self, Package};
+use crate::version;
+
+const OSV_QUERY_URL: &str = "https://api.osv.dev/v1/query";
+
+/// A known vulnerability affecting an installed package version.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub package: String,
+    pub version: String,
+    pub backend: String,
+    pub id: String,
+    /// Other identifiers for the same issue, usually CVEs
+    pub aliases: Vec<String>,
+    pub summary: String,
+    /// Versions that fix it, oldest first
+    pub fixed: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct QueryResponse {
+    #[serde(default)]
+    vulns: Vec<Vulnerability>,
+}
+
+#[derive(Deserialize)]
+struct Vulnerability {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    affected: Vec<Affected>,
+}
+
+#[derive(Deserialize)]
+struct Affected {
+    package: Option<AffectedPackage>,
+    #[serde(default)]
+    ranges: Vec<Range>,
+}
+
+#[derive(Deserialize)]
+struct AffectedPackage {
+    name: String,
+    ecosystem: String,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    #[serde(default)]
+    events: Vec<RangeEvent>,
+}
+
+#[derive(Deserialize)]
+struct RangeEvent {
+    fixed: Option<String>,
+}
+
+/// The OSV ecosystem a backend's packages are published in, for backends
+/// whose package names OSV knows.
+pub fn osv_ecosystem(pm_name: &str) -> Option<&'static str> {
+    match pm_name {
+        "cargo" => Some("crates.io"),
+        "pip" => Some("PyPI"),
+        "npm" => Some("npm"),
+        "gem" => Some("RubyGems"),
+        "go" => Some("Go"),
+        _ => None,
+    }
+}
+
+/// Look up every installed version in OSV. Versions from backends without an
+/// OSV ecosystem, and `latest` installs whose release is unknown, are skipped
+/// and returned by name.
+pub fn find_vulnerabilities(packages: &[Package]) -> Result<(Vec<Finding>, Vec<String>)> {
+    let mut findings = Vec::new();
+    let mut skipped = Vec::new();
+    let url = config::mirror_url("osv", OSV_QUERY_URL)?;
+
+    for package in packages {
+        let mut versions: Vec<&String> = package.versions.keys().collect();
+        versions.sort();
+        for recorded in versions {
+            let info = &package.versions[recorded];
+            let backend = info.package_manager.clone().unwrap_or_default();
+            let version = match info.exact_version(recorded) {
+                Some(version) => version,
+                None => {
+                    skipped.push(format!("{} {} (release unknown)", package.name, recorded));
+                    continue;
+                }
+            };
+            let ecosystem = match osv_ecosystem(&backend) {
+                Some(ecosystem) => ecosystem,
+                None => {
+                    skipped.push(format!("{} {} (not in OSV)", package.name, version));
+                    continue;
+                }
+            };
+
+            let response: QueryResponse = net::client()
+                .post(&url)
+                .json(&json!({ "version": version, "package": { "name": package.name, "ecosystem": ecosystem } }))
+                .send_retrying()
+                .and_then(|response| response.error_for_status())
+                .and_then(|response| response.json())
+                .with_context(|| format!("Failed to query OSV for {} {}", package.name, version))?;
+
+            for vuln in response.vulns {
+                let mut fixed: Vec<String> = vuln
+                    .affected
+                    .iter()
+                    .filter(|affected| {
+                        affected.package.as_ref().is_none_or(|p| p.name == package.name && p.ecosystem == ecosystem)
+                    })
+                    .flat_map(|affected| &affected.ranges)
+                    .flat_map(|range| &range.events)
+                    .filter_map(|event| event.fixed.clone())
+                    .collect();
+                version::sort(&mut fixed);
+                fixed.dedup();
+                findings.push(Finding {
+                    package: package.name.clone(),
+                    version: version.to_string(),
+                    backend: backend.clone(),
+                    id: vuln.id,
+                    aliases: vuln.aliases,
+                    summary: vuln.summary,
+                    fixed,
+                });
+            }
+        }
+    }
+    Ok((findings, skipped))
+}
+
+/// Report vulnerable installs, failing when there are any so scripts and CI
+/// can gate on it.
+pub fn audit() -> Result<()> {
+    let packages = package::installed(false, false)?;
+    let (findings, skipped) = find_vulnerabilities(&packages)?;
+
+    if !skipped.is_empty() {
+        warn!("{} {}", "Not checked:".yellow(), skipped.join(", "));
+    }
+    if findings.is_empty() {
+        println!("{}", "No known vulnerabilities found".green());
+        return Ok(());
+    }
+
+    for finding in &findings {
+        let aliases = if finding.aliases.is_empty() { String::new() } else { format!(" ({})", finding.aliases.join(", ")) };
+        println!(
+            "{} {} [{}] {}{}",
+            finding.package.yellow().bold(),
+            finding.version.cyan(),
+            finding.backend,
+            finding.id.red().bold(),
+            aliases
+        );
+        if !finding.summary.is_empty() {
+            println!("  {}", finding.summary);
+        }
+        // Suggest the oldest fix that is newer than what is installed
+        match finding.fixed.iter().find(|fixed| version::compare(fixed, &finding.version).is_gt()) {
+            Some(fixed) => println!(
+                "  {} {} {}",
+                "Fixed in".green(),
+                fixed.cyan(),
+                format!("(updater install {} --version {})", finding.package, fixed).dimmed()
+            ),
+            None => println!("  {}", "No fixed version published".yellow()),
+        }
+    }
+
+    let vulnerable: std::collections::BTreeSet<_> = findings.iter().map(|f| (&f.package, &f.version)).collect();
+    info!("{} {}", vulnerable.len().to_string().yellow().bold(), "vulnerable installs".yellow());
+    Err(Error::Vulnerable(findings.len()).into())
+}
@@ -0,0 +1,167 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use dialoguer::Password;
+use reqwest::blocking::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+use tracing::{debug, info, warn};
+
+/// Keyring service name all updater credentials are stored under.
+const KEYRING_SERVICE: &str = "updater";
+
+/// Hosts with stored credentials. Tokens themselves live in the system keyring,
+/// this file only remembers which hosts have one and how to present it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AuthHosts {
+    hosts: BTreeMap<String, HostAuth>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct HostAuth {
+    /// Send the token as the password of HTTP basic auth for this user
+    /// instead of as a bearer token
+    username: Option<String>,
+}
+
+fn get_hosts_path() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    let updater_dir = data_dir.join("updater");
+    fs::create_dir_all(&updater_dir).expect("Failed to create data directory");
+    updater_dir.join("auth.json")
+}
+
+fn load_hosts() -> Result<AuthHosts> {
+    let path = get_hosts_path();
+    if !path.exists() {
+        return Ok(AuthHosts::default());
+    }
+    let data = fs::read_to_string(&path).context("Failed to read auth hosts")?;
+    serde_json::from_str(&data).context("Failed to parse auth hosts")
+}
+
+fn save_hosts(hosts: &AuthHosts) -> Result<()> {
+    let data = serde_json::to_string_pretty(hosts).context("Failed to serialize auth hosts")?;
+    fs::write(get_hosts_path(), data).context("Failed to write auth hosts")?;
+    Ok(())
+}
+
+fn keyring_entry(host: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, host).context("Failed to access the system keyring")
+}
+
+/// Accept either a bare host or a URL and return the host.
+fn normalize_host(source: &str) -> Result<String> {
+    if source.contains("://") {
+        let url = reqwest::Url::parse(source).with_context(|| format!("Invalid URL: {}", source))?;
+        return url.host_str().map(str::to_string).with_context(|| format!("URL has no host: {}", source));
+    }
+    Ok(source.trim_end_matches('/').to_string())
+}
+
+pub fn login(source: &str, token: Option<String>, username: Option<String>) -> Result<()> {
+    let host = normalize_host(source)?;
+    let token = match token {
+        Some(token) => token,
+        // Typed tokens aren't echoed; piped ones are read as a line
+        None if io::stdin().is_terminal() => Password::new()
+            .with_prompt(format!("Token for {}", host))
+            .allow_empty_password(true)
+            .interact()
+            .context("Failed to read token")?
+            .trim()
+            .to_string(),
+        None => {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim().to_string()
+        }
+    };
+    if token.is_empty() {
+        bail!("No token given");
+    }
+
+    keyring_entry(&host)?.set_password(&token).context("Failed to store token in the system keyring")?;
+    let mut hosts = load_hosts()?;
+    hosts.hosts.insert(host.clone(), HostAuth { username });
+    save_hosts(&hosts)?;
+
+    info!("{} {}", "Stored credentials for".green(), host.yellow().bold());
+    Ok(())
+}
+
+pub fn logout(source: &str) -> Result<()> {
+    let host = normalize_host(source)?;
+    let mut hosts = load_hosts()?;
+    if hosts.hosts.remove(&host).is_none() {
+        warn!("{} {}", "No credentials stored for".yellow(), host);
+        return Ok(());
+    }
+
+    // The keyring entry may already be gone if it was removed by hand
+    if let Ok(entry) = keyring_entry(&host) {
+        entry.delete_password().ok();
+    }
+    save_hosts(&hosts)?;
+    info!("{} {}", "Removed credentials for".green(), host.yellow().bold());
+    Ok(())
+}
+
+pub fn status() -> Result<()> {
+    let hosts = load_hosts()?;
+    if hosts.hosts.is_empty() {
+        println!("{}", "No credentials stored".yellow());
+        return Ok(());
+    }
+
+    for (host, auth) in &hosts.hosts {
+        let stored = keyring_entry(host).and_then(|e| Ok(e.get_password()?)).is_ok();
+        let kind = match &auth.username {
+            Some(user) => format!("basic auth as {}", user),
+            None => "bearer token".to_string(),
+        };
+        let state = if stored { "ok".green() } else { "missing from keyring".red() };
+        println!("{} ({}) {}", host.green().bold(), kind, state);
+    }
+    Ok(())
+}
+
+fn url_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string))
+}
+
+/// Whether credentials are stored for the host of `url`.
+pub fn has_credentials(url: &str) -> bool {
+    url_host(url).is_some_and(|host| load_hosts().is_ok_and(|hosts| hosts.hosts.contains_key(&host)))
+}
+
+/// Attach stored credentials for the request's host, if any. Only over
+/// https, so tokens never cross the network in the clear.
+pub fn authorize(request: RequestBuilder, url: &str) -> RequestBuilder {
+    let host = match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "https" => match parsed.host_str() {
+            Some(host) => host.to_string(),
+            None => return request,
+        },
+        Ok(_) => {
+            debug!("Not sending credentials over plain {}", url);
+            return request;
+        }
+        Err(_) => return request,
+    };
+    let auth = match load_hosts().ok().and_then(|mut hosts| hosts.hosts.remove(&host)) {
+        Some(auth) => auth,
+        None => return request,
+    };
+    let token = match keyring_entry(&host).and_then(|e| Ok(e.get_password()?)) {
+        Ok(token) => token,
+        Err(_) => return request,
+    };
+
+    match auth.username {
+        Some(user) => request.basic_auth(user, Some(token)),
+        None => request.bearer_auth(token),
+    }
+}
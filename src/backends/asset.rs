@@ -0,0 +1,214 @@
+//! Picking the asset to install among everything a release ships: builds for
+//! other systems and architectures, checksums, signatures, source tarballs
+//! and package formats updater can't unpack.
+//!
+//! Every candidate is scored on how well its file name fits the running
+//! platform, and the best one wins. A package can name its asset instead with
+//! a pattern, a substring of the file name or a glob with `*`.
+
+use crate::compat::{self, Libc};
+
+/// Architecture names as `std::env::consts::ARCH` spells them, with the other
+/// spellings release assets use. Ones spelled as a prefix of another come last.
+const ARCHES: &[(&str, &[&str])] = &[
+    ("x86_64", &["x86_64", "x86-64", "amd64", "x64", "linux64"]),
+    ("aarch64", &["aarch64", "arm64", "armv8"]),
+    ("riscv64", &["riscv64", "riscv64gc"]),
+    ("powerpc64", &["ppc64le", "powerpc64le", "ppc64"]),
+    ("s390x", &["s390x"]),
+    ("arm", &["armv7", "armv7l", "armhf", "armv6", "arm"]),
+    ("x86", &["i686", "i586", "i386", "x86", "386", "linux32"]),
+];
+
+/// Systems other than Linux that show up in asset names.
+const OTHER_OSES: &[&str] = &[
+    "windows", "win", "win32", "win64", "darwin", "macos", "mac", "osx", "apple", "freebsd", "netbsd", "openbsd", "dragonfly", "illumos", "solaris", "android", "ios",
+];
+
+/// Suffixes of files that accompany a release rather than being installable.
+const NOT_INSTALLABLE: &[&str] = &[
+    ".sha256", ".sha256sum", ".sha512", ".sha512sum", ".sha1", ".md5", ".sig", ".asc", ".minisig", ".pem", ".cert", ".crt", ".sbom", ".spdx", ".json", ".jsonl", ".txt", ".md",
+    ".deb", ".rpm", ".apk", ".pkg", ".dmg", ".msi", ".exe", ".snap", ".flatpak", ".whl", ".nupkg", ".vsix",
+];
+
+const ARCHIVES: &[&str] = &[".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.zst", ".tar.bz2", ".tar", ".zip"];
+
+/// The platform assets are picked for, with the target architecture of `--arch`.
+#[derive(Debug, Clone, Copy)]
+pub struct Platform {
+    /// Architecture as `std::env::consts::ARCH` spells it
+    pub arch: &'static str,
+    pub libc: Libc,
+    /// Rank musl and static builds above glibc ones, on musl or an old glibc
+    pub prefer_static: bool,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        Platform { arch: compat::target_arch(), libc: compat::libc(), prefer_static: compat::prefers_static() }
+    }
+}
+
+/// `arch` as `std::env::consts::ARCH` spells it, for any of its spellings.
+pub fn canonical_arch(arch: &str) -> Option<&'static str> {
+    let arch = arch.to_lowercase();
+    ARCHES
+        .iter()
+        .find(|(canonical, spellings)| *canonical == arch || spellings.contains(&arch.as_str()))
+        .map(|(canonical, _)| *canonical)
+}
+
+/// Whether `word` appears in `name` on its own, not as part of a longer word.
+fn has_word(name: &str, word: &str) -> bool {
+    name.match_indices(word).any(|(start, _)| {
+        let before = name[..start].chars().next_back();
+        let after = name[start + word.len()..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_alphanumeric()) && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+/// The architecture an asset is built for, as far as its name tells.
+fn arch_of(name: &str) -> Option<&'static str> {
+    ARCHES
+        .iter()
+        .find(|(_, spellings)| spellings.iter().any(|spelling| has_word(name, spelling)))
+        .map(|(arch, _)| *arch)
+}
+
+/// Whether `name` matches `pattern`: a glob when it has `*`, a substring otherwise.
+pub fn matches_pattern(name: &str, pattern: &str) -> bool {
+    if !pattern.contains('*') {
+        return name.contains(pattern);
+    }
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || name.len() < first.len() + last.len() || !name.ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Whether `name` ends in a file extension, not just a dot in a version number.
+fn has_extension(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((_, extension)) => extension.len() <= 8 && extension.chars().all(|c| c.is_ascii_alphanumeric()) && !extension.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// How well the asset named `name` fits `platform`, or `None` when it can't
+/// be installed there at all.
+fn score(name: &str, platform: &Platform) -> Option<i32> {
+    let name = name.to_lowercase();
+    if NOT_INSTALLABLE.iter().any(|suffix| name.ends_with(suffix))
+        || ["checksums", "sha256sums", "sha512sums", "source", "src"].iter().any(|word| has_word(&name, word))
+    {
+        return None;
+    }
+    let mut score = 0;
+
+    if has_word(&name, "linux") {
+        score += 4;
+    } else if OTHER_OSES.iter().any(|os| has_word(&name, os)) {
+        return None;
+    }
+
+    match arch_of(&name) {
+        Some(arch) if arch == platform.arch => score += 8,
+        Some(_) => return None,
+        // Scripts and universal binaries name no architecture
+        None => {}
+    }
+
+    let musl = name.contains("musl");
+    let gnu = name.contains("gnu") || name.contains("glibc");
+    let static_build = has_word(&name, "static");
+    match platform.libc {
+        Libc::Musl if gnu && !musl => return None,
+        _ if (musl || static_build) && platform.prefer_static => score += 3,
+        _ if musl || static_build => score += 1,
+        Libc::Gnu if gnu => score += 2,
+        _ => {}
+    }
+
+    if ARCHIVES.iter().any(|suffix| name.ends_with(suffix)) {
+        score += 3;
+    } else if name.ends_with(".appimage") {
+        score += 1;
+    } else if has_extension(&name) {
+        score -= 2;
+    } else {
+        // A bare binary
+        score += 2;
+    }
+    Some(score)
+}
+
+/// The best asset among `names` for `platform`, or the first one matching
+/// `pattern` when given.
+pub fn select<'a>(names: &[&'a str], platform: &Platform, pattern: Option<&str>) -> Option<&'a str> {
+    if let Some(pattern) = pattern {
+        return names.iter().find(|name| matches_pattern(name, pattern)).copied();
+    }
+    names
+        .iter()
+        .filter_map(|name| score(name, platform).map(|score| (score, *name)))
+        // Among equally good assets the shortest name is usually the plain build
+        .max_by(|(a, a_name), (b, b_name)| a.cmp(b).then(b_name.len().cmp(&a_name.len())))
+        .map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GNU: Platform = Platform { arch: "x86_64", libc: Libc::Gnu, prefer_static: false };
+    const MUSL: Platform = Platform { arch: "x86_64", libc: Libc::Musl, prefer_static: true };
+
+    const RELEASE: &[&str] = &[
+        "tool-1.0-checksums.txt",
+        "tool-1.0-x86_64-unknown-linux-gnu.tar.gz.sha256",
+        "tool-1.0-x86_64-apple-darwin.tar.gz",
+        "tool-1.0-aarch64-unknown-linux-gnu.tar.gz",
+        "tool-1.0-x86_64-unknown-linux-gnu.tar.gz",
+        "tool-1.0-x86_64-unknown-linux-musl.tar.gz",
+        "tool_1.0_amd64.deb",
+        "tool-1.0-src.tar.gz",
+    ];
+
+    #[test]
+    fn picks_the_build_for_the_platform() {
+        assert_eq!(select(RELEASE, &GNU, None), Some("tool-1.0-x86_64-unknown-linux-gnu.tar.gz"));
+        assert_eq!(select(RELEASE, &MUSL, None), Some("tool-1.0-x86_64-unknown-linux-musl.tar.gz"));
+        let gnu_only = ["tool-linux-amd64-gnu.tar.gz", "tool-darwin-amd64.tar.gz"];
+        assert_eq!(select(&gnu_only, &MUSL, None), None);
+        assert_eq!(select(&["tool-linux-arm64", "tool-windows-amd64.exe"], &GNU, None), None);
+        // A bare binary beats a package format updater can't unpack
+        assert_eq!(select(&["tool_1.0_amd64.deb", "tool-linux-x64"], &GNU, None), Some("tool-linux-x64"));
+    }
+
+    #[test]
+    fn patterns_override_scoring() {
+        assert_eq!(select(RELEASE, &GNU, Some("aarch64")), Some("tool-1.0-aarch64-unknown-linux-gnu.tar.gz"));
+        assert_eq!(select(RELEASE, &GNU, Some("*darwin*.tar.gz")), Some("tool-1.0-x86_64-apple-darwin.tar.gz"));
+        assert_eq!(select(RELEASE, &GNU, Some("*.zip")), None);
+        assert!(matches_pattern("tool-1.0-linux.tar.gz", "tool-*-linux*"));
+        assert!(!matches_pattern("tool-1.0-linux.tar.gz", "tool-*-darwin*"));
+    }
+
+    #[test]
+    fn arch_spellings_are_canonical() {
+        assert_eq!(canonical_arch("amd64"), Some("x86_64"));
+        assert_eq!(canonical_arch("ARM64"), Some("aarch64"));
+        assert_eq!(canonical_arch("armhf"), Some("arm"));
+        assert_eq!(canonical_arch("i386"), Some("x86"));
+        assert_eq!(canonical_arch("mips"), None);
+    }
+}
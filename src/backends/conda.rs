@@ -0,0 +1,164 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{run_command, write_wrapper, VersionSource};
+use crate::config;
+use crate::system::{PackageManager, SearchResult};
+
+const CHANNEL: &str = "conda-forge";
+const CHANNEL_URL: &str = "https://conda.anaconda.org/conda-forge";
+
+/// Creates a dedicated conda environment per package version and wraps its binaries.
+pub struct CondaBackend {
+    /// mamba when available, since it resolves environments much faster
+    tool: &'static str,
+    /// Binaries to expose; defaults to the ones owned by the package itself
+    expose: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CondaMeta {
+    name: String,
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CondaSearchEntry {
+    version: String,
+}
+
+impl CondaBackend {
+    pub fn new(expose: Vec<String>) -> Self {
+        let tool = if which::which("mamba").is_ok() { "mamba" } else { "conda" };
+        CondaBackend { tool, expose }
+    }
+
+    fn env_dir(install_dir: &Path) -> PathBuf {
+        install_dir.join("env")
+    }
+
+    /// Binaries that belong to `name` according to the environment's conda-meta records.
+    fn package_binaries(env_dir: &Path, name: &str) -> Result<Vec<String>> {
+        let mut binaries = Vec::new();
+        for entry in fs::read_dir(env_dir.join("conda-meta"))?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let meta: CondaMeta = match serde_json::from_str(&fs::read_to_string(&path)?) {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+            if meta.name == name {
+                binaries.extend(meta.files.iter().filter_map(|file| file.strip_prefix("bin/")).map(str::to_string));
+            }
+        }
+        Ok(binaries)
+    }
+
+    fn expose_binaries(&self, name: &str, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let env_dir = Self::env_dir(install_dir);
+        let binaries = if self.expose.is_empty() {
+            Self::package_binaries(&env_dir, name)?
+        } else {
+            self.expose.clone()
+        };
+
+        let bin_dir = install_dir.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+
+        let mut bin_paths = Vec::new();
+        for binary in binaries {
+            let target = env_dir.join("bin").join(&binary);
+            if !target.exists() {
+                bail!("Binary {} not found in conda environment for {}", binary, name);
+            }
+            let wrapper = bin_dir.join(&binary);
+            write_wrapper(&wrapper, &target, &[("CONDA_PREFIX", &env_dir)])?;
+            bin_paths.push(wrapper);
+        }
+        Ok(bin_paths)
+    }
+}
+
+impl PackageManager for CondaBackend {
+    fn get_name(&self) -> &str {
+        "conda"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        let spec = match version {
+            None | Some("latest") => name.to_string(),
+            Some(v) => format!("{}={}", name, v),
+        };
+
+        run_command(
+            Command::new(self.tool)
+                .args(["create", "--yes", "--quiet", "--channel", &channel()?, "--prefix"])
+                .arg(Self::env_dir(install_dir))
+                .arg(&spec),
+        )
+        .with_context(|| format!("Failed to create conda environment for {}", spec))?;
+
+        self.expose_binaries(name, install_dir)
+    }
+
+    fn update(&self, name: &str, _version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        run_command(
+            Command::new(self.tool)
+                .args(["update", "--yes", "--quiet", "--channel", &channel()?, "--prefix"])
+                .arg(Self::env_dir(install_dir))
+                .arg(name),
+        )
+        .with_context(|| format!("Failed to update conda environment for {}", name))?;
+        Ok(())
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let output = run_command(
+            Command::new(self.tool)
+                .args(["search", "--json", "--channel", &channel()?])
+                .arg(format!("*{}*", query)),
+        )?;
+        let results: HashMap<String, Vec<CondaSearchEntry>> =
+            serde_json::from_str(&output).context("Failed to parse conda search results")?;
+
+        Ok(results
+            .into_iter()
+            .map(|(name, entries)| SearchResult {
+                description: match entries.last() {
+                    Some(entry) => format!("{} (latest {})", CHANNEL, entry.version),
+                    None => CHANNEL.to_string(),
+                },
+                name,
+            })
+            .collect())
+    }
+}
+
+impl VersionSource for CondaBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        let output = run_command(Command::new(self.tool).args(["search", "--json", "--channel", &channel()?]).arg(name))?;
+        let mut results: HashMap<String, Vec<CondaSearchEntry>> =
+            serde_json::from_str(&output).context("Failed to parse conda search results")?;
+
+        let mut versions: Vec<String> = results.remove(name).unwrap_or_default().into_iter().map(|entry| entry.version).collect();
+        versions.dedup();
+        Ok(versions)
+    }
+}
+
+/// The channel to pass to conda, as a URL when a mirror is configured for it.
+fn channel() -> Result<String> {
+    let url = config::mirror_url("conda", CHANNEL_URL)?;
+    Ok(if url == CHANNEL_URL { CHANNEL.to_string() } else { url })
+}
+
+pub fn is_available() -> bool {
+    which::which("mamba").is_ok() || which::which("conda").is_ok()
+}
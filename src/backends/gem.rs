@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{run_command, write_wrapper, Metadata, VersionSource};
+use crate::config;
+use crate::net::{self, SendRetrying};
+use crate::sandbox;
+use crate::system::{PackageManager, SearchResult};
+
+const RUBYGEMS_URL: &str = "https://rubygems.org/";
+
+/// Installs gems into an isolated GEM_HOME per version and wraps their executables.
+pub struct GemBackend;
+
+#[derive(Deserialize)]
+struct GemSearchResult {
+    name: String,
+    version: String,
+    info: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GemVersion {
+    number: String,
+    #[serde(default)]
+    licenses: Vec<String>,
+}
+
+impl GemBackend {
+    fn gem_install(&self, name: &str, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let gem_home = install_dir.join("gems");
+        let gem_bin_dir = gem_home.join("bin");
+
+        let mut cmd = Command::new("gem");
+        cmd.arg("install")
+            .arg(name)
+            .arg("--no-document")
+            .arg("--install-dir")
+            .arg(&gem_home)
+            .arg("--bindir")
+            .arg(&gem_bin_dir);
+        if let Some(v) = version.filter(|v| *v != "latest") {
+            cmd.arg("--version").arg(v);
+        }
+        let source = rubygems_url("")?;
+        if source != RUBYGEMS_URL {
+            cmd.arg("--clear-sources").arg("--source").arg(source);
+        }
+        // Gems run their own extension builds while installing
+        run_command(&mut sandbox::confine(self.get_name(), &cmd, &[install_dir])?)
+            .with_context(|| format!("gem install failed for {}", name))?;
+
+        // Gem executables only work with GEM_HOME pointing at their install dir
+        let bin_dir = install_dir.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+        let mut bin_paths = Vec::new();
+        for entry in fs::read_dir(&gem_bin_dir)?.flatten() {
+            let wrapper = bin_dir.join(entry.file_name());
+            write_wrapper(&wrapper, &entry.path(), &[("GEM_HOME", &gem_home), ("GEM_PATH", &gem_home)])?;
+            bin_paths.push(wrapper);
+        }
+
+        bin_paths.sort();
+        Ok(bin_paths)
+    }
+}
+
+impl PackageManager for GemBackend {
+    fn get_name(&self) -> &str {
+        "gem"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.gem_install(name, version, install_dir)
+    }
+
+    fn update(&self, name: &str, _version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        self.gem_install(name, None, install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let results: Vec<GemSearchResult> = net::get(&rubygems_url("api/v1/search.json")?)
+            .query(&[("query", query)])
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .context("Failed to search RubyGems")?
+            .json()
+            .context("Failed to parse RubyGems search results")?;
+
+        Ok(results
+            .into_iter()
+            .map(|gem| SearchResult {
+                name: gem.name,
+                description: format!("{} ({})", gem.info.unwrap_or_default().trim(), gem.version),
+            })
+            .collect())
+    }
+}
+
+impl VersionSource for GemBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        let versions: Vec<GemVersion> = net::get(&rubygems_url(&format!("api/v1/versions/{}.json", name))?)
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to list versions of {}", name))?
+            .json()
+            .context("Failed to parse RubyGems versions")?;
+
+        Ok(versions.into_iter().map(|v| v.number).collect())
+    }
+}
+
+/// License of a gem version, or of its newest version for `latest`. Gems
+/// listing several licenses may be used under any of them.
+pub fn license(name: &str, version: &str) -> Result<Option<String>> {
+    let versions: Vec<GemVersion> = net::get(&rubygems_url(&format!("api/v1/versions/{}.json", name))?)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to look up {} on RubyGems", name))?
+        .json()
+        .context("Failed to parse RubyGems versions")?;
+
+    // Versions are listed newest first
+    let mut versions = versions.into_iter();
+    let found = if version == "latest" { versions.next() } else { versions.find(|v| v.number == version) };
+    Ok(found.map(|v| v.licenses.join(" OR ")).filter(|l| !l.is_empty()))
+}
+
+#[derive(Deserialize)]
+struct GemInfo {
+    info: Option<String>,
+    homepage_uri: Option<String>,
+    source_code_uri: Option<String>,
+}
+
+/// Description and links RubyGems lists for a gem.
+pub fn metadata(name: &str) -> Result<Metadata> {
+    let gem: GemInfo = net::get(&rubygems_url(&format!("api/v1/gems/{}.json", name))?)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to look up {} on RubyGems", name))?
+        .json()
+        .context("Failed to parse RubyGems gem")?;
+
+    Ok(Metadata { description: gem.info.map(|info| info.trim().to_string()), homepage: gem.homepage_uri, repository: gem.source_code_uri })
+}
+
+/// A RubyGems URL, after applying any configured mirror.
+fn rubygems_url(path: &str) -> Result<String> {
+    config::mirror_url("gem", &format!("{}{}", RUBYGEMS_URL, path))
+}
+
+pub fn is_available() -> bool {
+    which::which("gem").is_ok()
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{collect_binaries, run_command, VersionSource};
+use crate::config;
+use crate::net::{self, SendRetrying};
+use crate::system::{PackageManager, SearchResult};
+
+const PROXY_URL: &str = "https://proxy.golang.org";
+
+/// Installs Go CLIs with `go install module@version`, one GOBIN per version.
+pub struct GoBackend;
+
+impl GoBackend {
+    fn go_install(&self, module: &str, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let bin_dir = install_dir.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+
+        let mut cmd = Command::new("go");
+        cmd.arg("install").arg(format!("{}@{}", module, go_version(version))).env("GOBIN", &bin_dir);
+        let proxy = proxy_url()?;
+        if proxy != PROXY_URL {
+            cmd.env("GOPROXY", &proxy);
+        }
+        run_command(&mut cmd)
+        .with_context(|| format!("go install failed for {}", module))?;
+
+        Ok(collect_binaries(install_dir))
+    }
+}
+
+impl PackageManager for GoBackend {
+    fn get_name(&self) -> &str {
+        "go"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.go_install(name, version, install_dir)
+    }
+
+    fn update(&self, name: &str, _version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        self.go_install(name, None, install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        // The Go module proxy has no search API; modules are installed by import path
+        Ok(Vec::new())
+    }
+}
+
+impl VersionSource for GoBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        let url = format!("{}/{}/@v/list", proxy_url()?, escape_module_path(name));
+        let body = net::get(&url)
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .with_context(|| format!("Failed to list versions of {}", name))?;
+
+        Ok(body.lines().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect())
+    }
+}
+
+pub fn is_available() -> bool {
+    which::which("go").is_ok()
+}
+
+/// The module proxy, after applying any configured mirror.
+fn proxy_url() -> Result<String> {
+    config::mirror_url("go", PROXY_URL)
+}
+
+/// Go module versions always carry a `v` prefix.
+fn go_version(version: Option<&str>) -> String {
+    match version {
+        None | Some("latest") => "latest".to_string(),
+        Some(v) if v.starts_with(|c: char| c.is_ascii_digit()) => format!("v{}", v),
+        Some(v) => v.to_string(),
+    }
+}
+
+/// The module proxy expects upper-case letters as `!` followed by the lower-case letter.
+fn escape_module_path(module: &str) -> String {
+    let mut escaped = String::with_capacity(module.len());
+    for c in module.chars() {
+        if c.is_ascii_uppercase() {
+            escaped.push('!');
+            escaped.push(c.to_ascii_lowercase());
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::{privileged_command, run_command};
+
+/// Native package formats that can be installed from a local file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalPackageKind {
+    Deb,
+    Rpm,
+}
+
+impl LocalPackageKind {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("deb") => Ok(LocalPackageKind::Deb),
+            Some("rpm") => Ok(LocalPackageKind::Rpm),
+            _ => bail!("Unsupported package file: {} (expected .deb or .rpm)", path.display()),
+        }
+    }
+
+    pub fn from_manager_name(name: &str) -> Option<Self> {
+        match name {
+            "dpkg" => Some(LocalPackageKind::Deb),
+            "rpm" => Some(LocalPackageKind::Rpm),
+            _ => None,
+        }
+    }
+
+    /// Name recorded as the package manager in the package database.
+    pub fn manager_name(&self) -> &'static str {
+        match self {
+            LocalPackageKind::Deb => "dpkg",
+            LocalPackageKind::Rpm => "rpm",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LocalPackage {
+    pub kind: LocalPackageKind,
+    pub name: String,
+    pub version: String,
+}
+
+/// Read the package name and version out of a local package file.
+pub fn inspect(path: &Path) -> Result<LocalPackage> {
+    if !path.is_file() {
+        bail!("Package file not found: {}", path.display());
+    }
+
+    let kind = LocalPackageKind::from_path(path)?;
+    let output = match kind {
+        LocalPackageKind::Deb => run_command(
+            Command::new("dpkg-deb").arg("--show").arg("--showformat=${Package}\n${Version}\n").arg(path),
+        ),
+        LocalPackageKind::Rpm => run_command(
+            Command::new("rpm").arg("-qp").arg("--queryformat").arg("%{NAME}\n%{VERSION}-%{RELEASE}\n").arg(path),
+        ),
+    }
+    .with_context(|| format!("Failed to read package metadata from {}", path.display()))?;
+
+    let mut lines = output.lines().map(str::trim);
+    match (lines.next(), lines.next()) {
+        (Some(name), Some(version)) if !name.is_empty() && !version.is_empty() => Ok(LocalPackage {
+            kind,
+            name: name.to_string(),
+            version: version.to_string(),
+        }),
+        _ => bail!("Package file {} has no name or version", path.display()),
+    }
+}
+
+/// Install a local package file through the native backend and return the binaries it provides.
+pub fn install(package: &LocalPackage, path: &Path) -> Result<Vec<PathBuf>> {
+    match package.kind {
+        LocalPackageKind::Deb => {
+            // apt resolves dependencies of local files, plain dpkg does not
+            if which::which("apt-get").is_ok() {
+                run_command(privileged_command("apt-get").arg("install").arg("-y").arg(path))?;
+            } else {
+                run_command(privileged_command("dpkg").arg("-i").arg(path))?;
+            }
+        }
+        LocalPackageKind::Rpm => {
+            if which::which("dnf").is_ok() {
+                run_command(privileged_command("dnf").arg("install").arg("-y").arg(path))?;
+            } else {
+                run_command(privileged_command("rpm").arg("-i").arg(path))?;
+            }
+        }
+    }
+
+    installed_binaries(package.kind, &package.name)
+}
+
+/// Remove a package that was installed from a local file.
+pub fn remove(kind: LocalPackageKind, name: &str) -> Result<()> {
+    match kind {
+        LocalPackageKind::Deb => run_command(privileged_command("dpkg").arg("-r").arg(name))?,
+        LocalPackageKind::Rpm => run_command(privileged_command("rpm").arg("-e").arg(name))?,
+    };
+    Ok(())
+}
+
+/// Every file the native package manager installed for `name`.
+pub fn installed_files(kind: LocalPackageKind, name: &str) -> Result<Vec<PathBuf>> {
+    let output = match kind {
+        LocalPackageKind::Deb => run_command(Command::new("dpkg").arg("-L").arg(name))?,
+        LocalPackageKind::Rpm => run_command(Command::new("rpm").arg("-ql").arg(name))?,
+    };
+
+    // Both also list the directories a package touches
+    Ok(output
+        .lines()
+        .map(PathBuf::from)
+        .filter(|path| path.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir()))
+        .collect())
+}
+
+fn installed_binaries(kind: LocalPackageKind, name: &str) -> Result<Vec<PathBuf>> {
+    Ok(installed_files(kind, name)?
+        .into_iter()
+        .filter(|path| path.parent().is_some_and(|dir| dir.ends_with("bin")) && path.is_file())
+        .collect())
+}
@@ -0,0 +1,423 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, trace};
+
+use crate::error::Error;
+use crate::logging;
+use crate::system::{self, PackageManager};
+use source::SourceSpec;
+
+pub mod asset;
+pub mod conda;
+pub mod gem;
+pub mod go;
+pub mod local;
+pub mod oci;
+pub mod plugin;
+pub mod recipe;
+pub mod repo;
+pub mod source;
+pub mod upstream;
+pub mod url;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Backends that can list the versions a package has upstream.
+pub trait VersionSource {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>>;
+
+    /// What `version` points at upstream right now, like a commit or a
+    /// digest, so a rolling tag such as `nightly` can be told apart from the
+    /// build installed under it. `None` when the backend can't tell.
+    fn revision(&self, _name: &str, _version: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// Find the version source for a backend, if it can list upstream versions.
+pub fn get_version_source(pm_name: &str, source: Option<&SourceSpec>) -> Option<Box<dyn VersionSource>> {
+    match (pm_name, source) {
+        ("source", Some(spec)) => Some(Box::new(source::SourceBackend::new(spec.clone()))),
+        ("conda", _) => Some(Box::new(conda::CondaBackend::new(Vec::new()))),
+        ("gem", _) => Some(Box::new(gem::GemBackend)),
+        ("go", _) => Some(Box::new(go::GoBackend)),
+        ("apt", _) => Some(Box::new(upstream::AptVersions)),
+        ("dnf", _) => Some(Box::new(upstream::DnfVersions)),
+        ("cargo", _) => Some(Box::new(upstream::CratesIoVersions)),
+        ("pip", _) => Some(Box::new(upstream::PypiVersions)),
+        ("npm", _) => Some(Box::new(upstream::NpmVersions)),
+        ("repo", _) => Some(Box::new(repo::RepoBackend)),
+        ("oci", _) => Some(Box::new(oci::OciBackend)),
+        ("recipe", _) => Some(Box::new(recipe::RecipeBackend)),
+        (name, _) => match plugin::find(name) {
+            Ok(Some(plugin)) if plugin.supports("versions") => Some(Box::new(plugin)),
+            _ => None,
+        },
+    }
+}
+
+/// Look up a package manager by name, checking updater's own backends and then
+/// plugins before the system ones.
+pub fn get_package_manager_by_name(name: &str) -> Result<Box<dyn PackageManager>> {
+    match name {
+        "conda" => Ok(Box::new(conda::CondaBackend::new(Vec::new()))),
+        "gem" => Ok(Box::new(gem::GemBackend)),
+        "go" => Ok(Box::new(go::GoBackend)),
+        "repo" => Ok(Box::new(repo::RepoBackend)),
+        "oci" => Ok(Box::new(oci::OciBackend)),
+        "recipe" => Ok(Box::new(recipe::RecipeBackend)),
+        _ => match plugin::find(name)? {
+            Some(plugin) => Ok(Box::new(plugin)),
+            None => system::get_package_manager_by_name(name),
+        },
+    }
+}
+
+pub fn get_available_package_managers() -> Result<Vec<Box<dyn PackageManager>>> {
+    let mut package_managers = system::get_available_package_managers()?;
+    if conda::is_available() {
+        package_managers.push(Box::new(conda::CondaBackend::new(Vec::new())));
+    }
+    if gem::is_available() {
+        package_managers.push(Box::new(gem::GemBackend));
+    }
+    if go::is_available() {
+        package_managers.push(Box::new(go::GoBackend));
+    }
+    if repo::is_available() {
+        package_managers.push(Box::new(repo::RepoBackend));
+    }
+    if oci::is_available() {
+        package_managers.push(Box::new(oci::OciBackend));
+    }
+    if recipe::is_available() {
+        package_managers.push(Box::new(recipe::RecipeBackend));
+    }
+    for plugin in plugin::discover() {
+        if !package_managers.iter().any(|pm| pm.get_name() == plugin.get_name()) {
+            package_managers.push(Box::new(plugin));
+        }
+    }
+    Ok(package_managers)
+}
+
+/// The license a backend's registry declares for a package version, usually
+/// an SPDX expression. Lookup failures only leave the license unknown.
+pub fn license(pm_name: &str, name: &str, version: &str) -> Option<String> {
+    let license = match pm_name {
+        "cargo" => upstream::crates_io_license(name, version),
+        "pip" => upstream::pypi_license(name, version),
+        "npm" => upstream::npm_license(name, version),
+        "gem" => gem::license(name, version),
+        "repo" => repo::license(name),
+        "oci" => oci::license(name, version),
+        "url" | "source" => Ok(None),
+        backend => plugin::find(backend).and_then(|plugin| match plugin {
+            Some(plugin) => plugin.license(name, version),
+            None => Ok(None),
+        }),
+    };
+    match license {
+        Ok(license) => license.map(|l| l.trim().to_string()).filter(|l| !l.is_empty()),
+        Err(e) => {
+            debug!("Could not look up the license of {} {}: {:#}", name, version, e);
+            None
+        }
+    }
+}
+
+/// What a backend's registry says about a package, for `info` and `list --long`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    /// Upstream source repository
+    #[serde(default)]
+    pub repository: Option<String>,
+}
+
+/// Description and links a backend's registry lists for a package version.
+/// Lookup failures only leave them unknown.
+pub fn metadata(pm_name: &str, name: &str, version: &str) -> Metadata {
+    let metadata = match pm_name {
+        "cargo" => upstream::crates_io_metadata(name),
+        "pip" => upstream::pypi_metadata(name, version),
+        "npm" => upstream::npm_metadata(name, version),
+        "gem" => gem::metadata(name),
+        "repo" => repo::metadata(name),
+        "oci" => oci::metadata(name, version),
+        "recipe" => recipe::metadata(name),
+        "url" | "source" => Ok(Metadata::default()),
+        backend => plugin::find(backend).and_then(|plugin| match plugin {
+            Some(plugin) => plugin.metadata(name, version),
+            None => Ok(Metadata::default()),
+        }),
+    };
+    let metadata = metadata.unwrap_or_else(|e| {
+        debug!("Could not look up the metadata of {} {}: {:#}", name, version, e);
+        Metadata::default()
+    });
+    let clean = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    Metadata {
+        description: clean(metadata.description),
+        homepage: clean(metadata.homepage),
+        repository: clean(metadata.repository),
+    }
+}
+
+/// Packages a backend says a package version needs, which updater installs
+/// and tracks like any other. Backends that resolve dependencies themselves
+/// report none.
+pub fn dependencies(pm_name: &str, name: &str, version: &str) -> Vec<String> {
+    let dependencies = match pm_name {
+        "repo" => repo::resolve(name, Some(version)).map(|asset| asset.dependencies),
+        backend => plugin::find(backend).and_then(|plugin| match plugin {
+            Some(plugin) => plugin.dependencies(name, version),
+            None => Ok(Vec::new()),
+        }),
+    };
+    dependencies.unwrap_or_else(|e| {
+        debug!("Could not look up the dependencies of {} {}: {:#}", name, version, e);
+        Vec::new()
+    })
+}
+
+/// Every package name `pm` offers, for backends that keep an index of them
+/// which can be read in one go: searching those for nothing lists it all.
+pub fn name_index(pm: &dyn PackageManager) -> Option<Vec<String>> {
+    if !matches!(pm.get_name(), "repo" | "recipe") {
+        return None;
+    }
+    match pm.search("") {
+        Ok(results) => Some(results.into_iter().map(|result| result.name).collect()),
+        Err(e) => {
+            debug!("Could not list the packages of {}: {:#}", pm.get_name(), e);
+            None
+        }
+    }
+}
+
+/// Variables a backend says `updater env` should export for a package, like
+/// a home dir its binaries look for. Lookup failures are treated as none.
+pub fn env(pm_name: &str, name: &str) -> BTreeMap<String, String> {
+    let env = match pm_name {
+        "repo" => repo::env(name),
+        _ => Ok(BTreeMap::new()),
+    };
+    env.unwrap_or_else(|e| {
+        debug!("Could not look up the environment of {}: {:#}", name, e);
+        BTreeMap::new()
+    })
+}
+
+/// The name a backend says a package is published under now, when it was
+/// renamed upstream. Lookup failures are treated as no rename.
+pub fn renamed(pm_name: &str, name: &str) -> Option<String> {
+    let renamed = match pm_name {
+        "repo" => repo::renamed(name),
+        backend => plugin::find(backend).and_then(|plugin| match plugin {
+            Some(plugin) => plugin.renamed(name),
+            None => Ok(None),
+        }),
+    };
+    match renamed {
+        Ok(renamed) => renamed.filter(|new| !new.is_empty() && new != name),
+        Err(e) => {
+            debug!("Could not look up whether {} was renamed: {:#}", name, e);
+            None
+        }
+    }
+}
+
+/// The artifact a backend will download to install a package version, for
+/// backends that download through updater's cache. The others fetch on
+/// their own and can't be downloaded ahead of time.
+pub fn artifact_url(pm_name: &str, name: &str, version: Option<&str>, url: Option<&url::UrlSpec>) -> Option<String> {
+    match (pm_name, url) {
+        ("repo", _) => repo::resolve(name, version).map(|asset| asset.url).ok(),
+        ("url", Some(spec)) => url::UrlBackend::new(spec.clone()).cacheable_url(version),
+        _ => None,
+    }
+}
+
+/// Whether a backend's installs keep working after their directory is moved.
+/// Conda environments and source builds bake their prefix into binaries, and
+/// plugins have to say so themselves.
+pub fn is_relocatable(pm_name: &str) -> bool {
+    match pm_name {
+        "conda" | "source" => false,
+        name => plugin::find(name).ok().flatten().is_none_or(|plugin| plugin.is_relocatable()),
+    }
+}
+
+/// Whether a backend can install for an architecture other than this
+/// machine's, by picking artifacts for it. Native package managers and
+/// language toolchains install for the machine they run on.
+pub fn supports_foreign_arch(pm_name: &str) -> bool {
+    matches!(pm_name, "url" | "repo" | "recipe" | "oci" | "image")
+}
+
+/// Run a backend command to completion and return its stdout.
+pub fn run_command(cmd: &mut Command) -> Result<String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    debug!("Running {:?}", cmd);
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to execute {}", program))?;
+    for (stream, bytes) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        if !bytes.is_empty() {
+            trace!("{} {}:\n{}", program, stream, String::from_utf8_lossy(bytes).trim_end());
+        }
+    }
+
+    logging::log_command(cmd, &output);
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = format!(
+            "{} exited with {}: {}\nFull output is in {}",
+            program,
+            output.status,
+            stderr.trim(),
+            logging::get_command_log_path().display()
+        );
+        return Err(Error::Backend { backend: program, message }.into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Build a command that runs with root privileges, going through sudo when needed.
+pub fn privileged_command(program: &str) -> Command {
+    if is_root() {
+        Command::new(program)
+    } else {
+        let mut cmd = Command::new("sudo");
+        cmd.arg(program);
+        cmd
+    }
+}
+
+pub fn is_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim() == "0")
+        .unwrap_or(false)
+}
+
+/// Collect executable files from every `bin` directory below an install directory.
+pub fn collect_binaries(install_dir: &Path) -> Vec<PathBuf> {
+    let mut binaries = Vec::new();
+    let mut pending = vec![install_dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if dir.file_name().is_some_and(|name| name == "bin") && is_executable(&path) {
+                binaries.push(path);
+            }
+        }
+    }
+
+    binaries.sort();
+    binaries
+}
+
+/// `relative` inside `install_dir`, for recipes and plugins placing files.
+/// Only plain relative paths are allowed, and neither the file nor any dir on
+/// the way to it may be a symlink, which an extracted archive could have left
+/// pointing anywhere.
+pub(crate) fn placement(install_dir: &Path, relative: &str) -> Result<PathBuf> {
+    let path = Path::new(relative);
+    if relative.is_empty() || path.components().any(|c| !matches!(c, Component::Normal(_))) {
+        bail!("{} is not a plain relative path", relative);
+    }
+    let mut dest = install_dir.to_path_buf();
+    for component in path.components() {
+        dest.push(component);
+        if fs::symlink_metadata(&dest).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            bail!("{} leads through a symlink", relative);
+        }
+    }
+    let parent = dest.parent().context("Placed file has no parent directory")?;
+    fs::create_dir_all(parent)?;
+    if !parent.canonicalize()?.starts_with(install_dir.canonicalize()?) {
+        bail!("{} is outside the install dir", relative);
+    }
+    Ok(dest)
+}
+
+/// Create or truncate a file from `placement`, refusing to follow a symlink
+/// that appeared there since.
+pub(crate) fn create_placed(dest: &Path) -> Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(dest)
+        .with_context(|| format!("Failed to create {}", dest.display()))
+}
+
+/// `value` as a single-quoted POSIX shell word, taken literally whatever it holds.
+pub fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Write a small shell wrapper that sets environment variables before running `target`.
+pub fn write_wrapper(wrapper: &Path, target: &Path, env: &[(&str, &Path)]) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut script = String::from("#!/bin/sh\n");
+    for (key, value) in env {
+        script.push_str(&format!("export {}={}\n", key, sh_quote(&value.to_string_lossy())));
+    }
+    script.push_str(&format!("exec {} \"$@\"\n", sh_quote(&target.to_string_lossy())));
+
+    fs::write(wrapper, script).with_context(|| format!("Failed to write wrapper {}", wrapper.display()))?;
+    fs::set_permissions(wrapper, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn placement_stays_in_the_install_dir() {
+        let install_dir = std::env::temp_dir().join(format!("updater-placement-{}", std::process::id()));
+        fs::create_dir_all(&install_dir).unwrap();
+        symlink("/tmp", install_dir.join("escape")).unwrap();
+        symlink("/etc/passwd", install_dir.join("passwd")).unwrap();
+
+        assert_eq!(placement(&install_dir, "bin/tool").unwrap(), install_dir.join("bin/tool"));
+        for bad in ["", "../tool", "/etc/tool", "bin/../../tool", "escape/tool", "passwd"] {
+            assert!(placement(&install_dir, bad).is_err(), "{:?} accepted", bad);
+        }
+        fs::remove_dir_all(&install_dir).unwrap();
+    }
+}
@@ -0,0 +1,547 @@
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use super::{collect_binaries, run_command, Metadata, VersionSource};
+use crate::archive;
+use crate::auth;
+use crate::compat;
+use crate::config::{self, RepoKind};
+use crate::download;
+use crate::error::Error;
+use crate::landlock;
+use crate::net::{self, SendRetrying};
+use crate::system::{PackageManager, SearchResult};
+use crate::verify;
+use crate::version::{self, Channel};
+
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, application/vnd.docker.distribution.manifest.v2+json";
+
+/// Annotation ORAS stores each pushed file's name under.
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// Installs packages published to a container registry as OCI artifacts,
+/// e.g. with `oras push ghcr.io/corp/tool:1.2.0 tool-linux-x86_64.tar.gz`.
+///
+/// Packages are named by their repository, like `ghcr.io/corp/tool`, or just
+/// `tool` when an `oci` repository such as `ghcr.io/corp` is configured.
+/// Tags are versions. Credentials come from `updater auth login <registry>`
+/// or, failing that, from `~/.docker/config.json`.
+pub struct OciBackend;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    media_type: Option<String>,
+    /// Set for image indexes, which list one manifest per platform
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+    /// Digest of the manifest, or of the index it was picked from, that the tag points at
+    #[serde(skip)]
+    digest: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    digest: String,
+    #[serde(default)]
+    platform: Option<Platform>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+#[derive(Deserialize)]
+struct TagList {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DockerConfig {
+    #[serde(default)]
+    auths: BTreeMap<String, DockerAuth>,
+}
+
+#[derive(Deserialize)]
+struct DockerAuth {
+    /// Base64 of `user:password`, ready to use as HTTP basic credentials
+    auth: Option<String>,
+}
+
+/// One repository on a registry, with the bearer token the registry handed
+/// out for it once it asked for one.
+struct Registry {
+    host: String,
+    repository: String,
+    token: Option<String>,
+}
+
+impl Registry {
+    /// Split a reference like `ghcr.io/corp/tool` into registry and repository.
+    fn parse(reference: &str) -> Result<Self> {
+        let reference = reference.trim_start_matches("oci://");
+        let (host, repository) = reference
+            .split_once('/')
+            .filter(|(host, _)| host.contains('.') || host.contains(':') || *host == "localhost")
+            .ok_or_else(|| anyhow!("{} is not a registry reference like ghcr.io/owner/name", reference))?;
+        Ok(Registry { host: host.to_string(), repository: repository.to_string(), token: None })
+    }
+
+    fn base_url(&self) -> String {
+        // Local development registries rarely have TLS
+        let scheme = if self.host.starts_with("localhost") || self.host.starts_with("127.0.0.1") { "http" } else { "https" };
+        format!("{}://{}", scheme, self.host)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v2/{}/{}", self.base_url(), self.repository, path)
+    }
+
+    /// Send a GET to `path`, fetching a token first when the registry asks for one.
+    fn get(&mut self, path: &str, accept: Option<&str>) -> Result<Response> {
+        let url = self.url(path);
+        let request = |token: Option<&str>| {
+            let mut request = net::client().get(&url);
+            if let Some(accept) = accept {
+                request = request.header(ACCEPT, accept);
+            }
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => credentials(request, &self.base_url(), &self.host),
+            }
+        };
+
+        let response = request(self.token.as_deref()).send_retrying().with_context(|| format!("Failed to request {}", url))?;
+        if response.status() != StatusCode::UNAUTHORIZED || self.token.is_some() {
+            return response.error_for_status().with_context(|| format!("Failed to fetch {}", url));
+        }
+        let challenge = response.headers().get(WWW_AUTHENTICATE).and_then(|h| h.to_str().ok()).unwrap_or_default().to_string();
+        let token = self.fetch_token(&challenge)?;
+        let response = request(Some(&token)).send_retrying().with_context(|| format!("Failed to request {}", url))?;
+        self.token = Some(token);
+        response.error_for_status().with_context(|| format!("Failed to fetch {}", url))
+    }
+
+    /// Exchange stored credentials for a token as a `Bearer` challenge describes,
+    /// anonymously when there are none.
+    fn fetch_token(&self, challenge: &str) -> Result<String> {
+        let params = match challenge.strip_prefix("Bearer ") {
+            Some(params) => parse_challenge(params),
+            None => bail!("Registry {} requires credentials, run `updater auth login {}`", self.host, self.host),
+        };
+        let realm = params.get("realm").ok_or_else(|| anyhow!("Registry {} sent no token realm", self.host))?;
+        let mut request = net::client().get(realm);
+        let scope = format!("repository:{}:pull", self.repository);
+        let query: Vec<(&str, &str)> = params
+            .get("service")
+            .map(|service| ("service", service.as_str()))
+            .into_iter()
+            .chain([("scope", params.get("scope").map_or(scope.as_str(), String::as_str))])
+            .collect();
+        request = request.query(&query);
+        let response: TokenResponse = credentials(request, &self.base_url(), &self.host)
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to get a token for {} from {}", self.repository, self.host))?
+            .json()
+            .context("Failed to parse registry token")?;
+        response.token.or(response.access_token).ok_or_else(|| anyhow!("Registry {} sent an empty token", self.host))
+    }
+
+    /// The manifest for `tag`, picking this platform's from an image index.
+    fn manifest(&mut self, tag: &str) -> Result<Manifest> {
+        let body = self.get(&format!("manifests/{}", tag), Some(MANIFEST_TYPES))?.bytes().context("Failed to download OCI manifest")?;
+        let mut manifest: Manifest = serde_json::from_slice(&body).context("Failed to parse OCI manifest")?;
+        let digest = format!("sha256:{:x}", Sha256::digest(&body));
+        let is_index = manifest.media_type.as_deref().is_some_and(|t| t.ends_with("image.index.v1+json")) || !manifest.manifests.is_empty();
+        if !is_index {
+            manifest.digest = digest;
+            return Ok(manifest);
+        }
+        let (os, arch) = platform();
+        let entry = manifest
+            .manifests
+            .iter()
+            .find(|m| m.platform.as_ref().is_some_and(|p| p.os == os && p.architecture == arch))
+            .ok_or_else(|| anyhow!("{}:{} has no manifest for {}/{}", self.repository, tag, os, arch))?;
+        let mut manifest: Manifest = self.get(&format!("manifests/{}", entry.digest), Some(MANIFEST_TYPES))?.json().context("Failed to parse OCI manifest")?;
+        manifest.digest = digest;
+        Ok(manifest)
+    }
+
+    /// Check the signature of what `manifest` was fetched from, if its source needs one.
+    fn verify_signature(&self, manifest: &Manifest) -> Result<()> {
+        verify::cosign_image(&format!("{}/{}@{}", self.host, self.repository, manifest.digest))
+    }
+
+    fn tags(&mut self) -> Result<Vec<String>> {
+        let tags: TagList = self.get("tags/list", None)?.json().context("Failed to parse registry tag list")?;
+        Ok(tags.tags)
+    }
+
+    /// Download the blob `digest` to `dest`, checking it against the digest.
+    fn download_blob(&mut self, digest: &str, dest: &Path) -> Result<()> {
+        let expected = digest.strip_prefix("sha256:").ok_or_else(|| anyhow!("Unsupported blob digest {}", digest))?;
+        let mut response = self.get(&format!("blobs/{}", digest), None)?;
+        io::copy(&mut response, &mut File::create(dest)?).with_context(|| format!("Failed to download blob {}", digest))?;
+        let checksum = download::sha256_file(dest)?;
+        if !checksum.eq_ignore_ascii_case(expected) {
+            fs::remove_file(dest).ok();
+            return Err(Error::Verification(format!("Digest mismatch for {}: expected {}, got sha256:{}", self.repository, digest, checksum)).into());
+        }
+        Ok(())
+    }
+}
+
+/// Stored credentials for `host`, from updater's own store or Docker's.
+fn credentials(request: RequestBuilder, base_url: &str, host: &str) -> RequestBuilder {
+    if auth::has_credentials(base_url) {
+        return auth::authorize(request, base_url);
+    }
+    match docker_auth(host) {
+        Some(basic) => request.header(AUTHORIZATION, format!("Basic {}", basic)),
+        None => request,
+    }
+}
+
+fn docker_auth(host: &str) -> Option<String> {
+    let path = dirs::home_dir()?.join(".docker").join("config.json");
+    let config: DockerConfig = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    config
+        .auths
+        .into_iter()
+        .find(|(registry, _)| registry.trim_start_matches("https://").trim_end_matches('/') == host)
+        .and_then(|(_, auth)| auth.auth)
+}
+
+/// `key="value"` pairs of a `WWW-Authenticate` challenge.
+fn parse_challenge(params: &str) -> BTreeMap<String, String> {
+    let mut parsed = BTreeMap::new();
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        parsed.insert(key, value.to_string());
+        rest = remainder;
+    }
+    parsed
+}
+
+/// The platform installed for as OCI names it.
+fn platform() -> (&'static str, &'static str) {
+    let arch = match compat::target_arch() {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        arch => arch,
+    };
+    (std::env::consts::OS, arch)
+}
+
+/// The registry repository a package name refers to: the name itself when it
+/// is a full reference, otherwise the first configured `oci` repository
+/// that has it.
+fn registry(name: &str) -> Result<Registry> {
+    if let Ok(registry) = Registry::parse(name) {
+        return Ok(registry);
+    }
+    let repos = config::load_config()?.repos_of(RepoKind::Oci);
+    for repo in &repos {
+        let mut registry = Registry::parse(&format!("{}/{}", repo.url.trim_end_matches('/'), name))?;
+        if registry.tags().is_ok() {
+            return Ok(registry);
+        }
+    }
+    Err(Error::PackageNotFound { name: name.to_string(), suggestions: Vec::new() }.into())
+}
+
+/// The tag to install: `version` itself, or the newest stable one.
+fn resolve_tag(registry: &mut Registry, version: Option<&str>) -> Result<String> {
+    if let Some(version) = version.filter(|v| *v != "latest") {
+        return Ok(version.to_string());
+    }
+    let tags = registry.tags()?;
+    Ok(version::latest(&tags, Channel::Stable).unwrap_or_else(|| "latest".to_string()))
+}
+
+impl OciBackend {
+    fn download_and_extract(&self, name: &str, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut registry = registry(name)?;
+        let tag = resolve_tag(&mut registry, version)?;
+        let manifest = registry.manifest(&tag)?;
+        registry.verify_signature(&manifest)?;
+
+        // Artifacts with several files carry one per platform
+        let (os, arch) = platform();
+        let layer = match manifest.layers.as_slice() {
+            [] => bail!("{}:{} has no files", name, tag),
+            [layer] => layer,
+            layers => layers
+                .iter()
+                .find(|layer| {
+                    let title = layer.annotations.get(TITLE_ANNOTATION).map(|t| t.to_lowercase()).unwrap_or_default();
+                    title.contains(os) && (title.contains(arch) || title.contains(compat::target_arch()))
+                })
+                .ok_or_else(|| anyhow!("{}:{} has no file for {}/{}", name, tag, os, arch))?,
+        };
+        let file_name = layer.annotations.get(TITLE_ANNOTATION).cloned().unwrap_or_else(|| name.rsplit('/').next().unwrap_or(name).to_string());
+
+        let download_dir = install_dir.join(".artifact");
+        fs::create_dir_all(&download_dir)?;
+        let artifact = download_dir.join(Path::new(&file_name).file_name().unwrap_or_default());
+        registry.download_blob(&layer.digest, &artifact)?;
+
+        archive::extract(&artifact, name, install_dir)?;
+        Ok(collect_binaries(install_dir))
+    }
+}
+
+impl PackageManager for OciBackend {
+    fn get_name(&self) -> &str {
+        "oci"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.download_and_extract(name, version, install_dir)
+    }
+
+    fn update(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        self.download_and_extract(name, version, install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        // Registries have no search API beyond listing a repository's tags
+        Ok(Vec::new())
+    }
+}
+
+impl VersionSource for OciBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        registry(name)?.tags()
+    }
+
+    fn revision(&self, name: &str, version: &str) -> Result<Option<String>> {
+        let mut registry = registry(name)?;
+        let tag = resolve_tag(&mut registry, Some(version))?;
+        Ok(Some(registry.manifest(&tag)?.digest))
+    }
+}
+
+/// License, description and links from the manifest's standard annotations.
+fn annotations(name: &str, version: &str) -> Result<BTreeMap<String, String>> {
+    let mut registry = registry(name)?;
+    let tag = resolve_tag(&mut registry, Some(version))?;
+    Ok(registry.manifest(&tag)?.annotations)
+}
+
+pub fn license(name: &str, version: &str) -> Result<Option<String>> {
+    Ok(annotations(name, version)?.remove("org.opencontainers.image.licenses"))
+}
+
+pub fn metadata(name: &str, version: &str) -> Result<Metadata> {
+    let mut annotations = annotations(name, version)?;
+    Ok(Metadata {
+        description: annotations.remove("org.opencontainers.image.description"),
+        homepage: annotations.remove("org.opencontainers.image.url"),
+        repository: annotations.remove("org.opencontainers.image.source"),
+    })
+}
+
+/// Binaries to take out of a container image, for versions installed with
+/// `--from-image`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageSpec {
+    /// Image reference without the tag, like `ghcr.io/org/tool`; versions are tags
+    pub image: String,
+    /// Absolute paths of the binaries inside the image
+    pub bins: Vec<String>,
+}
+
+impl ImageSpec {
+    /// Split `ghcr.io/org/tool:1.2` into the spec and its tag, if it has one.
+    pub fn parse(reference: &str, bins: Vec<String>) -> (Self, Option<String>) {
+        let (image, tag) = match reference.rsplit_once(':') {
+            // A colon before the last slash belongs to the registry's port
+            Some((image, tag)) if !tag.contains('/') => (image, Some(tag.to_string())),
+            _ => (reference, None),
+        };
+        (ImageSpec { image: image.to_string(), bins }, tag)
+    }
+
+    /// Name to track the package under when none is given: the image's own.
+    pub fn default_name(&self) -> &str {
+        self.image.rsplit('/').next().unwrap_or(&self.image)
+    }
+}
+
+/// Pulls a container image and keeps just the requested binaries, for tools
+/// only published as images. Statically linked binaries work best, since
+/// the libraries inside the image are left behind.
+pub struct ImageBackend {
+    spec: ImageSpec,
+}
+
+/// Hops through symlinks inside the image before giving up on a binary.
+const MAX_LINK_DEPTH: usize = 8;
+
+impl ImageBackend {
+    pub fn new(spec: ImageSpec) -> Self {
+        ImageBackend { spec }
+    }
+
+    fn pull(&self, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        if self.spec.bins.is_empty() {
+            bail!("Name the binaries to take from {} with --bin", self.spec.image);
+        }
+        let mut registry = Registry::parse(&self.spec.image)?;
+        let tag = version.filter(|v| *v != "latest").unwrap_or("latest");
+        let manifest = registry.manifest(tag)?;
+        registry.verify_signature(&manifest)?;
+
+        let layers_dir = install_dir.join(".layers");
+        let rootfs = layers_dir.join("rootfs");
+        fs::create_dir_all(&rootfs)?;
+        let mut layers = Vec::new();
+        for (i, layer) in manifest.layers.iter().enumerate() {
+            let path = layers_dir.join(format!("{}.tar", i));
+            registry.download_blob(&layer.digest, &path)?;
+            let members = run_command(Command::new("tar").arg("-tf").arg(&path)).context("Failed to list image layer")?;
+            // Layers list members with or without a leading `./`
+            let members: HashMap<String, String> =
+                members.lines().map(|m| (m.trim_start_matches("./").to_string(), m.to_string())).collect();
+            layers.push((path, members));
+        }
+
+        let bin_dir = install_dir.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+        let mut bin_paths = Vec::new();
+        for bin in &self.spec.bins {
+            let resolved = extract_following_links(&layers, &rootfs, bin)
+                .with_context(|| format!("Failed to take {} from {}:{}", bin, self.spec.image, tag))?;
+            let dest = bin_dir.join(Path::new(bin).file_name().unwrap_or_default());
+            fs::copy(&resolved, &dest)?;
+            fs::set_permissions(&dest, fs::Permissions::from_mode(0o755))?;
+            bin_paths.push(dest);
+        }
+        fs::remove_dir_all(&layers_dir).ok();
+        Ok(bin_paths)
+    }
+}
+
+/// Extract `path` from the layers into `rootfs`, later layers winning, and
+/// follow it through symlinks to the file they point at.
+fn extract_following_links(layers: &[(PathBuf, HashMap<String, String>)], rootfs: &Path, path: &str) -> Result<PathBuf> {
+    let mut member = path.trim_start_matches('/').to_string();
+    for _ in 0..MAX_LINK_DEPTH {
+        let mut found = false;
+        for (layer, members) in layers {
+            let name = match members.get(&member) {
+                Some(name) => name,
+                None => continue,
+            };
+            debug!("Extracting {} from {}", name, layer.display());
+            let mut cmd = Command::new("tar");
+            cmd.arg("-xf").arg(layer).arg("-C").arg(rootfs).arg("--no-same-owner").arg(name);
+            // Layers are untrusted, so members can't write outside the scratch dir
+            let _ruleset = landlock::confine_writes(&mut cmd, rootfs)?;
+            run_command(&mut cmd).context("Failed to extract from image layer")?;
+            found = true;
+        }
+        if !found {
+            bail!("/{} is not in the image", member);
+        }
+        let extracted = rootfs.join(&member);
+        let target = match fs::read_link(&extracted) {
+            Ok(target) => target,
+            Err(_) => return Ok(extracted),
+        };
+        // Links are relative to their own directory or to the image's root
+        let parent = Path::new(&member).parent().unwrap_or(Path::new(""));
+        let target = if target.is_absolute() { target } else { Path::new("/").join(parent).join(target) };
+        member = normalize(&target);
+    }
+    bail!("Too many levels of symlinks at {}", path)
+}
+
+/// `path` relative to the image root with `.` and `..` resolved.
+fn normalize(path: &Path) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.iter().filter_map(|p| p.to_str()) {
+        match part {
+            "/" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+impl PackageManager for ImageBackend {
+    fn get_name(&self) -> &str {
+        "image"
+    }
+
+    fn install(&self, _name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.pull(version, install_dir)
+    }
+
+    fn update(&self, _name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        self.pull(version, install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        Ok(Vec::new())
+    }
+}
+
+impl VersionSource for ImageBackend {
+    fn available_versions(&self, _name: &str) -> Result<Vec<String>> {
+        Registry::parse(&self.spec.image)?.tags()
+    }
+
+    fn revision(&self, _name: &str, version: &str) -> Result<Option<String>> {
+        Ok(Some(Registry::parse(&self.spec.image)?.manifest(version)?.digest))
+    }
+}
+
+pub fn is_available() -> bool {
+    config::load_config().is_ok_and(|config| !config.repos_of(RepoKind::Oci).is_empty())
+}
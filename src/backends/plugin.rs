@@ -0,0 +1,312 @@
+//! Backends provided by external executables named `updater-backend-<name>`,
+//! or, when built with the `wasm` feature, by sandboxed WebAssembly modules
+//! named `updater-backend-<name>.wasm` in the plugins dir (see `wasm.rs`).
+//!
+//! Each call runs the executable once with a single JSON request on stdin:
+//!
+//! ```json
+//! {"protocol": 1, "method": "install", "params": {"name": "caddy", "version": "2.7.6", "install_dir": "/path", "user": true}}
+//! ```
+//!
+//! and expects a single JSON response on stdout, either `{"result": ...}` or
+//! `{"error": "message"}`. Anything written to stderr ends up in the command
+//! log. The methods are:
+//!
+//! - `capabilities` → `{"methods": ["search", ...], "relocatable": false}`
+//! - `search` `{query}` → `[{"name": ..., "description": ...}]`
+//! - `install` `{name, version, install_dir, user}` → paths of the installed binaries
+//! - `update` `{name, version, install_dir, user}` → `null`
+//! - `remove` `{name, version, install_dir, user}` → `null`
+//! - `versions` `{name}` → every version available upstream
+//! - `license` `{name, version}` → SPDX license expression, or `null` if unknown
+//! - `dependencies` `{name, version}` → names of the packages it needs
+//! - `metadata` `{name, version}` → `{"description": ..., "homepage": ..., "repository": ...}`, any of them `null`
+//! - `renamed` `{name}` → the name the package is published under now, or `null`
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use tracing::{debug, warn};
+
+use super::{is_executable, Metadata, VersionSource};
+use crate::error::Error;
+use crate::logging;
+use crate::sandbox;
+use crate::system::{PackageManager, SearchResult};
+
+pub const PREFIX: &str = "updater-backend-";
+
+/// Where WASM plugins are installed.
+pub fn plugins_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    data_dir.join("updater").join("plugins")
+}
+
+/// Version of the protocol spoken to plugins, sent with every request.
+const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Deserialize, Default)]
+struct Capabilities {
+    #[serde(default)]
+    methods: Vec<String>,
+    /// Whether installs keep working after their directory is moved
+    #[serde(default)]
+    relocatable: bool,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<Value>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PluginSearchResult {
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+pub struct PluginBackend {
+    name: String,
+    path: PathBuf,
+    capabilities: Capabilities,
+}
+
+impl PluginBackend {
+    fn load(name: &str, path: PathBuf) -> Result<Self> {
+        let mut plugin = PluginBackend { name: name.to_string(), path, capabilities: Capabilities::default() };
+        plugin.capabilities = plugin
+            .call("capabilities", json!({}))
+            .with_context(|| format!("Backend plugin {} did not report its capabilities", plugin.path.display()))?;
+        Ok(plugin)
+    }
+
+    pub fn supports(&self, method: &str) -> bool {
+        self.capabilities.methods.iter().any(|m| m == method)
+    }
+
+    pub fn is_relocatable(&self) -> bool {
+        self.capabilities.relocatable
+    }
+
+    fn is_wasm(&self) -> bool {
+        self.path.extension().is_some_and(|ext| ext == "wasm")
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        self.call_in(method, params, None)
+    }
+
+    /// Like `call`, for methods that install into `install_dir`. The plugin
+    /// runs sandboxed there if its backend is configured to be.
+    fn call_in<T: DeserializeOwned>(&self, method: &str, params: Value, install_dir: Option<&Path>) -> Result<T> {
+        let request = json!({ "protocol": PROTOCOL_VERSION, "method": method, "params": params });
+        if self.is_wasm() {
+            let response = self.run_wasm(&request, install_dir)?;
+            return self.parse_response(method, &response, None);
+        }
+
+        let mut cmd = match install_dir {
+            Some(dir) => sandbox::confine(&self.name, &Command::new(&self.path), &[dir])?,
+            None => Command::new(&self.path),
+        };
+        cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        debug!("Running {:?} {}", cmd, method);
+        let mut child = cmd.spawn().with_context(|| format!("Failed to execute {}", self.path.display()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin may exit without reading its request, which is its own error to report
+            stdin.write_all(format!("{}\n", request).as_bytes()).ok();
+        }
+        let output = child.wait_with_output()?;
+        logging::log_command(&cmd, &output);
+        self.parse_response(method, &output.stdout, Some(output.status))
+    }
+
+    #[cfg(feature = "wasm")]
+    fn run_wasm(&self, request: &Value, install_dir: Option<&Path>) -> Result<Vec<u8>> {
+        let package = request["params"]["name"].as_str();
+        super::wasm::call(&self.name, &self.path, request.to_string().as_bytes(), install_dir, package)
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn run_wasm(&self, _request: &Value, _install_dir: Option<&Path>) -> Result<Vec<u8>> {
+        bail!("{} is a WASM plugin, but updater was built without the wasm feature", self.path.display())
+    }
+
+    /// The result in a plugin's response, `status` being how an executable exited.
+    fn parse_response<T: DeserializeOwned>(&self, method: &str, output: &[u8], status: Option<ExitStatus>) -> Result<T> {
+        let response: Response = serde_json::from_slice(output).with_context(|| match status {
+            Some(status) => format!(
+                "{} {} returned an invalid response ({})\nFull output is in {}",
+                self.path.display(),
+                method,
+                status,
+                logging::get_command_log_path().display()
+            ),
+            None => format!("{} {} returned an invalid response", self.path.display(), method),
+        })?;
+        let failure = match (response.error, status) {
+            (Some(error), _) => Some(format!("{} {} failed: {}", self.name, method, error)),
+            (None, Some(status)) if !status.success() => Some(format!("{} {} exited with {}", self.name, method, status)),
+            (None, _) => None,
+        };
+        if let Some(message) = failure {
+            return Err(Error::Backend { backend: self.name.clone(), message }.into());
+        }
+        serde_json::from_value(response.result.unwrap_or(Value::Null))
+            .with_context(|| format!("Unexpected result from {} {}", self.name, method))
+    }
+
+    fn require(&self, method: &str) -> Result<()> {
+        if !self.supports(method) {
+            bail!("Backend plugin {} does not support {}", self.name, method);
+        }
+        Ok(())
+    }
+
+    pub fn license(&self, name: &str, version: &str) -> Result<Option<String>> {
+        if !self.supports("license") {
+            return Ok(None);
+        }
+        self.call("license", json!({ "name": name, "version": version }))
+    }
+
+    pub fn metadata(&self, name: &str, version: &str) -> Result<Metadata> {
+        if !self.supports("metadata") {
+            return Ok(Metadata::default());
+        }
+        self.call("metadata", json!({ "name": name, "version": version }))
+    }
+
+    pub fn renamed(&self, name: &str) -> Result<Option<String>> {
+        if !self.supports("renamed") {
+            return Ok(None);
+        }
+        self.call("renamed", json!({ "name": name }))
+    }
+
+    pub fn dependencies(&self, name: &str, version: &str) -> Result<Vec<String>> {
+        if !self.supports("dependencies") {
+            return Ok(Vec::new());
+        }
+        self.call("dependencies", json!({ "name": name, "version": version }))
+    }
+
+    /// Let the plugin clean up after a version it installed, before its files are deleted.
+    pub fn remove(&self, name: &str, version: &str, install_dir: &Path, user: bool) -> Result<()> {
+        if !self.supports("remove") {
+            return Ok(());
+        }
+        self.call(
+            "remove",
+            json!({ "name": name, "version": version, "install_dir": install_dir, "user": user }),
+        )
+    }
+}
+
+impl PackageManager for PluginBackend {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, user: bool) -> Result<Vec<PathBuf>> {
+        self.require("install")?;
+        self.call_in(
+            "install",
+            json!({ "name": name, "version": version, "install_dir": install_dir, "user": user }),
+            Some(install_dir),
+        )
+    }
+
+    fn update(&self, name: &str, version: Option<&str>, install_dir: &Path, user: bool) -> Result<()> {
+        self.require("update")?;
+        self.call_in(
+            "update",
+            json!({ "name": name, "version": version, "install_dir": install_dir, "user": user }),
+            Some(install_dir),
+        )
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        if !self.supports("search") {
+            return Ok(Vec::new());
+        }
+        let results: Vec<PluginSearchResult> = self.call("search", json!({ "query": query }))?;
+        Ok(results
+            .into_iter()
+            .map(|result| SearchResult { name: result.name, description: result.description })
+            .collect())
+    }
+}
+
+impl VersionSource for PluginBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        self.require("versions")?;
+        self.call("versions", json!({ "name": name }))
+    }
+}
+
+/// The WASM plugin for backend `name` in the plugins dir, when updater can run them.
+fn wasm_plugin_path(name: &str) -> Option<PathBuf> {
+    let path = plugins_dir().join(format!("{}{}.wasm", PREFIX, name));
+    (cfg!(feature = "wasm") && path.is_file()).then_some(path)
+}
+
+/// The plugin providing backend `name`, if one is on PATH or in the plugins dir.
+pub fn find(name: &str) -> Result<Option<PluginBackend>> {
+    match which::which(format!("{}{}", PREFIX, name)).ok().or_else(|| wasm_plugin_path(name)) {
+        Some(path) => PluginBackend::load(name, path).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Every plugin on PATH, the first one found winning when a name appears twice.
+/// Plugins that fail to report their capabilities are skipped with a warning.
+pub fn discover() -> Vec<PluginBackend> {
+    let mut found = BTreeMap::new();
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    for dir in env::split_paths(&path_var) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let name = match file_name.strip_prefix(PREFIX) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => continue,
+            };
+            if !found.contains_key(&name) && is_executable(&entry.path()) {
+                found.insert(name, entry.path());
+            }
+        }
+    }
+    // Executables on PATH win over WASM plugins of the same name
+    for entry in fs::read_dir(plugins_dir()).into_iter().flatten().flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(name) = file_name.strip_prefix(PREFIX).and_then(|name| name.strip_suffix(".wasm")) {
+            if let Some(path) = wasm_plugin_path(name).filter(|_| !found.contains_key(name)) {
+                found.insert(name.to_string(), path);
+            }
+        }
+    }
+
+    found
+        .into_iter()
+        .filter_map(|(name, path)| match PluginBackend::load(&name, path) {
+            Ok(plugin) => Some(plugin),
+            Err(e) => {
+                warn!("{:#}", e);
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,445 @@
+//! Packages described by small Rhai scripts or TOML files, for upstreams
+//! whose release schemes no other backend understands.
+//!
+//! Recipes are looked up in the recipes dir and then in every tap, by
+//! priority. A script recipe is a file `<name>.rhai` that defines
+//! `versions()`, returning every version upstream as strings, and either
+//! `url(version)`, returning the archive or binary to download and unpack,
+//! or `install(version)`, laying out the files itself. `metadata()` may
+//! return `#{ description: ..., homepage: ..., repository: ... }`.
+//!
+//! ```rhai
+//! fn versions() {
+//!     github_tags("acme/tool").map(|tag| tag.sub_string(1))
+//! }
+//!
+//! fn url(version) {
+//!     `https://downloads.acme.example/${version}/tool-${os()}-${arch()}.tar.gz`
+//! }
+//! ```
+//!
+//! Scripts can't touch the filesystem or run programs; all they get is
+//! `http_get(url)`, `parse_json(text)`, `github_tags(owner_repo)`, `os()`,
+//! `arch()` and, while installing, `download(url, path)`, `extract(path)`,
+//! `write_file(path, text)`, `make_executable(path)` and `bin(path)` to
+//! name the binaries to expose. Paths are relative to the install dir and
+//! can't leave it. Downloads are checked like any other artifact, against
+//! first-use digests, `[[verify.cosign]]` and `[[verify.minisign]]` rules and
+//! provenance.
+//!
+//! Recipes that only need a download URL can be a `<name>.toml` instead:
+//!
+//! ```toml
+//! description = "Does tool things"
+//! homepage = "https://acme.example/tool"
+//! github = "acme/tool"   # or versions = ["1.2.0", "1.3.0"]
+//! url = "https://downloads.acme.example/{version}/tool-{os}-{arch}.tar.gz"
+//! bins = ["tool"]
+//! ```
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+use super::{collect_binaries, create_placed, Metadata, VersionSource};
+use crate::archive;
+use crate::compat;
+use crate::download;
+use crate::net::{self, SendRetrying};
+use crate::system::{PackageManager, SearchResult};
+use crate::tap;
+use crate::verify;
+use crate::version::{self, Channel};
+
+/// Operations a single recipe call may take before it is stopped.
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+/// Where recipes are looked up.
+pub fn recipes_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    data_dir.join("updater").join("recipes")
+}
+
+/// File extensions of recipes, script recipes winning over TOML ones.
+const EXTENSIONS: [&str; 2] = ["rhai", "toml"];
+
+/// Directories recipes are looked up in, in order.
+pub fn recipe_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![recipes_dir()];
+    dirs.extend(tap::recipe_dirs());
+    dirs
+}
+
+/// The recipe file for `name`, from the first directory that has one.
+fn find(name: &str) -> Option<PathBuf> {
+    recipe_dirs()
+        .into_iter()
+        .flat_map(|dir| EXTENSIONS.map(|extension| dir.join(format!("{}.{}", name, extension))))
+        .find(|path| path.is_file())
+}
+
+/// Names of the recipes in `dir`.
+pub fn names_in(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let extension = path.extension()?.to_str()?;
+            EXTENSIONS.contains(&extension).then(|| path.file_stem()?.to_str().map(str::to_string)).flatten()
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Names of every available recipe, the first one found winning.
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = recipe_dirs().iter().flat_map(|dir| names_in(dir)).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// What the script may change while it runs.
+#[derive(Default)]
+struct Context {
+    install_dir: Option<PathBuf>,
+    /// Version being installed, while installing
+    version: Option<String>,
+    package: String,
+    bins: Vec<PathBuf>,
+}
+
+/// A recipe written as a `<name>.toml` instead of a script.
+#[derive(Deserialize)]
+struct Declarative {
+    /// Download URL, with `{version}`, `{os}` and `{arch}` filled in
+    url: String,
+    /// Versions upstream, when they aren't taken from GitHub
+    #[serde(default)]
+    versions: Vec<String>,
+    /// `owner/repo` whose release tags are the versions, a leading `v` dropped
+    github: Option<String>,
+    /// Binaries to expose, relative to the install dir; every executable when empty
+    #[serde(default)]
+    bins: Vec<String>,
+    #[serde(flatten)]
+    metadata: Metadata,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+}
+
+type ScriptResult<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+fn script_error(e: anyhow::Error) -> Box<EvalAltResult> {
+    format!("{:#}", e).into()
+}
+
+/// `path` inside the install dir, for relative paths that stay in it.
+fn placement(context: &Mutex<Context>, path: &str) -> Result<PathBuf> {
+    let context = context.lock().unwrap();
+    let install_dir = context.install_dir.as_ref().ok_or_else(|| anyhow!("Files can only be placed while installing"))?;
+    super::placement(install_dir, path)
+}
+
+fn http_get(url: &str) -> Result<String> {
+    net::get(url)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .text()
+        .with_context(|| format!("Failed to read {}", url))
+}
+
+fn github_tags(repo: &str) -> Result<Vec<String>> {
+    let url = format!("https://api.github.com/repos/{}/releases?per_page=100", repo);
+    let releases: Vec<GithubRelease> = serde_json::from_str(&http_get(&url)?).context("Failed to parse GitHub releases")?;
+    Ok(releases.into_iter().map(|release| release.tag_name).collect())
+}
+
+/// Download `url` to `dest` and hold it to the same checks as any other
+/// artifact: its first-use digest, configured signatures and provenance.
+fn verified_download(package: &str, version: &str, url: &str, dest: &Path) -> Result<()> {
+    download::fetch(url, dest)?;
+    // Only URLs naming the version keep serving the same file
+    if url.contains(version) {
+        verify::trust_on_first_use(url, url, dest, package, version)?;
+    }
+    verify::signatures(url, url, dest)?;
+    if let Some(repository) = verify::github_release_repo(url) {
+        verify::check_provenance(package, &repository, dest)?;
+    }
+    Ok(())
+}
+
+/// An engine exposing only the recipe API, working on `context`.
+fn engine(context: &Arc<Mutex<Context>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.disable_symbol("eval");
+    // `import` would otherwise read other scripts from disk
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.on_print(|text| debug!("[recipe] {}", text));
+    engine.on_debug(|text, _, _| debug!("[recipe] {}", text));
+
+    engine.register_fn("http_get", |url: &str| -> ScriptResult<String> { http_get(url).map_err(script_error) });
+    engine.register_fn("parse_json", |text: &str| -> ScriptResult<Dynamic> {
+        let value: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        rhai::serde::to_dynamic(value)
+    });
+    engine.register_fn("github_tags", |repo: &str| -> ScriptResult<Array> {
+        Ok(github_tags(repo).map_err(script_error)?.into_iter().map(Dynamic::from).collect())
+    });
+    engine.register_fn("os", || std::env::consts::OS.to_string());
+    engine.register_fn("arch", || compat::target_arch().to_string());
+
+    let ctx = Arc::clone(context);
+    engine.register_fn("download", move |url: &str, path: &str| -> ScriptResult<()> {
+        let dest = placement(&ctx, path).map_err(script_error)?;
+        let (package, version) = {
+            let context = ctx.lock().unwrap();
+            (context.package.clone(), context.version.clone().unwrap_or_default())
+        };
+        verified_download(&package, &version, url, &dest).map_err(script_error)
+    });
+    let ctx = Arc::clone(context);
+    engine.register_fn("extract", move |path: &str| -> ScriptResult<()> {
+        let artifact = placement(&ctx, path).map_err(script_error)?;
+        let (package, install_dir) = {
+            let context = ctx.lock().unwrap();
+            (context.package.clone(), context.install_dir.clone().unwrap_or_default())
+        };
+        archive::extract(&artifact, &package, &install_dir).map_err(script_error)
+    });
+    let ctx = Arc::clone(context);
+    engine.register_fn("write_file", move |path: &str, text: &str| -> ScriptResult<()> {
+        let dest = placement(&ctx, path).map_err(script_error)?;
+        create_placed(&dest).and_then(|mut file| Ok(file.write_all(text.as_bytes())?)).map_err(script_error)
+    });
+    let ctx = Arc::clone(context);
+    engine.register_fn("make_executable", move |path: &str| -> ScriptResult<()> {
+        let dest = placement(&ctx, path).map_err(script_error)?;
+        fs::set_permissions(dest, fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string().into())
+    });
+    let ctx = Arc::clone(context);
+    engine.register_fn("bin", move |path: &str| -> ScriptResult<()> {
+        let dest = placement(&ctx, path).map_err(script_error)?;
+        ctx.lock().unwrap().bins.push(dest);
+        Ok(())
+    });
+    engine
+}
+
+/// A compiled script recipe and the state its calls share.
+struct Script {
+    engine: Engine,
+    ast: AST,
+    context: Arc<Mutex<Context>>,
+}
+
+enum Body {
+    Script(Box<Script>),
+    Declarative(Declarative),
+}
+
+/// A loaded recipe, ready to call into.
+struct Recipe {
+    name: String,
+    body: Body,
+}
+
+impl Recipe {
+    fn load(name: &str) -> Result<Self> {
+        let path = find(name).ok_or_else(|| anyhow!("No recipe for {} in {} or any tap", name, recipes_dir().display()))?;
+        Self::load_from(name, &path)
+    }
+
+    fn load_from(name: &str, path: &Path) -> Result<Self> {
+        let body = if path.extension().is_some_and(|extension| extension == "toml") {
+            let data = fs::read_to_string(path).with_context(|| format!("Failed to read recipe {}", path.display()))?;
+            Body::Declarative(toml::from_str(&data).with_context(|| format!("Failed to parse recipe {}", path.display()))?)
+        } else {
+            let context = Arc::new(Mutex::new(Context { package: name.to_string(), ..Default::default() }));
+            let engine = engine(&context);
+            let ast = engine.compile_file(path.to_path_buf()).map_err(|e| anyhow!("Failed to load recipe {}: {}", path.display(), e))?;
+            Body::Script(Box::new(Script { engine, ast, context }))
+        };
+        Ok(Recipe { name: name.to_string(), body })
+    }
+
+    fn script(&self) -> Option<&Script> {
+        match &self.body {
+            Body::Script(script) => Some(script),
+            Body::Declarative(_) => None,
+        }
+    }
+
+    fn defines(&self, function: &str) -> bool {
+        self.script().is_some_and(|script| script.ast.iter_functions().any(|f| f.name == function))
+    }
+
+    fn call<T: Clone + Send + Sync + 'static>(&self, function: &str, args: impl rhai::FuncArgs) -> Result<T> {
+        let script = self.script().ok_or_else(|| anyhow!("Recipe {} is not a script", self.name))?;
+        script
+            .engine
+            .call_fn::<T>(&mut Scope::new(), &script.ast, function, args)
+            .map_err(|e| anyhow!("Recipe {} {}() failed: {}", self.name, function, e))
+    }
+
+    fn versions(&self) -> Result<Vec<String>> {
+        if let Body::Declarative(recipe) = &self.body {
+            return match &recipe.github {
+                Some(repo) => Ok(github_tags(repo)?.into_iter().map(|tag| tag.strip_prefix('v').map(str::to_string).unwrap_or(tag)).collect()),
+                None => Ok(recipe.versions.clone()),
+            };
+        }
+        let versions: Array = self.call("versions", ())?;
+        versions
+            .into_iter()
+            .map(|v| v.into_string().map_err(|t| anyhow!("Recipe {} versions() returned a {} instead of a string", self.name, t)))
+            .collect()
+    }
+
+    /// Download `url` for `version` into `install_dir` and unpack it there.
+    fn fetch(&self, url: &str, version: &str, install_dir: &Path) -> Result<()> {
+        let file_name = url.rsplit('/').next().filter(|f| !f.is_empty()).unwrap_or(&self.name).to_string();
+        let download_dir = install_dir.join(".artifact");
+        fs::create_dir_all(&download_dir)?;
+        let artifact = download_dir.join(file_name);
+        verified_download(&self.name, version, url, &artifact)?;
+        archive::extract(&artifact, &self.name, install_dir)
+    }
+
+    fn install(&self, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let version = match version.filter(|v| *v != "latest") {
+            Some(version) => version.to_string(),
+            None => version::latest(&self.versions()?, Channel::Stable)
+                .ok_or_else(|| anyhow!("Recipe {} lists no stable version", self.name))?,
+        };
+        let script = match &self.body {
+            Body::Script(script) => script,
+            Body::Declarative(recipe) => {
+                let url = recipe
+                    .url
+                    .replace("{version}", &version)
+                    .replace("{os}", std::env::consts::OS)
+                    .replace("{arch}", compat::target_arch());
+                self.fetch(&url, &version, install_dir)?;
+                let bins = recipe
+                    .bins
+                    .iter()
+                    .map(|bin| super::placement(install_dir, bin).with_context(|| format!("Recipe {} names binary {} outside its install dir", self.name, bin)))
+                    .collect::<Result<Vec<PathBuf>>>()?;
+                if let Some(missing) = bins.iter().find(|bin| !bin.is_file()) {
+                    bail!("Recipe {} names binary {} that the download doesn't contain", self.name, missing.display());
+                }
+                return Ok(if bins.is_empty() { collect_binaries(install_dir) } else { bins });
+            }
+        };
+        {
+            let mut context = script.context.lock().unwrap();
+            context.install_dir = Some(install_dir.to_path_buf());
+            context.version = Some(version.clone());
+        }
+
+        if self.defines("install") {
+            // Whatever install() returns is ignored, only the files it placed count
+            let _: Dynamic = self.call("install", (version,))?;
+        } else if self.defines("url") {
+            let url: String = self.call("url", (version.clone(),))?;
+            self.fetch(&url, &version, install_dir)?;
+        } else {
+            bail!("Recipe {} defines neither install(version) nor url(version)", self.name);
+        }
+
+        let bins = std::mem::take(&mut script.context.lock().unwrap().bins);
+        Ok(if bins.is_empty() { collect_binaries(install_dir) } else { bins })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        if let Body::Declarative(recipe) = &self.body {
+            return Ok(recipe.metadata.clone());
+        }
+        if !self.defines("metadata") {
+            return Ok(Metadata::default());
+        }
+        let metadata: Dynamic = self.call("metadata", ())?;
+        rhai::serde::from_dynamic(&metadata).map_err(|e| anyhow!("Recipe {} metadata() is invalid: {}", self.name, e))
+    }
+}
+
+/// Installs packages by running their recipes.
+pub struct RecipeBackend;
+
+impl PackageManager for RecipeBackend {
+    fn get_name(&self) -> &str {
+        "recipe"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        Recipe::load(name)?.install(version, install_dir)
+    }
+
+    fn update(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        Recipe::load(name)?.install(version, install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        tap::refresh_stale();
+        let query = query.to_lowercase();
+        // Taps are searched through the index built when they were fetched
+        let mut entries = describe(&recipes_dir());
+        entries.extend(tap::indexed());
+        let mut seen = std::collections::HashSet::new();
+        Ok(entries
+            .into_iter()
+            .filter(|entry| seen.insert(entry.name.clone()))
+            .filter(|entry| entry.name.to_lowercase().contains(&query) || entry.description.to_lowercase().contains(&query))
+            .collect())
+    }
+}
+
+impl VersionSource for RecipeBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        Recipe::load(name)?.versions()
+    }
+}
+
+/// Every recipe in `dir` with its description, for searching.
+pub fn describe(dir: &Path) -> Vec<SearchResult> {
+    names_in(dir)
+        .into_iter()
+        .map(|name| {
+            let path = EXTENSIONS.iter().map(|extension| dir.join(format!("{}.{}", name, extension))).find(|path| path.is_file());
+            let description = path
+                .and_then(|path| Recipe::load_from(&name, &path).and_then(|recipe| recipe.metadata()).ok())
+                .and_then(|metadata| metadata.description)
+                .unwrap_or_default();
+            SearchResult { name, description }
+        })
+        .collect()
+}
+
+/// Description and links the recipe for `name` declares.
+pub fn metadata(name: &str) -> Result<Metadata> {
+    Recipe::load(name)?.metadata()
+}
+
+pub fn is_available() -> bool {
+    !names().is_empty()
+}
@@ -0,0 +1,335 @@
+use anyhow::{anyhow, bail, Context, Result};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::{collect_binaries, Metadata, VersionSource};
+use crate::archive;
+use crate::compat;
+use crate::config::{self, RepoKind, Repository};
+use crate::download;
+use crate::error::Error;
+use crate::ipfs;
+use crate::net::{self, SendRetrying};
+use crate::system::{PackageManager, SearchResult};
+use crate::version::{self, Channel};
+
+/// Platform key used for assets that run anywhere, e.g. scripts.
+const ANY_PLATFORM: &str = "any";
+
+/// Package index served by a native updater repository.
+///
+/// The index is JSON (or TOML when the URL ends in `.toml`) and is signed with
+/// ed25519; the hex-encoded signature over the raw index bytes lives next to
+/// it at `<index url>.sig`. With openssl:
+///
+/// ```sh
+/// openssl pkeyutl -sign -rawin -inkey key.pem -in index.json | xxd -p -c 64 > index.json.sig
+/// ```
+///
+/// ```json
+/// {
+///   "packages": {
+///     "tool": {
+///       "description": "Internal tooling",
+///       "homepage": "https://tools.corp.example/tool",
+///       "license": "MIT",
+///       "env": { "TOOL_HOME": "{install_dir}" },
+///       "versions": {
+///         "1.2.0": {
+///           "dependencies": ["libtool"],
+///           "assets": {
+///             "x86_64-linux": { "url": "https://packages.corp.example/tool-1.2.0.tar.gz", "sha256": "…" }
+///           }
+///         }
+///       }
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RepoIndex {
+    #[serde(default)]
+    pub packages: BTreeMap<String, RepoPackage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoPackage {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    /// Where the package's source code lives
+    #[serde(default)]
+    pub repository: Option<String>,
+    /// SPDX license expression covering every version
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Name the package is published under now, e.g. `eza` for `exa`
+    #[serde(default)]
+    pub renamed_to: Option<String>,
+    /// Variables `updater env` exports, `{install_dir}` standing for the
+    /// active version's install dir
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub versions: BTreeMap<String, RepoVersion>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoVersion {
+    /// Names of packages from the same repositories this version needs
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Downloads keyed by `<arch>-<os>`, or `any`
+    #[serde(default)]
+    pub assets: BTreeMap<String, RepoAsset>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoAsset {
+    /// Absolute URL, or a path relative to the index
+    pub url: String,
+    pub sha256: String,
+}
+
+/// The artifact a repository publishes for one version of a package on this platform.
+pub struct ResolvedAsset {
+    pub version: String,
+    /// Download URL, with mirrors applied
+    pub url: String,
+    pub sha256: String,
+    pub dependencies: Vec<String>,
+}
+
+/// Find the asset to install for `name`, the newest stable version when
+/// `version` is `None` or `latest`.
+pub fn resolve(name: &str, version: Option<&str>) -> Result<ResolvedAsset> {
+    let (repo, index) = find_package(name)?;
+    let package = &index.packages[name];
+    let version = match version {
+        Some(v) if v != "latest" => package
+            .versions
+            .keys()
+            .find(|candidate| candidate.trim_start_matches('v') == v.trim_start_matches('v'))
+            .cloned()
+            .ok_or_else(|| anyhow!("{} has no version {} in repository {}", name, v, repo.name))?,
+        _ => {
+            let versions: Vec<String> = package.versions.keys().cloned().collect();
+            version::latest(&versions, Channel::Stable)
+                .ok_or_else(|| anyhow!("{} has no stable release in repository {}", name, repo.name))?
+        }
+    };
+
+    let platform = platform();
+    let release = &package.versions[&version];
+    let asset = release
+        .assets
+        .get(&platform)
+        .or_else(|| release.assets.get(ANY_PLATFORM))
+        .ok_or_else(|| anyhow!("{} {} has no asset for {}", name, version, platform))?;
+
+    let url = config::mirror_url("repo", &asset_url(&index_url(&repo), &asset.url))?;
+    Ok(ResolvedAsset { url, sha256: asset.sha256.clone(), dependencies: release.dependencies.clone(), version })
+}
+
+/// Installs packages from the native repositories listed in the config.
+pub struct RepoBackend;
+
+impl RepoBackend {
+    fn download_and_extract(&self, name: &str, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let ResolvedAsset { url, sha256, .. } = resolve(name, version)?;
+        let file_name = url.rsplit('/').next().filter(|f| !f.is_empty()).unwrap_or(name).to_string();
+        let download_dir = install_dir.join(".artifact");
+        fs::create_dir_all(&download_dir)?;
+        let artifact = download_dir.join(&file_name);
+
+        download::fetch(&url, &artifact)?;
+        let checksum = download::sha256_file(&artifact)?;
+        if !checksum.eq_ignore_ascii_case(&sha256) {
+            fs::remove_file(&artifact).ok();
+            download::forget(&url)?;
+            return Err(Error::Verification(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                file_name, sha256, checksum
+            ))
+            .into());
+        }
+
+        archive::extract(&artifact, name, install_dir)?;
+        Ok(collect_binaries(install_dir))
+    }
+}
+
+impl PackageManager for RepoBackend {
+    fn get_name(&self) -> &str {
+        "repo"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.download_and_extract(name, version, install_dir)
+    }
+
+    fn update(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        self.download_and_extract(name, version, install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let query = query.to_lowercase();
+        let mut results = Vec::new();
+        for repo in config::load_config()?.repos_of(RepoKind::Updater) {
+            let index = match fetch_index(&repo) {
+                Ok(index) => index,
+                Err(e) => {
+                    warn!("Skipping repository {}: {:#}", repo.name, e);
+                    continue;
+                }
+            };
+            for (name, package) in index.packages {
+                if name.to_lowercase().contains(&query) || package.description.to_lowercase().contains(&query) {
+                    results.push(SearchResult {
+                        name,
+                        description: package.description,
+                    });
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl VersionSource for RepoBackend {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        let (_, index) = find_package(name)?;
+        Ok(index.packages[name].versions.keys().cloned().collect())
+    }
+
+    fn revision(&self, name: &str, version: &str) -> Result<Option<String>> {
+        Ok(Some(resolve(name, Some(version))?.sha256))
+    }
+}
+
+/// License the repository declares for `name`.
+pub fn license(name: &str) -> Result<Option<String>> {
+    let (_, mut index) = find_package(name)?;
+    Ok(index.packages.remove(name).and_then(|package| package.license))
+}
+
+/// Description and links the repository lists for `name`.
+pub fn metadata(name: &str) -> Result<Metadata> {
+    let (_, mut index) = find_package(name)?;
+    Ok(index.packages.remove(name).map_or_else(Metadata::default, |package| Metadata {
+        description: Some(package.description).filter(|d| !d.is_empty()),
+        homepage: package.homepage,
+        repository: package.repository,
+    }))
+}
+
+/// Variables the repository says `updater env` should export for `name`.
+pub fn env(name: &str) -> Result<BTreeMap<String, String>> {
+    let (_, mut index) = find_package(name)?;
+    Ok(index.packages.remove(name).map(|package| package.env).unwrap_or_default())
+}
+
+/// Name the repository says `name` moved to, if it was renamed.
+pub fn renamed(name: &str) -> Result<Option<String>> {
+    let (_, mut index) = find_package(name)?;
+    Ok(index.packages.remove(name).and_then(|package| package.renamed_to))
+}
+
+/// Whether any native repositories are configured.
+pub fn is_available() -> bool {
+    config::load_config().is_ok_and(|config| !config.repos_of(RepoKind::Updater).is_empty())
+}
+
+/// The highest priority repository carrying `name`, with its index.
+fn find_package(name: &str) -> Result<(Repository, RepoIndex)> {
+    let repos = config::load_config()?.repos_of(RepoKind::Updater);
+    if repos.is_empty() {
+        bail!("No repositories configured; add one with `updater repo add`");
+    }
+
+    for repo in repos {
+        let index = fetch_index(&repo)?;
+        if index.packages.contains_key(name) {
+            return Ok((repo, index));
+        }
+    }
+    Err(Error::PackageNotFound { name: name.to_string(), suggestions: Vec::new() }.into())
+}
+
+/// Download a repository index and check its signature.
+pub fn fetch_index(repo: &Repository) -> Result<RepoIndex> {
+    let url = config::mirror_url("repo", &index_url(repo))?;
+    let body = fetch_bytes(&url).with_context(|| format!("Failed to fetch index of repository {}", repo.name))?;
+
+    match &repo.public_key {
+        Some(key) => {
+            let signature = fetch_bytes(&format!("{}.sig", url))
+                .with_context(|| format!("Failed to fetch signature of repository {}", repo.name))?;
+            verify_signature(key, &body, &signature)
+                .map_err(|e| Error::Verification(format!("Repository {} failed verification: {:#}", repo.name, e)))?;
+        }
+        None if repo.allow_unsigned => {}
+        None => bail!("Repository {} has no public_key; set allow_unsigned = true to trust it anyway", repo.name),
+    }
+
+    let text = String::from_utf8(body).context("Repository index is not valid UTF-8")?;
+    if url.ends_with(".toml") {
+        toml::from_str(&text).context("Failed to parse repository index")
+    } else {
+        serde_json::from_str(&text).context("Failed to parse repository index")
+    }
+}
+
+fn fetch_bytes(url: &str) -> Result<Vec<u8>> {
+    if net::is_content_addressed(url) {
+        return ipfs::fetch(url);
+    }
+    let bytes = net::get(url)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())?;
+    Ok(bytes.to_vec())
+}
+
+fn verify_signature(public_key: &str, message: &[u8], signature: &[u8]) -> Result<()> {
+    let key: [u8; 32] = hex::decode(public_key.trim())
+        .context("public_key is not valid hex")?
+        .try_into()
+        .map_err(|_| anyhow!("public_key must be 32 bytes"))?;
+    let signature: [u8; 64] = hex::decode(String::from_utf8_lossy(signature).split_whitespace().collect::<String>())
+        .context("Signature is not valid hex")?
+        .try_into()
+        .map_err(|_| anyhow!("Signature must be 64 bytes"))?;
+
+    VerifyingKey::from_bytes(&key)
+        .context("Invalid public_key")?
+        .verify(message, &Signature::from_bytes(&signature))
+        .context("Index signature does not match")
+}
+
+fn index_url(repo: &Repository) -> String {
+    if repo.url.ends_with(".json") || repo.url.ends_with(".toml") {
+        repo.url.clone()
+    } else {
+        format!("{}/index.json", repo.url.trim_end_matches('/'))
+    }
+}
+
+/// Resolve an asset URL relative to the index it was listed in.
+fn asset_url(index_url: &str, asset: &str) -> String {
+    if asset.contains("://") {
+        return asset.to_string();
+    }
+    let base = index_url.rsplit_once('/').map_or(index_url, |(base, _)| base);
+    format!("{}/{}", base, asset.trim_start_matches('/'))
+}
+
+fn platform() -> String {
+    format!("{}-{}", compat::target_arch(), std::env::consts::OS)
+}
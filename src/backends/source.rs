@@ -0,0 +1,226 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+use super::{collect_binaries, run_command, VersionSource};
+use crate::config;
+use crate::sandbox;
+use crate::system::{PackageManager, SearchResult};
+
+/// File in the install dir naming the tag it was built from.
+const BUILT_TAG_FILE: &str = ".updater-tag";
+
+/// How a package is built from its source tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildSystem {
+    Make,
+    Cargo,
+    Cmake,
+    Custom,
+}
+
+/// Everything needed to rebuild a package from source, stored with each installed version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceSpec {
+    pub repo: String,
+    /// Shell command used instead of the detected build system. `PREFIX`
+    /// points at the install directory while it runs.
+    pub build_command: Option<String>,
+}
+
+pub struct SourceBackend {
+    spec: SourceSpec,
+}
+
+impl SourceBackend {
+    pub fn new(spec: SourceSpec) -> Self {
+        SourceBackend { spec }
+    }
+
+    /// Repository URL to fetch from, after applying any configured mirror.
+    fn repo_url(&self) -> Result<String> {
+        config::mirror_url(self.get_name(), &self.spec.repo)
+    }
+
+    /// Newest tag in the repository, preferring the highest semver-looking one.
+    fn latest_tag(&self) -> Result<String> {
+        let tags = list_tags(&self.repo_url()?)?;
+        let newest = tags
+            .iter()
+            .filter_map(|tag| semver::Version::parse(tag.trim_start_matches('v')).ok().map(|v| (v, tag)))
+            .filter(|(v, _)| v.pre.is_empty())
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, tag)| tag.clone());
+
+        newest
+            .or_else(|| tags.last().cloned())
+            .with_context(|| format!("No tags found in {}", self.spec.repo))
+    }
+
+    /// Map a requested version onto an actual tag, accepting both `1.2.3` and `v1.2.3`.
+    fn resolve_tag(&self, version: Option<&str>) -> Result<String> {
+        let version = match version {
+            None | Some("latest") => return self.latest_tag(),
+            Some(v) => v,
+        };
+
+        let tags = list_tags(&self.repo_url()?)?;
+        let candidates = [version.to_string(), format!("v{}", version), version.trim_start_matches('v').to_string()];
+        candidates
+            .into_iter()
+            .find(|candidate| tags.contains(candidate))
+            .with_context(|| format!("Tag {} not found in {}", version, self.spec.repo))
+    }
+
+    fn build_and_install(&self, name: &str, version: Option<&str>, install_dir: &Path) -> Result<Vec<PathBuf>> {
+        let tag = self.resolve_tag(version)?;
+        info!("Building {} from {} at {}", name.yellow(), self.spec.repo.cyan(), tag.cyan());
+
+        let build_dir = std::env::temp_dir().join(format!("updater-build-{}-{}", name, std::process::id()));
+        if build_dir.exists() {
+            fs::remove_dir_all(&build_dir)?;
+        }
+
+        let result = self.build_in(&tag, &build_dir, install_dir);
+        fs::remove_dir_all(&build_dir).ok();
+        result?;
+        fs::write(install_dir.join(BUILT_TAG_FILE), &tag).context("Failed to record the built tag")?;
+
+        let bin_paths = collect_binaries(install_dir);
+        if bin_paths.is_empty() {
+            warn!("Build finished but installed no binaries");
+        }
+        Ok(bin_paths)
+    }
+
+    fn build_in(&self, tag: &str, build_dir: &Path, install_dir: &Path) -> Result<()> {
+        run_command(
+            Command::new("git")
+                .args(["clone", "--depth", "1", "--branch", tag, &self.repo_url()?])
+                .arg(build_dir),
+        )
+        .context("Failed to clone source repository")?;
+
+        let build_system = match &self.spec.build_command {
+            Some(_) => BuildSystem::Custom,
+            None => detect_build_system(build_dir)?,
+        };
+        info!("Using build system: {}", format!("{:?}", build_system).to_lowercase().cyan());
+
+        // Everything after the clone runs the package's own code
+        let step = |cmd: &mut Command| -> Result<String> {
+            run_command(&mut sandbox::confine(self.get_name(), cmd, &[build_dir, install_dir])?)
+        };
+        match build_system {
+            BuildSystem::Make => {
+                step(Command::new("make").current_dir(build_dir))?;
+                step(
+                    Command::new("make")
+                        .arg("install")
+                        .arg(format!("PREFIX={}", install_dir.display()))
+                        .arg(format!("prefix={}", install_dir.display()))
+                        .current_dir(build_dir),
+                )?;
+            }
+            BuildSystem::Cargo => {
+                step(
+                    Command::new("cargo")
+                        .args(["install", "--locked", "--path", "."])
+                        .arg("--root")
+                        .arg(install_dir)
+                        .current_dir(build_dir),
+                )?;
+            }
+            BuildSystem::Cmake => {
+                step(
+                    Command::new("cmake")
+                        .args(["-S", ".", "-B", "build", "-DCMAKE_BUILD_TYPE=Release"])
+                        .arg(format!("-DCMAKE_INSTALL_PREFIX={}", install_dir.display()))
+                        .current_dir(build_dir),
+                )?;
+                step(Command::new("cmake").args(["--build", "build"]).current_dir(build_dir))?;
+                step(Command::new("cmake").args(["--install", "build"]).current_dir(build_dir))?;
+            }
+            BuildSystem::Custom => {
+                let build_command = self.spec.build_command.as_deref().unwrap_or_default();
+                step(
+                    Command::new("sh")
+                        .arg("-c")
+                        .arg(build_command)
+                        .env("PREFIX", install_dir)
+                        .current_dir(build_dir),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl PackageManager for SourceBackend {
+    fn get_name(&self) -> &str {
+        "source"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.build_and_install(name, version, install_dir)
+    }
+
+    fn update(&self, name: &str, _version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        // Rebuild from the newest tag in place, unless that is what was built last time
+        let tag = self.latest_tag()?;
+        if fs::read_to_string(install_dir.join(BUILT_TAG_FILE)).is_ok_and(|built| built.trim() == tag) {
+            info!("{} {} {}", name.yellow(), "is already built from".green(), tag.cyan());
+            return Ok(());
+        }
+        self.build_and_install(name, Some(&tag), install_dir)?;
+        Ok(())
+    }
+
+    fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        // Source builds are addressed by repository URL, there is nothing to search
+        Ok(Vec::new())
+    }
+}
+
+impl VersionSource for SourceBackend {
+    fn available_versions(&self, _name: &str) -> Result<Vec<String>> {
+        list_tags(&self.repo_url()?)
+    }
+
+    fn revision(&self, _name: &str, version: &str) -> Result<Option<String>> {
+        let reference = format!("refs/tags/{}", version);
+        let output = run_command(Command::new("git").args(["ls-remote", "--tags"]).arg(self.repo_url()?).arg(&reference))
+            .context("Failed to look up repository tag")?;
+        Ok(output.split_whitespace().next().map(str::to_string))
+    }
+}
+
+pub fn list_tags(repo: &str) -> Result<Vec<String>> {
+    let output = run_command(Command::new("git").args(["ls-remote", "--tags", "--refs", repo]))
+        .context("Failed to list repository tags")?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .filter_map(|reference| reference.strip_prefix("refs/tags/"))
+        .map(str::to_string)
+        .collect())
+}
+
+fn detect_build_system(build_dir: &Path) -> Result<BuildSystem> {
+    if build_dir.join("Cargo.toml").exists() {
+        Ok(BuildSystem::Cargo)
+    } else if build_dir.join("CMakeLists.txt").exists() {
+        Ok(BuildSystem::Cmake)
+    } else if build_dir.join("Makefile").exists() || build_dir.join("makefile").exists() {
+        Ok(BuildSystem::Make)
+    } else {
+        bail!("Could not detect a build system, pass --build-command")
+    }
+}
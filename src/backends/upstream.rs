@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Command;
+
+use super::{run_command, Metadata, VersionSource};
+use crate::config;
+use crate::net::{self, SendRetrying};
+
+/// Version listing for the distro and language package managers in `system`.
+pub struct AptVersions;
+pub struct DnfVersions;
+pub struct CratesIoVersions;
+pub struct PypiVersions;
+pub struct NpmVersions;
+
+impl VersionSource for AptVersions {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        // Lines look like: `ripgrep | 13.0.0-4 | http://archive.ubuntu.com/... Packages`
+        let output = run_command(Command::new("apt-cache").arg("madison").arg(name))?;
+        let mut versions: Vec<String> = output
+            .lines()
+            .filter_map(|line| line.split('|').nth(1))
+            .map(|v| v.trim().to_string())
+            .collect();
+        versions.dedup();
+        Ok(versions)
+    }
+}
+
+impl VersionSource for DnfVersions {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        // Lines look like: `ripgrep.x86_64    13.0.0-6.fc39    fedora`
+        let output = run_command(Command::new("dnf").args(["list", "--quiet", "--showduplicates"]).arg(name))?;
+        let prefix = format!("{}.", name);
+        let mut versions: Vec<String> = output
+            .lines()
+            .filter(|line| line.starts_with(&prefix))
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .map(str::to_string)
+            .collect();
+        versions.dedup();
+        Ok(versions)
+    }
+}
+
+#[derive(Deserialize)]
+struct CrateVersions {
+    versions: Vec<CrateVersion>,
+}
+
+#[derive(Deserialize)]
+struct CrateVersion {
+    num: String,
+    yanked: bool,
+}
+
+impl VersionSource for CratesIoVersions {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        // crates.io rejects requests without a user agent
+        let response: CrateVersions = net::get(&config::mirror_url("cargo", &format!("https://crates.io/api/v1/crates/{}/versions", name))?)
+            .header("User-Agent", concat!("updater/", env!("CARGO_PKG_VERSION")))
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to list versions of {} on crates.io", name))?
+            .json()
+            .context("Failed to parse crates.io versions")?;
+
+        Ok(response.versions.into_iter().filter(|v| !v.yanked).map(|v| v.num).collect())
+    }
+}
+
+#[derive(Deserialize)]
+struct PypiProject {
+    releases: HashMap<String, Vec<serde_json::Value>>,
+}
+
+impl VersionSource for PypiVersions {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        let project: PypiProject = net::get(&config::mirror_url("pip", &format!("https://pypi.org/pypi/{}/json", name))?)
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to list versions of {} on PyPI", name))?
+            .json()
+            .context("Failed to parse PyPI project")?;
+
+        // Releases without any uploaded files cannot be installed
+        Ok(project
+            .releases
+            .into_iter()
+            .filter(|(_, files)| !files.is_empty())
+            .map(|(version, _)| version)
+            .collect())
+    }
+}
+
+impl VersionSource for NpmVersions {
+    fn available_versions(&self, name: &str) -> Result<Vec<String>> {
+        let output = run_command(Command::new("npm").args(["view", "--json"]).arg(name).arg("versions"))?;
+        // A package with a single version prints a plain string instead of a list
+        let versions = match serde_json::from_str::<serde_json::Value>(&output).context("Failed to parse npm versions")? {
+            serde_json::Value::Array(values) => values.into_iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            serde_json::Value::String(version) => vec![version],
+            _ => Vec::new(),
+        };
+        Ok(versions)
+    }
+}
+
+#[derive(Deserialize)]
+struct CrateLicenses {
+    versions: Vec<CrateLicense>,
+}
+
+#[derive(Deserialize)]
+struct CrateLicense {
+    num: String,
+    license: Option<String>,
+}
+
+/// License of a crate version, or of its newest version for `latest`.
+pub fn crates_io_license(name: &str, version: &str) -> Result<Option<String>> {
+    let response: CrateLicenses = net::get(&config::mirror_url("cargo", &format!("https://crates.io/api/v1/crates/{}/versions", name))?)
+        .header("User-Agent", concat!("updater/", env!("CARGO_PKG_VERSION")))
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to look up {} on crates.io", name))?
+        .json()
+        .context("Failed to parse crates.io versions")?;
+
+    // Versions are listed newest first
+    let mut versions = response.versions.into_iter();
+    let found = if version == "latest" { versions.next() } else { versions.find(|v| v.num == version) };
+    Ok(found.and_then(|v| v.license))
+}
+
+#[derive(Deserialize)]
+struct CrateInfo {
+    #[serde(rename = "crate")]
+    krate: CrateDetails,
+}
+
+#[derive(Deserialize)]
+struct CrateDetails {
+    description: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+}
+
+/// Description and links crates.io lists for a crate.
+pub fn crates_io_metadata(name: &str) -> Result<Metadata> {
+    let info: CrateInfo = net::get(&config::mirror_url("cargo", &format!("https://crates.io/api/v1/crates/{}", name))?)
+        .header("User-Agent", concat!("updater/", env!("CARGO_PKG_VERSION")))
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to look up {} on crates.io", name))?
+        .json()
+        .context("Failed to parse crates.io crate")?;
+
+    let CrateDetails { description, homepage, repository } = info.krate;
+    Ok(Metadata { description, homepage, repository })
+}
+
+#[derive(Deserialize)]
+struct PypiRelease {
+    info: PypiInfo,
+}
+
+#[derive(Deserialize)]
+struct PypiInfo {
+    license_expression: Option<String>,
+    license: Option<String>,
+    summary: Option<String>,
+    home_page: Option<String>,
+    project_urls: Option<HashMap<String, String>>,
+}
+
+fn pypi_release(name: &str, version: &str) -> Result<PypiRelease> {
+    let path = if version == "latest" { format!("{}/json", name) } else { format!("{}/{}/json", name, version) };
+    net::get(&config::mirror_url("pip", &format!("https://pypi.org/pypi/{}", path))?)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to look up {} on PyPI", name))?
+        .json()
+        .context("Failed to parse PyPI release")
+}
+
+/// License of a PyPI release. Older projects put free text, sometimes the
+/// whole license, in `license`, so only a short one-line value is used.
+pub fn pypi_license(name: &str, version: &str) -> Result<Option<String>> {
+    let release = pypi_release(name, version)?;
+    let license = release
+        .info
+        .license_expression
+        .or(release.info.license.filter(|l| l.len() <= 64 && !l.contains('\n')));
+    Ok(license)
+}
+
+/// Summary and links of a PyPI release. Project URL labels are free-form, so
+/// the common spellings are tried.
+pub fn pypi_metadata(name: &str, version: &str) -> Result<Metadata> {
+    let info = pypi_release(name, version)?.info;
+    let urls = info.project_urls.unwrap_or_default();
+    let url = |labels: &[&str]| {
+        urls.iter().find(|(label, _)| labels.iter().any(|wanted| label.eq_ignore_ascii_case(wanted))).map(|(_, url)| url.clone())
+    };
+    Ok(Metadata {
+        description: info.summary,
+        homepage: info.home_page.filter(|url| !url.is_empty()).or_else(|| url(&["homepage", "home"])),
+        repository: url(&["source", "source code", "repository", "code"]),
+    })
+}
+
+#[derive(Deserialize)]
+struct NpmManifest {
+    license: Option<serde_json::Value>,
+    description: Option<String>,
+    homepage: Option<String>,
+    repository: Option<serde_json::Value>,
+}
+
+fn npm_manifest(name: &str, version: &str) -> Result<NpmManifest> {
+    net::get(&config::mirror_url("npm", &format!("https://registry.npmjs.org/{}/{}", name, version))?)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to look up {} on the npm registry", name))?
+        .json()
+        .context("Failed to parse npm package manifest")
+}
+
+/// Description and links of an npm package version.
+pub fn npm_metadata(name: &str, version: &str) -> Result<Metadata> {
+    let manifest = npm_manifest(name, version)?;
+    // Like licenses, repositories are either a plain string or `{"type": "git", "url": ...}`
+    let repository = match manifest.repository {
+        Some(serde_json::Value::String(url)) => Some(url),
+        Some(serde_json::Value::Object(repository)) => repository.get("url").and_then(|u| u.as_str()).map(str::to_string),
+        _ => None,
+    };
+    Ok(Metadata { description: manifest.description, homepage: manifest.homepage, repository })
+}
+
+/// License of an npm package version, `latest` being a dist-tag the registry resolves.
+pub fn npm_license(name: &str, version: &str) -> Result<Option<String>> {
+    let manifest = npm_manifest(name, version)?;
+
+    // Old packages use `{"type": "MIT", "url": ...}` instead of a plain string
+    Ok(match manifest.license {
+        Some(serde_json::Value::String(license)) => Some(license),
+        Some(serde_json::Value::Object(license)) => license.get("type").and_then(|t| t.as_str()).map(str::to_string),
+        _ => None,
+    })
+}
@@ -0,0 +1,240 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::asset::{self, Platform};
+use super::collect_binaries;
+use crate::archive;
+use crate::compat;
+use crate::config;
+use crate::download;
+use crate::net::{self, SendRetrying};
+use crate::system::{PackageManager, SearchResult};
+use crate::verify;
+
+/// Directory inside each install dir holding the downloaded artifact, kept
+/// around as the base for delta updates.
+const ARTIFACT_DIR: &str = ".artifact";
+
+/// Download location for packages installed straight from a URL.
+///
+/// Besides an artifact, the URL can be a GitHub repository like
+/// `https://github.com/owner/repo`, installing from its releases, or a
+/// directory listing ending in `/`. The asset that fits this system is then
+/// picked from the release or listing (see `asset.rs`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlSpec {
+    /// Artifact URL, where `{version}` is replaced with the version being
+    /// installed, `{os}` and `{arch}` with the platform installed for
+    pub url: String,
+    /// Pattern naming the asset to pick instead of the one that looks right
+    #[serde(default)]
+    pub asset: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// `owner/repo` for URLs of a GitHub repository or its releases page.
+fn github_repo(url: &str) -> Option<String> {
+    let path = url.strip_prefix("https://github.com/")?.trim_end_matches('/');
+    let path = path.strip_suffix("/releases").unwrap_or(path);
+    let (owner, repo) = path.split_once('/')?;
+    (!owner.is_empty() && !repo.is_empty() && !repo.contains('/')).then(|| format!("{}/{}", owner, repo))
+}
+
+/// Names and download URLs of the assets of `repo`'s release of `version`.
+fn github_assets(repo: &str, version: &str) -> Result<Vec<(String, String)>> {
+    let api = format!("https://api.github.com/repos/{}/releases", repo);
+    let candidates = if version == "latest" {
+        vec![format!("{}/latest", api)]
+    } else {
+        let version = version.trim_start_matches('v');
+        vec![format!("{}/tags/v{}", api, version), format!("{}/tags/{}", api, version)]
+    };
+    for url in candidates {
+        let response = net::get(&config::mirror_url("github", &url)?).send_retrying().with_context(|| format!("Failed to fetch {}", url))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            continue;
+        }
+        let release: GithubRelease = response
+            .error_for_status()
+            .with_context(|| format!("Failed to fetch {}", url))?
+            .json()
+            .context("Failed to parse GitHub release")?;
+        return Ok(release.assets.into_iter().map(|asset| (asset.name, asset.browser_download_url)).collect());
+    }
+    bail!("{} has no release {}", repo, version)
+}
+
+/// Names and URLs of the files a directory listing links to.
+fn listed_assets(url: &str) -> Result<Vec<(String, String)>> {
+    let base = reqwest::Url::parse(&net::http_url(url)).with_context(|| format!("Invalid URL {}", url))?;
+    let page = net::get(url)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .text()?;
+    let mut assets: Vec<(String, String)> = page
+        .split("href=")
+        .skip(1)
+        .filter_map(|rest| {
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let link = rest[1..].split(quote).next()?;
+            let target = base.join(link).ok()?;
+            // Subdirectories, sorting links and the like
+            if target.query().is_some() || link.ends_with('/') {
+                return None;
+            }
+            let name = target.path_segments()?.next_back()?.to_string();
+            // Keep IPFS files under their CID so what is downloaded gets checked against it
+            let target = if net::is_content_addressed(url) { format!("{}{}", url, name) } else { target.to_string() };
+            (!name.is_empty()).then_some((name, target))
+        })
+        .collect();
+    assets.dedup();
+    Ok(assets)
+}
+
+pub struct UrlBackend {
+    spec: UrlSpec,
+}
+
+impl UrlBackend {
+    pub fn new(spec: UrlSpec) -> Self {
+        UrlBackend { spec }
+    }
+
+    /// Where upstream publishes the artifact for `version`, before mirrors
+    /// rewrite it. Verification rules and pins are matched against this one.
+    fn upstream_url(&self, version: Option<&str>) -> Result<String> {
+        let version = version.unwrap_or("latest");
+        let url = self
+            .spec
+            .url
+            .replace("{version}", version.trim_start_matches('v'))
+            .replace("{os}", std::env::consts::OS)
+            .replace("{arch}", compat::target_arch());
+        if let Some(repo) = github_repo(&url) {
+            self.pick(&github_assets(&repo, version)?, &url)
+        } else if url.ends_with('/') {
+            self.pick(&listed_assets(&url)?, &url)
+        } else {
+            Ok(url)
+        }
+    }
+
+    /// The URL the artifact for `version` is downloaded from.
+    fn artifact_url(&self, version: Option<&str>) -> Result<String> {
+        config::mirror_url(self.get_name(), &self.upstream_url(version)?)
+    }
+
+    /// The URL of the asset among `assets` to install, offered at `from`.
+    fn pick(&self, assets: &[(String, String)], from: &str) -> Result<String> {
+        let names: Vec<&str> = assets.iter().map(|(name, _)| name.as_str()).collect();
+        let platform = Platform::current();
+        match asset::select(&names, &platform, self.spec.asset.as_deref()) {
+            Some(name) => Ok(assets.iter().find(|(n, _)| n == name).map(|(_, url)| url.clone()).unwrap_or_default()),
+            None if self.spec.asset.is_some() => bail!("No asset at {} matches {}", from, self.spec.asset.as_deref().unwrap_or_default()),
+            None => bail!(
+                "None of the assets at {} fit {} with {}, pick one with --asset: {}",
+                from,
+                platform.arch,
+                platform.libc,
+                names.join(", ")
+            ),
+        }
+    }
+
+    /// Unversioned URLs point at whatever is newest, so the cache is never
+    /// trusted for them. IPFS content never changes under its CID.
+    fn is_versioned(&self, version: Option<&str>) -> bool {
+        if net::is_content_addressed(&self.spec.url) {
+            return true;
+        }
+        let templated = self.spec.url.contains("{version}") || github_repo(&self.spec.url).is_some();
+        templated && !matches!(version, None | Some("latest"))
+    }
+
+    /// The artifact installing `version` fetches through the download cache,
+    /// for ones that can be downloaded ahead of time.
+    pub fn cacheable_url(&self, version: Option<&str>) -> Option<String> {
+        self.is_versioned(version).then(|| self.artifact_url(version).ok()).flatten()
+    }
+
+    fn download_and_extract(&self, name: &str, version: Option<&str>, install_dir: &Path, refresh: bool) -> Result<Vec<PathBuf>> {
+        let upstream = self.upstream_url(version)?;
+        let url = config::mirror_url(self.get_name(), &upstream)?;
+        if refresh || !self.is_versioned(version) {
+            download::forget(&url)?;
+        }
+        let file_name = url.rsplit('/').next().filter(|f| !f.is_empty()).unwrap_or(name).to_string();
+
+        let artifact_dir = install_dir.join(ARTIFACT_DIR);
+        let base = previous_artifact(install_dir);
+        fs::create_dir_all(&artifact_dir)?;
+        let artifact = artifact_dir.join(&file_name);
+
+        download::fetch_with_delta(&url, &artifact, name, base.as_ref().map(|(path, v)| (path.as_path(), v.as_str())))?;
+        // Nothing else vouches for what a URL serves
+        if self.is_versioned(version) {
+            verify::trust_on_first_use(&upstream, &url, &artifact, name, version.unwrap_or("latest"))?;
+        }
+        verify::signatures(&upstream, &url, &artifact)?;
+        if let Some(repository) = github_repo(&self.spec.url).or_else(|| verify::github_release_repo(&upstream)) {
+            verify::check_provenance(name, &repository, &artifact)?;
+        }
+        archive::extract(&artifact, name, install_dir)?;
+
+        Ok(collect_binaries(install_dir))
+    }
+}
+
+impl PackageManager for UrlBackend {
+    fn get_name(&self) -> &str {
+        "url"
+    }
+
+    fn install(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<Vec<PathBuf>> {
+        self.download_and_extract(name, version, install_dir, false)
+    }
+
+    fn update(&self, name: &str, version: Option<&str>, install_dir: &Path, _user: bool) -> Result<()> {
+        self.download_and_extract(name, version, install_dir, true)?;
+        Ok(())
+    }
+
+    fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        Ok(Vec::new())
+    }
+}
+
+/// The most recently downloaded artifact of another version of the same package.
+fn previous_artifact(install_dir: &Path) -> Option<(PathBuf, String)> {
+    let package_dir = install_dir.parent()?;
+    let mut candidates = Vec::new();
+
+    for version_dir in fs::read_dir(package_dir).ok()?.flatten() {
+        let version_path = version_dir.path();
+        if version_path == install_dir {
+            continue;
+        }
+        let version = version_dir.file_name().to_string_lossy().into_owned();
+        for artifact in fs::read_dir(version_path.join(ARTIFACT_DIR)).into_iter().flatten().flatten() {
+            let modified = artifact.metadata().and_then(|m| m.modified()).ok();
+            candidates.push((modified, artifact.path(), version.clone()));
+        }
+    }
+
+    candidates.sort_by_key(|(modified, _, _)| *modified);
+    candidates.pop().map(|(_, path, version)| (path, version))
+}
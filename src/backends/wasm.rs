@@ -0,0 +1,237 @@
+//! Backend plugins compiled to WebAssembly, run in-process with wasmtime.
+//!
+//! A WASM plugin is a module named `updater-backend-<name>.wasm` in the
+//! plugins dir. It speaks the same JSON requests and responses as executable
+//! plugins (see `plugin.rs`), but can only reach the outside world through
+//! the host functions below: no filesystem, environment, clock or sockets of
+//! its own, bounded memory and a fuel budget per call.
+//!
+//! The module exports `memory`, `alloc(len: i32) -> i32` returning space for
+//! `len` bytes, and `handle(ptr: i32, len: i32) -> i64` taking the request and
+//! returning the response as `(ptr << 32) | len`. It may import from the
+//! `updater` module, where strings and buffers are passed as pointer and
+//! length into its memory and `-1` means failure, with the reason logged:
+//!
+//! - `log(ptr, len)` writes a line to the command log
+//! - `http_get(url_ptr, url_len) -> i64` returns the body of a GET as `(ptr << 32) | len`
+//! - `fetch(url_ptr, url_len, path_ptr, path_len) -> i32` downloads to a path in the install dir
+//! - `write_file(path_ptr, path_len, data_ptr, data_len, executable) -> i32` writes a file in the install dir
+//! - `extract(path_ptr, path_len) -> i32` unpacks an archive placed in the install dir into it
+//!
+//! Install dir paths are relative and can't leave it. Fetches go out without
+//! stored credentials, and only to the hosts listed for the plugin under
+//! `[plugins.hosts]` when it has an entry there.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::{debug, warn};
+use wasmtime::{Caller, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::create_placed;
+use crate::archive;
+use crate::config;
+use crate::net::{self, SendRetrying};
+
+/// Instructions a single call may run before it is stopped.
+const FUEL: u64 = 20_000_000_000;
+
+/// Most memory a plugin instance may grow to.
+const MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Largest body `http_get` hands to a plugin; bigger downloads go through `fetch`.
+const HTTP_GET_LIMIT: u64 = 16 * 1024 * 1024;
+
+struct HostState {
+    plugin: String,
+    /// Where file placement is allowed, for methods that install
+    install_dir: Option<PathBuf>,
+    /// Package being installed, for naming bare binaries on `extract`
+    package: Option<String>,
+    hosts: Option<Vec<String>>,
+    limits: StoreLimits,
+}
+
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).expect("Failed to set up the WASM engine")
+    })
+}
+
+/// Compiled modules by path, so a plugin is only compiled once per run.
+fn module(path: &Path) -> Result<Module> {
+    static MODULES: OnceLock<Mutex<HashMap<PathBuf, Module>>> = OnceLock::new();
+    let mut modules = MODULES.get_or_init(Default::default).lock().unwrap();
+    if let Some(module) = modules.get(path) {
+        return Ok(module.clone());
+    }
+    let module = Module::from_file(engine(), path).with_context(|| format!("Failed to load WASM plugin {}", path.display()))?;
+    modules.insert(path.to_path_buf(), module.clone());
+    Ok(module)
+}
+
+/// Send `request` to the plugin at `path` and return its raw response.
+pub fn call(plugin: &str, path: &Path, request: &[u8], install_dir: Option<&Path>, package: Option<&str>) -> Result<Vec<u8>> {
+    let module = module(path)?;
+    let mut linker = Linker::new(engine());
+    link_host_functions(&mut linker)?;
+
+    let state = HostState {
+        plugin: plugin.to_string(),
+        install_dir: install_dir.map(Path::to_path_buf),
+        package: package.map(str::to_string),
+        hosts: config::load_config()?.plugins.hosts.remove(plugin),
+        limits: StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build(),
+    };
+    let mut store = Store::new(engine(), state);
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(FUEL)?;
+
+    let instance = linker.instantiate(&mut store, &module).with_context(|| format!("Failed to start WASM plugin {}", plugin))?;
+    let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| anyhow!("WASM plugin {} exports no memory", plugin))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+    let handle = instance.get_typed_func::<(i32, i32), i64>(&mut store, "handle")?;
+
+    let ptr = alloc.call(&mut store, request.len() as i32)?;
+    memory.write(&mut store, ptr as usize, request)?;
+    let packed = handle.call(&mut store, (ptr, request.len() as i32)).with_context(|| format!("WASM plugin {} crashed", plugin))?;
+    let (ptr, len) = unpack(packed);
+    let mut response = vec![0; len];
+    memory.read(&store, ptr, &mut response).with_context(|| format!("WASM plugin {} returned an invalid response", plugin))?;
+    Ok(response)
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    ((packed as u64 >> 32) as usize, (packed as u64 & 0xffff_ffff) as usize)
+}
+
+fn guest_memory(caller: &mut Caller<'_, HostState>) -> Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => bail!("WASM plugin exports no memory"),
+    }
+}
+
+fn read_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>> {
+    let memory = guest_memory(caller)?;
+    let mut bytes = vec![0; len as usize];
+    memory.read(&*caller, ptr as usize, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).context("WASM plugin passed a string that isn't UTF-8")
+}
+
+/// Copy `bytes` into memory the plugin allocates for them.
+fn write_guest(caller: &mut Caller<'_, HostState>, bytes: &[u8]) -> Result<i64> {
+    let alloc = match caller.get_export("alloc") {
+        Some(Extern::Func(alloc)) => alloc.typed::<i32, i32>(&*caller)?,
+        _ => bail!("WASM plugin exports no alloc"),
+    };
+    let ptr = alloc.call(&mut *caller, bytes.len() as i32)?;
+    guest_memory(caller)?.write(&mut *caller, ptr as usize, bytes)?;
+    Ok(((ptr as u32 as i64) << 32) | bytes.len() as i64)
+}
+
+/// `path` inside the install dir, for relative paths that stay in it.
+fn placement(state: &HostState, path: &str) -> Result<PathBuf> {
+    let install_dir = state.install_dir.as_ref().ok_or_else(|| anyhow!("Files can only be placed while installing"))?;
+    super::placement(install_dir, path)
+}
+
+fn check_host(state: &HostState, url: &str) -> Result<String> {
+    let http_url = net::http_url(url);
+    let parsed = reqwest::Url::parse(&http_url).with_context(|| format!("Invalid URL {}", url))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("{} is not an HTTP URL", url);
+    }
+    if let Some(hosts) = &state.hosts {
+        let host = parsed.host_str().unwrap_or_default();
+        if !hosts.iter().any(|allowed| allowed == host) {
+            bail!("{} may not fetch from {}", state.plugin, host);
+        }
+    }
+    Ok(http_url)
+}
+
+/// Log why a host function failed and report it to the plugin as `-1`.
+fn report<T: From<i8>>(caller: &Caller<'_, HostState>, function: &str, result: Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        warn!("WASM plugin {} {} failed: {:#}", caller.data().plugin, function, e);
+        T::from(-1)
+    })
+}
+
+fn link_host_functions(linker: &mut Linker<HostState>) -> Result<()> {
+    linker.func_wrap("updater", "log", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+        if let Ok(message) = read_string(&mut caller, ptr, len) {
+            debug!("[{}] {}", caller.data().plugin, message);
+        }
+    })?;
+
+    linker.func_wrap("updater", "http_get", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i64 {
+        let result = (|| {
+            let url = read_string(&mut caller, ptr, len)?;
+            let url = check_host(caller.data(), &url)?;
+            let response = net::client().get(&url).send_retrying().and_then(|r| r.error_for_status())?;
+            let mut body = Vec::new();
+            response.take(HTTP_GET_LIMIT).read_to_end(&mut body)?;
+            write_guest(&mut caller, &body)
+        })();
+        report(&caller, "http_get", result)
+    })?;
+
+    linker.func_wrap(
+        "updater",
+        "fetch",
+        |mut caller: Caller<'_, HostState>, url_ptr: i32, url_len: i32, path_ptr: i32, path_len: i32| -> i32 {
+            let result = (|| {
+                let (url, path) = (read_string(&mut caller, url_ptr, url_len)?, read_string(&mut caller, path_ptr, path_len)?);
+                let url = check_host(caller.data(), &url)?;
+                let dest = placement(caller.data(), &path)?;
+                let mut response = net::client().get(&url).send_retrying().and_then(|r| r.error_for_status())?;
+                io::copy(&mut response, &mut create_placed(&dest)?)?;
+                Ok(0)
+            })();
+            report(&caller, "fetch", result)
+        },
+    )?;
+
+    linker.func_wrap(
+        "updater",
+        "write_file",
+        |mut caller: Caller<'_, HostState>, path_ptr: i32, path_len: i32, data_ptr: i32, data_len: i32, executable: i32| -> i32 {
+            let result = (|| {
+                let (path, data) = (read_string(&mut caller, path_ptr, path_len)?, read_bytes(&mut caller, data_ptr, data_len)?);
+                let dest = placement(caller.data(), &path)?;
+                create_placed(&dest)?.write_all(&data)?;
+                if executable != 0 {
+                    fs::set_permissions(&dest, fs::Permissions::from_mode(0o755))?;
+                }
+                Ok(0)
+            })();
+            report(&caller, "write_file", result)
+        },
+    )?;
+
+    linker.func_wrap("updater", "extract", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i32 {
+        let result = (|| {
+            let path = read_string(&mut caller, ptr, len)?;
+            let artifact = placement(caller.data(), &path)?;
+            let state = caller.data();
+            let install_dir = state.install_dir.as_ref().unwrap();
+            archive::extract(&artifact, state.package.as_deref().unwrap_or(&state.plugin), install_dir)?;
+            Ok(0)
+        })();
+        report(&caller, "extract", result)
+    })?;
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::backends::local as local_package;
+use crate::backends::run_command;
+use crate::manifest::{self, PackageSpec};
+use crate::package::{self, Package, PackageVersion};
+use crate::privilege;
+use crate::transaction;
+use crate::version;
+
+const INDEX_FILE: &str = "bundle.json";
+
+/// Metadata stored at the root of a bundle next to the `files/` tree.
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleIndex {
+    created: String,
+    packages: HashMap<String, Package>,
+}
+
+fn staging_dir(purpose: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("updater-bundle-{}-{}", purpose, std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// The installed version satisfying a manifest entry, if there is one.
+fn installed_version(package: Option<&Package>, spec: &PackageSpec) -> Option<String> {
+    let package = package?;
+    match &spec.version {
+        Some(v) if version::is_constraint(v) => {
+            let req = version::parse_constraint(v).ok()?;
+            package.active_version.clone().filter(|active| version::parse(active).is_some_and(|parsed| req.matches(&parsed)))
+        }
+        Some(v) => package.versions.contains_key(v).then(|| v.clone()),
+        None => package.active_version.clone(),
+    }
+}
+
+/// Whether a version lives entirely inside updater's install dir (or came
+/// from a package file kept there), so copying that dir captures it.
+fn is_bundleable(pkg_version: &PackageVersion) -> bool {
+    pkg_version.local_file.is_some()
+        || (pkg_version.install_path.is_dir()
+            && pkg_version.bin_paths.iter().all(|bin| bin.starts_with(&pkg_version.install_path)))
+}
+
+/// Install everything in a manifest (if needed) and pack it into a tar bundle.
+pub fn create(manifest_path: &Path, output: &Path) -> Result<()> {
+    let manifest = manifest::load_manifest(manifest_path)?;
+    let staging = staging_dir("create")?;
+    let result = create_in(&manifest, &staging, output);
+    fs::remove_dir_all(&staging).ok();
+    result
+}
+
+fn create_in(manifest: &manifest::Manifest, staging: &Path, output: &Path) -> Result<()> {
+    let mut bundled: HashMap<String, Package> = HashMap::new();
+
+    for (name, entry) in &manifest.packages {
+        let spec = entry.spec();
+        let installed = installed_version(package::load_packages()?.get(name), &spec);
+        let version = match installed {
+            Some(version) => version,
+            None => package::install(name, spec.install_options())?,
+        };
+
+        let packages = package::load_packages()?;
+        let package = &packages[name];
+        let pkg_version = &package.versions[&version];
+        if !is_bundleable(pkg_version) {
+            warn!("{} {} {}", "Skipping".yellow(), name.yellow().bold(), "(installed system-wide by its package manager)".yellow());
+            continue;
+        }
+
+        let dest = staging.join("files").join(name).join(&version);
+        fs::create_dir_all(dest.parent().unwrap())?;
+        run_command(Command::new("cp").arg("-a").arg(&pkg_version.install_path).arg(&dest))
+            .with_context(|| format!("Failed to copy files of {}", name))?;
+
+        let mut entry = package.clone();
+        entry.versions.retain(|v, _| *v == version);
+        entry.active_version = Some(version.clone());
+        bundled.insert(name.clone(), entry);
+        info!("{} {} {}", "Bundled".green(), name.yellow().bold(), version.cyan());
+    }
+
+    let index = BundleIndex {
+        created: chrono::Local::now().to_rfc3339(),
+        packages: bundled,
+    };
+    let data = serde_json::to_string_pretty(&index).context("Failed to serialize bundle index")?;
+    fs::write(staging.join(INDEX_FILE), data)?;
+
+    run_command(Command::new("tar").arg("-cf").arg(output).arg("-C").arg(staging).arg("."))
+        .context("Failed to write bundle")?;
+    info!("{} {}", "Wrote bundle".green(), output.display().to_string().cyan());
+    Ok(())
+}
+
+/// Install every package in a bundle without touching the network.
+pub fn install(bundle: &Path) -> Result<()> {
+    if !bundle.is_file() {
+        bail!("Bundle not found: {}", bundle.display());
+    }
+    let staging = staging_dir("install")?;
+    let result = install_from(bundle, &staging);
+    fs::remove_dir_all(&staging).ok();
+    result
+}
+
+/// Bundle indexes come from wherever the bundle did, so a name or version
+/// must be a single path component before it is joined onto install dirs.
+fn check_component(what: &str, value: &str) -> Result<()> {
+    let mut components = Path::new(value).components();
+    if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+        bail!("Bundle has an invalid package {} {:?}", what, value);
+    }
+    Ok(())
+}
+
+fn install_from(bundle: &Path, staging: &Path) -> Result<()> {
+    run_command(Command::new("tar").arg("-xf").arg(bundle).arg("-C").arg(staging)).context("Failed to extract bundle")?;
+    let data = fs::read_to_string(staging.join(INDEX_FILE)).context("Bundle has no index")?;
+    let index: BundleIndex = serde_json::from_str(&data).context("Failed to parse bundle index")?;
+
+    for (name, bundled) in &index.packages {
+        check_component("name", name)?;
+        for version in bundled.versions.keys() {
+            check_component("version", version)?;
+        }
+    }
+
+    let mut packages = package::load_packages()?;
+    for (name, mut bundled) in index.packages {
+        let versions = std::mem::take(&mut bundled.versions);
+        for (version, mut pkg_version) in versions {
+            let already_installed = packages.get(&name).is_some_and(|p| p.versions.contains_key(&version));
+            if already_installed {
+                info!("{} {} {}", name.yellow().bold(), version.cyan(), "is already installed".green());
+                continue;
+            }
+
+            let dest = package::base_install_path(!bundled.system).join(&name).join(&version);
+            let files = staging.join("files").join(&name).join(&version);
+            relocate(&mut pkg_version, &files, &dest)?;
+            privilege::place(&files, &dest).with_context(|| format!("Failed to copy files of {}", name))?;
+            if let Some(file) = &pkg_version.local_file {
+                let local = local_package::inspect(file)?;
+                pkg_version.bin_paths = local_package::install(&local, file)?;
+            }
+            pkg_version.install_date = chrono::Local::now().to_rfc3339();
+
+            let package = packages.entry(name.clone()).or_insert_with(|| Package {
+                active_version: None,
+                ..bundled.clone()
+            });
+            package.versions.insert(version.clone(), pkg_version);
+            if package.active_version.is_none() {
+                package.active_version = Some(version.clone());
+            }
+            info!("{} {} {}", "Installed".green(), name.yellow().bold(), version.cyan());
+        }
+    }
+
+    package::save_packages(&packages)
+}
+
+/// Point a bundled or promoted version at its new install dir, fixing up absolute paths
+/// baked into scripts and symlinks under `tree`, where its files are until they move there.
+pub(crate) fn relocate(pkg_version: &mut PackageVersion, tree: &Path, dest: &Path) -> Result<()> {
+    let old_root = pkg_version.install_path.clone();
+    let moved = |path: &Path| -> PathBuf {
+        match path.strip_prefix(&old_root) {
+            Ok(rest) => dest.join(rest),
+            Err(_) => path.to_path_buf(),
+        }
+    };
+
+    pkg_version.bin_paths = pkg_version.bin_paths.iter().map(|bin| moved(bin)).collect();
+    pkg_version.local_file = pkg_version.local_file.as_deref().map(moved);
+    pkg_version.files = pkg_version.files.iter().map(|file| moved(file)).collect();
+    pkg_version.checksums = std::mem::take(&mut pkg_version.checksums).into_iter().map(|(file, sum)| (moved(&file), sum)).collect();
+    pkg_version.install_path = dest.to_path_buf();
+    transaction::relocate_tree(tree, &old_root, dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_versions_must_be_one_component() {
+        assert!(check_component("name", "ripgrep").is_ok());
+        assert!(check_component("version", "1.2.3").is_ok());
+        for bad in ["", ".", "..", "../etc", "a/b", "/abs"] {
+            assert!(check_component("name", bad).is_err(), "{:?} accepted", bad);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Upstream release notes, from GitHub releases for packages built from or
+//! downloaded off GitHub, and from the distro changelog for apt packages.
+//! Notes are cached per installed version so they can be read after the fact.
+
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::debug;
+
+use crate::backends::run_command;
+use crate::config;
+use crate::error::Error;
+use crate::net::{self, SendRetrying};
+use crate::package::{self, PackageVersion};
+use crate::version;
+
+/// Notes published for one release.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    pub notes: String,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    draft: bool,
+}
+
+fn same_version(a: &str, b: &str) -> bool {
+    a.trim_start_matches('v') == b.trim_start_matches('v')
+}
+
+fn notes_dir(name: &str) -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    // Names like `@scope/pkg` and `github.com/x/y` must stay one path component
+    data_dir.join("updater").join("notes").join(name.replace('/', "%2F"))
+}
+
+fn notes_path(name: &str, version: &str) -> PathBuf {
+    notes_dir(name).join(format!("{}.md", version.replace('/', "%2F")))
+}
+
+/// Keep the notes cached for a package that was renamed under its new name.
+pub fn rename(old: &str, new: &str) {
+    let (from, to) = (notes_dir(old), notes_dir(new));
+    if !from.exists() || to.exists() {
+        return;
+    }
+    if let Err(e) = fs::rename(&from, &to) {
+        debug!("Could not move release notes of {} to {}: {}", old, new, e);
+    }
+}
+
+/// Notes cached for `version`, if any were fetched before.
+pub fn cached(name: &str, version: &str) -> Option<String> {
+    fs::read_to_string(notes_path(name, version)).ok()
+}
+
+fn store(name: &str, release: &Release, version: &str) {
+    let path = notes_path(name, version);
+    let written = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(&path, &release.notes));
+    if let Err(e) = written {
+        debug!("Could not cache release notes of {} {}: {}", name, version, e);
+    }
+}
+
+/// Fetch and cache the notes of a freshly installed version. Failures are
+/// only logged, since plenty of packages publish no notes at all.
+pub fn record(name: &str, pm_name: &str, info: &PackageVersion, version: &str) {
+    if version == "latest" || cached(name, version).is_some() {
+        return;
+    }
+    match releases(name, pm_name, info) {
+        Ok(releases) => {
+            if let Some(release) = releases.iter().find(|release| same_version(&release.version, version)) {
+                store(name, release, version);
+            }
+        }
+        Err(e) => debug!("No release notes for {} {}: {:#}", name, version, e),
+    }
+}
+
+/// `owner/repo` of the GitHub repository a version was built or downloaded from.
+fn github_repo(info: &PackageVersion) -> Option<String> {
+    let url = info.source.as_ref().map(|spec| spec.repo.as_str()).or(info.url.as_ref().map(|spec| spec.url.as_str()))?;
+    let path = url.strip_prefix("https://github.com/").or_else(|| url.strip_prefix("git@github.com:"))?;
+    let mut parts = path.split('/');
+    let owner = parts.next().filter(|s| !s.is_empty())?;
+    let repo = parts.next().filter(|s| !s.is_empty())?.trim_end_matches(".git");
+    Some(format!("{}/{}", owner, repo))
+}
+
+fn github_releases(repo: &str) -> Result<Vec<Release>> {
+    let url = config::mirror_url("github", &format!("https://api.github.com/repos/{}/releases?per_page=100", repo))?;
+    // The GitHub API rejects requests without a user agent
+    let releases: Vec<GithubRelease> = net::get(&url)
+        .header("User-Agent", concat!("updater/", env!("CARGO_PKG_VERSION")))
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch releases of {}", repo))?
+        .json()
+        .context("Failed to parse GitHub releases")?;
+    Ok(releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .map(|release| Release { version: release.tag_name, notes: release.body.unwrap_or_default() })
+        .collect())
+}
+
+/// Entries of a Debian changelog, which start with `name (version) dist; urgency=...`.
+fn apt_changelog(name: &str) -> Result<Vec<Release>> {
+    let output = run_command(Command::new("apt-get").arg("changelog").arg(name))?;
+    let mut releases: Vec<Release> = Vec::new();
+    for line in output.lines() {
+        let header = line.strip_prefix(name).and_then(|rest| rest.trim_start().strip_prefix('(')).and_then(|rest| rest.split_once(')'));
+        match (header, releases.last_mut()) {
+            (Some((version, _)), _) => releases.push(Release { version: version.to_string(), notes: String::new() }),
+            (None, Some(release)) => {
+                release.notes.push_str(line);
+                release.notes.push('\n');
+            }
+            (None, None) => {}
+        }
+    }
+    Ok(releases)
+}
+
+/// Every release the backend or upstream publishes notes for, in no particular order.
+pub fn releases(name: &str, pm_name: &str, info: &PackageVersion) -> Result<Vec<Release>> {
+    if let Some(repo) = github_repo(info) {
+        return github_releases(&repo);
+    }
+    match pm_name {
+        "apt" => apt_changelog(name),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Notes for the releases after `from` up to and including `to`, oldest
+/// first. With no usable `from`, only the notes of `to` itself.
+pub fn between(name: &str, pm_name: &str, info: &PackageVersion, from: &str, to: &str) -> Result<Vec<Release>> {
+    let mut releases: Vec<Release> = releases(name, pm_name, info)?
+        .into_iter()
+        .filter(|release| {
+            let is_target = same_version(&release.version, to);
+            let in_range = version::parse(from).is_some()
+                && version::compare(&release.version, from).is_gt()
+                && version::compare(&release.version, to).is_le();
+            is_target || in_range
+        })
+        .collect();
+    releases.sort_by(|a, b| version::compare(&a.version, &b.version));
+    Ok(releases)
+}
+
+/// Print what changed between the installed and target versions, if upstream says.
+pub fn show(name: &str, pm_name: &str, info: &PackageVersion, from: &str, to: &str) {
+    let releases = match between(name, pm_name, info, from, to) {
+        Ok(releases) => releases,
+        Err(e) => {
+            debug!("No changelog for {}: {:#}", name, e);
+            return;
+        }
+    };
+    if releases.is_empty() {
+        return;
+    }
+
+    println!("{} {} {} -> {}", "Changes in".bold(), name.yellow().bold(), from.cyan(), to.cyan());
+    for release in &releases {
+        println!();
+        println!("{}", release.version.green().bold());
+        print_notes(&release.notes);
+    }
+    println!();
+    if let Some(release) = releases.iter().find(|release| same_version(&release.version, to)) {
+        store(name, release, to);
+    }
+}
+
+fn print_notes(notes: &str) {
+    let notes = notes.trim();
+    if notes.is_empty() {
+        println!("  {}", "No release notes".dimmed());
+    }
+    for line in notes.lines() {
+        println!("  {}", line.trim_end());
+    }
+}
+
+/// Print the cached release notes of an installed version, the active one by
+/// default. Versions installed before notes were cached are fetched now.
+pub fn notes(name: &str, version: Option<&str>) -> Result<()> {
+    let packages = package::load_packages()?;
+    let package = packages.get(name).ok_or_else(|| package::package_not_found(name, &packages))?;
+    let version = match version.or(package.active_version.as_deref()) {
+        Some(version) => version.to_string(),
+        None => return Err(Error::VersionNotFound { package: name.to_string(), version: "active".to_string() }.into()),
+    };
+    let info = package.versions.get(&version).ok_or_else(|| Error::VersionNotFound {
+        package: name.to_string(),
+        version: version.clone(),
+    })?;
+
+    if cached(name, &version).is_none() {
+        record(name, info.package_manager.as_deref().unwrap_or_default(), info, &version);
+    }
+    match cached(name, &version) {
+        Some(notes) => {
+            println!("{} {}", name.yellow().bold(), version.green().bold());
+            print_notes(&notes);
+        }
+        None => println!("No release notes recorded for {} {}", name, version),
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Which C library the system runs on, and whether downloaded binaries can
+//! run against it.
+//!
+//! Binaries linked against glibc record the newest glibc symbol version they
+//! use (`GLIBC_2.34` and the like); on an older glibc, or on musl, they fail
+//! to start. Installs warn about such binaries and ask before going ahead.
+//!
+//! Packages can also be installed for another architecture with `--arch`,
+//! into a separate prefix for building images or chroots. Backends then pick
+//! artifacts for the target architecture instead of this machine's.
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use tracing::warn;
+
+use crate::backends::asset;
+use crate::error::Error;
+use crate::prompt;
+
+/// Set while installing for another architecture, and inherited by the
+/// commands updater runs meanwhile.
+const ARCH_VAR: &str = "UPDATER_ARCH";
+
+/// glibc releases older than this count as old, and musl or static builds
+/// are preferred over glibc ones for them.
+pub const OLD_GLIBC: GlibcVersion = GlibcVersion(2, 28);
+
+/// C library the system's binaries link against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Gnu,
+    Musl,
+}
+
+impl fmt::Display for Libc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Libc::Gnu => write!(f, "glibc"),
+            Libc::Musl => write!(f, "musl"),
+        }
+    }
+}
+
+/// A glibc release, like 2.35.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GlibcVersion(pub u32, pub u32);
+
+impl GlibcVersion {
+    /// Parse `2.35`, ignoring anything after the minor version.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor: String = parts.next()?.chars().take_while(|c| c.is_ascii_digit()).collect();
+        Some(GlibcVersion(major, minor.parse().ok()?))
+    }
+}
+
+impl fmt::Display for GlibcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.0, self.1)
+    }
+}
+
+/// The system's C library, told apart by the dynamic loader installed.
+pub fn libc() -> Libc {
+    static LIBC: OnceLock<Libc> = OnceLock::new();
+    *LIBC.get_or_init(|| {
+        let has_musl_loader = ["/lib", "/usr/lib"]
+            .iter()
+            .flat_map(|dir| fs::read_dir(dir).into_iter().flatten().flatten())
+            .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"));
+        if has_musl_loader && glibc_version().is_none() {
+            Libc::Musl
+        } else {
+            Libc::Gnu
+        }
+    })
+}
+
+/// The installed glibc release, from `getconf` or else `ldd --version`.
+pub fn glibc_version() -> Option<GlibcVersion> {
+    static VERSION: OnceLock<Option<GlibcVersion>> = OnceLock::new();
+    *VERSION.get_or_init(|| {
+        let run = |program: &str, args: &[&str]| -> Option<String> {
+            let output = Command::new(program).args(args).output().ok()?;
+            output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        };
+        // `glibc 2.35`
+        if let Some(version) = run("getconf", &["GNU_LIBC_VERSION"]).and_then(|out| out.split_whitespace().nth(1).and_then(GlibcVersion::parse)) {
+            return Some(version);
+        }
+        // `ldd (GNU libc) 2.35`; musl's ldd prints its version to stderr and fails
+        let first_line = run("ldd", &["--version"])?.lines().next()?.to_string();
+        first_line.contains("libc").then(|| first_line.split_whitespace().last().and_then(GlibcVersion::parse)).flatten()
+    })
+}
+
+/// Whether builds for musl, or static ones, suit this system better than glibc builds.
+pub fn prefers_static() -> bool {
+    libc() == Libc::Musl || glibc_version().is_some_and(|version| version < OLD_GLIBC)
+}
+
+/// The newest glibc `binary` needs, `None` for binaries that don't link
+/// against glibc or aren't ELF files at all.
+pub fn required_glibc(binary: &Path) -> Option<GlibcVersion> {
+    let data = fs::read(binary).ok()?;
+    if !data.starts_with(b"\x7fELF") {
+        return None;
+    }
+    const MARKER: &[u8] = b"GLIBC_";
+    data.windows(MARKER.len())
+        .enumerate()
+        .filter(|(_, window)| *window == MARKER)
+        .filter_map(|(start, _)| {
+            let rest = &data[start + MARKER.len()..];
+            let end = rest.iter().position(|b| !(b.is_ascii_digit() || *b == b'.')).unwrap_or(rest.len());
+            GlibcVersion::parse(std::str::from_utf8(&rest[..end]).ok()?)
+        })
+        .max()
+}
+
+/// The architecture packages are installed for, as `std::env::consts::ARCH`
+/// spells it: the one given with `--arch`, otherwise this machine's.
+pub fn target_arch() -> &'static str {
+    std::env::var(ARCH_VAR).ok().and_then(|arch| asset::canonical_arch(&arch)).unwrap_or(std::env::consts::ARCH)
+}
+
+/// Whether packages are being installed for an architecture other than this machine's.
+pub fn is_foreign() -> bool {
+    target_arch() != std::env::consts::ARCH
+}
+
+/// Run `f` installing for `arch`, when given, then go back to the previous target.
+pub fn with_target_arch<T>(arch: Option<&str>, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let arch = match arch {
+        Some(arch) => asset::canonical_arch(arch).ok_or_else(|| anyhow!("Unknown architecture {}", arch))?,
+        None => return f(),
+    };
+    let previous = std::env::var_os(ARCH_VAR);
+    std::env::set_var(ARCH_VAR, arch);
+    let result = f();
+    match previous {
+        Some(previous) => std::env::set_var(ARCH_VAR, previous),
+        None => std::env::remove_var(ARCH_VAR),
+    }
+    result
+}
+
+/// Why `binary` won't run here, if it won't.
+fn incompatibility(binary: &Path) -> Option<String> {
+    let required = required_glibc(binary)?;
+    match (libc(), glibc_version()) {
+        (Libc::Musl, _) => Some(format!("needs glibc {} but this system uses musl", required)),
+        (Libc::Gnu, Some(available)) if required > available => Some(format!("needs glibc {} but this system has {}", required, available)),
+        _ => None,
+    }
+}
+
+/// Warn about binaries among `bin_paths` that need a newer glibc than the
+/// system has, or glibc at all on musl, and ask whether to install `name` anyway.
+pub fn confirm_runnable(name: &str, bin_paths: &[PathBuf]) -> Result<()> {
+    // Binaries for another architecture aren't meant to run here
+    if is_foreign() {
+        return Ok(());
+    }
+    let problems: Vec<(String, String)> = bin_paths
+        .iter()
+        .filter_map(|bin| {
+            let file_name = bin.file_name()?.to_string_lossy().into_owned();
+            incompatibility(bin).map(|reason| (file_name, reason))
+        })
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for (bin, reason) in &problems {
+        warn!("{} {} {}", bin.yellow().bold(), reason.yellow(), "and will likely fail to start".yellow());
+    }
+    if !prompt::confirm(&format!("Install {} anyway?", name))? {
+        return Err(Error::Aborted(format!("Aborted installing {}", name)).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,498 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::version::UpdateLevel;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub daemon: DaemonConfig,
+    pub notifications: NotificationsConfig,
+    pub search: SearchConfig,
+    pub mirrors: Vec<Mirror>,
+    pub repos: Vec<Repository>,
+    pub hooks: HooksConfig,
+    pub install: InstallConfig,
+    pub downloads: DownloadsConfig,
+    pub network: NetworkConfig,
+    pub ipfs: IpfsConfig,
+    pub plugins: PluginsConfig,
+    pub sandbox: SandboxConfig,
+    pub fs_snapshots: FsSnapshotsConfig,
+    pub verify: VerifyConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Hours between outdated checks
+    pub check_interval_hours: u64,
+    /// Updates the daemon applies on its own; anything bigger is only logged
+    pub auto_update: UpdateLevel,
+    /// Address to serve Prometheus metrics on at `/metrics`, e.g. `127.0.0.1:9464`
+    pub metrics_listen: Option<SocketAddr>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            check_interval_hours: 24,
+            auto_update: UpdateLevel::Off,
+            metrics_listen: None,
+        }
+    }
+}
+
+/// Desktop notifications, and where the daemon reports to besides the
+/// desktop, for servers nobody logs into:
+///
+/// ```toml
+/// [[notifications.webhooks]]
+/// url = "https://hooks.slack.com/services/T000/B000/XXXX"
+/// kind = "slack"
+/// template = "{host}: {summary}\n{packages}"
+///
+/// [notifications.email]
+/// to = ["ops@example.com"]
+/// events = ["updated"]
+/// ```
+///
+/// Templates can use `{host}`, `{event}`, `{summary}`, `{count}` and
+/// `{packages}`, one `name current -> latest` line per package.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Send desktop notifications from `outdated` and the daemon
+    pub enabled: bool,
+    pub webhooks: Vec<Webhook>,
+    pub email: Option<EmailConfig>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        NotificationsConfig { enabled: true, webhooks: Vec::new(), email: None }
+    }
+}
+
+/// What the daemon reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyEvent {
+    /// Updates became available that the daemon won't apply on its own
+    Outdated,
+    /// The daemon applied updates
+    Updated,
+}
+
+impl fmt::Display for NotifyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotifyEvent::Outdated => write!(f, "outdated"),
+            NotifyEvent::Updated => write!(f, "updated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    /// The event, message and packages as JSON
+    #[default]
+    Generic,
+    /// Slack incoming webhook
+    Slack,
+    /// Matrix hookshot webhook
+    Matrix,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Events to send; every event when empty
+    #[serde(default)]
+    pub events: Vec<NotifyEvent>,
+    /// Message template, `{host}: {summary}\n{packages}` by default
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Mail sent through the local `sendmail`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    pub to: Vec<String>,
+    pub from: Option<String>,
+    /// Events to send; every event when empty
+    pub events: Vec<NotifyEvent>,
+    /// Body template, `{host}: {summary}\n{packages}` by default
+    pub template: Option<String>,
+    /// Subject template, `[updater] {host}: {summary}` by default
+    pub subject: Option<String>,
+    /// sendmail-compatible command, `sendmail` by default
+    pub sendmail: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Hours before cached search results are refreshed in the background
+    pub cache_ttl_hours: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig { cache_ttl_hours: 24 }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstallConfig {
+    /// Without root, install system packages under the user prefix until
+    /// `updater promote` moves them, instead of failing
+    pub rootless_fallback: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadsConfig {
+    /// Artifacts fetched at once when installing or updating several packages
+    pub concurrency: usize,
+    /// Fetch large artifacts over BitTorrent with aria2c when a torrent is
+    /// published next to them as `<url>.torrent`, or on `seed_server`
+    pub p2p: bool,
+    /// Internal seed server publishing torrents as `<seed_server>/<sha256 of url>.torrent`
+    pub seed_server: Option<String>,
+    /// Smaller artifacts are always downloaded directly
+    pub p2p_min_size_mb: u64,
+}
+
+impl Default for DownloadsConfig {
+    fn default() -> Self {
+        DownloadsConfig { concurrency: 4, p2p: false, seed_server: None, p2p_min_size_mb: 100 }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Seconds to wait for a connection before giving up
+    pub connect_timeout_secs: u64,
+    /// Seconds a whole request, body included, may take; 0 for no limit
+    pub timeout_secs: u64,
+    /// Times a request is retried after a dropped connection, timeout,
+    /// rate limit or server error, waiting longer after each
+    pub retries: u32,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig { connect_timeout_secs: 15, timeout_secs: 0, retries: 3 }
+    }
+}
+
+/// What WASM backend plugins may reach, e.g.
+///
+/// ```toml
+/// [plugins.hosts]
+/// acme = ["api.acme.example", "downloads.acme.example"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    /// Hosts each plugin may fetch from; plugins without an entry may fetch from any
+    pub hosts: BTreeMap<String, Vec<String>>,
+}
+
+/// Where `ipfs://` and `ipns://` URLs, in package sources and repository
+/// indexes alike, are fetched from.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpfsConfig {
+    /// HTTP gateway, e.g. `https://ipfs.io`. Defaults to the local daemon's
+    /// gateway on 127.0.0.1:8080 when it is running and to `https://ipfs.io`
+    /// otherwise. Files at `ipfs://` URLs are checked against their CID
+    /// whichever gateway serves them; `ipns://` names can't be.
+    pub gateway: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FsSnapshotsConfig {
+    /// Snapshot install prefixes on btrfs or ZFS before updates and removals
+    pub enabled: bool,
+}
+
+/// Signatures artifacts and images from some sources must carry, e.g.
+///
+/// ```toml
+/// [[verify.cosign]]
+/// source = "https://github.com/sigstore/cosign/releases/"
+/// identity = "keyless@projectsigstore.iam.gserviceaccount.com"
+/// issuer = "https://accounts.google.com"
+///
+/// [[verify.cosign]]
+/// source = "ghcr.io/corp/"
+/// key = "/etc/updater/corp-cosign.pub"
+///
+/// [[verify.minisign]]
+/// source = "https://github.com/jedisct1/"
+/// public_key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3"
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerifyConfig {
+    pub cosign: Vec<CosignRule>,
+    pub minisign: Vec<MinisignRule>,
+}
+
+/// The key downloads from URLs starting with `source` have to be signed with,
+/// by minisign or OpenBSD's signify. The longest matching source applies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinisignRule {
+    pub source: String,
+    /// Public key as the base64 line of a `.pub` file
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// `.pub` file holding the public key, instead of `public_key`
+    #[serde(default)]
+    pub key: Option<PathBuf>,
+    /// Signatures are signify's `.sig` files rather than minisign's `.minisig`
+    #[serde(default)]
+    pub signify: bool,
+}
+
+/// Who has to have signed downloads from URLs, or images from references,
+/// starting with `source`. The longest matching source applies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CosignRule {
+    pub source: String,
+    /// Certificate identity of keyless signatures, usually an email address or workflow URL
+    #[serde(default)]
+    pub identity: Option<String>,
+    /// Regular expression the certificate identity has to match, instead of `identity`
+    #[serde(default)]
+    pub identity_regexp: Option<String>,
+    /// OIDC issuer of keyless signatures, e.g. `https://token.actions.githubusercontent.com`
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub issuer_regexp: Option<String>,
+    /// Public key signatures are made with, instead of keyless signing
+    #[serde(default)]
+    pub key: Option<PathBuf>,
+    /// Accept signatures missing from the Rekor transparency log, e.g. for
+    /// keys used offline
+    #[serde(default)]
+    pub skip_tlog: bool,
+}
+
+/// Backends whose install and build steps run inside bubblewrap, e.g.
+///
+/// ```toml
+/// [sandbox.backends.source]
+/// network = true
+/// readable = ["/home/me/.rustup"]
+/// writable = ["/home/me/.cargo"]
+/// ```
+///
+/// The source and gem backends and plugins can be sandboxed; a policy for any
+/// other backend is an error rather than silently ignored. Recipes aren't
+/// among them: their scripts run inside updater, limited by the recipe API
+/// rather than a sandbox. Hooks are the user's own commands and run unconfined.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    pub backends: BTreeMap<String, SandboxPolicy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxPolicy {
+    pub enabled: bool,
+    /// Allow network access, e.g. for builds that fetch their dependencies
+    pub network: bool,
+    /// Paths under the home dir, which is hidden, that stay visible read-only,
+    /// e.g. toolchains
+    pub readable: Vec<PathBuf>,
+    /// Paths writable besides the build and install dirs
+    pub writable: Vec<PathBuf>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        SandboxPolicy { enabled: true, network: false, readable: Vec::new(), writable: Vec::new() }
+    }
+}
+
+/// Shell commands run around package operations, globally and per package:
+///
+/// ```toml
+/// [hooks]
+/// post_update = ["notify-send \"$UPDATER_PACKAGE updated\""]
+///
+/// [hooks.packages.caddy]
+/// post_update = ["systemctl --user restart caddy"]
+/// ```
+///
+/// Executables in `hooks/<event>` and `hooks/<package>/<event>` next to the
+/// config file run as well.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    #[serde(flatten)]
+    pub global: HookCommands,
+    pub packages: BTreeMap<String, HookCommands>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookCommands {
+    pub pre_install: Vec<String>,
+    pub post_install: Vec<String>,
+    pub pre_update: Vec<String>,
+    pub post_update: Vec<String>,
+    pub pre_remove: Vec<String>,
+    pub post_remove: Vec<String>,
+}
+
+/// URL rewrite rule redirecting downloads to a mirror, e.g.
+///
+/// ```toml
+/// [[mirrors]]
+/// backend = "pip"
+/// from = "https://pypi.org/"
+/// to = "https://pypi.corp.example/"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mirror {
+    /// Backend the rule applies to; applies to every backend when omitted
+    pub backend: Option<String>,
+    /// URL prefix to replace
+    pub from: String,
+    /// Replacement prefix
+    pub to: String,
+}
+
+/// Extra package source, e.g. a native updater repository serving a signed
+/// package index:
+///
+/// ```toml
+/// [[repos]]
+/// name = "corp"
+/// url = "https://packages.corp.example/index.json"
+/// public_key = "3b6a27bcceb6a42d62a3a8d02a6f0d73653215771de243a63ac048a18b59da29"
+/// priority = 10
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Repository {
+    pub name: String,
+    /// Index URL for native repos (`index.json` is appended when it points at
+    /// a directory), remote URL for flatpak, git URL for taps, registry
+    /// namespace for OCI
+    pub url: String,
+    #[serde(default)]
+    pub kind: RepoKind,
+    /// Repositories with a higher priority are searched and installed from first
+    #[serde(default)]
+    pub priority: i32,
+    /// Hex-encoded ed25519 key the index signature must verify against
+    pub public_key: Option<String>,
+    /// Accept the index without a signature when no key is configured
+    #[serde(default)]
+    pub allow_unsigned: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoKind {
+    /// Native updater repository with a signed index
+    #[default]
+    Updater,
+    /// Flatpak remote
+    Flatpak,
+    /// Git repository of package recipes
+    Tap,
+    /// Container registry namespace, like `ghcr.io/corp`, of packages
+    /// published as OCI artifacts
+    Oci,
+}
+
+impl fmt::Display for RepoKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepoKind::Updater => write!(f, "updater"),
+            RepoKind::Flatpak => write!(f, "flatpak"),
+            RepoKind::Tap => write!(f, "tap"),
+            RepoKind::Oci => write!(f, "oci"),
+        }
+    }
+}
+
+impl Config {
+    /// Repositories of `kind`, highest priority first.
+    pub fn repos_of(&self, kind: RepoKind) -> Vec<Repository> {
+        let mut repos: Vec<Repository> = self.repos.iter().filter(|repo| repo.kind == kind).cloned().collect();
+        repos.sort_by_key(|repo| std::cmp::Reverse(repo.priority));
+        repos
+    }
+
+    /// Rewrite `url` with the first matching mirror rule for `backend`.
+    pub fn mirror_url(&self, backend: &str, url: &str) -> String {
+        self.mirrors
+            .iter()
+            .filter(|mirror| mirror.backend.as_deref().is_none_or(|b| b == backend))
+            .find_map(|mirror| url.strip_prefix(&mirror.from).map(|rest| format!("{}{}", mirror.to, rest)))
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    /// The cosign rule for the most specific source `location` starts with.
+    pub fn cosign_rule(&self, location: &str) -> Option<&CosignRule> {
+        self.verify.cosign.iter().filter(|rule| location.starts_with(&rule.source)).max_by_key(|rule| rule.source.len())
+    }
+
+    /// The minisign rule for the most specific source `url` starts with.
+    pub fn minisign_rule(&self, url: &str) -> Option<&MinisignRule> {
+        self.verify.minisign.iter().filter(|rule| url.starts_with(&rule.source)).max_by_key(|rule| rule.source.len())
+    }
+}
+
+/// Rewrite `url` according to the configured mirrors.
+pub fn mirror_url(backend: &str, url: &str) -> Result<String> {
+    Ok(load_config()?.mirror_url(backend, url))
+}
+
+pub fn get_config_path() -> PathBuf {
+    let config_dir = dirs::config_dir().expect("Could not determine config directory");
+    config_dir.join("updater").join("config.toml")
+}
+
+pub fn load_config() -> Result<Config> {
+    let config_path = get_config_path();
+    if !config_path.exists() {
+        return Ok(Config::default());
+    }
+
+    let data = fs::read_to_string(&config_path).context("Failed to read config file")?;
+    let config: Config = toml::from_str(&data).context("Failed to parse config file")?;
+    Ok(config)
+}
+
+pub fn save_config(config: &Config) -> Result<()> {
+    let config_path = get_config_path();
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent).context("Failed to create config directory")?;
+    }
+
+    let data = toml::to_string_pretty(config).context("Failed to serialize config")?;
+    fs::write(&config_path, data).context("Failed to write config file")?;
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use tracing::info;
+
+use crate::config::{self, NotifyEvent};
+use crate::metrics;
+use crate::notify;
+use crate::package;
+use crate::prompt_status;
+
+/// How often the daemon wakes up to notice pause/resume and config changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// State shared between the running daemon and the control commands.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DaemonState {
+    pub pid: Option<u32>,
+    pub paused: bool,
+    pub last_check: Option<String>,
+    pub outdated: Vec<package::OutdatedPackage>,
+}
+
+fn get_daemon_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    let daemon_dir = data_dir.join("updater");
+    fs::create_dir_all(&daemon_dir).expect("Failed to create data directory");
+    daemon_dir
+}
+
+pub fn get_state_path() -> PathBuf {
+    get_daemon_dir().join("daemon.json")
+}
+
+pub fn get_log_path() -> PathBuf {
+    get_daemon_dir().join("daemon.log")
+}
+
+pub fn load_state() -> Result<DaemonState> {
+    let state_path = get_state_path();
+    if !state_path.exists() {
+        return Ok(DaemonState::default());
+    }
+
+    let data = fs::read_to_string(&state_path).context("Failed to read daemon state")?;
+    serde_json::from_str(&data).context("Failed to parse daemon state")
+}
+
+pub fn save_state(state: &DaemonState) -> Result<()> {
+    let data = serde_json::to_string_pretty(state).context("Failed to serialize daemon state")?;
+    fs::write(get_state_path(), data).context("Failed to write daemon state")?;
+    Ok(())
+}
+
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// The pid of the daemon if one is currently running.
+fn running_pid(state: &DaemonState) -> Option<u32> {
+    state.pid.filter(|pid| is_running(*pid) && *pid != std::process::id())
+}
+
+/// Whether a daemon is running with its checks not paused.
+pub fn is_checking() -> bool {
+    load_state().is_ok_and(|state| !state.paused && running_pid(&state).is_some())
+}
+
+fn log(message: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_log_path())
+        .context("Failed to open daemon log")?;
+    writeln!(file, "{} {}", chrono::Local::now().to_rfc3339(), message)?;
+    Ok(())
+}
+
+/// Start the daemon detached from the terminal.
+pub fn start() -> Result<()> {
+    let state = load_state()?;
+    if let Some(pid) = running_pid(&state) {
+        info!("{} {}", "Daemon is already running with pid".yellow(), pid.to_string().cyan());
+        return Ok(());
+    }
+
+    let updater = std::env::current_exe().context("Could not determine updater executable")?;
+    let child = Command::new(updater)
+        .args(["daemon", "run"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .context("Failed to start daemon")?;
+
+    info!("{} {}", "Started daemon with pid".green(), child.id().to_string().cyan());
+    info!("Logging to {}", get_log_path().display());
+    Ok(())
+}
+
+/// Run the check loop in the foreground, as `daemon start` and service managers do.
+pub fn run() -> Result<()> {
+    let mut state = load_state()?;
+    if let Some(pid) = running_pid(&state) {
+        bail!("Daemon is already running with pid {}", pid);
+    }
+    state.pid = Some(std::process::id());
+    save_state(&state)?;
+    log("daemon started")?;
+
+    if let Some(listen) = config::load_config()?.daemon.metrics_listen {
+        thread::spawn(move || {
+            if let Err(e) = metrics::serve(listen) {
+                log(&format!("metrics server failed: {:#}", e)).ok();
+            }
+        });
+    }
+
+    loop {
+        // A config or state file caught halfway through an edit is tried again next tick
+        let (config, state) = match config::load_config().and_then(|config| Ok((config, load_state()?))) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log(&format!("skipping check: {:#}", e)).ok();
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+        let interval = chrono::Duration::hours(config.daemon.check_interval_hours.max(1) as i64);
+
+        let due = match state.last_check.as_deref().and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok()) {
+            Some(last_check) => chrono::Local::now().signed_duration_since(last_check) >= interval,
+            None => true,
+        };
+
+        if !state.paused && due {
+            if let Err(e) = check_and_update() {
+                metrics::record_failure("check");
+                log(&format!("check failed: {:#}", e))?;
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// One daemon iteration: find outdated packages and apply the ones the config allows.
+fn check_and_update() -> Result<()> {
+    let config = config::load_config()?;
+    let packages = package::load_packages()?;
+    let outdated = package::find_outdated(&packages);
+    log(&format!("checked {} packages, {} outdated", packages.len(), outdated.len()))?;
+    prompt_status::store(&outdated);
+
+    // Reloaded under the lock, so whatever was installed or removed during the check stays
+    let lock = package::lock_db()?;
+    let mut packages = package::load_packages()?;
+    let mut remaining = Vec::new();
+    let mut updated = Vec::new();
+    for pkg in outdated {
+        let package = match packages.get(&pkg.name) {
+            Some(package) if package.active_version.as_deref() == Some(pkg.current.as_str()) => package,
+            _ => continue,
+        };
+        if package.policy == package::UpdatePolicy::Manual {
+            continue;
+        }
+        if let Some(reason) = package.held_back() {
+            log(&format!("{} {} -> {} held back because {}", pkg.name, pkg.current, pkg.latest, reason))?;
+            remaining.push(pkg);
+            continue;
+        }
+        if !package.auto_update.unwrap_or(config.daemon.auto_update).allows(&pkg.current, &pkg.latest) {
+            log(&format!("{} {} -> {} needs manual approval", pkg.name, pkg.current, pkg.latest))?;
+            remaining.push(pkg);
+            continue;
+        }
+
+        match package::apply_update(&mut packages, &pkg.name, &pkg.latest) {
+            Ok(()) => {
+                log(&format!("updated {} {} -> {}", pkg.name, pkg.current, pkg.latest))?;
+                updated.push(pkg);
+            }
+            Err(e) => {
+                metrics::record_failure("update");
+                log(&format!("failed to update {}: {:#}", pkg.name, e))?;
+                remaining.push(pkg);
+            }
+        }
+    }
+    package::save_packages(&packages)?;
+    drop(lock);
+
+    notify::updated(&updated.iter().map(|pkg| pkg.name.clone()).collect::<Vec<_>>())?;
+    notify::outdated(&remaining.iter().map(|pkg| pkg.name.clone()).collect::<Vec<_>>())?;
+
+    let mut state = load_state()?;
+    if !updated.is_empty() {
+        report(NotifyEvent::Updated, &updated)?;
+    }
+    // Webhooks and mail hear about pending updates once, not on every check
+    if remaining.iter().any(|pkg| !state.outdated.iter().any(|known| known.name == pkg.name && known.latest == pkg.latest)) {
+        report(NotifyEvent::Outdated, &remaining)?;
+    }
+    state.last_check = Some(chrono::Local::now().to_rfc3339());
+    state.outdated = remaining;
+    save_state(&state)
+}
+
+/// Send `packages` to the configured webhooks and email, logging failures.
+fn report(event: NotifyEvent, packages: &[package::OutdatedPackage]) -> Result<()> {
+    if let Err(e) = notify::report(event, packages) {
+        log(&format!("{} notification failed: {:#}", event, e))?;
+    }
+    Ok(())
+}
+
+pub fn stop() -> Result<()> {
+    let mut state = load_state()?;
+    match running_pid(&state) {
+        Some(pid) => {
+            let status = Command::new("kill").arg(pid.to_string()).status().context("Failed to run kill")?;
+            if !status.success() {
+                bail!("Failed to stop daemon with pid {}", pid);
+            }
+            state.pid = None;
+            save_state(&state)?;
+            log("daemon stopped")?;
+            info!("{}", "Daemon stopped".green());
+        }
+        None => info!("{}", "Daemon is not running".yellow()),
+    }
+    Ok(())
+}
+
+pub fn set_paused(paused: bool) -> Result<()> {
+    let mut state = load_state()?;
+    state.paused = paused;
+    save_state(&state)?;
+    log(if paused { "paused" } else { "resumed" })?;
+
+    if paused {
+        info!("{}", "Daemon checks paused".green());
+    } else {
+        info!("{}", "Daemon checks resumed".green());
+    }
+    Ok(())
+}
+
+pub fn status() -> Result<()> {
+    let state = load_state()?;
+    let config = config::load_config()?;
+
+    match running_pid(&state) {
+        Some(pid) if state.paused => println!("{} (pid {})", "Paused".yellow().bold(), pid),
+        Some(pid) => println!("{} (pid {})", "Running".green().bold(), pid),
+        None => println!("{}", "Not running".red().bold()),
+    }
+    println!("Check interval: every {} hours", config.daemon.check_interval_hours);
+    println!("Automatic updates: {:?}", config.daemon.auto_update);
+    println!("Last check: {}", state.last_check.as_deref().unwrap_or("never"));
+
+    if !state.outdated.is_empty() {
+        println!("{} {}", state.outdated.len().to_string().yellow().bold(), "packages awaiting update:".yellow());
+        for pkg in &state.outdated {
+            println!("  {} {} -> {}", pkg.name.green(), pkg.current.yellow(), pkg.latest.cyan());
+        }
+    }
+    println!("Log: {}", get_log_path().display());
+    Ok(())
+}
@@ -0,0 +1,475 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use tracing::{debug, info, warn};
+
+use crate::backends::run_command;
+use crate::config::{self, DownloadsConfig};
+use crate::ipfs;
+use crate::net::{self, SendRetrying};
+use crate::verify;
+
+/// Shared download cache. Artifacts are stored once under `blobs/<sha256>`
+/// and `index.json` maps each URL to the blob it produced.
+pub fn get_cache_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    let cache_dir = data_dir.join("updater").join("cache");
+    fs::create_dir_all(cache_dir.join("blobs")).expect("Failed to create cache directory");
+    fs::create_dir_all(cache_dir.join("partial")).expect("Failed to create cache directory");
+    cache_dir
+}
+
+/// Serializes changes to the cache index between prefetch workers.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// URLs queued or being downloaded by `prefetch`, which `fetch` waits for
+/// instead of downloading them a second time.
+static IN_FLIGHT: (Mutex<Option<HashSet<String>>>, Condvar) = (Mutex::new(None), Condvar::new());
+
+fn load_index() -> Result<HashMap<String, String>> {
+    let index_path = get_cache_dir().join("index.json");
+    if !index_path.exists() {
+        return Ok(HashMap::new());
+    }
+    let data = fs::read_to_string(&index_path).context("Failed to read download cache index")?;
+    serde_json::from_str(&data).context("Failed to parse download cache index")
+}
+
+fn save_index(index: &HashMap<String, String>) -> Result<()> {
+    let data = serde_json::to_string_pretty(index).context("Failed to serialize download cache index")?;
+    fs::write(get_cache_dir().join("index.json"), data).context("Failed to write download cache index")?;
+    Ok(())
+}
+
+/// Cached artifact previously downloaded from `url`, if it is still there.
+pub fn cached(url: &str) -> Result<Option<PathBuf>> {
+    let index = load_index()?;
+    Ok(index
+        .get(url)
+        .map(|digest| get_cache_dir().join("blobs").join(digest))
+        .filter(|blob| blob.is_file()))
+}
+
+/// Drop the cache entry for a URL whose content may have changed, like a
+/// `latest` link, along with what an interrupted download of it left behind.
+pub fn forget(url: &str) -> Result<()> {
+    let partial = partial_path(url);
+    fs::remove_file(validator_path(&partial)).ok();
+    fs::remove_file(&partial).ok();
+    let _guard = INDEX_LOCK.lock().unwrap();
+    let mut index = load_index()?;
+    if index.remove(url).is_some() {
+        save_index(&index)?;
+    }
+    Ok(())
+}
+
+/// Where a download of `url` is written until it is complete.
+fn partial_path(url: &str) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    get_cache_dir().join("partial").join(format!("{:x}", hasher.finalize()))
+}
+
+/// The ETag or Last-Modified date of the file a partial download is part of,
+/// which a resumed download has to still match.
+fn validator_path(partial: &Path) -> PathBuf {
+    partial.with_extension("validator")
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Move a downloaded file into the cache as the content for `url`.
+fn store(url: &str, file: &Path) -> Result<PathBuf> {
+    let digest = sha256_file(file)?;
+    let blob = get_cache_dir().join("blobs").join(&digest);
+    if blob.exists() {
+        fs::remove_file(file)?;
+    } else {
+        fs::rename(file, &blob)?;
+    }
+
+    let _guard = INDEX_LOCK.lock().unwrap();
+    let mut index = load_index()?;
+    index.insert(url.to_string(), digest);
+    save_index(&index)?;
+    Ok(blob)
+}
+
+/// Put a cached blob at `dest`, hard linking when possible to avoid a copy.
+fn link_or_copy(blob: &Path, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        fs::remove_file(dest)?;
+    }
+    if fs::hard_link(blob, dest).is_err() {
+        fs::copy(blob, dest).with_context(|| format!("Failed to copy {}", blob.display()))?;
+    }
+    Ok(())
+}
+
+/// Start downloading `urls` into the cache in the background, `limit` at a
+/// time and in order, so installs that need them can go ahead as soon as
+/// their own artifact is there. Failed downloads are left for `fetch` to
+/// retry and report.
+pub fn prefetch(urls: Vec<String>, limit: usize) {
+    let urls: Vec<String> = urls.into_iter().filter(|url| !matches!(cached(url), Ok(Some(_)))).collect();
+    if urls.is_empty() {
+        return;
+    }
+    {
+        let mut in_flight = IN_FLIGHT.0.lock().unwrap();
+        in_flight.get_or_insert_with(HashSet::new).extend(urls.iter().cloned());
+    }
+    debug!("Prefetching {} artifact(s), {} at a time", urls.len(), limit);
+
+    let queue = Arc::new(Mutex::new(VecDeque::from(urls)));
+    for _ in 0..limit.max(1) {
+        let queue = Arc::clone(&queue);
+        thread::spawn(move || loop {
+            let url = match queue.lock().unwrap().pop_front() {
+                Some(url) => url,
+                None => break,
+            };
+            if let Err(e) = download_to_cache(&url, false) {
+                debug!("Prefetching {} failed: {:#}", url, e);
+            }
+            let (lock, done) = &IN_FLIGHT;
+            if let Some(in_flight) = lock.lock().unwrap().as_mut() {
+                in_flight.remove(&url);
+            }
+            done.notify_all();
+        });
+    }
+}
+
+/// Block while a prefetch worker still has `url`.
+fn wait_for_prefetch(url: &str) {
+    let (lock, done) = &IN_FLIGHT;
+    let pending = |in_flight: &Option<HashSet<String>>| in_flight.as_ref().is_some_and(|urls| urls.contains(url));
+    let mut in_flight = lock.lock().unwrap();
+    if pending(&in_flight) {
+        info!("{} {}", "Waiting for download of".green(), url.cyan());
+    }
+    while pending(&in_flight) {
+        in_flight = done.wait(in_flight).unwrap();
+    }
+}
+
+fn download_to_cache(url: &str, progress: bool) -> Result<PathBuf> {
+    let partial = partial_path(url);
+    if net::is_content_addressed(url) {
+        fs::write(&partial, ipfs::fetch(url)?)?;
+        return store(url, &partial);
+    }
+    match fetch_p2p(url, &partial) {
+        Ok(true) => return store(url, &partial),
+        Ok(false) => {}
+        Err(e) => warn!("{} {:#}", "Peer-to-peer download failed, downloading directly:".yellow(), e),
+    }
+    // Interrupted downloads pick up where they stopped, a few times over
+    let mut attempt = 0;
+    while !download_resumable(url, &partial, progress)? {
+        if attempt >= net::retries() {
+            bail!("Download of {} kept getting interrupted, run the command again to resume", url);
+        }
+        let wait = net::backoff(attempt);
+        warn!("{} {}", "Download interrupted, resuming in".yellow(), format!("{:.1}s", wait.as_secs_f32()).cyan());
+        thread::sleep(wait);
+        attempt += 1;
+    }
+    fs::remove_file(validator_path(&partial)).ok();
+    store(url, &partial)
+}
+
+/// Download `url` to `dest` through the shared cache, resuming an earlier
+/// interrupted download of the same URL.
+pub fn fetch(url: &str, dest: &Path) -> Result<()> {
+    wait_for_prefetch(url);
+    if let Some(blob) = cached(url)? {
+        info!("{} {}", "Using cached download for".green(), url.cyan());
+        return link_or_copy(&blob, dest);
+    }
+
+    let blob = download_to_cache(url, true)?;
+    link_or_copy(&blob, dest)
+}
+
+/// A strong ETag, or else the Last-Modified date, identifying the file
+/// `response` serves, for resuming it with `If-Range`.
+fn validator_of(response: &reqwest::blocking::Response) -> Option<String> {
+    let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok());
+    // Weak ETags can't be used with If-Range
+    header(reqwest::header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(reqwest::header::LAST_MODIFIED))
+        .map(str::to_string)
+}
+
+/// Download `url` into `partial`, appending to what an earlier attempt left
+/// there. Returns `false` if the connection dropped partway.
+///
+/// Only downloads whose file has a validator are resumed, and only with
+/// `If-Range`, so a file replaced upstream is downloaded whole instead of
+/// being spliced onto the old one.
+fn download_resumable(url: &str, partial: &Path, show_progress: bool) -> Result<bool> {
+    let validator_path = validator_path(partial);
+    let validator = fs::read_to_string(&validator_path).ok();
+    let existing = match validator {
+        Some(_) => fs::metadata(partial).map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+
+    let mut request = net::get(url);
+    if let Some(validator) = validator.as_deref().filter(|_| existing > 0) {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing)).header(reqwest::header::IF_RANGE, validator);
+    }
+    let response = request.send_retrying().with_context(|| format!("Failed to download {}", url))?;
+    if existing > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The earlier attempt got everything but didn't get to store it
+        let total = response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok()?.strip_prefix("bytes */")?.parse::<u64>().ok());
+        if total == Some(existing) {
+            return Ok(true);
+        }
+        fs::remove_file(&validator_path).ok();
+        fs::remove_file(partial).ok();
+        return download_resumable(url, partial, show_progress);
+    }
+    let mut response = response.error_for_status().with_context(|| format!("Failed to download {}", url))?;
+
+    // Servers that ignore the Range header send the whole file again
+    let resumed = existing > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let offset = if resumed { existing } else { 0 };
+    if resumed {
+        info!("{} {}", "Resuming download at".green(), format!("{} KiB", existing / 1024).cyan());
+    } else {
+        match validator_of(&response) {
+            Some(validator) => fs::write(&validator_path, validator)?,
+            None => {
+                fs::remove_file(&validator_path).ok();
+            }
+        }
+    }
+
+    let progress = match response.content_length() {
+        Some(len) => ProgressBar::new(offset + len),
+        None => ProgressBar::new_spinner(),
+    };
+    progress.set_style(
+        ProgressStyle::with_template("{spinner} {bytes}/{total_bytes} [{bar:30}] {bytes_per_sec}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    progress.set_position(offset);
+    // Bars from concurrent prefetches would draw over each other
+    if !show_progress || !tracing::enabled!(tracing::Level::INFO) {
+        progress.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(partial)
+        .with_context(|| format!("Failed to open {}", partial.display()))?;
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let read = match response.read(&mut buf) {
+            Ok(read) => read,
+            Err(e) => {
+                progress.finish_and_clear();
+                debug!("Download of {} interrupted: {}", url, e);
+                return Ok(false);
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read])?;
+        progress.inc(read as u64);
+    }
+    progress.finish_and_clear();
+    Ok(true)
+}
+
+/// Where the torrent for the artifact at `url` is published, if anywhere:
+/// next to the artifact itself, or on the configured seed server.
+fn torrent_url(url: &str, settings: &DownloadsConfig) -> Result<Option<String>> {
+    let mut candidates = vec![format!("{}.torrent", url)];
+    if let Some(seed_server) = &settings.seed_server {
+        let digest = Sha256::digest(url.as_bytes());
+        candidates.push(format!("{}/{:x}.torrent", seed_server.trim_end_matches('/'), digest));
+    }
+    for candidate in candidates {
+        let response = net::head(&candidate).send_retrying().with_context(|| format!("Failed to request {}", candidate))?;
+        if response.status().is_success() {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// Download `url` to `partial` over BitTorrent with aria2c, using the
+/// original URL as a web seed so the swarm is never the only source.
+///
+/// Returns `Ok(false)` when peer-to-peer downloads are off, aria2c is missing,
+/// the artifact is too small to be worth it or no torrent is published.
+fn fetch_p2p(url: &str, partial: &Path) -> Result<bool> {
+    let settings = config::load_config().map(|config| config.downloads).unwrap_or_default();
+    if !settings.p2p || which::which("aria2c").is_err() {
+        return Ok(false);
+    }
+    let size = net::head(url)
+        .send_retrying()
+        .ok()
+        .and_then(|response| response.headers().get(reqwest::header::CONTENT_LENGTH)?.to_str().ok()?.parse::<u64>().ok());
+    if size.is_some_and(|size| size < settings.p2p_min_size_mb * 1024 * 1024) {
+        return Ok(false);
+    }
+    let torrent_url = match torrent_url(url, &settings)? {
+        Some(torrent_url) => torrent_url,
+        None => return Ok(false),
+    };
+
+    let swarm_dir = partial.with_extension("p2p");
+    fs::create_dir_all(&swarm_dir)?;
+    let torrent = swarm_dir.join("artifact.torrent");
+    let mut response = net::get(&torrent_url)
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to download {}", torrent_url))?;
+    io::copy(&mut response, &mut File::create(&torrent)?).context("Failed to download torrent")?;
+
+    info!("{} {}", "Downloading from peers:".green(), url.cyan());
+    // aria2c keeps its own resume state in the download directory
+    let result = run_command(
+        Command::new("aria2c")
+            .args(["--seed-time=0", "--quiet=true", "--console-log-level=error", "--file-allocation=none"])
+            .arg(format!("--dir={}", swarm_dir.join("files").display()))
+            .arg("--torrent-file")
+            .arg(&torrent)
+            .arg(url),
+    );
+    if let Err(e) = result {
+        bail!("aria2c failed: {}", e);
+    }
+
+    let files: Vec<PathBuf> = fs::read_dir(swarm_dir.join("files"))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_none_or(|ext| ext != "aria2"))
+        .collect();
+    let file = match files.as_slice() {
+        [file] => file,
+        _ => bail!("Torrent {} has to contain exactly one file", torrent_url),
+    };
+    fs::rename(file, partial)?;
+    fs::remove_dir_all(&swarm_dir).ok();
+    Ok(true)
+}
+
+/// Where a delta from `from_version` to the artifact at `url` is published.
+///
+/// Publishers create it with `zstd --patch-from=<old artifact> <new artifact>`
+/// and upload it next to the full artifact.
+pub fn delta_url(url: &str, from_version: &str) -> String {
+    format!("{}.patch-from-{}.zst", url, from_version)
+}
+
+/// Download `url` to `dest`, rebuilding it from a previously downloaded
+/// artifact plus a published delta when possible and falling back to a full
+/// download otherwise. Deltas are only used when the rebuilt artifact can be
+/// checked against the SHA-256 `package`'s artifact must have.
+pub fn fetch_with_delta(url: &str, dest: &Path, package: &str, base: Option<(&Path, &str)>) -> Result<()> {
+    wait_for_prefetch(url);
+    if let Some(blob) = cached(url)? {
+        info!("{} {}", "Using cached download for".green(), url.cyan());
+        return link_or_copy(&blob, dest);
+    }
+
+    // A rebuilt artifact couldn't be checked against the CID
+    if let Some((base_path, from_version)) = base.filter(|_| !net::is_content_addressed(url)) {
+        match fetch_delta(url, dest, package, base_path, from_version) {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => warn!("{} {}", "Delta update failed, downloading full artifact:".yellow(), e),
+        }
+    }
+    fetch(url, dest)
+}
+
+/// Returns `Ok(false)` when no delta has been published for this pair of versions.
+fn fetch_delta(url: &str, dest: &Path, package: &str, base_path: &Path, from_version: &str) -> Result<bool> {
+    if which::which("zstd").is_err() || !base_path.is_file() {
+        return Ok(false);
+    }
+    // A patch vouches for nothing, so what it rebuilds has to match a known digest
+    let expected = match verify::expected_sha256(package, url)? {
+        Some(expected) => expected,
+        None => {
+            debug!("Not using a delta for {}, there is no checksum to check the result against", url);
+            return Ok(false);
+        }
+    };
+
+    let patch_url = delta_url(url, from_version);
+    let response = net::get(&patch_url).send_retrying().with_context(|| format!("Failed to request {}", patch_url))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    let mut response = response.error_for_status()?;
+
+    let patch_path = dest.with_extension("patch.zst");
+    let mut patch_file = File::create(&patch_path)?;
+    let patch_size = io::copy(&mut response, &mut patch_file).context("Failed to download delta")?;
+    drop(patch_file);
+
+    let result = run_command(
+        Command::new("zstd")
+            .args(["--decompress", "--force", "--quiet", "--long=31"])
+            .arg(format!("--patch-from={}", base_path.display()))
+            .arg(&patch_path)
+            .arg("-o")
+            .arg(dest),
+    );
+    fs::remove_file(&patch_path).ok();
+    if let Err(e) = result {
+        fs::remove_file(dest).ok();
+        bail!("Failed to apply delta: {}", e);
+    }
+    let checksum = sha256_file(dest)?;
+    if !checksum.eq_ignore_ascii_case(&expected) {
+        fs::remove_file(dest).ok();
+        bail!("Artifact rebuilt from delta has SHA-256 {}, expected {}", checksum, expected);
+    }
+
+    info!(
+        "{} {} {}",
+        "Applied delta from".green(),
+        from_version.cyan(),
+        format!("({} KiB downloaded)", patch_size / 1024).normal()
+    );
+
+    // Cache the rebuilt artifact like a full download so switching back is free
+    let rebuilt = dest.with_extension("rebuilt");
+    fs::rename(dest, &rebuilt)?;
+    let blob = store(url, &rebuilt)?;
+    link_or_copy(&blob, dest)?;
+    Ok(true)
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use colored::*;
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::package;
+
+/// Bytes used by the files under `path`, without following symlinks.
+pub fn dir_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Disk usage of one package, largest version first.
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageUsage {
+    pub name: String,
+    pub system: bool,
+    pub size: u64,
+    pub versions: Vec<(String, u64)>,
+}
+
+/// Disk usage of every package, largest first. Sizes are recorded at install
+/// time; versions installed before that are measured once and recorded.
+pub fn usage() -> Result<Vec<PackageUsage>> {
+    let mut packages = package::load_packages()?;
+    let mut changed = false;
+    let mut usage = Vec::new();
+
+    for package in packages.values_mut() {
+        let mut versions = Vec::new();
+        for (version, info) in package.versions.iter_mut() {
+            let size = match info.size {
+                Some(size) => size,
+                None => {
+                    let size = dir_size(&info.install_path);
+                    info.size = Some(size);
+                    changed = true;
+                    size
+                }
+            };
+            versions.push((version.clone(), size));
+        }
+        versions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        usage.push(PackageUsage {
+            name: package.name.clone(),
+            system: package.system,
+            size: versions.iter().map(|(_, size)| size).sum(),
+            versions,
+        });
+    }
+    if changed {
+        package::save_packages(&packages)?;
+    }
+
+    usage.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    Ok(usage)
+}
+
+/// Print per-package and per-version disk usage with user and system totals.
+pub fn du() -> Result<()> {
+    let usage = usage()?;
+    if usage.is_empty() {
+        println!("No packages installed");
+        return Ok(());
+    }
+
+    let width = usage
+        .iter()
+        .flat_map(|package| std::iter::once(package.name.len()).chain(package.versions.iter().map(|(v, _)| v.len() + 2)))
+        .max()
+        .unwrap_or(0);
+    for package in &usage {
+        let kind = if package.system { "system" } else { "user" };
+        println!(
+            "{:<width$}  {:>10}  {}",
+            package.name.yellow().bold(),
+            HumanBytes(package.size).to_string(),
+            kind.dimmed(),
+            width = width
+        );
+        for (version, size) in &package.versions {
+            println!("  {:<width$}  {:>10}", version.cyan(), HumanBytes(*size).to_string(), width = width - 2);
+        }
+    }
+
+    let user: u64 = usage.iter().filter(|p| !p.system).map(|p| p.size).sum();
+    let system: u64 = usage.iter().filter(|p| p.system).map(|p| p.size).sum();
+    println!();
+    println!("{:<width$}  {:>10}", "User", HumanBytes(user).to_string(), width = width);
+    println!("{:<width$}  {:>10}", "System", HumanBytes(system).to_string(), width = width);
+    println!("{:<width$}  {:>10}", "Total".bold(), HumanBytes(user + system).to_string(), width = width);
+    Ok(())
+}
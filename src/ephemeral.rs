@@ -0,0 +1,100 @@
+//! Packages used for one shell session or command without installing them:
+//! versions that aren't installed are fetched into a prefix in the cache,
+//! which nothing else looks at.
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::info;
+
+use crate::download;
+use crate::package::{self, InstallOptions, Package};
+use crate::profile;
+
+fn get_ephemeral_prefix() -> PathBuf {
+    download::get_cache_dir().join("ephemeral")
+}
+
+/// The bin dirs of the requested version of a package, if it is recorded.
+fn bin_dirs(package: Option<&Package>, version: Option<&str>) -> Option<Vec<PathBuf>> {
+    let package = package?;
+    let version = version.or(package.active_version.as_deref())?;
+    let info = package.versions.get(version)?;
+    let mut dirs: Vec<PathBuf> = info.bin_paths.iter().filter_map(|bin| bin.parent().map(PathBuf::from)).collect();
+    dirs.dedup();
+    Some(dirs)
+}
+
+/// Bin dirs for every `name`, `name@version` or `backend:name@version`:
+/// the installed version if there is one, otherwise a cached one, fetched
+/// now if need be.
+pub fn resolve(specs: &[String]) -> Result<Vec<PathBuf>> {
+    let installed = package::load_packages()?;
+    let prefix = get_ephemeral_prefix();
+    let mut path = Vec::new();
+
+    for spec in specs {
+        let (backend, name, version) = package::parse_list_entry(spec);
+        if let Some(dirs) = bin_dirs(installed.get(name), version) {
+            path.extend(dirs);
+            continue;
+        }
+        let dirs = profile::with_prefix(&prefix, || {
+            if let Some(dirs) = bin_dirs(package::load_packages()?.get(name), version) {
+                return Ok(dirs);
+            }
+            info!("{} {} {}", "Fetching".green(), spec.yellow().bold(), "into the cache".green());
+            let version = package::install(name, InstallOptions {
+                version: version.map(str::to_string),
+                user: true,
+                backend: backend.map(str::to_string),
+                ..Default::default()
+            })?;
+            bin_dirs(package::load_packages()?.get(name), Some(&version))
+                .with_context(|| format!("{} was fetched but not recorded", spec))
+        })?;
+        path.extend(dirs);
+    }
+    Ok(path)
+}
+
+/// `PATH` with `dirs` in front.
+fn path_with(dirs: &[PathBuf]) -> Result<std::ffi::OsString> {
+    let current = std::env::var_os("PATH").unwrap_or_default();
+    std::env::join_paths(dirs.iter().cloned().chain(std::env::split_paths(&current)))
+        .context("A package's bin dir can't be put on PATH")
+}
+
+/// Replace this process with the user's shell, with the packages in `specs`
+/// first on PATH. Leaving the shell leaves everything as it was.
+pub fn shell(specs: &[String]) -> Result<()> {
+    let dirs = resolve(specs)?;
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    info!("{} {} {}", "Starting".green(), shell.cyan(), format!("with {}, exit to leave", specs.join(", ")).green());
+
+    let err = Command::new(&shell)
+        .env("PATH", path_with(&dirs)?)
+        .env("UPDATER_SHELL", specs.join(" "))
+        .exec();
+    Err(err).with_context(|| format!("Failed to start {}", shell))
+}
+
+/// Replace this process with `command` run with one package first on PATH.
+/// Without a command, run the package's binary of the same name.
+pub fn run(spec: &str, command: &[String]) -> Result<()> {
+    let dirs = resolve(&[spec.to_string()])?;
+    let (_, name, _) = package::parse_list_entry(spec);
+    let (program, args) = match command.split_first() {
+        Some((program, args)) => (program.as_str(), args),
+        None => (name.rsplit('/').next().unwrap_or(name), &[][..]),
+    };
+    // Look the program up on the new PATH rather than this process's
+    let path = path_with(&dirs)?;
+    let program = which::which_in(program, Some(&path), std::env::current_dir()?)
+        .with_context(|| format!("{} is not provided by {}", program, spec))?;
+
+    let err = Command::new(&program).args(args).env("PATH", path).exec();
+    Err(err).with_context(|| format!("Failed to execute {}", program.display()))
+}
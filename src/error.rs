@@ -0,0 +1,114 @@
+use std::io;
+
+/// Failures callers may want to tell apart. They travel inside `anyhow::Error`
+/// like every other error, and `exit_code` finds them anywhere in the chain.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Package not found: {name}{}", did_you_mean(.suggestions))]
+    PackageNotFound { name: String, suggestions: Vec<String> },
+    #[error("Version {version} of {package} is not installed")]
+    VersionNotFound { package: String, version: String },
+    #[error("Repository {0} not found")]
+    RepositoryNotFound(String),
+    /// A backend command or plugin failed; `message` carries its output
+    #[error("{message}")]
+    Backend { backend: String, message: String },
+    #[error("{0}")]
+    Verification(String),
+    #[error("{0}")]
+    Aborted(String),
+    #[error("Failed to update {0} package(s)")]
+    UpdatesFailed(usize),
+    #[error("Failed to install {0} package(s)")]
+    InstallsFailed(usize),
+    #[error("Found {0} known vulnerabilities in installed packages")]
+    Vulnerable(usize),
+    #[error("Found {0} package version(s) with denied licenses")]
+    LicenseDenied(usize),
+    #[error("Installed packages differ from the manifest in {0} place(s)")]
+    Drift(usize),
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(" (did you mean {}?)", suggestions.join(", "))
+    }
+}
+
+/// Process exit codes, from `updater help exit-codes`.
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_USAGE: u8 = 2;
+pub const EXIT_NOT_FOUND: u8 = 3;
+pub const EXIT_BACKEND: u8 = 4;
+pub const EXIT_PERMISSION: u8 = 5;
+pub const EXIT_ABORTED: u8 = 6;
+pub const EXIT_NETWORK: u8 = 7;
+pub const EXIT_VERIFICATION: u8 = 8;
+pub const EXIT_PARTIAL: u8 = 9;
+pub const EXIT_VULNERABLE: u8 = 10;
+pub const EXIT_LICENSE: u8 = 11;
+pub const EXIT_DRIFT: u8 = 12;
+
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+
+  0  Success
+  1  Any other error
+  2  Invalid command line usage
+  3  Package, version or repository not found (not-found)
+  4  A package manager or plugin backend failed (backend)
+  5  Permission denied (permission)
+  6  Aborted at a confirmation prompt, or one was needed without a terminal (aborted)
+  7  Network request failed (network)
+  8  Checksum or signature verification failed (verification)
+  9  Some packages failed to update or install while others succeeded (partial)
+ 10  `updater audit` found known vulnerabilities (vulnerable)
+ 11  `updater licenses --deny` found a denied license (license)
+ 12  `updater diff` found packages that differ from the manifest (drift)
+
+The name in parentheses is the error kind reported in machine-readable output.";
+
+/// A short, stable name for the kind of failure, e.g. `not-found`.
+pub fn kind(error: &anyhow::Error) -> &'static str {
+    match exit_code(error) {
+        EXIT_NOT_FOUND => "not-found",
+        EXIT_BACKEND => "backend",
+        EXIT_PERMISSION => "permission",
+        EXIT_ABORTED => "aborted",
+        EXIT_NETWORK => "network",
+        EXIT_VERIFICATION => "verification",
+        EXIT_PARTIAL => "partial",
+        EXIT_VULNERABLE => "vulnerable",
+        EXIT_LICENSE => "license",
+        EXIT_DRIFT => "drift",
+        _ => "other",
+    }
+}
+
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    for cause in error.chain() {
+        if let Some(error) = cause.downcast_ref::<Error>() {
+            return match error {
+                Error::PackageNotFound { .. } | Error::VersionNotFound { .. } | Error::RepositoryNotFound(_) => EXIT_NOT_FOUND,
+                Error::Backend { .. } => EXIT_BACKEND,
+                Error::Verification(_) => EXIT_VERIFICATION,
+                Error::Aborted(_) => EXIT_ABORTED,
+                Error::UpdatesFailed(_) | Error::InstallsFailed(_) => EXIT_PARTIAL,
+                Error::Vulnerable(_) => EXIT_VULNERABLE,
+                Error::LicenseDenied(_) => EXIT_LICENSE,
+                Error::Drift(_) => EXIT_DRIFT,
+            };
+        }
+        if let Some(error) = cause.downcast_ref::<io::Error>() {
+            if error.kind() == io::ErrorKind::PermissionDenied {
+                return EXIT_PERMISSION;
+            }
+        }
+        if cause.downcast_ref::<reqwest::Error>().is_some() {
+            return EXIT_NETWORK;
+        }
+    }
+    EXIT_FAILURE
+}
@@ -0,0 +1,214 @@
+//! Filesystem snapshots of the install prefixes before updates and removals
+//! on btrfs and ZFS, recorded in a transaction log so `updater rollback` can
+//! copy the prefixes back out of them.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::backends::{privileged_command, run_command};
+use crate::config;
+use crate::error::Error;
+use crate::package;
+use crate::prompt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filesystem {
+    Btrfs,
+    Zfs,
+}
+
+/// A snapshot covering one install prefix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsSnapshot {
+    pub filesystem: Filesystem,
+    /// The install prefix the snapshot was taken for
+    pub prefix: PathBuf,
+    /// Mount point of the snapshotted subvolume or dataset
+    pub mount_point: PathBuf,
+    /// Snapshot subvolume path on btrfs, `dataset@name` on ZFS
+    pub id: String,
+}
+
+impl FsSnapshot {
+    /// Where the prefix's files can be read back from inside the snapshot.
+    fn prefix_in_snapshot(&self) -> Option<PathBuf> {
+        let relative = self.prefix.strip_prefix(&self.mount_point).ok()?;
+        let root = match self.filesystem {
+            Filesystem::Btrfs => PathBuf::from(&self.id),
+            Filesystem::Zfs => self.mount_point.join(".zfs").join("snapshot").join(self.id.split_once('@')?.1),
+        };
+        Some(root.join(relative))
+    }
+}
+
+/// One update or removal that was snapshotted first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    pub date: String,
+    pub operation: String,
+    pub snapshots: Vec<FsSnapshot>,
+}
+
+/// Next to the package database, whose copies it keeps, so every profile and
+/// prefix has its own.
+fn get_transactions_dir() -> PathBuf {
+    package::get_package_db_path().with_file_name("transactions")
+}
+
+fn get_transaction_log_path() -> PathBuf {
+    get_transactions_dir().join("log.jsonl")
+}
+
+/// Copy of the package database taken with a transaction's snapshots.
+fn database_copy_path(id: &str) -> PathBuf {
+    get_transactions_dir().join(format!("{}.packages.json", id))
+}
+
+pub fn load_transactions() -> Result<Vec<Transaction>> {
+    let path = get_transaction_log_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = fs::read_to_string(&path).context("Failed to read transaction log")?;
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).context("Failed to parse transaction log"))
+        .collect()
+}
+
+fn filesystem_of(path: &Path) -> Option<(Filesystem, PathBuf, String)> {
+    let output = run_command(Command::new("findmnt").args(["-n", "-o", "FSTYPE,TARGET,SOURCE", "--target"]).arg(path)).ok()?;
+    let mut fields = output.split_whitespace();
+    let filesystem = match fields.next()? {
+        "btrfs" => Filesystem::Btrfs,
+        "zfs" => Filesystem::Zfs,
+        _ => return None,
+    };
+    let mount_point = PathBuf::from(fields.next()?);
+    let source = fields.next().unwrap_or_default().to_string();
+    Some((filesystem, mount_point, source))
+}
+
+fn take(prefix: &Path, filesystem: Filesystem, mount_point: PathBuf, source: &str, name: &str) -> Result<FsSnapshot> {
+    let id = match filesystem {
+        Filesystem::Btrfs => {
+            let dir = mount_point.join(".updater-snapshots");
+            run_command(privileged_command("mkdir").arg("-p").arg(&dir))?;
+            let snapshot = dir.join(name);
+            run_command(privileged_command("btrfs").args(["subvolume", "snapshot", "-r"]).arg(&mount_point).arg(&snapshot))
+                .with_context(|| format!("Failed to snapshot {}", mount_point.display()))?;
+            snapshot.display().to_string()
+        }
+        Filesystem::Zfs => {
+            let id = format!("{}@{}", source, name);
+            run_command(privileged_command("zfs").arg("snapshot").arg(&id)).with_context(|| format!("Failed to snapshot {}", source))?;
+            id
+        }
+    };
+    Ok(FsSnapshot { filesystem, prefix: prefix.to_path_buf(), mount_point, id })
+}
+
+/// Snapshot the install prefixes before `operation` if filesystem snapshots
+/// are enabled, and log it. Prefixes on other filesystems are skipped, and a
+/// failed snapshot only warns rather than blocking the operation.
+pub fn before(operation: &str) -> Result<()> {
+    if !config::load_config()?.fs_snapshots.enabled {
+        return Ok(());
+    }
+
+    let id = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let name = format!("updater-{}", id);
+    let mut snapshots: Vec<FsSnapshot> = Vec::new();
+    for prefix in [package::base_install_path(true), package::base_install_path(false)] {
+        if !prefix.exists() {
+            continue;
+        }
+        let (filesystem, mount_point, source) = match filesystem_of(&prefix) {
+            Some(found) => found,
+            None => continue,
+        };
+        // Both prefixes often live on the same subvolume or dataset
+        if let Some(existing) = snapshots.iter().find(|s| s.mount_point == mount_point) {
+            snapshots.push(FsSnapshot { prefix: prefix.clone(), ..existing.clone() });
+            continue;
+        }
+        match take(&prefix, filesystem, mount_point, &source, &name) {
+            Ok(snapshot) => {
+                info!("{} {}", "Took snapshot".green(), snapshot.id.cyan());
+                snapshots.push(snapshot);
+            }
+            Err(e) => warn!("{} {}: {:#}", "Could not snapshot".yellow(), prefix.display(), e),
+        }
+    }
+    if snapshots.is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(get_transactions_dir())?;
+    let db_path = package::get_package_db_path();
+    if db_path.exists() {
+        fs::copy(&db_path, database_copy_path(&id)).context("Failed to copy package database")?;
+    }
+    let transaction = Transaction { id, date: chrono::Local::now().to_rfc3339(), operation: operation.to_string(), snapshots };
+    let mut log = OpenOptions::new().create(true).append(true).open(get_transaction_log_path()).context("Failed to open transaction log")?;
+    writeln!(log, "{}", serde_json::to_string(&transaction)?).context("Failed to write transaction log")?;
+    Ok(())
+}
+
+pub fn history() -> Result<()> {
+    let transactions = load_transactions()?;
+    if transactions.is_empty() {
+        println!("No snapshotted transactions");
+        return Ok(());
+    }
+    for transaction in transactions.iter().rev() {
+        println!("{} {} {}", transaction.id.yellow().bold(), transaction.date.dimmed(), transaction.operation);
+        for snapshot in &transaction.snapshots {
+            println!("  {} {}", snapshot.prefix.display(), snapshot.id.cyan());
+        }
+    }
+    Ok(())
+}
+
+/// Put the install prefixes and package database back the way they were
+/// before transaction `id`, copying the prefixes out of its snapshots.
+pub fn rollback(id: &str) -> Result<()> {
+    let transactions = load_transactions()?;
+    let transaction = match transactions.iter().find(|t| t.id == id) {
+        Some(transaction) => transaction,
+        None => bail!("Transaction {} not found, see `updater history`", id),
+    };
+    let prompt = format!("Roll back {} to before `{}` at {}?", transaction.snapshots.iter().map(|s| s.prefix.display().to_string()).collect::<Vec<_>>().join(" and "), transaction.operation, transaction.date);
+    if !prompt::confirm(&prompt)? {
+        return Err(Error::Aborted(format!("Aborted rolling back {}", id)).into());
+    }
+
+    for snapshot in &transaction.snapshots {
+        let saved = snapshot.prefix_in_snapshot().with_context(|| format!("Snapshot {} does not cover {}", snapshot.id, snapshot.prefix.display()))?;
+        if !saved.is_dir() {
+            bail!("{} is missing from snapshot {}", snapshot.prefix.display(), snapshot.id);
+        }
+        info!("{} {} {} {}", "Restoring".green(), snapshot.prefix.display(), "from".green(), snapshot.id.cyan());
+        // Only the system prefix needs root to replace
+        let command = |program: &str| if snapshot.prefix == package::base_install_path(false) { privileged_command(program) } else { Command::new(program) };
+        run_command(command("rm").arg("-rf").arg(&snapshot.prefix))?;
+        run_command(command("cp").args(["-a", "--reflink=auto"]).arg(&saved).arg(&snapshot.prefix))
+            .with_context(|| format!("Failed to restore {}", snapshot.prefix.display()))?;
+    }
+
+    let db_copy = database_copy_path(id);
+    if db_copy.exists() {
+        let data = fs::read_to_string(&db_copy).context("Failed to read saved package database")?;
+        package::save_packages(&serde_json::from_str(&data).context("Failed to parse saved package database")?)?;
+    }
+    info!("{} {}", "Rolled back to before".green(), id.yellow().bold());
+    Ok(())
+}
@@ -0,0 +1,79 @@
+/// Similarity below which a typo'd name is no longer considered a match.
+const MIN_SIMILARITY: f64 = 0.7;
+
+/// How well `name` (or its description) matches `query`, higher is better.
+/// Exact names rank above prefixes, prefixes above substrings, and those above
+/// names that are only a few typos away; `None` means no match at all.
+pub fn score(query: &str, name: &str, description: &str) -> Option<f64> {
+    let query = query.trim().to_lowercase();
+    let name = name.to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+
+    let coverage = query.len() as f64 / name.len().max(1) as f64;
+    if name == query {
+        return Some(4.0);
+    }
+    if name.starts_with(&query) {
+        return Some(3.0 + coverage);
+    }
+    if name.contains(&query) {
+        return Some(2.0 + coverage);
+    }
+
+    // Compare against the whole name and each of its parts, so `ripgrp` still
+    // finds `ripgrep-all` and `nodjs` finds `python3-nodejs`
+    let similarity = std::iter::once(name.as_str())
+        .chain(name.split(['-', '_', '.', '/', ':']))
+        .map(|part| strsim::normalized_damerau_levenshtein(&query, part))
+        .fold(0.0, f64::max);
+    if similarity >= MIN_SIMILARITY {
+        return Some(1.0 + similarity);
+    }
+
+    if description.to_lowercase().contains(&query) {
+        return Some(0.5);
+    }
+    None
+}
+
+/// The closest names to a misspelt `query`, best first.
+pub fn suggest(query: &str, candidates: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut scored: Vec<(f64, String)> = candidates
+        .into_iter()
+        .filter(|candidate| candidate != query)
+        .filter_map(|candidate| score(query, &candidate, "").map(|score| (score, candidate)))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored.into_iter().take(3).map(|(_, candidate)| candidate).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closer_matches_rank_higher() {
+        let exact = score("ripgrep", "ripgrep", "").unwrap();
+        let prefix = score("rip", "ripgrep", "").unwrap();
+        let substring = score("grep", "ripgrep", "").unwrap();
+        let typo = score("ripgrp", "ripgrep-all", "").unwrap();
+        let description = score("search", "ripgrep", "Recursively search directories").unwrap();
+        assert!(exact > prefix && prefix > substring && substring > typo && typo > description);
+        assert_eq!(score("RipGrep ", "ripgrep", ""), Some(4.0));
+        assert_eq!(score("zzz", "ripgrep", "line-oriented search"), None);
+        assert_eq!(score("", "ripgrep", ""), None);
+    }
+
+    #[test]
+    fn suggestions_skip_the_query_and_keep_the_best_three() {
+        let candidates = ["ripgrep", "riggrep", "ripgrep-all", "ripgrep-bin", "rg", "grip"].map(String::from);
+        let suggestions = suggest("riggrep", candidates);
+        assert_eq!(suggestions.len(), 3);
+        assert_eq!(suggestions[0], "ripgrep");
+        assert!(!suggestions.contains(&"riggrep".to_string()));
+        assert!(suggest("nothing-like-it", ["ripgrep".to_string()]).is_empty());
+    }
+}
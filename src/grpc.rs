@@ -0,0 +1,178 @@
+//! `updater serve --grpc`: the control interface from `proto/updater.proto`,
+//! for fleet-management controllers. Queries answer from the library API;
+//! installs, updates and removals run as operations and stream their output.
+
+// tonic's Status is the error type of every handler, big as it is
+#![allow(clippy::result_large_err)]
+
+use colored::*;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use crate::operation::{self, OperationStatus, Operations};
+use crate::package;
+use crate::server;
+
+pub mod proto {
+    tonic::include_proto!("updater.v1");
+}
+
+use proto::progress_event::Event;
+use proto::updater_server::{Updater, UpdaterServer};
+
+/// Progress events buffered per stream before a slow client holds up the operation's reader.
+const STREAM_BUFFER: usize = 64;
+
+type ProgressStream = ReceiverStream<Result<proto::ProgressEvent, Status>>;
+
+struct Service {
+    operations: Operations,
+}
+
+impl Service {
+    /// Start an operation and stream its output, ending with a `Finished` event.
+    fn follow(&self, args: Vec<String>) -> Response<ProgressStream> {
+        let id = self.operations.start(args);
+        let operations = self.operations.clone();
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+
+        tokio::task::spawn_blocking(move || {
+            let event = |event| Ok(proto::ProgressEvent { operation_id: id as u64, event: Some(event) });
+            let mut seen = 0;
+            while let Some((lines, status)) = operations.wait(id, seen) {
+                seen += lines.len();
+                for line in lines {
+                    // The client went away; the operation itself keeps running
+                    if sender.blocking_send(event(Event::Line(line))).is_err() {
+                        return;
+                    }
+                }
+                if status != OperationStatus::Running {
+                    let finished = proto::Finished { success: status == OperationStatus::Succeeded };
+                    sender.blocking_send(event(Event::Finished(finished))).ok();
+                    return;
+                }
+            }
+        });
+        Response::new(ReceiverStream::new(receiver))
+    }
+}
+
+/// Run blocking library code off the async runtime, which also keeps
+/// reqwest's blocking client away from it.
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(|e| Status::internal(format!("{:#}", e)))
+}
+
+fn invalid_argument(e: anyhow::Error) -> Status {
+    Status::invalid_argument(format!("{:#}", e))
+}
+
+#[tonic::async_trait]
+impl Updater for Service {
+    async fn list_packages(&self, request: Request<proto::ListPackagesRequest>) -> Result<Response<proto::ListPackagesResponse>, Status> {
+        let request = request.into_inner();
+        let installed = blocking(move || package::installed(request.system_only, request.user_only)).await?;
+
+        let packages = installed
+            .into_iter()
+            .map(|package| {
+                let mut versions: Vec<proto::InstalledVersion> = package
+                    .versions
+                    .into_iter()
+                    .map(|(version, info)| proto::InstalledVersion {
+                        version,
+                        install_path: info.install_path.display().to_string(),
+                        install_date: info.install_date,
+                        backend: info.package_manager.unwrap_or_default(),
+                    })
+                    .collect();
+                versions.sort_by(|a, b| a.version.cmp(&b.version));
+                proto::Package {
+                    name: package.name,
+                    active_version: package.active_version.unwrap_or_default(),
+                    system: package.system,
+                    versions,
+                }
+            })
+            .collect();
+        Ok(Response::new(proto::ListPackagesResponse { packages }))
+    }
+
+    async fn outdated(&self, _request: Request<proto::OutdatedRequest>) -> Result<Response<proto::OutdatedResponse>, Status> {
+        let outdated = blocking(|| Ok(package::find_outdated(&package::load_packages()?))).await?;
+        let packages = outdated
+            .into_iter()
+            .map(|pkg| proto::OutdatedPackage { name: pkg.name, current: pkg.current, latest: pkg.latest })
+            .collect();
+        Ok(Response::new(proto::OutdatedResponse { packages }))
+    }
+
+    async fn search(&self, request: Request<proto::SearchRequest>) -> Result<Response<proto::SearchResponse>, Status> {
+        let request = request.into_inner();
+        let results = blocking(move || package::find_packages(&request.query, request.refresh)).await?;
+        let hits = results
+            .hits
+            .into_iter()
+            .map(|hit| proto::SearchHit { name: hit.name, description: hit.description, backend: hit.backend })
+            .collect();
+        Ok(Response::new(proto::SearchResponse { hits, stale: results.stale }))
+    }
+
+    async fn versions(&self, request: Request<proto::VersionsRequest>) -> Result<Response<proto::VersionsResponse>, Status> {
+        let name = request.into_inner().name;
+        let upstream = blocking(move || package::upstream_versions(&name)).await?;
+        let sources = upstream
+            .into_iter()
+            .map(|source| proto::UpstreamVersions { backend: source.backend, versions: source.versions })
+            .collect();
+        Ok(Response::new(proto::VersionsResponse { sources }))
+    }
+
+    type InstallStream = ProgressStream;
+
+    async fn install(&self, request: Request<proto::InstallRequest>) -> Result<Response<Self::InstallStream>, Status> {
+        let request = request.into_inner();
+        Ok(self.follow(operation::install_args(request.name, request.version, request.user, request.backend).map_err(invalid_argument)?))
+    }
+
+    type UpdateStream = ProgressStream;
+
+    async fn update(&self, request: Request<proto::UpdateRequest>) -> Result<Response<Self::UpdateStream>, Status> {
+        Ok(self.follow(operation::update_args(request.into_inner().name).map_err(invalid_argument)?))
+    }
+
+    type RemoveStream = ProgressStream;
+
+    async fn remove(&self, request: Request<proto::RemoveRequest>) -> Result<Response<Self::RemoveStream>, Status> {
+        let request = request.into_inner();
+        Ok(self.follow(operation::remove_args(request.name, request.version).map_err(invalid_argument)?))
+    }
+}
+
+pub fn serve(listen: SocketAddr, token: Option<String>) -> anyhow::Result<()> {
+    let expected = server::resolve_token(listen, token).map(|token| format!("Bearer {}", token));
+    let service = UpdaterServer::with_interceptor(Service { operations: Operations::default() }, move |request: Request<()>| {
+        match &expected {
+            Some(expected) if request.metadata().get("authorization").is_none_or(|value| value != expected.as_str()) => {
+                Err(Status::unauthenticated("Missing or wrong bearer token"))
+            }
+            _ => Ok(request),
+        }
+    });
+
+    info!("{} {}", "Serving the updater gRPC interface on".green(), listen.to_string().cyan());
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(tonic::transport::Server::builder().add_service(service).serve(listen))?;
+    Ok(())
+}
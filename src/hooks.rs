@@ -0,0 +1,111 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, warn};
+
+use crate::backends::is_executable;
+use crate::config::{self, HookCommands};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreInstall,
+    PostInstall,
+    PreUpdate,
+    PostUpdate,
+    PreRemove,
+    PostRemove,
+}
+
+impl Hook {
+    /// Event name, used for hook scripts and `UPDATER_HOOK`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::PreInstall => "pre-install",
+            Hook::PostInstall => "post-install",
+            Hook::PreUpdate => "pre-update",
+            Hook::PostUpdate => "post-update",
+            Hook::PreRemove => "pre-remove",
+            Hook::PostRemove => "post-remove",
+        }
+    }
+
+    fn is_pre(&self) -> bool {
+        matches!(self, Hook::PreInstall | Hook::PreUpdate | Hook::PreRemove)
+    }
+
+    fn commands<'a>(&self, commands: &'a HookCommands) -> &'a [String] {
+        match self {
+            Hook::PreInstall => &commands.pre_install,
+            Hook::PostInstall => &commands.post_install,
+            Hook::PreUpdate => &commands.pre_update,
+            Hook::PostUpdate => &commands.post_update,
+            Hook::PreRemove => &commands.pre_remove,
+            Hook::PostRemove => &commands.post_remove,
+        }
+    }
+}
+
+/// What a hook is running for, passed to it as `UPDATER_*` environment variables.
+#[derive(Debug, Default)]
+pub struct HookContext<'a> {
+    pub package: &'a str,
+    pub version: Option<&'a str>,
+    /// Version being replaced by an update
+    pub previous_version: Option<&'a str>,
+    pub install_path: Option<&'a Path>,
+    pub backend: Option<&'a str>,
+}
+
+pub fn get_hooks_dir() -> PathBuf {
+    let config_dir = dirs::config_dir().expect("Could not determine config directory");
+    config_dir.join("updater").join("hooks")
+}
+
+/// Run the global and then the package's hooks for `hook`. A failing pre hook
+/// aborts the operation; a failing post hook is only reported, since the
+/// operation already happened.
+pub fn run(hook: Hook, context: &HookContext) -> Result<()> {
+    let config = config::load_config()?;
+    let mut commands: Vec<Command> = Vec::new();
+
+    let package_commands = config.hooks.packages.get(context.package);
+    for command in hook.commands(&config.hooks.global).iter().chain(package_commands.into_iter().flat_map(|c| hook.commands(c))) {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        commands.push(cmd);
+    }
+    let hooks_dir = get_hooks_dir();
+    for script in [hooks_dir.join(hook.name()), hooks_dir.join(context.package).join(hook.name())] {
+        if is_executable(&script) {
+            commands.push(Command::new(script));
+        }
+    }
+
+    for mut cmd in commands {
+        cmd.env("UPDATER_HOOK", hook.name()).env("UPDATER_PACKAGE", context.package);
+        let vars = [
+            ("UPDATER_VERSION", context.version),
+            ("UPDATER_PREVIOUS_VERSION", context.previous_version),
+            ("UPDATER_BACKEND", context.backend),
+        ];
+        for (var, value) in vars {
+            if let Some(value) = value {
+                cmd.env(var, value);
+            }
+        }
+        if let Some(path) = context.install_path {
+            cmd.env("UPDATER_INSTALL_PATH", path);
+        }
+
+        debug!("Running {} hook {:?}", hook.name(), cmd);
+        let status = cmd.status().with_context(|| format!("Failed to run {} hook", hook.name()))?;
+        if status.success() {
+            continue;
+        }
+        if hook.is_pre() {
+            bail!("{} hook for {} exited with {}", hook.name(), context.package, status);
+        }
+        warn!("{} hook for {} exited with {}", hook.name(), context.package, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,411 @@
+//! Trustless fetching of `ipfs://` URLs. The gateway is asked for the CAR
+//! archive of the blocks behind a path, every block is checked against the
+//! hash in its CID, and the file is put back together from the checked
+//! blocks, so no gateway can serve anything but what the CID names.
+
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::header::ACCEPT;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::net::{self, SendRetrying};
+
+/// Block encodings: UnixFS nodes and plain file chunks.
+const DAG_PB: u64 = 0x70;
+const RAW: u64 = 0x55;
+
+/// Multihash functions blocks can be checked with.
+const IDENTITY: u64 = 0x00;
+const SHA2_256: u64 = 0x12;
+
+/// UnixFS node types.
+const UNIXFS_RAW: u64 = 0;
+const DIRECTORY: u64 = 1;
+const FILE: u64 = 2;
+const HAMT_SHARD: u64 = 5;
+
+const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Verified blocks by multihash.
+type Blocks = HashMap<(u64, Vec<u8>), Vec<u8>>;
+
+/// A content identifier: how the block is encoded and the hash of its bytes.
+#[derive(Debug, Clone, PartialEq)]
+struct Cid {
+    codec: u64,
+    hash: u64,
+    digest: Vec<u8>,
+}
+
+impl Cid {
+    /// A CID in its string form: CIDv0 `Qm…` or a multibase-encoded CIDv1.
+    fn parse(text: &str) -> Result<Cid> {
+        let bytes = match text.as_bytes() {
+            [b'Q', b'm', ..] => base58(text),
+            [b'b', rest @ ..] | [b'B', rest @ ..] => base32(rest),
+            [b'z', ..] => base58(&text[1..]),
+            [b'f', ..] | [b'F', ..] => hex::decode(&text[1..]).ok(),
+            _ => None,
+        };
+        let bytes = bytes.ok_or_else(|| anyhow!("{} is not a CID", text))?;
+        let mut rest = bytes.as_slice();
+        let cid = Cid::read(&mut rest)?;
+        if !rest.is_empty() {
+            bail!("{} is not a CID", text);
+        }
+        Ok(cid)
+    }
+
+    /// A binary CID from the front of `bytes`.
+    fn read(bytes: &mut &[u8]) -> Result<Cid> {
+        // CIDv0 is a bare sha2-256 multihash of a dag-pb block
+        if bytes.starts_with(&[SHA2_256 as u8, 32]) {
+            let (hash, digest) = multihash(bytes)?;
+            return Ok(Cid { codec: DAG_PB, hash, digest });
+        }
+        let version = varint(bytes)?;
+        if version != 1 {
+            bail!("Unsupported CID version {}", version);
+        }
+        let codec = varint(bytes)?;
+        let (hash, digest) = multihash(bytes)?;
+        Ok(Cid { codec, hash, digest })
+    }
+
+    fn key(&self) -> (u64, Vec<u8>) {
+        (self.hash, self.digest.clone())
+    }
+
+    /// Fail unless `data` is what this CID names.
+    fn check(&self, data: &[u8]) -> Result<()> {
+        let matches = match self.hash {
+            SHA2_256 => Sha256::digest(data).as_slice() == self.digest,
+            IDENTITY => data == self.digest,
+            other => bail!("Can't check blocks hashed with multihash 0x{:x}", other),
+        };
+        if !matches {
+            bail!("Block does not match its CID");
+        }
+        Ok(())
+    }
+}
+
+fn varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or_else(|| anyhow!("Truncated varint"))?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("Varint is too long")
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if bytes.len() < len {
+        bail!("Truncated data");
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn multihash(bytes: &mut &[u8]) -> Result<(u64, Vec<u8>)> {
+    let hash = varint(bytes)?;
+    let len = varint(bytes)? as usize;
+    Ok((hash, take(bytes, len)?.to_vec()))
+}
+
+/// RFC 4648 base32 without padding, in either case.
+fn base32(text: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.iter().map(u8::to_ascii_lowercase) {
+        let value = match c {
+            b'a'..=b'z' => c - b'a',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | u32::from(value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+fn base58(text: &str) -> Option<Vec<u8>> {
+    let mut number: Vec<u8> = Vec::new();
+    for c in text.bytes() {
+        let mut carry = BASE58.iter().position(|&b| b == c)? as u32;
+        for byte in number.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            number.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut out = vec![0; text.bytes().take_while(|&c| c == b'1').count()];
+    out.extend(number);
+    Some(out)
+}
+
+/// A URL path segment as the name it is in the directory.
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%').then(|| segment.get(i + 1..i + 3)).flatten().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The blocks of a CARv1 archive, each checked against its CID.
+fn read_car(mut car: &[u8]) -> Result<Blocks> {
+    let header = varint(&mut car)? as usize;
+    take(&mut car, header)?;
+    let mut blocks = Blocks::new();
+    while !car.is_empty() {
+        let len = varint(&mut car)? as usize;
+        let mut section = take(&mut car, len)?;
+        let cid = Cid::read(&mut section)?;
+        cid.check(section)?;
+        blocks.insert(cid.key(), section.to_vec());
+    }
+    Ok(blocks)
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// The fields of a protobuf message, leaving out fixed-width ones since
+/// neither dag-pb nor UnixFS uses them.
+fn fields(mut bytes: &[u8]) -> Result<Vec<(u64, Field<'_>)>> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = varint(&mut bytes)?;
+        let field = match key & 7 {
+            0 => Field::Varint(varint(&mut bytes)?),
+            1 => {
+                take(&mut bytes, 8)?;
+                continue;
+            }
+            2 => {
+                let len = varint(&mut bytes)? as usize;
+                Field::Bytes(take(&mut bytes, len)?)
+            }
+            5 => {
+                take(&mut bytes, 4)?;
+                continue;
+            }
+            wire => bail!("Unsupported protobuf wire type {}", wire),
+        };
+        fields.push((key >> 3, field));
+    }
+    Ok(fields)
+}
+
+struct Link<'a> {
+    cid: Cid,
+    name: &'a [u8],
+}
+
+/// A UnixFS node: its type, the file bytes it holds itself and its children.
+struct Node<'a> {
+    kind: u64,
+    data: &'a [u8],
+    links: Vec<Link<'a>>,
+}
+
+fn node<'a>(blocks: &'a Blocks, cid: &'a Cid) -> Result<Node<'a>> {
+    let block = match cid.hash {
+        IDENTITY => cid.digest.as_slice(),
+        _ => blocks.get(&cid.key()).map(Vec::as_slice).ok_or_else(|| anyhow!("The gateway left out a block"))?,
+    };
+    match cid.codec {
+        RAW => Ok(Node { kind: UNIXFS_RAW, data: block, links: Vec::new() }),
+        DAG_PB => {
+            let mut node = Node { kind: UNIXFS_RAW, data: &[], links: Vec::new() };
+            let mut unixfs = None;
+            for (number, field) in fields(block)? {
+                match (number, field) {
+                    (1, Field::Bytes(data)) => unixfs = Some(data),
+                    (2, Field::Bytes(link)) => {
+                        let (mut cid, mut name) = (None, &[][..]);
+                        for (number, field) in fields(link)? {
+                            match (number, field) {
+                                (1, Field::Bytes(mut hash)) => cid = Some(Cid::read(&mut hash)?),
+                                (2, Field::Bytes(bytes)) => name = bytes,
+                                _ => {}
+                            }
+                        }
+                        let cid = cid.ok_or_else(|| anyhow!("Link without a CID"))?;
+                        node.links.push(Link { cid, name });
+                    }
+                    _ => {}
+                }
+            }
+            let unixfs = unixfs.ok_or_else(|| anyhow!("Block is not a UnixFS node"))?;
+            for (number, field) in fields(unixfs)? {
+                match (number, field) {
+                    (1, Field::Varint(kind)) => node.kind = kind,
+                    (2, Field::Bytes(data)) => node.data = data,
+                    _ => {}
+                }
+            }
+            Ok(node)
+        }
+        other => bail!("Unsupported block codec 0x{:x}", other),
+    }
+}
+
+/// The CID `path` leads to from the directory `root`.
+fn resolve(blocks: &Blocks, root: Cid, path: &[String]) -> Result<Cid> {
+    let mut cid = root;
+    for segment in path {
+        let next = {
+            let node = node(blocks, &cid)?;
+            match node.kind {
+                DIRECTORY => {}
+                HAMT_SHARD => bail!("Sharded directories are not supported"),
+                _ => bail!("Can't look up {} in a file", segment),
+            }
+            let link = node.links.into_iter().find(|link| link.name == segment.as_bytes());
+            link.ok_or_else(|| anyhow!("No {} in the directory", segment))?.cid
+        };
+        cid = next;
+    }
+    Ok(cid)
+}
+
+/// Append the content of the file `cid` to `out`.
+fn assemble(blocks: &Blocks, cid: &Cid, out: &mut Vec<u8>) -> Result<()> {
+    let node = node(blocks, cid)?;
+    match node.kind {
+        UNIXFS_RAW | FILE => {}
+        DIRECTORY | HAMT_SHARD => bail!("A directory, not a file"),
+        other => bail!("Unsupported UnixFS node type {}", other),
+    }
+    out.extend_from_slice(node.data);
+    for link in &node.links {
+        assemble(blocks, &link.cid, out)?;
+    }
+    Ok(())
+}
+
+/// The file `url`, `ipfs://<cid>/<path>`, names, from whichever gateway is
+/// configured and checked against the CID.
+pub fn fetch(url: &str) -> Result<Vec<u8>> {
+    let rest = url.strip_prefix("ipfs://").ok_or_else(|| anyhow!("{} is not an ipfs:// URL", url))?;
+    let mut segments = rest.split('/').filter(|segment| !segment.is_empty());
+    let root = Cid::parse(segments.next().unwrap_or_default()).with_context(|| format!("Invalid CID in {}", url))?;
+    let path: Vec<String> = segments.map(percent_decode).collect();
+
+    let car = net::get(url)
+        .query(&[("format", "car")])
+        .header(ACCEPT, "application/vnd.ipld.car; version=1")
+        .send_retrying()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .with_context(|| format!("Failed to fetch {}", url))?;
+
+    let mut content = Vec::new();
+    read_car(&car)
+        .and_then(|blocks| {
+            let file = resolve(&blocks, root, &path)?;
+            assemble(&blocks, &file, &mut content)
+        })
+        .with_context(|| format!("What the gateway sent for {} does not check out against its CID", url))?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_cid(data: &[u8]) -> Vec<u8> {
+        let mut cid = vec![1, RAW as u8, SHA2_256 as u8, 32];
+        cid.extend_from_slice(&Sha256::digest(data));
+        cid
+    }
+
+    /// A CAR with an empty header and one section per block.
+    fn car(blocks: &[(Vec<u8>, &[u8])]) -> Vec<u8> {
+        let mut car = vec![0];
+        for (cid, data) in blocks {
+            car.push((cid.len() + data.len()) as u8);
+            car.extend_from_slice(cid);
+            car.extend_from_slice(data);
+        }
+        car
+    }
+
+    #[test]
+    fn parses_cid_strings() {
+        let v0 = Cid::parse("QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n").unwrap();
+        assert_eq!((v0.codec, v0.hash, v0.digest.len()), (DAG_PB, SHA2_256, 32));
+        let v1 = Cid::parse("bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e").unwrap();
+        assert_eq!((v1.codec, v1.hash, v1.digest.len()), (RAW, SHA2_256, 32));
+        assert!(Cid::parse("not-a-cid").is_err());
+    }
+
+    #[test]
+    fn assembles_checked_raw_blocks() {
+        let cid = raw_cid(b"hello");
+        let blocks = read_car(&car(&[(cid.clone(), b"hello")])).unwrap();
+        let mut content = Vec::new();
+        assemble(&blocks, &Cid::read(&mut cid.as_slice()).unwrap(), &mut content).unwrap();
+        assert_eq!(content, b"hello");
+    }
+
+    #[test]
+    fn assembles_files_split_into_chunks() {
+        let (first, second) = (raw_cid(b"hello "), raw_cid(b"world"));
+        // PBNode { Links: [first, second], Data: UnixFS { Type: File } }
+        let mut file = Vec::new();
+        for link in [&first, &second] {
+            file.extend_from_slice(&[0x12, link.len() as u8 + 2, 0x0a, link.len() as u8]);
+            file.extend_from_slice(link);
+        }
+        file.extend_from_slice(&[0x0a, 2, 0x08, FILE as u8]);
+        let mut root = vec![1, DAG_PB as u8, SHA2_256 as u8, 32];
+        root.extend_from_slice(&Sha256::digest(&file));
+
+        let blocks = read_car(&car(&[(root.clone(), &file), (first, b"hello "), (second, b"world")])).unwrap();
+        let mut content = Vec::new();
+        assemble(&blocks, &Cid::read(&mut root.as_slice()).unwrap(), &mut content).unwrap();
+        assert_eq!(content, b"hello world");
+    }
+
+    #[test]
+    fn rejects_tampered_blocks() {
+        assert!(read_car(&car(&[(raw_cid(b"hello"), b"evil!")])).is_err());
+    }
+
+    #[test]
+    fn missing_blocks_fail() {
+        let cid = Cid::read(&mut raw_cid(b"hello").as_slice()).unwrap();
+        assert!(assemble(&Blocks::new(), &cid, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Landlock confinement for the tools updater unpacks archives with, so a
+//! hostile archive with `../` or absolute member paths can't write outside
+//! the install dir. Kernels without Landlock (before 5.13, or with it
+//! disabled) run the tools unconfined.
+
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+/// Linking or renaming across directories, from ABI 2
+const ACCESS_FS_REFER: u64 = 1 << 13;
+/// Truncating files, from ABI 3
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+/// Everything that modifies the filesystem in the first Landlock ABI. Reads
+/// stay unrestricted, the tools need their libraries and the archive.
+const ACCESS_FS_WRITE: u64 = ACCESS_FS_WRITE_FILE
+    | ACCESS_FS_REMOVE_DIR
+    | ACCESS_FS_REMOVE_FILE
+    | ACCESS_FS_MAKE_CHAR
+    | ACCESS_FS_MAKE_DIR
+    | ACCESS_FS_MAKE_REG
+    | ACCESS_FS_MAKE_SOCK
+    | ACCESS_FS_MAKE_FIFO
+    | ACCESS_FS_MAKE_BLOCK
+    | ACCESS_FS_MAKE_SYM;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: libc::c_int,
+}
+
+/// The Landlock ABI version the kernel supports, if any.
+fn abi_version() -> Option<i64> {
+    // SAFETY: querying the version takes no attributes
+    let version = unsafe {
+        libc::syscall(libc::SYS_landlock_create_ruleset, std::ptr::null::<RulesetAttr>(), 0usize, CREATE_RULESET_VERSION)
+    };
+    (version > 0).then_some(version)
+}
+
+/// Only let `cmd` write below `dir` (and to `/dev/null`) once it starts.
+/// The ruleset is built here and applied in the child right before exec, so
+/// updater itself stays unconfined; keep the returned ruleset until `cmd`
+/// has been spawned.
+pub fn confine_writes(cmd: &mut Command, dir: &Path) -> Result<Option<OwnedFd>> {
+    let abi = match abi_version() {
+        Some(abi) => abi,
+        None => {
+            debug!("Landlock is not available, extracting without it");
+            return Ok(None);
+        }
+    };
+    let mut handled = ACCESS_FS_WRITE;
+    if abi >= 2 {
+        handled |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        handled |= ACCESS_FS_TRUNCATE;
+    }
+
+    let attr = RulesetAttr { handled_access_fs: handled };
+    // SAFETY: attr is a valid ruleset attribute of the size passed
+    let ruleset = unsafe { libc::syscall(libc::SYS_landlock_create_ruleset, &attr, std::mem::size_of::<RulesetAttr>(), 0u32) };
+    if ruleset < 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to create Landlock ruleset");
+    }
+    // SAFETY: the syscall returned a new file descriptor that nothing else owns
+    let ruleset = unsafe { OwnedFd::from_raw_fd(ruleset as libc::c_int) };
+
+    allow(&ruleset, dir, handled)?;
+    // A file only takes file rights, not ones for creating entries below it
+    allow(&ruleset, Path::new("/dev/null"), handled & (ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE))?;
+
+    let ruleset_fd = ruleset.as_raw_fd();
+    // SAFETY: prctl and the Landlock syscall are async-signal-safe, and the
+    // closure neither allocates nor takes locks
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+                || libc::syscall(libc::SYS_landlock_restrict_self, ruleset_fd, 0u32) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(Some(ruleset))
+}
+
+fn allow(ruleset: &OwnedFd, path: &Path, access: u64) -> Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: c_path is a valid NUL-terminated path
+    let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("Failed to open {}", path.display()));
+    }
+    // SAFETY: open returned a new file descriptor that nothing else owns
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let rule = PathBeneathAttr { allowed_access: access, parent_fd: fd.as_raw_fd() };
+    // SAFETY: rule is a valid path-beneath attribute and ruleset a Landlock ruleset
+    let result = unsafe { libc::syscall(libc::SYS_landlock_add_rule, ruleset.as_raw_fd(), RULE_PATH_BENEATH, &rule, 0u32) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("Failed to allow writes to {}", path.display()));
+    }
+    Ok(())
+}
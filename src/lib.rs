@@ -0,0 +1,82 @@
+//! Modern package management for Linux, as a library.
+//!
+//! The `updater` binary is a thin command line front end over this crate;
+//! other tools can embed the same package database and backends:
+//!
+//! ```no_run
+//! use updater::package::{self, InstallOptions};
+//!
+//! let version = package::install("ripgrep", InstallOptions { user: true, ..Default::default() })?;
+//! println!("installed ripgrep {}", version);
+//!
+//! for package in package::installed(false, true)? {
+//!     println!("{} {:?}", package.name, package.active_version);
+//! }
+//! for outdated in package::find_outdated(&package::load_packages()?) {
+//!     println!("{} {} -> {}", outdated.name, outdated.current, outdated.latest);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Progress and diagnostics are reported through `tracing`, so nothing is
+//! printed unless the embedding program installs a subscriber. Functions that
+//! exist to draw the CLI, like `package::list`, print to stdout.
+
+pub mod apply;
+pub mod audit;
+pub mod auth;
+pub mod backends;
+pub mod bundle;
+pub mod changelog;
+pub mod config;
+pub mod daemon;
+pub mod du;
+pub mod ephemeral;
+pub mod error;
+pub mod fs_snapshot;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod license;
+pub mod lock;
+pub mod logging;
+pub mod metrics;
+pub mod motd;
+pub mod package;
+pub mod profile;
+pub mod prompt;
+pub mod prompt_status;
+pub mod repo;
+pub mod rpc;
+pub mod sbom;
+pub mod schedule;
+pub mod server;
+pub mod shell;
+pub mod shim;
+pub mod snapshot;
+pub mod stats;
+pub mod system;
+pub mod tap;
+pub mod ui;
+pub mod utils;
+pub mod verify;
+pub mod version;
+
+mod archive;
+mod compat;
+mod download;
+mod fuzzy;
+mod hooks;
+mod ipfs;
+mod landlock;
+mod manifest;
+mod net;
+mod notify;
+mod operation;
+mod privilege;
+mod sandbox;
+mod search_index;
+mod share;
+mod template;
+mod transaction;
+
+pub use package::{InstallOptions, InstallReason, OutdatedPackage, Package, PackageVersion, SearchHit, SearchResults, UpstreamVersions};
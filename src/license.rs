@@ -0,0 +1,127 @@
+use anyhow::Result;
+use colored::*;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::backends;
+use crate::error::Error;
+use crate::package;
+
+/// The license of one installed package version.
+#[derive(Debug, Clone, Serialize)]
+pub struct LicenseEntry {
+    pub name: String,
+    pub version: String,
+    pub backend: String,
+    pub license: Option<String>,
+}
+
+/// Licenses of every installed version, looking up and recording the ones
+/// that were installed before licenses were tracked.
+pub fn licenses() -> Result<Vec<LicenseEntry>> {
+    let mut packages = package::load_packages()?;
+    let mut changed = false;
+    let mut entries = Vec::new();
+
+    for package in packages.values_mut() {
+        for (version, info) in package.versions.iter_mut() {
+            let backend = info.package_manager.clone().unwrap_or_default();
+            if info.license.is_none() {
+                info.license = backends::license(&backend, &package.name, version);
+                changed |= info.license.is_some();
+            }
+            entries.push(LicenseEntry {
+                name: package.name.clone(),
+                version: version.clone(),
+                backend,
+                license: info.license.clone(),
+            });
+        }
+    }
+    if changed {
+        package::save_packages(&packages)?;
+    }
+
+    entries.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    Ok(entries)
+}
+
+/// Whether an SPDX expression only allows using the package under denied
+/// licenses. `GPL-3.0` also denies `GPL-3.0-only`, `GPL-3.0-or-later` and
+/// `GPL-3.0+`, and an `OR` is fine as long as one side is allowed.
+pub fn is_denied(expression: &str, deny: &[String]) -> bool {
+    let expression = expression.replace(['(', ')'], " ");
+    let mut alternatives = vec![Vec::new()];
+    let mut tokens = expression.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token.to_uppercase().as_str() {
+            "OR" => alternatives.push(Vec::new()),
+            "AND" => {}
+            // The exception only narrows the license it follows
+            "WITH" => {
+                tokens.next();
+            }
+            _ => alternatives.last_mut().unwrap().push(token),
+        }
+    }
+
+    let alternatives: Vec<_> = alternatives.into_iter().filter(|ids| !ids.is_empty()).collect();
+    !alternatives.is_empty() && alternatives.iter().all(|ids| ids.iter().any(|id| deny.iter().any(|denied| matches_id(id, denied))))
+}
+
+fn matches_id(id: &str, denied: &str) -> bool {
+    let id = id.to_lowercase();
+    let denied = denied.to_lowercase();
+    id.strip_prefix(&denied).is_some_and(|rest| matches!(rest, "" | "+" | "-only" | "-or-later"))
+}
+
+/// Print the license of every installed version, failing if any is denied.
+pub fn report(deny: &[String]) -> Result<()> {
+    let entries = licenses()?;
+    if entries.is_empty() {
+        println!("No packages installed");
+        return Ok(());
+    }
+
+    let mut denied = 0;
+    let mut unknown = Vec::new();
+    for entry in &entries {
+        let license = match &entry.license {
+            Some(license) if is_denied(license, deny) => {
+                denied += 1;
+                format!("{} {}", license.red().bold(), "(denied)".red())
+            }
+            Some(license) => license.green().to_string(),
+            None => {
+                unknown.push(format!("{} {}", entry.name, entry.version));
+                "unknown".dimmed().to_string()
+            }
+        };
+        println!("{} {} [{}] {}", entry.name.yellow().bold(), entry.version.cyan(), entry.backend, license);
+    }
+
+    if !deny.is_empty() && !unknown.is_empty() {
+        warn!("{} {}", "Could not check unknown licenses of:".yellow(), unknown.join(", "));
+    }
+    if denied > 0 {
+        return Err(Error::LicenseDenied(denied).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denied_licenses_cover_their_variants() {
+        let deny = vec!["GPL-3.0".to_string()];
+        for expression in ["GPL-3.0", "gpl-3.0-only", "GPL-3.0-or-later", "GPL-3.0+", "(MIT AND GPL-3.0)", "GPL-3.0 WITH GCC-exception-3.1"] {
+            assert!(is_denied(expression, &deny), "{:?} allowed", expression);
+        }
+        for expression in ["MIT", "MIT OR GPL-3.0", "LGPL-3.0", "GPL-3.0-with-bison-exception", "Apache-2.0 WITH GPL-3.0", ""] {
+            assert!(!is_denied(expression, &deny), "{:?} denied", expression);
+        }
+        assert!(is_denied("(MIT AND GPL-3.0) OR AGPL-3.0", &["GPL-3.0".to_string(), "AGPL-3.0".to_string()]));
+    }
+}
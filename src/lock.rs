@@ -0,0 +1,231 @@
+//! `updater.lock`: the exact versions, download URLs and checksums a manifest
+//! resolved to, so `apply --locked` installs the same bits everywhere.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::apply;
+use crate::download;
+use crate::error::Error;
+use crate::manifest::{self, Manifest, ManifestEntry, PackageSpec};
+use crate::package::{self, Package};
+use crate::sbom;
+
+pub const LOCK_FILE: &str = "updater.lock";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub generated: String,
+    #[serde(default)]
+    pub packages: BTreeMap<String, LockedPackage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub version: String,
+    pub backend: String,
+    /// Where the version was downloaded from, as far as updater can tell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// SHA-256 of the downloaded artifact, when updater kept it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// SHA-256 of every installed file, keyed by its path inside the install dir
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+}
+
+/// The lockfile that belongs to a manifest, next to it.
+pub fn lock_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_file_name(LOCK_FILE)
+}
+
+pub fn load(path: &Path) -> Result<Lockfile> {
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read lockfile {}, create it with `updater lock`", path.display()))?;
+    toml::from_str(&data).with_context(|| format!("Failed to parse lockfile {}", path.display()))
+}
+
+/// Record the installed version of every package in the manifest at
+/// `manifest_path`. The manifest has to be applied first, so what gets
+/// locked is what was actually installed and checked.
+pub fn lock(manifest_path: &Path) -> Result<()> {
+    let manifest = manifest::load_manifest(manifest_path)?;
+    let packages = package::load_packages()?;
+    let mut lockfile = Lockfile { generated: chrono::Local::now().to_rfc3339(), ..Default::default() };
+
+    for (name, entry) in &manifest.packages {
+        let spec = entry.spec();
+        let package = packages.get(name);
+        let version = match (package, &spec.version) {
+            (Some(package), Some(wanted)) => apply::installed_match(package, wanted),
+            (Some(package), None) => package.active_version.clone(),
+            (None, _) => None,
+        };
+        let (package, version) = match (package, version) {
+            (Some(package), Some(version)) => (package, version),
+            _ => bail!("{} isn't installed as declared, run `updater apply {}` first", name, manifest_path.display()),
+        };
+        if version == "latest" {
+            warn!("{} {}", name.yellow().bold(), "is installed as latest, which names no exact version to lock".yellow());
+        }
+
+        let info = &package.versions[&version];
+        let backend = info.package_manager.clone().unwrap_or_default();
+        let files = info
+            .checksums
+            .iter()
+            .filter_map(|(file, sum)| Some((file.strip_prefix(&info.install_path).ok()?.to_string_lossy().into_owned(), sum.clone())))
+            .collect();
+        lockfile.packages.insert(
+            name.clone(),
+            LockedPackage {
+                url: info.source_url.clone().or_else(|| sbom::download_url(&package.name, &version, info, &backend)),
+                sha256: sbom::artifact_sha256(info),
+                version,
+                backend,
+                files,
+            },
+        );
+    }
+
+    let path = lock_path(manifest_path);
+    let data = toml::to_string_pretty(&lockfile).context("Failed to serialize lockfile")?;
+    fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("{} {} {} {}", "Locked".green(), lockfile.packages.len().to_string().cyan(), "packages to".green(), path.display().to_string().yellow().bold());
+    Ok(())
+}
+
+/// `manifest` with every package pinned to its locked version and backend.
+pub fn pin(manifest: &Manifest, lockfile: &Lockfile) -> Result<Manifest> {
+    let mut pinned = Manifest::default();
+    for (name, entry) in &manifest.packages {
+        let locked = lockfile.packages.get(name).with_context(|| format!("{} doesn't cover {}, run `updater lock` again", LOCK_FILE, name))?;
+        let spec = entry.spec();
+        let pinned_spec = PackageSpec {
+            version: Some(locked.version.clone()).filter(|version| version != "latest"),
+            backend: spec.backend.clone().or_else(|| Some(locked.backend.clone()).filter(|_| spec.url.is_none() && spec.git.is_none() && spec.image.is_none())),
+            ..spec
+        };
+        pinned.packages.insert(name.clone(), ManifestEntry::Detailed(Box::new(pinned_spec)));
+    }
+    Ok(pinned)
+}
+
+/// Check that every locked package is installed at its locked version with
+/// exactly the locked artifact and files.
+pub fn verify(lockfile: &Lockfile) -> Result<()> {
+    let problems = differences(lockfile, &package::load_packages()?);
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("  {} {}", "!".red().bold(), problem);
+        }
+        return Err(Error::Verification(format!("{} difference(s) from {}", problems.len(), LOCK_FILE)).into());
+    }
+    info!("{} {}", "Verified every package against".green(), LOCK_FILE.yellow().bold());
+    Ok(())
+}
+
+/// Every way the installed `packages` differ from `lockfile`.
+fn differences(lockfile: &Lockfile, packages: &HashMap<String, Package>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, locked) in &lockfile.packages {
+        let info = match packages.get(name).and_then(|package| package.versions.get(&locked.version)) {
+            Some(info) => info,
+            None => {
+                problems.push(format!("{} {} is not installed", name, locked.version));
+                continue;
+            }
+        };
+        if let (Some(expected), Some(actual)) = (&locked.sha256, sbom::artifact_sha256(info)) {
+            if *expected != actual {
+                problems.push(format!("{} {} was installed from a different artifact", name, locked.version));
+            }
+        }
+        for (file, expected) in &locked.files {
+            let path = info.install_path.join(file);
+            match download::sha256_file(&path) {
+                Ok(actual) if actual == *expected => {}
+                Ok(_) => problems.push(format!("{} differs", path.display())),
+                Err(_) => problems.push(format!("{} is missing", path.display())),
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked(version: &str, backend: &str) -> LockedPackage {
+        LockedPackage { version: version.to_string(), backend: backend.to_string(), url: None, sha256: None, files: BTreeMap::new() }
+    }
+
+    #[test]
+    fn pin_takes_locked_versions_and_backends() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [packages]
+            ripgrep = "^14"
+            fd = { version = "latest" }
+            tool = { url = "https://example.com/tool-{version}.tar.gz" }
+            "#,
+        )
+        .unwrap();
+        let mut lockfile = Lockfile::default();
+        lockfile.packages.insert("ripgrep".into(), locked("14.1.0", "cargo"));
+        lockfile.packages.insert("fd".into(), locked("latest", "apt"));
+        lockfile.packages.insert("tool".into(), locked("1.2.0", "url"));
+
+        let pinned = pin(&manifest, &lockfile).unwrap();
+        let spec = |name: &str| pinned.packages[name].spec();
+        assert_eq!(spec("ripgrep").version.as_deref(), Some("14.1.0"));
+        assert_eq!(spec("ripgrep").backend.as_deref(), Some("cargo"));
+        assert_eq!(spec("fd").version, None);
+        assert_eq!(spec("fd").backend.as_deref(), Some("apt"));
+        // A URL decides its own backend
+        assert_eq!(spec("tool").backend, None);
+        assert_eq!(spec("tool").version.as_deref(), Some("1.2.0"));
+
+        lockfile.packages.remove("fd");
+        assert!(pin(&manifest, &lockfile).is_err());
+    }
+
+    #[test]
+    fn differences_find_missing_versions_and_changed_files() {
+        let install_dir = std::env::temp_dir().join(format!("updater-lock-{}", std::process::id()));
+        fs::create_dir_all(install_dir.join("bin")).unwrap();
+        fs::write(install_dir.join("bin/tool"), "#!/bin/sh\n").unwrap();
+        let checksum = download::sha256_file(&install_dir.join("bin/tool")).unwrap();
+        let package: Package = serde_json::from_value(serde_json::json!({
+            "name": "tool",
+            "versions": { "1.0": { "install_path": install_dir, "install_date": "", "bin_paths": [], "package_manager": "url" } },
+            "active_version": "1.0",
+            "system": false,
+        }))
+        .unwrap();
+        let packages = HashMap::from([("tool".to_string(), package)]);
+
+        let mut lockfile = Lockfile::default();
+        let mut tool = locked("1.0", "url");
+        tool.files.insert("bin/tool".into(), checksum);
+        lockfile.packages.insert("tool".into(), tool.clone());
+        assert!(differences(&lockfile, &packages).is_empty());
+
+        tool.files.insert("bin/tool".into(), "0".repeat(64));
+        tool.files.insert("bin/gone".into(), "0".repeat(64));
+        lockfile.packages.insert("tool".into(), tool);
+        lockfile.packages.insert("other".into(), locked("2.0", "cargo"));
+        let problems = differences(&lockfile, &packages);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems.contains(&"other 2.0 is not installed".to_string()));
+        assert!(problems.contains(&format!("{} differs", install_dir.join("bin/tool").display())));
+        assert!(problems.contains(&format!("{} is missing", install_dir.join("bin/gone").display())));
+        fs::remove_dir_all(&install_dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use colored::*;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable overriding the log filter, e.g. `UPDATER_LOG=updater::download=trace`.
+const LOG_ENV: &str = "UPDATER_LOG";
+
+/// Size at which the command log is rotated.
+const COMMAND_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated command logs kept next to the current one, as `commands.log.1` and up.
+const COMMAND_LOG_KEEP: usize = 3;
+
+/// Plain messages at the default level, level and module prefixes once verbose.
+struct Format {
+    verbose: bool,
+}
+
+impl<S, N> FormatEvent<S, N> for Format
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        match *metadata.level() {
+            Level::ERROR => write!(writer, "{} ", "error:".red().bold())?,
+            Level::WARN => write!(writer, "{} ", "warning:".yellow().bold())?,
+            Level::INFO => {}
+            level => write!(writer, "{} ", level.to_string().to_lowercase().dimmed())?,
+        }
+        if self.verbose {
+            write!(writer, "{} ", format!("{}:", metadata.target()).dimmed())?;
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Send diagnostics to stderr: errors only when quiet, info by default, backend
+/// commands with `-v` and their output with `-vv`.
+pub fn init(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    // Only updater itself gets chattier, dependencies stay at warnings
+    let others = if quiet { "error" } else { "warn" };
+    let filter = EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new(format!("{},updater={}", others, level)));
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .event_format(Format { verbose: verbose > 0 })
+        .init();
+}
+
+pub fn get_command_log_path() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    let log_dir = data_dir.join("updater").join("logs");
+    fs::create_dir_all(&log_dir).expect("Failed to create log directory");
+    log_dir.join("commands.log")
+}
+
+/// Append a finished backend command with its full output to the command log.
+/// Logging is best effort and never fails the command itself.
+pub fn log_command(cmd: &Command, output: &Output) {
+    let path = get_command_log_path();
+    rotate(&path);
+
+    let entry = format!(
+        "==> {} {:?}\n--- {}\n--- stdout\n{}\n--- stderr\n{}\n\n",
+        chrono::Local::now().to_rfc3339(),
+        cmd,
+        output.status,
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        String::from_utf8_lossy(&output.stderr).trim_end()
+    );
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        file.write_all(entry.as_bytes()).ok();
+    }
+}
+
+/// Shift `commands.log` to `commands.log.1` and so on once it grows too big.
+fn rotate(path: &Path) {
+    if !fs::metadata(path).is_ok_and(|m| m.len() >= COMMAND_LOG_MAX_BYTES) {
+        return;
+    }
+
+    let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    for n in (1..COMMAND_LOG_KEEP).rev() {
+        fs::rename(rotated(n), rotated(n + 1)).ok();
+    }
+    fs::rename(path, rotated(1)).ok();
+}
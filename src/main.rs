@@ -1,32 +1,107 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use colored::*;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing::{error, info};
 
-mod package;
-mod system;
-mod utils;
-mod version;
+use updater::backends::oci::ImageSpec;
+use updater::backends::source::SourceSpec;
+use updater::backends::url::UrlSpec;
+use updater::config::{RepoKind, Repository};
+use updater::error::{self, EXIT_CODES_HELP};
+use updater::package::{InstallReason, UpdatePolicy};
+use updater::sbom::SbomFormat;
+use updater::schedule::Frequency;
+use updater::shell::Shell;
+use updater::version::{Channel, UpdateLevel};
+use updater::{apply, audit, auth, bundle, changelog, daemon, du, ephemeral, fs_snapshot, license, lock, logging, metrics, motd, package, profile, prompt, prompt_status, repo, rpc, sbom, schedule, server, shell, shim, snapshot, stats, tap, ui, verify};
 
 #[derive(Parser)]
-#[command(author, version, about = "Modern package manager for Linux")]
+#[command(author, version, about = "Modern package manager for Linux", after_help = "Exit codes are described by `updater help exit-codes`.")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Answer yes to every confirmation prompt (also UPDATER_ASSUME_YES=1)
+    #[arg(short, long, global = true)]
+    yes: bool,
+    /// Fail instead of warning when an artifact differs from its first download (also UPDATER_STRICT=1)
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Refuse to install artifacts without verified build provenance (also UPDATER_REQUIRE_PROVENANCE=1)
+    #[arg(long, global = true)]
+    require_provenance: bool,
+    /// Show backend commands (-v) and their output (-vv)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only print errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Keep the package database, packages and shims under this dir instead (also UPDATER_PREFIX)
+    #[arg(long, global = true, value_name = "DIR")]
+    prefix: Option<PathBuf>,
 }
 
+// Parsed once per run, so the install arguments may make it large
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Install a package
     Install {
         /// Name of the package to install
-        name: String,
-        /// Specific version to install
+        #[arg(required_unless_present_any = ["file", "from", "from_image"])]
+        name: Option<String>,
+        /// Specific version or semver constraint (e.g. "^1.4", ">=1, <2") to install
         #[arg(short, long)]
         version: Option<String>,
         /// Install as user package (not system-wide)
         #[arg(short, long)]
         user: bool,
+        /// Install a local .deb or .rpm file through the native package manager
+        #[arg(long, conflicts_with_all = ["name", "version", "user"])]
+        file: Option<PathBuf>,
+        /// Install every package listed in this file, one `name`, `name@version` or `backend:name` per line
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["name", "version", "file", "git", "url", "from_image"])]
+        from: Option<PathBuf>,
+        /// Backend to install with (e.g. go, gem, conda), defaults to the system package manager
+        #[arg(short, long)]
+        backend: Option<String>,
+        /// Binary to expose from a conda environment, may be repeated
+        #[arg(long)]
+        expose: Vec<String>,
+        /// Release channel to install from and follow on update
+        #[arg(long, value_enum)]
+        channel: Option<Channel>,
+        /// Build from source by cloning this git repository at the version's tag
+        #[arg(long)]
+        git: Option<String>,
+        /// Build command to run instead of the detected build system (make, cargo, cmake)
+        #[arg(long, requires = "git")]
+        build_command: Option<String>,
+        /// Download an archive or binary from this URL, `{version}` replaced with the version,
+        /// or pick one from a GitHub repository's releases or a directory listing ending in /
+        #[arg(long, conflicts_with = "git")]
+        url: Option<String>,
+        /// Asset to pick from a release or listing --url, as a substring or glob of its file name
+        #[arg(long, requires = "url")]
+        asset: Option<String>,
+        /// Take binaries out of this container image, e.g. ghcr.io/org/tool:1.2; the tag is the version
+        #[arg(long, value_name = "IMAGE", conflicts_with_all = ["git", "url", "file"], requires = "bin")]
+        from_image: Option<String>,
+        /// Path of a binary inside the image to install, may be repeated
+        #[arg(long, value_name = "PATH", requires = "from_image")]
+        bin: Vec<String>,
+        /// Install for this architecture instead of this machine's, e.g. aarch64; needs --prefix
+        #[arg(long, value_name = "ARCH")]
+        arch: Option<String>,
+        /// Variable for `updater env` to export for the package, as KEY=VALUE, may be repeated;
+        /// `{install_dir}` is replaced with the active version's install dir
+        #[arg(long, value_name = "KEY=VALUE", value_parser = shell::parse_var)]
+        env: Vec<(String, String)>,
+        /// Show what would be installed without touching anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Remove a package
     Remove {
@@ -35,11 +110,34 @@ enum Commands {
         /// Specific version to remove, removes all versions if not specified
         #[arg(short, long)]
         version: Option<String>,
+        /// Show what would be deleted without touching anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Remove the package even if other packages depend on it
+        #[arg(long)]
+        force: bool,
+    },
+    /// Summarize installed packages, updates, usage and disk usage from local data only
+    Stats,
+    /// Remove packages that were only installed as dependencies and are no longer needed
+    Autoremove {
+        /// Show what would be removed without touching anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Update packages
     Update {
         /// Specific package to update, updates all if not specified
         name: Option<String>,
+        /// Update every outdated package without asking which ones
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+        /// Show which versions would be installed without touching anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't show upstream release notes before updating
+        #[arg(long)]
+        no_changelog: bool,
     },
     /// List installed packages
     List {
@@ -49,11 +147,203 @@ enum Commands {
         /// Show user packages only
         #[arg(long)]
         user: bool,
+        /// Also show each package's description, license and links
+        #[arg(short, long, conflicts_with = "format")]
+        long: bool,
+        /// Only show packages whose binaries weren't run in this many days (90 if not given)
+        #[arg(long, value_name = "DAYS", num_args = 0..=1, default_missing_value = "90", conflicts_with_all = ["long", "format"])]
+        stale: Option<u64>,
+        /// Print each package with this template instead, e.g. '{name}\t{active_version}\t{type}'
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+    },
+    /// List packages with newer versions available upstream
+    Outdated {
+        /// Print each package with this template instead, e.g. '{name}\t{current}\t{latest}'
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+    },
+    /// Print the number of pending updates for a shell prompt, from the last check
+    PromptStatus {
+        /// Shown after the count
+        #[arg(long, default_value = "⬆")]
+        symbol: String,
+        /// Check upstream and refresh the cache instead, as the background check does
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Print a login summary of pending updates, for /etc/update-motd.d or shell rc files
+    Motd {
+        /// Write the summary to this file instead, emptying it when nothing is pending
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Check installed versions against known vulnerabilities (OSV)
+    Audit,
+    /// List binary names provided by more than one package
+    Conflicts,
+    /// Make a package win the shims for binary names it shares with others
+    Prefer {
+        /// Name of the package
+        name: String,
+    },
+    /// Expose a package's binary under a different shim name
+    RenameBin {
+        /// Name of the package
+        name: String,
+        /// Binary as the package installs it
+        bin: String,
+        /// Shim name to expose it as, the binary's own name to undo a rename
+        new_name: String,
+    },
+    /// Also expose a package's binary under another name, optionally for one version
+    Alias {
+        /// Name of the package, or name@version to always run that version
+        package: String,
+        /// Binary as the package installs it
+        bin: String,
+        /// Extra shim name, e.g. terraform15
+        alias: String,
+        /// Remove the alias instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Show which package provides a binary
+    Which {
+        /// Binary name, shim or path to an installed binary
+        bin: String,
+    },
+    /// Show everything recorded about an installed package
+    Info {
+        /// Name of the package
+        name: String,
+    },
+    /// Change whether a package counts as explicitly installed or only as a dependency
+    #[command(group = clap::ArgGroup::new("reason").required(true).args(["explicit", "auto"]))]
+    Mark {
+        /// Name of the package
+        name: String,
+        /// Keep the package until it is removed by name
+        #[arg(long)]
+        explicit: bool,
+        /// Let `autoremove` remove the package once nothing needs it
+        #[arg(long)]
+        auto: bool,
+    },
+    /// Move a package that was renamed upstream over to its new name
+    Migrate {
+        /// Name the package is installed under
+        name: String,
+        /// New name, if its backend doesn't report the rename
+        to: Option<String>,
+    },
+    /// Start a shell with extra packages on PATH, fetching missing ones into the cache
+    Shell {
+        /// Packages as name, name@version or backend:name@version
+        #[arg(required = true)]
+        packages: Vec<String>,
+    },
+    /// Run a command with a package on PATH without installing it, e.g. `run ripgrep@14 -- rg TODO`
+    Run {
+        /// Package as name, name@version or backend:name@version
+        package: String,
+        /// Command to run, the package's own binary if omitted
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// Show or change how `updater update` and the daemon treat a package
+    Policy {
+        /// Name of the package
+        name: String,
+        /// auto updates it with everything else, notify-only and manual only when named
+        #[arg(value_enum)]
+        policy: Option<UpdatePolicy>,
+        /// Update it at most this often, unless it is named explicitly
+        #[arg(long, value_enum, conflicts_with = "any_frequency")]
+        max_frequency: Option<Frequency>,
+        /// Drop the --max-frequency limit
+        #[arg(long)]
+        any_frequency: bool,
+        /// How far the daemon may update it on its own; major steps need this to be major
+        #[arg(long, value_enum, conflicts_with = "default_auto_update")]
+        auto_update: Option<UpdateLevel>,
+        /// Follow daemon.auto_update from the config again
+        #[arg(long)]
+        default_auto_update: bool,
+    },
+    /// Never update a package to a known-bad version
+    Skip {
+        /// Name of the package
+        name: String,
+        /// Version to pass over, e.g. 2.1.0
+        version: String,
+        /// Allow updating to the version again
+        #[arg(long)]
+        undo: bool,
+    },
+    /// Move a package installed under the user prefix for lack of root to the system prefix
+    Promote {
+        /// Name of the package
+        name: String,
+    },
+    /// List installed packages that depend on a package
+    Rdeps {
+        /// Name of the package
+        name: String,
+    },
+    /// Show which package installed a file
+    Owns {
+        /// Path to the file
+        path: PathBuf,
+    },
+    /// Fetch an installed version again and replace its files
+    Reinstall {
+        /// Name of the package
+        name: String,
+        /// Version to reinstall, the active one if omitted
+        #[arg(long)]
+        version: Option<String>,
+    },
+    /// Show the release notes recorded for an installed version
+    Notes {
+        /// Name of the package
+        name: String,
+        /// Version to show, the active one if omitted
+        version: Option<String>,
+    },
+    /// Check installed files against the manifests recorded at install time
+    Verify {
+        /// Only check this package
+        name: Option<String>,
+    },
+    /// Show how much disk space each package and version uses
+    Du,
+    /// List the license of every installed package
+    Licenses {
+        /// Fail if a package is only available under this license, e.g. GPL-3.0
+        #[arg(long, value_name = "LICENSE", value_delimiter = ',')]
+        deny: Vec<String>,
+    },
+    /// Print a software bill of materials for every tracked package
+    Sbom {
+        #[arg(long, value_enum, default_value_t = SbomFormat::Cyclonedx)]
+        format: SbomFormat,
     },
     /// Search for packages
     Search {
         /// Query to search for
         query: String,
+        /// Query every backend instead of answering from the search cache
+        #[arg(long)]
+        refresh: bool,
+        /// Print each result with this template instead, e.g. '{name}\t{backend}'
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+    },
+    /// List versions of a package available upstream
+    Versions {
+        /// Package name
+        name: String,
     },
     /// Switch between versions of a package
     Switch {
@@ -62,48 +352,537 @@ enum Commands {
         /// Version to switch to
         version: String,
     },
+    /// Install and switch to an older upstream version of a package
+    Downgrade {
+        /// Package name
+        name: String,
+        /// Version to go back to, the newest release before the active one if omitted
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Run the background update daemon, or control a running one
+    Daemon {
+        #[command(subcommand)]
+        action: Option<DaemonAction>,
+    },
+    /// Manage a systemd timer that runs `updater update` periodically
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Create or install offline bundles for air-gapped machines
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+    /// List updates and removals that were preceded by a filesystem snapshot
+    History,
+    /// Restore the install prefixes from the snapshot taken before a transaction
+    Rollback {
+        /// Transaction id from `updater history`
+        id: String,
+    },
+    /// Install, switch and remove packages until they match a manifest
+    Apply {
+        /// Manifest declaring the packages, like the one `bundle create` takes
+        manifest: PathBuf,
+        /// Also remove explicitly installed packages the manifest doesn't declare
+        #[arg(long)]
+        prune: bool,
+        /// Install the versions in the updater.lock next to the manifest and verify them against it
+        #[arg(long)]
+        locked: bool,
+        /// Show the changes without making them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write updater.lock with the exact versions and checksums a manifest is installed at
+    Lock {
+        /// Manifest declaring the packages, already applied
+        manifest: PathBuf,
+    },
+    /// Show how the installed packages differ from a manifest, failing if they do
+    Diff {
+        /// Manifest declaring the packages
+        manifest: PathBuf,
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Keep separate package sets, like work and personal ones, and switch between them
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    /// Save the installed packages under a label and restore them later
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Manage credentials for private registries and artifact servers
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    /// Manage extra package repositories, flatpak remotes and taps
+    Repo {
+        #[command(subcommand)]
+        action: RepoAction,
+    },
+    /// Manage taps, git repositories of community install recipes
+    Tap {
+        #[command(subcommand)]
+        action: TapAction,
+    },
+    /// Browse, switch and update packages in an interactive dashboard
+    Ui,
+    /// Serve a JSON API for listing, installing and updating packages
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: SocketAddr,
+        /// Bearer token clients must send (also UPDATER_SERVE_TOKEN)
+        #[arg(long)]
+        token: Option<String>,
+        /// Serve the gRPC interface from proto/updater.proto instead of JSON
+        #[cfg(feature = "grpc")]
+        #[arg(long)]
+        grpc: bool,
+    },
+    /// Answer JSON-RPC requests on stdin, for editors and GUI frontends
+    Rpc,
+    /// Pin a package version for the current directory in .updater-versions
+    Local {
+        /// Package name
+        name: String,
+        /// Version to use inside this directory
+        version: String,
+    },
+    /// Print shell commands that activate updater, e.g. eval "$(updater env bash)"
+    Env {
+        /// Shell to print commands for, detected from $SHELL if omitted
+        #[arg(value_enum)]
+        shell: Option<Shell>,
+    },
+    /// Print the startup file snippet that sets up PATH, MANPATH and completions
+    Init {
+        /// Shell to print the snippet for, detected from $SHELL if omitted
+        #[arg(value_enum)]
+        shell: Option<Shell>,
+        /// Append the snippet to the shell's startup file instead
+        #[arg(long)]
+        write: bool,
+    },
+    /// Explain what updater's exit codes mean
+    #[command(long_about = EXIT_CODES_HELP)]
+    ExitCodes,
+    /// Run a managed binary through its shim
+    #[command(hide = true)]
+    ShimExec {
+        /// Binary name
+        bin: String,
+        /// Arguments passed to the binary
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Start the daemon in the background (default)
+    Start,
+    /// Run the daemon in the foreground
+    Run,
+    /// Stop the running daemon
+    Stop,
+    /// Show whether the daemon is running and what it found
+    Status,
+    /// Pause periodic checks
+    Pause,
+    /// Resume periodic checks
+    Resume,
+}
+
+#[derive(Subcommand)]
+enum ScheduleAction {
+    /// Write and enable the service and timer units
+    Install {
+        /// Run every hour
+        #[arg(long, group = "frequency")]
+        hourly: bool,
+        /// Run every day (default)
+        #[arg(long, group = "frequency")]
+        daily: bool,
+        /// Run every week
+        #[arg(long, group = "frequency")]
+        weekly: bool,
+        /// Install system units instead of user units
+        #[arg(long)]
+        system: bool,
+    },
+    /// Disable and delete the units
+    Remove {
+        /// Manage system units instead of user units
+        #[arg(long)]
+        system: bool,
+    },
+    /// Show whether the timer is enabled and when it runs next
+    Status {
+        /// Manage system units instead of user units
+        #[arg(long)]
+        system: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BundleAction {
+    /// Pack the packages in a manifest, with their files, into a tar bundle
+    Create {
+        /// Manifest listing the packages to bundle
+        manifest: PathBuf,
+        /// Bundle file to write
+        #[arg(short, long, default_value = "bundle.tar")]
+        output: PathBuf,
+    },
+    /// Install every package in a bundle without network access
+    Install {
+        /// Bundle file created with `bundle create`
+        bundle: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// List profiles, marking the active one
+    List,
+    /// Create an empty profile
+    Create {
+        /// Name of the profile, e.g. work
+        name: String,
+    },
+    /// Make a profile the active one
+    Switch {
+        /// Name of the profile, `default` for the original one
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Record every package, version and active selection
+    Create {
+        /// Name to restore the snapshot by
+        label: String,
+    },
+    /// Install, switch and remove packages until they match a snapshot
+    Restore {
+        /// Snapshot to restore
+        label: String,
+        /// Show the changes without making them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List saved snapshots
+    List,
+}
+
+#[derive(Subcommand)]
+enum AuthAction {
+    /// Store a token for a host in the system keyring
+    Login {
+        /// Host or URL of the registry, e.g. github.example.com
+        source: String,
+        /// Token to store, read from stdin if omitted
+        #[arg(long)]
+        token: Option<String>,
+        /// Send the token with HTTP basic auth as this user instead of as a bearer token
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// Remove the stored token for a host
+    Logout {
+        /// Host or URL of the registry
+        source: String,
+    },
+    /// List hosts with stored credentials
+    Status,
+}
+
+#[derive(Subcommand)]
+enum TapAction {
+    /// Clone a tap and index its recipes, e.g. github.com/org/updater-recipes
+    Add {
+        /// Git URL of the tap
+        url: String,
+        /// Name to refer to the tap by, `<owner>-<repo>` by default
+        #[arg(long)]
+        name: Option<String>,
+        /// Taps with a higher priority win when several have a recipe
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+    },
+    /// Remove a tap and its checkout
+    Remove {
+        /// Tap name
+        name: String,
+    },
+    /// List taps with their recipe counts
+    List,
+    /// Pull the latest recipes of one or every tap
+    Update {
+        /// Tap name, all taps if omitted
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RepoAction {
+    /// Register a repository
+    Add {
+        /// Name to refer to the repository by
+        name: String,
+        /// Index URL, flatpak remote URL, tap git URL or OCI registry namespace
+        url: String,
+        /// Kind of repository
+        #[arg(long, value_enum, default_value_t = RepoKind::Updater)]
+        kind: RepoKind,
+        /// Repositories with a higher priority are searched and installed from first
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
+        /// Hex-encoded ed25519 key the index is signed with
+        #[arg(long)]
+        public_key: Option<String>,
+        /// Trust an updater repository without a signature
+        #[arg(long, conflicts_with = "public_key")]
+        allow_unsigned: bool,
+    },
+    /// Unregister a repository
+    Remove {
+        /// Repository name
+        name: String,
+    },
+    /// List registered repositories by priority
+    List,
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    logging::init(cli.verbose, cli.quiet);
+    if cli.strict {
+        verify::set_strict();
+    }
+    if cli.require_provenance {
+        verify::set_require_provenance();
+    }
+    if cli.yes {
+        prompt::set_assume_yes();
+    }
+    if let Some(prefix) = &cli.prefix {
+        if let Err(e) = profile::set_prefix(prefix) {
+            error!("{:#}", e);
+            return ExitCode::from(error::exit_code(&e));
+        }
+    }
     
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let operation = match cli.command {
+                Commands::Install { .. } => Some("install"),
+                Commands::Update { .. } => Some("update"),
+                Commands::Remove { .. } => Some("remove"),
+                _ => None,
+            };
+            if let Some(operation) = operation {
+                metrics::record_failure(operation);
+            }
+            error!("{:#}", e);
+            ExitCode::from(error::exit_code(&e))
+        }
+    }
+}
+
+/// Commands that load, change and save the package database, holding its
+/// lock while they run.
+fn changes_packages(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Install { .. }
+            | Commands::Remove { .. }
+            | Commands::Autoremove { .. }
+            | Commands::Update { .. }
+            | Commands::Prefer { .. }
+            | Commands::RenameBin { .. }
+            | Commands::Alias { .. }
+            | Commands::Mark { .. }
+            | Commands::Migrate { .. }
+            | Commands::Policy { .. }
+            | Commands::Skip { .. }
+            | Commands::Promote { .. }
+            | Commands::Reinstall { .. }
+            | Commands::Downgrade { .. }
+            | Commands::Switch { .. }
+            | Commands::Rollback { .. }
+            | Commands::Apply { .. }
+            | Commands::Bundle { action: BundleAction::Install { .. } }
+            | Commands::Snapshot { action: SnapshotAction::Restore { .. } }
+    )
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let _lock = if changes_packages(&cli.command) { Some(package::lock_db()?) } else { None };
     match &cli.command {
-        Commands::Install { name, version, user } => {
-            println!("{}{}{}{}",
+        Commands::Install { file: Some(path), dry_run, .. } => {
+            info!("{}{}", "Installing package file ".green(), path.display().to_string().yellow().bold());
+            package::install_file(path, *dry_run)
+        }
+        Commands::Install { from: Some(path), user, backend, expose, channel, dry_run, .. } => {
+            info!("{}{}", "Installing packages from ".green(), path.display().to_string().yellow().bold());
+            package::install_list(path, package::InstallOptions {
+                user: *user,
+                backend: backend.clone(),
+                expose: expose.clone(),
+                channel: *channel,
+                dry_run: *dry_run,
+                ..Default::default()
+            })
+        }
+        Commands::Install { name, version, user, backend, expose, channel, git, build_command, url, asset, from_image, bin, arch, env, dry_run, .. } => {
+            let (image, tag) = match from_image {
+                Some(reference) => {
+                    let (spec, tag) = ImageSpec::parse(reference, bin.clone());
+                    (Some(spec), tag)
+                }
+                None => (None, None),
+            };
+            let version = version.clone().or(tag);
+            let name = name.clone().or(image.as_ref().map(|spec| spec.default_name().to_string())).context("A package name is required")?;
+            info!("{}{}{}{}",
                 "Installing package ".green(),
                 name.yellow().bold(),
-                if let Some(v) = version { format!(" version {}", v.cyan()) } else { "".to_string() },
+                if let Some(v) = &version { format!(" version {}", v.cyan()) } else { "".to_string() },
                 if *user { " (user package)".to_string() } else { "".to_string() }
             );
-            package::install(name, version.clone(), *user)
+            let source = git.as_ref().map(|repo| SourceSpec {
+                repo: repo.clone(),
+                build_command: build_command.clone(),
+            });
+            package::install(&name, package::InstallOptions {
+                version,
+                user: *user,
+                backend: backend.clone(),
+                source,
+                url: url.as_ref().map(|url| UrlSpec { url: url.clone(), asset: asset.clone() }),
+                image,
+                expose: expose.clone(),
+                channel: *channel,
+                dry_run: *dry_run,
+                arch: arch.clone(),
+                env: env.iter().cloned().collect(),
+            })
+            .map(|_| ())
         }
-        Commands::Remove { name, version } => {
-            println!("{}{}{}",
+        Commands::Remove { name, version, dry_run, force } => {
+            info!("{}{}{}",
                 "Removing package ".green(),
                 name.yellow().bold(),
                 if let Some(v) = version { format!(" version {}", v.cyan()) } else { "".to_string() }
             );
-            package::remove(name, version.clone())
+            package::remove(name, version.clone(), *dry_run, *force)
         }
-        Commands::Update { name } => {
+        Commands::Stats => stats::stats(),
+        Commands::Autoremove { dry_run } => {
+            info!("{}", "Looking for packages that are no longer needed".green());
+            package::autoremove(*dry_run)
+        }
+        Commands::Update { name, all, dry_run, no_changelog } => {
             if let Some(package_name) = name {
-                println!("{} {}", "Updating package".green(), package_name.yellow().bold());
-                package::update(Some(package_name))
+                info!("{} {}", "Updating package".green(), package_name.yellow().bold());
+                package::update(Some(package_name), false, *dry_run, !*no_changelog)
+            } else {
+                info!("{}", "Updating all packages".green());
+                package::update(None, *all, *dry_run, !*no_changelog)
+            }
+        }
+        Commands::List { stale: Some(days), .. } => package::list_stale(*days),
+        Commands::List { system, user, long, format, .. } => {
+            info!("{}", "Listing installed packages".green());
+            package::list(*system, *user, *long, format.as_deref())
+        }
+        Commands::Outdated { format } => {
+            info!("{}", "Checking for outdated packages".green());
+            package::outdated(format.as_deref())
+        }
+        Commands::PromptStatus { symbol, refresh } => {
+            if *refresh {
+                prompt_status::refresh()
             } else {
-                println!("{}", "Updating all packages".green());
-                package::update(None)
+                prompt_status::print(symbol);
+                Ok(())
+            }
+        }
+        Commands::Motd { output } => motd::motd(output.as_deref()),
+        Commands::Audit => {
+            info!("{}", "Checking installed packages for known vulnerabilities".green());
+            audit::audit()
+        }
+        Commands::Conflicts => shim::print_conflicts(),
+        Commands::Prefer { name } => shim::prefer(name),
+        Commands::RenameBin { name, bin, new_name } => shim::rename_bin(name, bin, new_name),
+        Commands::Alias { package, bin, alias, remove } => {
+            let (name, version) = match package.rsplit_once('@') {
+                Some((name, version)) if !name.is_empty() => (name, Some(version)),
+                _ => (package.as_str(), None),
+            };
+            shim::alias(name, version, bin, alias, *remove)
+        }
+        Commands::Which { bin } => shim::which(bin),
+        Commands::Info { name } => package::info(name),
+        Commands::Mark { name, explicit, .. } => {
+            let reason = if *explicit { InstallReason::Explicit } else { InstallReason::Dependency };
+            package::mark(name, reason)
+        }
+        Commands::Migrate { name, to } => package::migrate(name, to.as_deref()),
+        Commands::Shell { packages } => ephemeral::shell(packages),
+        Commands::Run { package, command } => ephemeral::run(package, command),
+        Commands::Policy { name, policy, max_frequency, any_frequency, auto_update, default_auto_update } => {
+            let max_frequency = if *any_frequency { Some(None) } else { max_frequency.map(Some) };
+            let auto_update = if *default_auto_update { Some(None) } else { auto_update.map(Some) };
+            if policy.is_none() && max_frequency.is_none() && auto_update.is_none() {
+                return package::info(name);
             }
+            package::set_policy(name, *policy, max_frequency, auto_update)
+        }
+        Commands::Skip { name, version, undo } => package::skip(name, version, *undo),
+        Commands::Promote { name } => {
+            info!("Promoting package {}", name.yellow().bold());
+            package::promote(name)
         }
-        Commands::List { system, user } => {
-            println!("{}", "Listing installed packages".green());
-            package::list(*system, *user)
+        Commands::Rdeps { name } => package::rdeps(name),
+        Commands::Owns { path } => package::owns(path),
+        Commands::Notes { name, version } => changelog::notes(name, version.as_deref()),
+        Commands::Verify { name } => package::verify(name.as_deref()),
+        Commands::Reinstall { name, version } => {
+            info!("Reinstalling package {}", name.yellow().bold());
+            package::reinstall(name, version.as_deref())
         }
-        Commands::Search { query } => {
-            println!("{} {}", "Searching for".green(), query.yellow());
-            package::search(query)
+        Commands::Du => du::du(),
+        Commands::Licenses { deny } => license::report(deny),
+        Commands::Sbom { format } => sbom::sbom(*format),
+        Commands::Search { query, refresh, format } => {
+            info!("{} {}", "Searching for".green(), query.yellow());
+            package::search(query, *refresh, format.as_deref())
+        }
+        Commands::Versions { name } => {
+            info!("{} {}", "Listing available versions of".green(), name.yellow().bold());
+            package::versions(name)
+        }
+        Commands::Downgrade { name, to } => {
+            info!("{} {}", "Downgrading".green(), name.yellow().bold());
+            package::downgrade(name, to.as_deref())
         }
         Commands::Switch { name, version } => {
-            println!("{} {} {}{}", 
+            info!("{} {} {}{}", 
                 "Switching".green(), 
                 name.yellow().bold(),
                 "to version".green(),
@@ -111,5 +890,104 @@ fn main() -> Result<()> {
             );
             package::switch(name, version)
         }
+        Commands::Daemon { action } => match action.as_ref().unwrap_or(&DaemonAction::Start) {
+            DaemonAction::Start => daemon::start(),
+            DaemonAction::Run => daemon::run(),
+            DaemonAction::Stop => daemon::stop(),
+            DaemonAction::Status => daemon::status(),
+            DaemonAction::Pause => daemon::set_paused(true),
+            DaemonAction::Resume => daemon::set_paused(false),
+        },
+        Commands::Schedule { action } => match action {
+            ScheduleAction::Install { hourly, weekly, system, .. } => {
+                let frequency = if *hourly {
+                    Frequency::Hourly
+                } else if *weekly {
+                    Frequency::Weekly
+                } else {
+                    Frequency::Daily
+                };
+                schedule::install(frequency, *system)
+            }
+            ScheduleAction::Remove { system } => schedule::remove(*system),
+            ScheduleAction::Status { system } => schedule::status(*system),
+        },
+        Commands::Bundle { action } => match action {
+            BundleAction::Create { manifest, output } => {
+                info!("{} {}", "Creating bundle from".green(), manifest.display().to_string().yellow());
+                bundle::create(manifest, output)
+            }
+            BundleAction::Install { bundle } => {
+                info!("{} {}", "Installing bundle".green(), bundle.display().to_string().yellow());
+                bundle::install(bundle)
+            }
+        },
+        Commands::History => fs_snapshot::history(),
+        Commands::Rollback { id } => {
+            info!("{} {}", "Rolling back transaction".green(), id.yellow().bold());
+            fs_snapshot::rollback(id)
+        }
+        Commands::Apply { manifest, prune, locked, dry_run } => {
+            info!("{} {}", "Applying".green(), manifest.display().to_string().yellow().bold());
+            apply::apply(manifest, *prune, *dry_run, *locked)
+        }
+        Commands::Lock { manifest } => lock::lock(manifest),
+        Commands::Diff { manifest, json } => apply::diff(manifest, *json),
+        Commands::Profile { action } => match action {
+            ProfileAction::List => profile::list(),
+            ProfileAction::Create { name } => profile::create(name),
+            ProfileAction::Switch { name } => profile::switch(name),
+        },
+        Commands::Snapshot { action } => match action {
+            SnapshotAction::Create { label } => snapshot::create(label),
+            SnapshotAction::Restore { label, dry_run } => {
+                info!("{} {}", "Restoring snapshot".green(), label.yellow().bold());
+                snapshot::restore(label, *dry_run)
+            }
+            SnapshotAction::List => snapshot::list(),
+        },
+        Commands::Auth { action } => match action {
+            AuthAction::Login { source, token, username } => auth::login(source, token.clone(), username.clone()),
+            AuthAction::Logout { source } => auth::logout(source),
+            AuthAction::Status => auth::status(),
+        },
+        Commands::Repo { action } => match action {
+            RepoAction::Add { name, url, kind, priority, public_key, allow_unsigned } => repo::add(Repository {
+                name: name.clone(),
+                url: url.clone(),
+                kind: *kind,
+                priority: *priority,
+                public_key: public_key.clone(),
+                allow_unsigned: *allow_unsigned,
+            }),
+            RepoAction::Remove { name } => repo::remove(name),
+            RepoAction::List => repo::list(),
+        },
+        Commands::Tap { action } => match action {
+            TapAction::Add { url, name, priority } => repo::add(Repository {
+                name: name.clone().unwrap_or_else(|| tap::default_name(url)),
+                url: tap::clone_url(url),
+                kind: RepoKind::Tap,
+                priority: *priority,
+                public_key: None,
+                allow_unsigned: false,
+            }),
+            TapAction::Remove { name } => tap::remove(name),
+            TapAction::List => tap::list(),
+            TapAction::Update { name } => tap::update(name.as_deref()),
+        },
+        Commands::Ui => ui::run(),
+        #[cfg(feature = "grpc")]
+        Commands::Serve { listen, token, grpc: true } => updater::grpc::serve(*listen, token.clone()),
+        Commands::Serve { listen, token, .. } => server::serve(*listen, token.clone()),
+        Commands::Rpc => rpc::run(),
+        Commands::Local { name, version } => shim::set_local(name, version),
+        Commands::Env { shell } => shell::print_env(*shell),
+        Commands::Init { shell, write } => shell::init(*shell, *write),
+        Commands::ExitCodes => {
+            println!("{}", EXIT_CODES_HELP);
+            Ok(())
+        }
+        Commands::ShimExec { bin, args } => shim::exec(bin, args),
     }
 }
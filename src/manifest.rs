@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::backends::oci::ImageSpec;
+use crate::backends::source::SourceSpec;
+use crate::backends::url::UrlSpec;
+use crate::package::InstallOptions;
+use crate::version::Channel;
+
+/// A declared set of packages, e.g.
+///
+/// ```toml
+/// [packages]
+/// ripgrep = "14.1.0"
+/// bat = { version = "0.24.0", backend = "cargo" }
+/// tool = { version = "1.2.0", url = "https://example.com/tool-{version}.tar.gz" }
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub packages: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ManifestEntry {
+    /// Just a version (or constraint)
+    Version(String),
+    Detailed(Box<PackageSpec>),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PackageSpec {
+    pub version: Option<String>,
+    pub backend: Option<String>,
+    pub channel: Option<Channel>,
+    pub user: bool,
+    pub url: Option<String>,
+    /// Asset to pick from a release or listing `url`, as a substring or glob
+    pub asset: Option<String>,
+    pub git: Option<String>,
+    pub build_command: Option<String>,
+    pub expose: Vec<String>,
+    /// Container image to take `bins` out of, e.g. `ghcr.io/org/tool:1.2`
+    pub image: Option<String>,
+    pub bins: Vec<String>,
+    /// Architecture to install for instead of this machine's, under a `--prefix`
+    pub arch: Option<String>,
+    /// Variables `updater env` exports for the package
+    pub env: BTreeMap<String, String>,
+}
+
+impl ManifestEntry {
+    pub fn spec(&self) -> PackageSpec {
+        match self {
+            ManifestEntry::Version(version) => PackageSpec {
+                version: Some(version.clone()),
+                ..PackageSpec::default()
+            },
+            ManifestEntry::Detailed(spec) => (**spec).clone(),
+        }
+    }
+}
+
+impl PackageSpec {
+    pub fn install_options(&self) -> InstallOptions {
+        // An image's tag stands in for the version
+        let (image, tag) = match &self.image {
+            Some(image) => {
+                let (spec, tag) = ImageSpec::parse(image, self.bins.clone());
+                (Some(spec), tag)
+            }
+            None => (None, None),
+        };
+        InstallOptions {
+            version: self.version.clone().or(tag),
+            user: self.user,
+            backend: self.backend.clone(),
+            source: self.git.as_ref().map(|repo| SourceSpec {
+                repo: repo.clone(),
+                build_command: self.build_command.clone(),
+            }),
+            url: self.url.as_ref().map(|url| UrlSpec { url: url.clone(), asset: self.asset.clone() }),
+            image,
+            expose: self.expose.clone(),
+            channel: self.channel,
+            dry_run: false,
+            arch: self.arch.clone(),
+            env: self.env.clone(),
+        }
+    }
+}
+
+pub fn load_manifest(path: &Path) -> Result<Manifest> {
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read manifest {}", path.display()))?;
+    toml::from_str(&data).with_context(|| format!("Failed to parse manifest {}", path.display()))
+}
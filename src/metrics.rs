@@ -0,0 +1,128 @@
+//! Prometheus metrics, served at `/metrics` by `updater serve` and, with
+//! `daemon.metrics_listen` set, by the daemon, so fleet monitoring can alert
+//! on machines that stopped updating.
+//!
+//! Operation failures are counted in a file, since installs, updates and
+//! removals run in whichever updater process was asked to do them.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tiny_http::{Header, Method, Response, Server};
+use tracing::{debug, info};
+
+use crate::package;
+use crate::prompt_status;
+
+/// Serializes changes to the counters between threads of one process.
+static COUNTERS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Counters {
+    /// Failed operations by kind: install, update, remove or check
+    failures: BTreeMap<String, u64>,
+}
+
+fn counters_path() -> PathBuf {
+    package::get_package_db_path().with_file_name("metrics.json")
+}
+
+fn load_counters() -> Counters {
+    fs::read_to_string(counters_path()).ok().and_then(|data| serde_json::from_str(&data).ok()).unwrap_or_default()
+}
+
+/// Count a failed `operation`, like `install` or `update`.
+pub fn record_failure(operation: &str) {
+    let _guard = COUNTERS_LOCK.lock().unwrap();
+    let mut counters = load_counters();
+    *counters.failures.entry(operation.to_string()).or_default() += 1;
+    let result = serde_json::to_string_pretty(&counters).map_err(anyhow::Error::from).and_then(|data| {
+        fs::write(counters_path(), data).context("Failed to write metrics")
+    });
+    if let Err(e) = result {
+        debug!("Not counting {} failure: {:#}", operation, e);
+    }
+}
+
+/// Escape a label value as the text exposition format wants.
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn timestamp(date: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(date).ok().map(|date| date.timestamp())
+}
+
+/// Every metric in the Prometheus text exposition format.
+pub fn render() -> Result<String> {
+    let packages = package::load_packages()?;
+    let mut out = String::new();
+
+    let system = packages.values().filter(|package| package.system).count();
+    writeln!(out, "# HELP updater_packages_installed Packages in the package database.")?;
+    writeln!(out, "# TYPE updater_packages_installed gauge")?;
+    writeln!(out, "updater_packages_installed{{scope=\"user\"}} {}", packages.len() - system)?;
+    writeln!(out, "updater_packages_installed{{scope=\"system\"}} {}", system)?;
+
+    writeln!(out, "# HELP updater_packages_outdated Packages with a newer upstream version as of the last check.")?;
+    writeln!(out, "# TYPE updater_packages_outdated gauge")?;
+    writeln!(out, "updater_packages_outdated {}", prompt_status::pending().len())?;
+
+    if let Some(checked) = prompt_status::last_check().as_deref().and_then(timestamp) {
+        writeln!(out, "# HELP updater_last_check_timestamp_seconds When upstream was last checked for updates.")?;
+        writeln!(out, "# TYPE updater_last_check_timestamp_seconds gauge")?;
+        writeln!(out, "updater_last_check_timestamp_seconds {}", checked)?;
+    }
+
+    writeln!(out, "# HELP updater_package_last_update_timestamp_seconds When each package was last updated, or installed if never.")?;
+    writeln!(out, "# TYPE updater_package_last_update_timestamp_seconds gauge")?;
+    let mut names: Vec<&String> = packages.keys().collect();
+    names.sort();
+    for name in names {
+        let package = &packages[name];
+        let installed = package.active_version.as_ref().and_then(|version| package.versions.get(version)).map(|info| info.install_date.as_str());
+        if let Some(updated) = package.last_updated.as_deref().or(installed).and_then(timestamp) {
+            writeln!(out, "updater_package_last_update_timestamp_seconds{{package=\"{}\"}} {}", label(name), updated)?;
+        }
+    }
+
+    writeln!(out, "# HELP updater_operation_failures_total Installs, updates, removals and daemon checks that failed.")?;
+    writeln!(out, "# TYPE updater_operation_failures_total counter")?;
+    for (operation, count) in load_counters().failures {
+        writeln!(out, "updater_operation_failures_total{{operation=\"{}\"}} {}", label(&operation), count)?;
+    }
+    Ok(out)
+}
+
+/// The `/metrics` response, or a plain text error.
+pub fn response() -> Response<std::io::Cursor<Vec<u8>>> {
+    let (status, body) = match render() {
+        Ok(body) => (200, body),
+        Err(e) => (500, format!("{:#}\n", e)),
+    };
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap())
+}
+
+/// Serve only `/metrics` on `listen`, as the daemon does.
+pub fn serve(listen: SocketAddr) -> Result<()> {
+    let server = Server::http(listen).map_err(|e| anyhow!("Failed to listen on {}: {}", listen, e))?;
+    info!("Serving metrics on http://{}/metrics", listen);
+    for request in server.incoming_requests() {
+        let response = match (request.method(), request.url().split('?').next()) {
+            (Method::Get, Some("/metrics")) => response(),
+            _ => Response::from_string("Not found\n").with_status_code(404),
+        };
+        if let Err(e) = request.respond(response) {
+            debug!("Failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
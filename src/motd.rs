@@ -0,0 +1,37 @@
+//! `updater motd`: a login summary of pending updates, from the same cache
+//! `prompt-status` reads, so logins never wait on the network.
+//!
+//! Run it from a script in `/etc/update-motd.d`, or from a shell rc file:
+//!
+//! ```sh
+//! #!/bin/sh
+//! exec updater motd
+//! ```
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::prompt_status;
+
+/// The summary for the pending updates, empty when there are none.
+pub fn summary() -> String {
+    match prompt_status::pending().len() {
+        0 => String::new(),
+        1 => "1 package can be updated (run `updater update`)\n".to_string(),
+        count => format!("{} packages can be updated (run `updater update`)\n", count),
+    }
+}
+
+/// Print the summary, or write it to `output` for motd setups that read a
+/// static file. The file is emptied when nothing is pending.
+pub fn motd(output: Option<&Path>) -> Result<()> {
+    let summary = summary();
+    match output {
+        Some(output) => fs::write(output, summary).with_context(|| format!("Failed to write {}", output.display())),
+        None => {
+            print!("{}", summary);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! HTTP for everything updater fetches: one shared client so connections
+//! are reused, timeouts from the `[network]` config, and retries with
+//! exponential backoff when a request fails in a way worth retrying.
+//! `ipfs://` and `ipns://` URLs are fetched through an IPFS HTTP gateway,
+//! the former checked against their CID by `ipfs`.
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::auth;
+use crate::config::{self, Config};
+
+/// Wait before the first retry, doubled for every one after it.
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait between attempts, also for servers asking for longer with `Retry-After`.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Gateway a local IPFS daemon serves by default.
+const LOCAL_GATEWAY: &str = "http://127.0.0.1:8080";
+
+const PUBLIC_GATEWAY: &str = "https://ipfs.io";
+
+fn settings() -> &'static Config {
+    static SETTINGS: OnceLock<Config> = OnceLock::new();
+    SETTINGS.get_or_init(|| config::load_config().unwrap_or_default())
+}
+
+fn gateway() -> &'static str {
+    static GATEWAY: OnceLock<String> = OnceLock::new();
+    GATEWAY.get_or_init(|| {
+        if let Some(gateway) = &settings().ipfs.gateway {
+            return gateway.trim_end_matches('/').to_string();
+        }
+        let local: SocketAddr = ([127, 0, 0, 1], 8080).into();
+        if TcpStream::connect_timeout(&local, Duration::from_millis(200)).is_ok() {
+            LOCAL_GATEWAY.to_string()
+        } else {
+            PUBLIC_GATEWAY.to_string()
+        }
+    })
+}
+
+/// The HTTP URL `url` is fetched from: `ipfs://<cid>/<path>` and
+/// `ipns://<name>/<path>` go through the gateway, anything else as is.
+pub fn http_url(url: &str) -> String {
+    for scheme in ["ipfs", "ipns"] {
+        if let Some(rest) = url.strip_prefix(scheme).and_then(|rest| rest.strip_prefix("://")) {
+            return format!("{}/{}/{}", gateway(), scheme, rest);
+        }
+    }
+    url.to_string()
+}
+
+/// Whether `url` names content by its hash, so what it points at never changes.
+pub fn is_content_addressed(url: &str) -> bool {
+    url.starts_with("ipfs://")
+}
+
+/// The client every request goes through, built once from the config.
+pub fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let settings = &settings().network;
+        let mut builder = Client::builder().connect_timeout(Duration::from_secs(settings.connect_timeout_secs));
+        if settings.timeout_secs > 0 {
+            builder = builder.timeout(Duration::from_secs(settings.timeout_secs));
+        }
+        builder.build().unwrap_or_else(|e| {
+            warn!("Falling back to default HTTP settings: {}", e);
+            Client::new()
+        })
+    })
+}
+
+/// Start a GET request to `url` with stored credentials attached.
+pub fn get(url: &str) -> RequestBuilder {
+    let url = http_url(url);
+    auth::authorize(client().get(&url), &url)
+}
+
+/// Start a HEAD request to `url` with stored credentials attached.
+pub fn head(url: &str) -> RequestBuilder {
+    let url = http_url(url);
+    auth::authorize(client().head(&url), &url)
+}
+
+/// How many times a failed request is retried.
+pub fn retries() -> u32 {
+    settings().network.retries
+}
+
+/// How long to wait before retry number `attempt`, counting from zero.
+pub fn backoff(attempt: u32) -> Duration {
+    BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_DELAY)
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Whether a request that failed this way may well succeed if sent again.
+pub fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect() || error.status().is_some_and(is_transient_status)
+}
+
+/// The wait a `Retry-After` header asks for, given in seconds or as an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+/// Sending with retries on dropped connections, timeouts, rate limiting and
+/// server errors.
+pub trait SendRetrying {
+    /// Like `send`, but a request that fails transiently is sent again after
+    /// a growing wait, or the one the server asks for. The last response is
+    /// returned as is, error status or not.
+    fn send_retrying(self) -> reqwest::Result<Response>;
+}
+
+impl SendRetrying for RequestBuilder {
+    fn send_retrying(self) -> reqwest::Result<Response> {
+        let retries = retries();
+        let mut attempt = 0;
+        loop {
+            // Requests with streamed bodies can't be copied, so they only get one try
+            let request = match self.try_clone() {
+                Some(request) => request,
+                None => return self.send(),
+            };
+            let (wait, reason) = match request.send() {
+                Ok(response) if attempt < retries && is_transient_status(response.status()) => {
+                    let wait = retry_after(&response).unwrap_or_else(|| backoff(attempt)).min(MAX_DELAY);
+                    (wait, response.status().to_string())
+                }
+                Err(e) if attempt < retries && is_transient(&e) => (backoff(attempt), e.to_string()),
+                result => return result,
+            };
+            warn!("Request failed ({}), retrying in {:.1}s", reason, wait.as_secs_f32());
+            thread::sleep(wait);
+            attempt += 1;
+        }
+    }
+}
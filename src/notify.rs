@@ -0,0 +1,161 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+
+use crate::config::{self, EmailConfig, NotifyEvent, Webhook, WebhookKind};
+use crate::net::{self, SendRetrying};
+use crate::package::OutdatedPackage;
+use crate::template::Template;
+
+/// Fields available to webhook and email templates.
+pub const MESSAGE_FIELDS: &[&str] = &["host", "event", "summary", "count", "packages"];
+
+const DEFAULT_TEMPLATE: &str = "{host}: {summary}\n{packages}";
+const DEFAULT_SUBJECT: &str = "[updater] {host}: {summary}";
+
+/// Send a desktop notification unless notifications are disabled in the config.
+/// Having no notification daemon (e.g. on a headless server) is not an error.
+fn send(summary: &str, body: &str) -> Result<()> {
+    if !config::load_config()?.notifications.enabled {
+        return Ok(());
+    }
+
+    let result = notify_rust::Notification::new()
+        .appname("updater")
+        .summary(summary)
+        .body(body)
+        .icon("system-software-update")
+        .show();
+    if let Err(e) = result {
+        warn!("Could not send desktop notification: {}", e);
+    }
+    Ok(())
+}
+
+fn summary(event: NotifyEvent, count: usize) -> String {
+    match (event, count) {
+        (NotifyEvent::Outdated, 1) => "1 package can be updated".to_string(),
+        (NotifyEvent::Outdated, n) => format!("{} packages can be updated", n),
+        (NotifyEvent::Updated, 1) => "Updated 1 package".to_string(),
+        (NotifyEvent::Updated, n) => format!("Updated {} packages", n),
+    }
+}
+
+pub fn outdated(names: &[String]) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    send(&summary(NotifyEvent::Outdated, names.len()), &names.join(", ")).context("Failed to send outdated notification")
+}
+
+pub fn updated(names: &[String]) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    send(&summary(NotifyEvent::Updated, names.len()), &names.join(", ")).context("Failed to send update notification")
+}
+
+/// One event's worth of packages, ready to render into messages.
+struct Report<'a> {
+    event: NotifyEvent,
+    host: String,
+    summary: String,
+    packages: &'a [OutdatedPackage],
+}
+
+impl Report<'_> {
+    fn render(&self, template: Option<&str>, default: &str) -> Result<String> {
+        let template = Template::parse(template.unwrap_or(default), MESSAGE_FIELDS)?;
+        Ok(template.render(|field| match field {
+            "host" => self.host.clone(),
+            "event" => self.event.to_string(),
+            "summary" => self.summary.clone(),
+            "count" => self.packages.len().to_string(),
+            _ => self.packages.iter().map(|pkg| format!("{} {} -> {}", pkg.name, pkg.current, pkg.latest)).collect::<Vec<_>>().join("\n"),
+        }))
+    }
+
+    fn post(&self, webhook: &Webhook) -> Result<()> {
+        let message = self.render(webhook.template.as_deref(), DEFAULT_TEMPLATE)?;
+        let payload = match webhook.kind {
+            WebhookKind::Generic => serde_json::json!({
+                "event": self.event,
+                "host": self.host,
+                "summary": self.summary,
+                "message": message,
+                "packages": self.packages,
+            }),
+            WebhookKind::Slack => serde_json::json!({ "text": message }),
+            WebhookKind::Matrix => serde_json::json!({ "text": message, "username": "updater" }),
+        };
+        net::client()
+            .post(&webhook.url)
+            .json(&payload)
+            .send_retrying()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow!("Webhook {} failed: {}", webhook.url, e.without_url()))?;
+        Ok(())
+    }
+
+    fn mail(&self, email: &EmailConfig) -> Result<()> {
+        if email.to.is_empty() {
+            bail!("Email notifications need at least one address in `to`");
+        }
+        let mut message = format!("To: {}\nSubject: {}\n", email.to.join(", "), self.render(email.subject.as_deref(), DEFAULT_SUBJECT)?);
+        if let Some(from) = &email.from {
+            message.push_str(&format!("From: {}\n", from));
+        }
+        message.push_str("Content-Type: text/plain; charset=utf-8\n\n");
+        message.push_str(&self.render(email.template.as_deref(), DEFAULT_TEMPLATE)?);
+        message.push('\n');
+
+        let sendmail = email.sendmail.as_deref().unwrap_or("sendmail");
+        let mut child = Command::new(sendmail)
+            .args(["-t", "-i"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to execute {}", sendmail))?;
+        child.stdin.take().context("No stdin for sendmail")?.write_all(message.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!("{} failed: {}", sendmail, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(())
+    }
+}
+
+/// Send `packages` to the webhooks and email configured for `event`, trying
+/// every destination before reporting the ones that failed.
+pub fn report(event: NotifyEvent, packages: &[OutdatedPackage]) -> Result<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let config = config::load_config()?.notifications;
+    let wants = |events: &[NotifyEvent]| events.is_empty() || events.contains(&event);
+    let report = Report {
+        event,
+        host: std::fs::read_to_string("/etc/hostname").map(|h| h.trim().to_string()).unwrap_or_else(|_| "localhost".to_string()),
+        summary: summary(event, packages.len()),
+        packages,
+    };
+
+    let mut failures = Vec::new();
+    for webhook in config.webhooks.iter().filter(|webhook| wants(&webhook.events)) {
+        match report.post(webhook) {
+            Ok(()) => debug!("Sent {} notification to {}", event, webhook.url),
+            Err(e) => failures.push(format!("{:#}", e)),
+        }
+    }
+    if let Some(email) = config.email.as_ref().filter(|email| wants(&email.events)) {
+        if let Err(e) = report.mail(email) {
+            failures.push(format!("{:#}", e));
+        }
+    }
+    if !failures.is_empty() {
+        bail!("{}", failures.join("; "));
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Installs, updates and removals requested by remote clients. Each one runs
+/// `updater <args>` as a child process, so a failing backend can't take the
+/// server down, and its output is kept for clients to follow.
+#[derive(Clone, Default)]
+pub struct Operations {
+    inner: Arc<(Mutex<Vec<Operation>>, Condvar)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub id: usize,
+    pub args: Vec<String>,
+    pub started: String,
+    pub status: OperationStatus,
+    pub output: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl Operations {
+    /// Start `updater <args>` in the background and return its operation id.
+    /// There is nobody to answer prompts, so the child always runs with `--yes`.
+    pub fn start(&self, args: Vec<String>) -> usize {
+        let id = {
+            let mut operations = self.inner.0.lock().unwrap();
+            let id = operations.len();
+            operations.push(Operation {
+                id,
+                args: args.clone(),
+                started: chrono::Local::now().to_rfc3339(),
+                status: OperationStatus::Running,
+                output: Vec::new(),
+            });
+            id
+        };
+
+        let operations = self.clone();
+        thread::spawn(move || {
+            let status = match operations.run(id, &args) {
+                Ok(true) => OperationStatus::Succeeded,
+                Ok(false) => OperationStatus::Failed,
+                Err(e) => {
+                    operations.push_line(id, format!("{:#}", e));
+                    OperationStatus::Failed
+                }
+            };
+            operations.update(id, |operation| operation.status = status);
+        });
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<Operation> {
+        self.inner.0.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Operation> {
+        self.inner.0.lock().unwrap().clone()
+    }
+
+    /// Block until operation `id` has printed more than `seen` lines or
+    /// finished, and return the new lines with its current status.
+    pub fn wait(&self, id: usize, seen: usize) -> Option<(Vec<String>, OperationStatus)> {
+        let (lock, changed) = &*self.inner;
+        let mut operations = lock.lock().unwrap();
+        loop {
+            let operation = operations.get(id)?;
+            if operation.output.len() > seen || operation.status != OperationStatus::Running {
+                return Some((operation.output[seen.min(operation.output.len())..].to_vec(), operation.status));
+            }
+            operations = changed.wait(operations).unwrap();
+        }
+    }
+
+    fn update(&self, id: usize, f: impl FnOnce(&mut Operation)) {
+        let (lock, changed) = &*self.inner;
+        if let Some(operation) = lock.lock().unwrap().get_mut(id) {
+            f(operation);
+        }
+        changed.notify_all();
+    }
+
+    fn push_line(&self, id: usize, line: String) {
+        self.update(id, |operation| operation.output.push(line));
+    }
+
+    fn run(&self, id: usize, args: &[String]) -> Result<bool> {
+        let updater = std::env::current_exe().context("Could not determine updater executable")?;
+        let mut child = Command::new(updater)
+            .arg("--yes")
+            .args(args)
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to start updater")?;
+
+        let stderr = child.stderr.take().context("Failed to capture output")?;
+        let stderr_thread = {
+            let operations = self.clone();
+            thread::spawn(move || operations.forward(id, stderr))
+        };
+        let stdout = child.stdout.take().context("Failed to capture output")?;
+        self.forward(id, stdout);
+        stderr_thread.join().ok();
+
+        Ok(child.wait()?.success())
+    }
+
+    fn forward(&self, id: usize, output: impl Read) {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            self.push_line(id, line);
+        }
+    }
+}
+
+/// Refuse client-supplied values that updater's argument parser would take
+/// for flags, like a package named `--file=/tmp/evil.deb`.
+fn check_value(what: &str, value: &str) -> Result<()> {
+    if value.is_empty() || value.starts_with('-') {
+        bail!("Invalid {} {:?}", what, value);
+    }
+    Ok(())
+}
+
+/// Arguments for an `updater install` operation.
+pub fn install_args(name: String, version: Option<String>, user: bool, backend: Option<String>) -> Result<Vec<String>> {
+    check_value("package name", &name)?;
+    let mut args = vec!["install".to_string()];
+    if let Some(version) = version {
+        check_value("version", &version)?;
+        args.extend(["--version".to_string(), version]);
+    }
+    if user {
+        args.push("--user".to_string());
+    }
+    if let Some(backend) = backend {
+        check_value("backend", &backend)?;
+        args.extend(["--backend".to_string(), backend]);
+    }
+    args.extend(["--".to_string(), name]);
+    Ok(args)
+}
+
+/// Arguments for an `updater update` operation, of every package when `name` is unset.
+pub fn update_args(name: Option<String>) -> Result<Vec<String>> {
+    match name {
+        Some(name) => {
+            check_value("package name", &name)?;
+            Ok(vec!["update".to_string(), "--".to_string(), name])
+        }
+        None => Ok(vec!["update".to_string(), "--all".to_string()]),
+    }
+}
+
+/// Arguments for an `updater remove` operation.
+pub fn remove_args(name: String, version: Option<String>) -> Result<Vec<String>> {
+    check_value("package name", &name)?;
+    let mut args = vec!["remove".to_string()];
+    if let Some(version) = version {
+        check_value("version", &version)?;
+        args.extend(["--version".to_string(), version]);
+    }
+    args.extend(["--".to_string(), name]);
+    Ok(args)
+}
@@ -1,39 +1,332 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use colored::*;
+use dialoguer::MultiSelect;
+use indicatif::HumanBytes;
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, IsTerminal, Write};
+use std::os::fd::AsRawFd;
 use std::path::{Path, PathBuf};
+use tracing::{debug, error, info, warn};
 
+use crate::backends::{self, Metadata};
+use crate::backends::conda::CondaBackend;
+use crate::backends::local::{self as local_package, LocalPackageKind};
+use crate::backends::plugin;
+use crate::backends::source::{SourceBackend, SourceSpec};
+use crate::backends::oci::{ImageBackend, ImageSpec};
+use crate::backends::url::{UrlBackend, UrlSpec};
+use crate::bundle;
+use crate::changelog;
+use crate::compat;
+use crate::config;
+use crate::download;
+use crate::du;
+use crate::error::Error;
+use crate::fs_snapshot;
+use crate::fuzzy;
+use crate::hooks::{self, Hook, HookContext};
+use crate::notify;
+use crate::privilege;
+use crate::profile;
+use crate::prompt;
+use crate::prompt_status;
+use crate::sandbox;
+use crate::sbom;
+use crate::schedule::Frequency;
+use crate::search_index::{self, CachedResult};
+use crate::share;
+use crate::shim;
 use crate::system::{self, PackageManager};
+use crate::template::Template;
+use crate::transaction::{self, StagedInstall};
 use crate::utils;
-use crate::version;
+use crate::verify::{self, Provenance, ProvenanceStatus};
+use crate::version::{self, Channel, UpdateLevel};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Package {
     pub name: String,
     pub versions: HashMap<String, PackageVersion>,
     pub active_version: Option<String>,
     pub system: bool,
+    /// Release channel the package follows for updates, if subscribed
+    #[serde(default)]
+    pub channel: Option<Channel>,
+    /// Version constraint (e.g. `^1.4`) that updates must stay within
+    #[serde(default)]
+    pub constraint: Option<String>,
+    /// Environment variables exported by `updater env` for this package
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Why the package is installed, which decides whether `autoremove` may remove it
+    #[serde(default)]
+    pub reason: InstallReason,
+    /// Packages with a higher priority win shims for binary names they share
+    #[serde(default)]
+    pub priority: i32,
+    /// Shim names to expose binaries under instead of their own, keyed by binary name
+    #[serde(default)]
+    pub renamed_bins: BTreeMap<String, String>,
+    /// Extra shim names for binaries, keyed by alias, next to their own names
+    #[serde(default)]
+    pub aliases: BTreeMap<String, BinAlias>,
+    /// Meant to be system-wide but installed under the user prefix for lack
+    /// of root, until `updater promote` moves it
+    #[serde(default)]
+    pub pending_system: bool,
+    /// Whether `updater update` and the daemon update the package on their own
+    #[serde(default)]
+    pub policy: UpdatePolicy,
+    /// Most often the package may be updated when it isn't named explicitly
+    #[serde(default)]
+    pub max_frequency: Option<Frequency>,
+    /// When the package was last updated, which `max_frequency` counts from
+    #[serde(default)]
+    pub last_updated: Option<String>,
+    /// How far unattended updates may move the package, `daemon.auto_update` if unset
+    #[serde(default)]
+    pub auto_update: Option<UpdateLevel>,
+    /// Versions that update resolution never picks, e.g. a broken release
+    #[serde(default)]
+    pub skipped_versions: BTreeSet<String>,
+    /// Name the package is published under now, once it was renamed upstream
+    #[serde(default)]
+    pub renamed_to: Option<String>,
+    /// Names the package was installed under before being migrated, oldest first
+    #[serde(default)]
+    pub previous_names: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How a package takes part in updates that don't name it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdatePolicy {
+    /// Updated by `updater update` and by the daemon, within `daemon.auto_update`
+    #[default]
+    Auto,
+    /// Reported by the daemon, but only updated when named
+    NotifyOnly,
+    /// Left alone by the daemon, and only updated when named
+    Manual,
+}
+
+impl std::fmt::Display for UpdatePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdatePolicy::Auto => write!(f, "auto"),
+            UpdatePolicy::NotifyOnly => write!(f, "notify-only"),
+            UpdatePolicy::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+impl Package {
+    /// Why updates that don't name the package skip it right now, if they do.
+    pub fn held_back(&self) -> Option<String> {
+        if self.policy != UpdatePolicy::Auto {
+            return Some(format!("its update policy is {}", self.policy));
+        }
+        let frequency = self.max_frequency?;
+        let last_updated = chrono::DateTime::parse_from_rfc3339(self.last_updated.as_deref()?).ok()?;
+        let next = last_updated + frequency.interval();
+        if chrono::Local::now() < next {
+            return Some(format!("it is updated at most {}, next after {}", frequency, next.format("%Y-%m-%d %H:%M")));
+        }
+        None
+    }
+}
+
+/// A binary exposed under another shim name, e.g. `terraform` 1.5 as `terraform15`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinAlias {
+    pub bin: String,
+    /// Version the alias always runs, the active (or pinned) one if unset
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallReason {
+    /// Asked for by name
+    #[default]
+    Explicit,
+    /// Pulled in because another package needs it
+    Dependency,
+}
+
+impl std::fmt::Display for InstallReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InstallReason::Explicit => "explicit",
+            InstallReason::Dependency => "dependency",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageVersion {
     pub install_path: PathBuf,
     pub install_date: String,
     pub bin_paths: Vec<PathBuf>,
     pub package_manager: Option<String>,
+    /// Package file this version was installed from, kept for native removal
+    #[serde(default)]
+    pub local_file: Option<PathBuf>,
+    /// Repository and build settings for versions built from source
+    #[serde(default)]
+    pub source: Option<SourceSpec>,
+    /// Artifact URL template for versions downloaded directly
+    #[serde(default)]
+    pub url: Option<UrlSpec>,
+    /// Image and binaries for versions taken out of a container image
+    #[serde(default)]
+    pub image: Option<ImageSpec>,
+    /// Architecture the version was installed for, this machine's unless `--arch` said otherwise
+    #[serde(default)]
+    pub arch: Option<String>,
+    /// Build provenance checked at install time, for backends that can check it
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    /// License declared upstream, usually an SPDX expression
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Description and links from the backend's registry
+    #[serde(flatten)]
+    pub metadata: Metadata,
+    /// Where the version was downloaded from, as far as updater can tell
+    #[serde(default)]
+    pub source_url: Option<String>,
+    /// Bytes on disk, measured when the version was installed
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Every file the version installed, including ones outside `install_path`
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// SHA-256 of each regular file under `install_path` at install time
+    #[serde(default)]
+    pub checksums: BTreeMap<PathBuf, String>,
+    /// Names of the packages this version needs
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Release a version recorded as `latest` was at install time, as far as
+    /// the backend's version listing could tell
+    #[serde(default)]
+    pub resolved_version: Option<String>,
+    /// What a rolling tag like `nightly` pointed at upstream when installed
+    #[serde(default)]
+    pub revision: Option<String>,
+}
+
+impl PackageVersion {
+    /// The exact version behind `version`, the key this version is recorded
+    /// under, or `None` for a `latest` install whose release is unknown.
+    pub fn exact_version<'a>(&'a self, version: &'a str) -> Option<&'a str> {
+        match version {
+            "latest" => self.resolved_version.as_deref(),
+            version => Some(version),
+        }
+    }
+}
+
+/// Options controlling where and how a package gets installed.
+#[derive(Debug, Default)]
+pub struct InstallOptions {
+    pub version: Option<String>,
+    pub user: bool,
+    /// Backend to install with instead of the detected system package manager
+    pub backend: Option<String>,
+    pub source: Option<SourceSpec>,
+    pub url: Option<UrlSpec>,
+    pub image: Option<ImageSpec>,
+    /// Binaries to expose for backends that install whole environments (conda)
+    pub expose: Vec<String>,
+    /// Release channel to resolve the version from and follow on update
+    pub channel: Option<Channel>,
+    /// Resolve and report what would happen without installing anything
+    pub dry_run: bool,
+    /// Architecture to install for instead of this machine's, under a `--prefix`
+    pub arch: Option<String>,
+    /// Variables `updater env` exports for the package, on top of the ones
+    /// its backend declares; `{install_dir}` stands for the active version's
+    pub env: BTreeMap<String, String>,
+}
+
+/// Fill in what the backend's registry says about a freshly installed
+/// version and where it came from.
+fn describe(pkg_version: &mut PackageVersion, name: &str, version: &str, pm_name: &str) {
+    pkg_version.metadata = backends::metadata(pm_name, name, version);
+    if let Some(spec) = &pkg_version.source {
+        pkg_version.metadata.repository.get_or_insert_with(|| spec.repo.clone());
+    }
+    if version == "latest" {
+        pkg_version.resolved_version = backends::get_version_source(pm_name, pkg_version.source.as_ref())
+            .and_then(|source| source.available_versions(name).ok())
+            .and_then(|versions| version::latest(&versions, Channel::Stable));
+    }
+    if version::is_rolling(version) {
+        pkg_version.revision = backends::get_version_source(pm_name, pkg_version.source.as_ref())
+            .and_then(|source| source.revision(name, version).ok().flatten());
+    }
+    let exact = pkg_version.exact_version(version).unwrap_or(version).to_string();
+    pkg_version.source_url = sbom::download_url(name, &exact, pkg_version, pm_name);
+}
+
+/// Define installation path based on user/system preference, under a
+/// profile's own dir for every profile but the default one. Under a prefix
+/// user and system packages share its `packages` dir.
+pub fn base_install_path(user: bool) -> PathBuf {
+    if let Some(prefix) = profile::prefix() {
+        return prefix.join("packages");
+    }
+    let base = if user {
+        dirs::home_dir().unwrap().join(".local/share/updater")
+    } else {
+        PathBuf::from("/opt/updater")
+    };
+    let profile = profile::active();
+    if profile == profile::DEFAULT_PROFILE {
+        base.join("packages")
+    } else {
+        base.join("profiles").join(profile).join("packages")
+    }
 }
 
 pub fn get_package_db_path() -> PathBuf {
     let data_dir = dirs::data_dir().expect("Could not determine data directory");
-    let updater_dir = data_dir.join("updater");
+    let updater_dir = profile::active_dir().unwrap_or_else(|| data_dir.join("updater"));
     fs::create_dir_all(&updater_dir).expect("Failed to create data directory");
     updater_dir.join("packages.json")
 }
 
+/// Exclusive hold on the package database, released when dropped. Commands
+/// that load, change and save it take one for the whole run, so two updaters,
+/// like the daemon and an install, can't overwrite each other's changes.
+pub struct DbLock {
+    _file: File,
+}
+
+pub fn lock_db() -> Result<DbLock> {
+    let path = get_package_db_path().with_file_name("packages.lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let fd = file.as_raw_fd();
+    if unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        info!("Waiting for another updater to finish with the package database");
+        if unsafe { libc::flock(fd, libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to lock package database");
+        }
+    }
+    Ok(DbLock { _file: file })
+}
+
 pub fn load_packages() -> Result<HashMap<String, Package>> {
     let db_path = get_package_db_path();
     if !db_path.exists() {
@@ -49,30 +342,224 @@ pub fn save_packages(packages: &HashMap<String, Package>) -> Result<()> {
     let db_path = get_package_db_path();
     let data = serde_json::to_string_pretty(packages).context("Failed to serialize package database")?;
     fs::write(&db_path, data).context("Failed to write package database")?;
+    
+    // Keep the shims, man pages and completions in sync with what the database now provides
+    shim::refresh(packages)?;
+    share::refresh(packages)?;
+    Ok(())
+}
+
+/// Install a package and return the version it was recorded under.
+pub fn install(name: &str, options: InstallOptions) -> Result<String> {
+    let arch = options.arch.clone();
+    compat::with_target_arch(arch.as_deref(), || install_package(name, options, &mut Vec::new()))
+}
+
+/// One line of a package list: `name`, `name@version` or `backend:name@version`.
+pub(crate) fn parse_list_entry(line: &str) -> (Option<&str>, &str, Option<&str>) {
+    let (backend, rest) = match line.split_once(':') {
+        Some((backend, rest)) if !backend.is_empty() && backend.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => (Some(backend), rest),
+        _ => (None, line),
+    };
+    // A leading @ belongs to scoped npm names like `@scope/pkg`
+    match rest.rfind('@') {
+        Some(at) if at > 0 => (backend, &rest[..at], Some(&rest[at + 1..])),
+        _ => (backend, rest, None),
+    }
+}
+
+/// Start downloading the artifacts of several upcoming installs, as
+/// `(backend, name, version, url)`, up to `downloads.concurrency` at a time.
+pub(crate) fn prefetch<'a>(installs: impl IntoIterator<Item = (&'a str, &'a str, Option<&'a str>, Option<&'a UrlSpec>)>) -> Result<()> {
+    let limit = config::load_config()?.downloads.concurrency;
+    // A limit of one keeps downloads strictly one after another
+    if limit <= 1 {
+        return Ok(());
+    }
+    let urls: Vec<String> = installs
+        .into_iter()
+        .filter_map(|(backend, name, version, url)| backends::artifact_url(backend, name, version, url))
+        .collect();
+    if urls.len() > 1 {
+        download::prefetch(urls, limit);
+    }
+    Ok(())
+}
+
+/// Install every package in a list file, one spec per line with `#`
+/// comments, and report which ones failed at the end instead of stopping.
+/// `options` apply to every entry, except for the backend of entries naming their own.
+pub fn install_list(path: &Path, options: InstallOptions) -> Result<()> {
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read package list {}", path.display()))?;
+    let entries: Vec<&str> = data
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    
+    if !options.dry_run {
+        prefetch(entries.iter().filter_map(|entry| {
+            let (backend, name, version) = parse_list_entry(entry);
+            Some((backend.or(options.backend.as_deref())?, name, version, None))
+        }))?;
+    }
+    
+    let mut installed = Vec::new();
+    let mut failed = Vec::new();
+    for entry in &entries {
+        let (backend, name, version) = parse_list_entry(entry);
+        info!("{} {}", "Installing".green(), entry.yellow().bold());
+        let entry_options = InstallOptions {
+            version: version.map(str::to_string),
+            user: options.user,
+            backend: backend.map(str::to_string).or_else(|| options.backend.clone()),
+            source: None,
+            url: None,
+            image: None,
+            expose: options.expose.clone(),
+            channel: options.channel,
+            dry_run: options.dry_run,
+            arch: options.arch.clone(),
+            env: options.env.clone(),
+        };
+        match install(name, entry_options) {
+            Ok(version) => installed.push(format!("{} {}", name, version)),
+            Err(e) => {
+                error!("{} {}: {:#}", "Failed to install".red(), entry.yellow(), e);
+                failed.push(entry.to_string());
+            }
+        }
+    }
+    
+    println!();
+    let done = if options.dry_run { "would be installed" } else { "installed" };
+    println!("{} {}", installed.len().to_string().green().bold(), done.green());
+    for package in &installed {
+        println!("  {}", package);
+    }
+    if !failed.is_empty() {
+        println!("{} {}", failed.len().to_string().red().bold(), "failed".red());
+        for entry in &failed {
+            println!("  {}", entry);
+        }
+        return Err(Error::InstallsFailed(failed.len()).into());
+    }
     Ok(())
 }
 
-pub fn install(name: &str, version: Option<String>, user: bool) -> Result<()> {
+/// `chain` holds the packages whose dependencies are being installed, so a
+/// dependency cycle stops instead of recursing forever.
+fn install_package(name: &str, options: InstallOptions, chain: &mut Vec<String>) -> Result<String> {
     let mut packages = load_packages()?;
+    let reason = if chain.is_empty() { InstallReason::Explicit } else { InstallReason::Dependency };
+    // The target architecture was set up by `install`
+    let InstallOptions { version, user, backend, source, url, image, expose, channel, dry_run, arch: _, env } = options;
     
-    // Determine the appropriate package manager for the system
-    let package_manager = system::detect_package_manager()?;
-    let version_to_install = version.clone().unwrap_or_else(|| "latest".to_string());
+    // Source builds, URLs and images bring their own backend, otherwise use the requested or system one
+    let package_manager: Box<dyn PackageManager> = match (&source, &url, &image, &backend) {
+        (Some(spec), _, _, _) => Box::new(SourceBackend::new(spec.clone())),
+        (None, Some(spec), _, _) => Box::new(UrlBackend::new(spec.clone())),
+        (None, None, Some(spec), _) => Box::new(ImageBackend::new(spec.clone())),
+        (None, None, None, Some(backend_name)) if backend_name == "conda" => Box::new(CondaBackend::new(expose)),
+        (None, None, None, Some(backend_name)) => backends::get_package_manager_by_name(backend_name)?,
+        (None, None, None, None) => system::detect_package_manager()?,
+    };
+    info!("Using package manager: {}", package_manager.get_name().cyan());
+    sandbox::check_supported(package_manager.get_name())?;
+    if compat::is_foreign() {
+        if !backends::supports_foreign_arch(package_manager.get_name()) {
+            bail!("{} can only install for this machine, not for {}", package_manager.get_name(), compat::target_arch());
+        }
+        // Foreign binaries next to the native ones would break the shims on PATH
+        if profile::prefix().is_none() {
+            bail!("Installing for {} needs a separate root, pass --prefix", compat::target_arch());
+        }
+    }
+    
+    // Without root, a system install can wait under the user prefix for `promote`
+    let requested_user = user;
+    let rootless = !user
+        && !dry_run
+        && config::load_config()?.install.rootless_fallback
+        && !backends::is_root()
+        && !privilege::is_writable(&base_install_path(false).join(name));
+    let user = user || rootless;
+    if rootless {
+        info!("{} {} {}", "No root access, installing".yellow(), name.yellow().bold(), format!("under {} until `updater promote {}`", base_install_path(true).display(), name).yellow());
+    }
     
-    println!("Using package manager: {}", package_manager.get_name().cyan());
+    let prompt = format!("Install {} system-wide into {}?", name, base_install_path(false).display());
+    if !user && !dry_run && !prompt::confirm(&prompt)? {
+        return Err(Error::Aborted(format!("Aborted installing {}", name)).into());
+    }
     
-    // Define installation path based on user/system preference
-    let base_install_path = if user {
-        dirs::home_dir().unwrap().join(".local/share/updater/packages")
+    // Constraints and channel subscriptions pin the install to the newest matching release
+    let constraint = version.clone().filter(|v| version::is_constraint(v));
+    let skipped = packages.get(name).map(|package| package.skipped_versions.clone()).unwrap_or_default();
+    let version = if constraint.is_some() || (channel.is_some() && version.is_none()) {
+        Some(resolve_version(name, &*package_manager, source.as_ref(), channel, constraint.as_deref(), &skipped)?)
     } else {
-        PathBuf::from("/opt/updater/packages")
+        version
     };
+    let version_to_install = version.clone().unwrap_or_else(|| "latest".to_string());
+    
+    // Dependencies the backend declares are installed first, through the same backend
+    let dependencies = backends::dependencies(package_manager.get_name(), name, &version_to_install);
+    chain.push(name.to_string());
+    let missing: Vec<&String> = dependencies
+        .iter()
+        .filter(|dependency| !packages.contains_key(*dependency) && !chain.contains(dependency))
+        .collect();
+    for dependency in &missing {
+        info!("{} {} {} {}", "Installing dependency".green(), dependency.yellow().bold(), "of".green(), name.yellow().bold());
+        let options = InstallOptions { user: requested_user, backend: Some(package_manager.get_name().to_string()), dry_run, ..Default::default() };
+        install_package(dependency, options, chain)?;
+    }
+    if !missing.is_empty() {
+        packages = load_packages()?;
+    }
+    
+    let install_dir = base_install_path(user).join(name).join(&version_to_install);
+    if dry_run {
+        let activates = packages.get(name).is_none_or(|p| p.active_version.is_none());
+        info!("{} {} {} {}", "Would install".yellow(), name.yellow().bold(), version_to_install.cyan(), format!("with {}", package_manager.get_name()).normal());
+        info!("{} {}", "Would create".yellow(), install_dir.display());
+        info!("{} {} {}{}", "Would record".yellow(), format!("{} {}", name, version_to_install).cyan(), "in the package database",
+            if activates { " as the active version" } else { "" });
+        return Ok(version_to_install);
+    }
     
-    let install_dir = base_install_path.join(name).join(&version_to_install);
-    fs::create_dir_all(&install_dir)?;
+    // Find out now rather than halfway through whether the system prefix is writable
+    if !user {
+        privilege::ensure_writable(install_dir.parent().unwrap())?;
+    }
     
-    // Use the appropriate package manager to install
-    let bin_paths = package_manager.install(name, version.as_deref(), &install_dir, user)?;
+    // Use the appropriate package manager to install into a staging dir, which
+    // is removed again if anything fails before it is moved into place
+    let hook_context = HookContext {
+        package: name,
+        version: Some(&version_to_install),
+        install_path: Some(&install_dir),
+        backend: Some(package_manager.get_name()),
+        ..Default::default()
+    };
+    hooks::run(Hook::PreInstall, &hook_context)?;
+    let staged = StagedInstall::begin(&install_dir, backends::is_relocatable(package_manager.get_name()))?;
+    let bin_paths = match package_manager.install(name, version.as_deref(), staged.dir(), user) {
+        Ok(bin_paths) => bin_paths,
+        Err(e) => {
+            if !packages.contains_key(name) {
+                print_suggestions(name, &*package_manager, &packages);
+            }
+            return Err(e);
+        }
+    };
+    let provenance = verify::take_provenance(name)?;
+    // Binaries that won't start here, or a download other than the locked
+    // one, are caught before they replace anything
+    compat::confirm_runnable(name, &bin_paths)?;
+    verify::check_expected_artifact(name, staged.dir())?;
+    let bin_paths = staged.commit(bin_paths)?;
     
     // Update package database
     let package = packages.entry(name.to_string())
@@ -81,38 +568,429 @@ pub fn install(name: &str, version: Option<String>, user: bool) -> Result<()> {
             versions: HashMap::new(),
             active_version: None,
             system: !user,
+            channel: None,
+            constraint: None,
+            env: BTreeMap::new(),
+            reason,
+            priority: 0,
+            renamed_bins: BTreeMap::new(),
+            pending_system: false,
+            policy: UpdatePolicy::default(),
+            max_frequency: None,
+            last_updated: None,
+            auto_update: None,
+            skipped_versions: BTreeSet::new(),
+            aliases: BTreeMap::new(),
+            renamed_to: None,
+            previous_names: Vec::new(),
         });
+    // Asking for a dependency by name makes it explicit
+    if reason == InstallReason::Explicit {
+        package.reason = reason;
+    }
+    if channel.is_some() {
+        package.channel = channel;
+    }
+    if constraint.is_some() {
+        package.constraint = constraint;
+    }
+    package.env.extend(backends::env(package_manager.get_name(), name));
+    package.env.extend(env);
+    package.pending_system |= rootless;
     
     let now = chrono::Local::now().to_rfc3339();
-    let package_version = PackageVersion {
+    let files = file_manifest(&install_dir, &bin_paths);
+    let mut package_version = PackageVersion {
         install_path: install_dir.clone(),
         install_date: now,
         bin_paths,
         package_manager: Some(package_manager.get_name().to_string()),
+        local_file: None,
+        source,
+        url,
+        image,
+        arch: Some(compat::target_arch().to_string()),
+        provenance,
+        license: backends::license(package_manager.get_name(), name, &version_to_install),
+        metadata: Metadata::default(),
+        source_url: None,
+        size: Some(du::dir_size(&install_dir)),
+        checksums: file_checksums(&install_dir, &files),
+        files,
+        dependencies,
+        resolved_version: None,
+        revision: None,
     };
+    describe(&mut package_version, name, &version_to_install, package_manager.get_name());
     
     package.versions.insert(version_to_install.clone(), package_version);
     
     // If this is the first version or no active version, make it active
     if package.active_version.is_none() {
-        package.active_version = Some(version_to_install);
+        package.active_version = Some(version_to_install.clone());
+    }
+    
+    save_packages(&packages)?;
+    changelog::record(name, package_manager.get_name(), &packages[name].versions[&version_to_install], &version_to_install);
+    info!("{} {}", "Successfully installed".green(), name.yellow().bold());
+    shim::warn_conflicts(&packages, name);
+    hooks::run(Hook::PostInstall, &hook_context)?;
+    
+    Ok(version_to_install)
+}
+
+/// Let the user pick which outdated packages to update, returning the ones left out.
+fn deselected_updates(packages: &HashMap<String, Package>) -> Result<HashSet<String>> {
+    let outdated = find_outdated(packages);
+    if outdated.len() < 2 {
+        return Ok(HashSet::new());
+    }
+    
+    let items: Vec<String> = outdated.iter()
+        .map(|o| format!("{} {} -> {}", o.name, o.current, o.latest))
+        .collect();
+    let selected = MultiSelect::new()
+        .with_prompt("Select packages to update (space to toggle, enter to confirm)")
+        .items(&items)
+        .defaults(&vec![true; items.len()])
+        .interact()
+        .context("Failed to read selection")?;
+    
+    Ok(outdated.into_iter()
+        .enumerate()
+        .filter(|(i, _)| !selected.contains(i))
+        .map(|(_, o)| o.name)
+        .collect())
+}
+
+/// A not-found error for `name` that suggests the closest installed packages.
+pub(crate) fn package_not_found(name: &str, packages: &HashMap<String, Package>) -> anyhow::Error {
+    let suggestions = fuzzy::suggest(name, packages.keys().cloned());
+    Error::PackageNotFound { name: name.to_string(), suggestions }.into()
+}
+
+/// Print the names closest to one `pm` could not install: installed packages,
+/// cached search results and, while none of `pm`'s are cached, its own index
+/// of names, or say there is nothing to compare it with.
+fn print_suggestions(name: &str, pm: &dyn PackageManager, packages: &HashMap<String, Package>) {
+    let index = search_index::load_index().unwrap_or_default();
+    let mut candidates: Vec<String> = packages.keys().cloned().collect();
+    candidates.extend(index.known().into_iter().map(|(_, result)| result.name));
+    let mut cold = false;
+    if !index.has_results(pm.get_name()) {
+        match backends::name_index(pm) {
+            Some(names) if !names.is_empty() => candidates.extend(names),
+            _ => cold = true,
+        }
+    }
+    let suggestions = fuzzy::suggest(name, candidates);
+    if !suggestions.is_empty() {
+        info!("{} {}", "Did you mean:".yellow(), suggestions.join(", ").cyan());
+    } else if cold {
+        info!("{}", format!("No {} package names are cached to suggest similar ones from; `updater search {}` looks it up", pm.get_name(), name).yellow());
+    }
+}
+
+pub fn install_file(path: &Path, dry_run: bool) -> Result<()> {
+    let mut packages = load_packages()?;
+    
+    let local = local_package::inspect(path)?;
+    info!("Using package manager: {}", local.kind.manager_name().cyan());
+    info!("Found {} version {}", local.name.yellow().bold(), local.version.cyan());
+    if dry_run {
+        let install_dir = base_install_path(false).join(&local.name).join(&local.version);
+        info!("{} {} {}", "Would install".yellow(), path.display(), format!("with {}", local.kind.manager_name()).normal());
+        info!("{} {}", "Would create".yellow(), install_dir.display());
+        info!("{} {} {}", "Would record".yellow(), format!("{} {}", local.name, local.version).cyan(), "in the package database");
+        return Ok(());
+    }
+    if !prompt::confirm(&format!("Install {} system-wide with {}?", local.name, local.kind.manager_name()))? {
+        return Err(Error::Aborted(format!("Aborted installing {}", local.name)).into());
+    }
+    
+    // Native packages are always system-wide; keep a copy of the file next to the record
+    let install_dir = base_install_path(false).join(&local.name).join(&local.version);
+    let hook_context = HookContext {
+        package: &local.name,
+        version: Some(&local.version),
+        install_path: Some(&install_dir),
+        backend: Some(local.kind.manager_name()),
+        ..Default::default()
+    };
+    privilege::ensure_writable(install_dir.parent().unwrap())?;
+    hooks::run(Hook::PreInstall, &hook_context)?;
+    let staged = StagedInstall::begin(&install_dir, true)?;
+    let file_name = path.file_name().context("Package file has no file name")?;
+    let staged_file = staged.dir().join(file_name);
+    fs::copy(path, &staged_file).context("Failed to copy package file")?;
+    let bin_paths = local_package::install(&local, &staged_file)?;
+    let bin_paths = staged.commit(bin_paths)?;
+    let stored_file = install_dir.join(file_name);
+    
+    let package = packages.entry(local.name.clone())
+        .or_insert_with(|| Package {
+            name: local.name.clone(),
+            versions: HashMap::new(),
+            active_version: None,
+            system: true,
+            channel: None,
+            constraint: None,
+            env: BTreeMap::new(),
+            reason: InstallReason::Explicit,
+            priority: 0,
+            renamed_bins: BTreeMap::new(),
+            pending_system: false,
+            policy: UpdatePolicy::default(),
+            max_frequency: None,
+            last_updated: None,
+            auto_update: None,
+            skipped_versions: BTreeSet::new(),
+            aliases: BTreeMap::new(),
+            renamed_to: None,
+            previous_names: Vec::new(),
+        });
+    
+    let files = file_manifest(&install_dir, &local_package::installed_files(local.kind, &local.name).unwrap_or_default());
+    let package_version = PackageVersion {
+        install_path: install_dir.clone(),
+        install_date: chrono::Local::now().to_rfc3339(),
+        bin_paths,
+        package_manager: Some(local.kind.manager_name().to_string()),
+        local_file: Some(stored_file),
+        source: None,
+        url: None,
+        image: None,
+        arch: Some(std::env::consts::ARCH.to_string()),
+        provenance: None,
+        license: None,
+        metadata: Metadata::default(),
+        source_url: None,
+        size: Some(du::dir_size(&install_dir)),
+        checksums: file_checksums(&install_dir, &files),
+        files,
+        dependencies: Vec::new(),
+        resolved_version: None,
+        revision: None,
+    };
+    
+    // The native package manager only keeps one version, so it replaced any
+    // other one installed from a file and the new one is always active
+    let replaced: Vec<String> = package
+        .versions
+        .iter()
+        .filter(|(version, info)| **version != local.version && info.local_file.is_some())
+        .map(|(version, _)| version.clone())
+        .collect();
+    for version in replaced {
+        if let Some(old) = package.versions.remove(&version) {
+            if let Err(e) = privilege::remove_dir_all(&old.install_path) {
+                warn!("{} {}: {:#}", "Could not remove".yellow(), old.install_path.display(), e);
+            }
+            info!("{} {} {}", "Replaced".green(), local.name.yellow().bold(), version.cyan());
+        }
     }
+    package.versions.insert(local.version.clone(), package_version);
+    package.active_version = Some(local.version.clone());
     
     save_packages(&packages)?;
-    println!("{} {}", "Successfully installed".green(), name.yellow().bold());
+    info!("{} {}", "Successfully installed".green(), local.name.yellow().bold());
+    shim::warn_conflicts(&packages, &local.name);
+    hooks::run(Hook::PostInstall, &hook_context)?;
+    
+    Ok(())
+}
+
+/// The newest upstream version within `channel` and `constraint`, passing over `skipped` ones.
+fn latest_matching(name: &str, pm_name: &str, source: Option<&SourceSpec>, channel: Option<Channel>, constraint: Option<&str>, skipped: &BTreeSet<String>) -> Result<String> {
+    let version_source = backends::get_version_source(pm_name, source)
+        .with_context(|| format!("{} cannot list upstream versions, so channels, version constraints and skipped versions are not supported", pm_name))?;
+    let mut versions = version_source.available_versions(name)?;
+    versions.retain(|v| !skipped.iter().any(|skip| version::compare(v, skip).is_eq()));
+    let channel = channel.unwrap_or_default();
+    
+    match constraint {
+        Some(spec) => {
+            let req = version::parse_constraint(spec)?;
+            version::best_match(&versions, &req, channel)
+                .with_context(|| format!("No {} release of {} matches {}", channel, name, spec))
+        }
+        None => version::latest(&versions, channel)
+            .with_context(|| format!("No {} release found for {}", channel, name)),
+    }
+}
+
+fn resolve_version(name: &str, pm: &dyn PackageManager, source: Option<&SourceSpec>, channel: Option<Channel>, constraint: Option<&str>, skipped: &BTreeSet<String>) -> Result<String> {
+    let resolved = latest_matching(name, pm.get_name(), source, channel, constraint, skipped)?;
+    
+    let requested = match constraint {
+        Some(spec) => format!("{} ({})", spec, channel.unwrap_or_default()),
+        None => format!("{} channel", channel.unwrap_or_default()),
+    };
+    info!("Resolved {} to version {}", requested.cyan(), resolved.cyan());
+    Ok(resolved)
+}
+
+fn package_manager_for(version_info: &PackageVersion, pm_name: &str) -> Result<Box<dyn PackageManager>> {
+    if let Some(spec) = &version_info.source {
+        return Ok(Box::new(SourceBackend::new(spec.clone())));
+    }
+    if let Some(spec) = &version_info.image {
+        return Ok(Box::new(ImageBackend::new(spec.clone())));
+    }
+    match &version_info.url {
+        Some(spec) => Ok(Box::new(UrlBackend::new(spec.clone()))),
+        None => backends::get_package_manager_by_name(pm_name),
+    }
+}
+
+/// Have the native package manager remove a version installed from a file,
+/// unless another version replaced it there since and is only being tidied
+/// out of the database.
+fn remove_local_file_install(name: &str, pkg_version: &PackageVersion, installed: bool) -> Result<()> {
+    if pkg_version.local_file.is_none() || !installed {
+        return Ok(());
+    }
+    
+    if let Some(kind) = pkg_version.package_manager.as_deref().and_then(LocalPackageKind::from_manager_name) {
+        local_package::remove(kind, name)?;
+    }
+    Ok(())
+}
+
+/// Every file under `install_dir`, plus the files in `outside` that the
+/// backend put elsewhere, such as wrappers or native package contents.
+fn file_manifest(install_dir: &Path, outside: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_files(install_dir, &mut files);
+    files.extend(outside.iter().filter(|file| !file.starts_with(install_dir)).cloned());
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Checksums of the regular files among `files` that live under `install_dir`;
+/// the native package manager vouches for the rest.
+fn file_checksums(install_dir: &Path, files: &[PathBuf]) -> BTreeMap<PathBuf, String> {
+    files
+        .iter()
+        .filter(|file| file.starts_with(install_dir) && fs::symlink_metadata(file).is_ok_and(|m| m.is_file()))
+        .filter_map(|file| Some((file.clone(), download::sha256_file(file).ok()?)))
+        .collect()
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => collect_files(&path, files),
+            Ok(_) => files.push(path),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Files a removed version installed outside its install dir that no other
+/// installed version still claims. The native package manager cleans up
+/// after packages installed from local files itself.
+fn stray_files<'a>(pkg_version: &'a PackageVersion, packages: &HashMap<String, Package>) -> Vec<&'a PathBuf> {
+    if pkg_version.local_file.is_some() {
+        return Vec::new();
+    }
+    pkg_version
+        .files
+        .iter()
+        .filter(|file| !file.starts_with(&pkg_version.install_path) && file.symlink_metadata().is_ok())
+        .filter(|file| {
+            !packages
+                .values()
+                .flat_map(|package| package.versions.values())
+                .any(|other| other.files.contains(file) || other.bin_paths.contains(file))
+        })
+        .collect()
+}
+
+fn remove_stray_files(pkg_version: &PackageVersion, packages: &HashMap<String, Package>) {
+    for file in stray_files(pkg_version, packages) {
+        match fs::remove_file(file) {
+            Ok(()) => debug!("Removed {}", file.display()),
+            Err(e) => warn!("{} {}: {}", "Could not remove".yellow(), file.display(), e),
+        }
+    }
+}
+
+/// Give a plugin backend the chance to undo anything it did outside the install dir.
+fn remove_plugin_install(name: &str, version: &str, pkg_version: &PackageVersion, user: bool) -> Result<()> {
+    let pm_name = match pkg_version.package_manager.as_deref() {
+        Some(pm_name) => pm_name,
+        None => return Ok(()),
+    };
+    if let Some(plugin) = plugin::find(pm_name)? {
+        plugin.remove(name, version, &pkg_version.install_path, user)?;
+    }
+    Ok(())
+}
+
+pub fn remove(name: &str, version: Option<String>, dry_run: bool, force: bool) -> Result<()> {
+    if !dry_run {
+        fs_snapshot::before(&format!("remove {}", name))?;
+    }
+    remove_package(name, version, dry_run, force, true)?;
     
+    let orphans = find_orphans(&load_packages()?);
+    if !dry_run && !orphans.is_empty() {
+        info!("{} {} {}", orphans.len().to_string().yellow(), "package(s) are no longer needed:".yellow(), orphans.join(", "));
+        info!("Remove them with `updater autoremove`");
+    }
     Ok(())
 }
 
-pub fn remove(name: &str, version: Option<String>) -> Result<()> {
+/// `remove` without the hint about orphans, and with the confirmation for
+/// removing every version only when `confirm` is set.
+pub(crate) fn remove_package(name: &str, version: Option<String>, dry_run: bool, force: bool, confirm: bool) -> Result<()> {
     let mut packages = load_packages()?;
     
+    // Only removing the last version leaves dependents without the package
+    let removes_package = packages.get(name).is_some_and(|package| match &version {
+        Some(ver) => package.versions.len() == 1 && package.versions.contains_key(ver),
+        None => true,
+    });
+    let dependents = reverse_dependencies(name, &packages);
+    if removes_package && !dependents.is_empty() {
+        let required_by = dependents.iter().map(|(dependent, ver)| format!("{} {}", dependent, ver)).collect::<Vec<_>>().join(", ");
+        if !force {
+            bail!("{} is required by {}; pass --force to remove it anyway", name, required_by);
+        }
+        warn!("{} {} {}", name.yellow().bold(), "is required by".yellow(), required_by);
+    }
+    
+    if dry_run {
+        return match packages.get(name) {
+            Some(package) => preview_remove(name, package, version.as_deref(), &packages),
+            None => Err(package_not_found(name, &packages)),
+        };
+    }
+    
     if let Some(package) = packages.get_mut(name) {
+        let mut removed = Vec::new();
         match version {
             Some(ver) => {
+                let installed = package.active_version.as_ref() == Some(&ver);
                 if let Some(pkg_version) = package.versions.remove(&ver) {
+                    let hook_context = HookContext {
+                        package: name,
+                        version: Some(&ver),
+                        install_path: Some(&pkg_version.install_path),
+                        backend: pkg_version.package_manager.as_deref(),
+                        ..Default::default()
+                    };
+                    hooks::run(Hook::PreRemove, &hook_context)?;
+                    remove_local_file_install(name, &pkg_version, installed)?;
+                    remove_plugin_install(name, &ver, &pkg_version, !package.system)?;
+                    
                     // Remove the package files
-                    fs::remove_dir_all(pkg_version.install_path)?;
+                    privilege::remove_dir_all(&pkg_version.install_path)?;
+                    hooks::run(Hook::PostRemove, &hook_context)?;
+                    removed.push(pkg_version);
                     
                     // If we removed the active version, set active to None
                     if package.active_version.as_ref() == Some(&ver) {
@@ -121,156 +999,1392 @@ pub fn remove(name: &str, version: Option<String>) -> Result<()> {
                         // Try to set another version as active if available
                         if let Some((next_ver, _)) = package.versions.iter().next() {
                             package.active_version = Some(next_ver.clone());
-                            println!("{} {} {}", 
+                            info!("{} {} {}", 
                                 "Set".green(), 
                                 next_ver.cyan(), 
                                 "as the active version".green());
                         }
                     }
                     
-                    println!("{} {} {}", 
+                    info!("{} {} {}", 
                         "Removed version".green(), 
                         ver.yellow(), 
                         "of package".green());
                 } else {
-                    println!("{} {}", 
-                        "Version not found:".red(), 
-                        ver.yellow());
-                    return Ok(());
+                    return Err(Error::VersionNotFound { package: name.to_string(), version: ver }.into());
                 }
             },
             None => {
+                let prompt = format!("Remove {} and all {} installed version(s)?", name, package.versions.len());
+                if confirm && !prompt::confirm(&prompt)? {
+                    return Err(Error::Aborted(format!("Aborted removing {}", name)).into());
+                }
+                
                 // Remove all versions of the package
-                for (_, pkg_version) in &package.versions {
+                let hook_context = HookContext { package: name, ..Default::default() };
+                hooks::run(Hook::PreRemove, &hook_context)?;
+                for (ver, pkg_version) in &package.versions {
+                    remove_local_file_install(name, pkg_version, package.active_version.as_ref() == Some(ver))?;
+                    remove_plugin_install(name, ver, pkg_version, !package.system)?;
                     if pkg_version.install_path.exists() {
-                        fs::remove_dir_all(&pkg_version.install_path)?;
+                        privilege::remove_dir_all(&pkg_version.install_path)?;
                     }
                 }
+                hooks::run(Hook::PostRemove, &hook_context)?;
+                removed.extend(package.versions.values().cloned());
                 packages.remove(name);
-                println!("{} {}", "Removed package".green(), name.yellow().bold());
+                info!("{} {}", "Removed package".green(), name.yellow().bold());
             }
         }
         
+        for pkg_version in &removed {
+            remove_stray_files(pkg_version, &packages);
+        }
         save_packages(&packages)?;
     } else {
-        println!("{} {}", "Package not found:".red(), name.yellow());
+        return Err(package_not_found(name, &packages));
     }
     
     Ok(())
 }
 
-pub fn update(name: Option<&str>) -> Result<()> {
-    let mut packages = load_packages()?;
-    
-    match name {
-        Some(package_name) => {
-            if let Some(package) = packages.get(package_name) {
-                if let Some(active_version) = &package.active_version {
-                    if let Some(version_info) = package.versions.get(active_version) {
-                        if let Some(pm_name) = &version_info.package_manager {
-                            let pm = system::get_package_manager_by_name(pm_name)?;
-                            pm.update(package_name, Some(active_version), &version_info.install_path, !package.system)?;
-                            println!("{} {}", "Updated package".green(), package_name.yellow().bold());
-                        }
-                    }
-                }
-            } else {
-                println!("{} {}", "Package not found:".red(), package_name.yellow());
-            }
-        },
-        None => {
-            // Update all packages
-            for (name, package) in &packages {
-                if let Some(active_version) = &package.active_version {
-                    if let Some(version_info) = package.versions.get(active_version) {
-                        if let Some(pm_name) = &version_info.package_manager {
-                            let pm = system::get_package_manager_by_name(pm_name)?;
-                            match pm.update(name, Some(active_version), &version_info.install_path, !package.system) {
-                                Ok(_) => println!("{} {}", "Updated package".green(), name.yellow()),
-                                Err(e) => println!("{} {}: {}", "Failed to update".red(), name.yellow(), e),
-                            }
-                        }
-                    }
-                }
-            }
+/// Packages installed as dependencies that no explicitly installed package
+/// still needs, directly or through other dependencies.
+pub fn find_orphans(packages: &HashMap<String, Package>) -> Vec<String> {
+    let mut needed = HashSet::new();
+    let mut queue: Vec<&str> = packages
+        .values()
+        .filter(|package| package.reason == InstallReason::Explicit)
+        .map(|package| package.name.as_str())
+        .collect();
+    while let Some(name) = queue.pop() {
+        if !needed.insert(name) {
+            continue;
+        }
+        if let Some(package) = packages.get(name) {
+            queue.extend(package.versions.values().flat_map(|v| v.dependencies.iter().map(String::as_str)));
         }
     }
     
-    save_packages(&packages)?;
+    let mut orphans: Vec<String> = packages.keys().filter(|name| !needed.contains(name.as_str())).cloned().collect();
+    orphans.sort();
+    orphans
+}
+
+/// Days without use after which `autoremove` suggests removing a package.
+pub const STALE_DAYS: u64 = 90;
+
+/// An installed package none of whose binaries were run through a shim lately.
+#[derive(Debug, Clone, Serialize)]
+pub struct StalePackage {
+    pub name: String,
+    /// When a shim last ran it, `None` if none has since tracking started
+    pub last_used: Option<String>,
+    /// Days since it was last used, or installed if it never was
+    pub idle_days: i64,
+}
+
+/// Packages with binaries that no shim ran in the last `days` days. Ones
+/// never run are counted from their newest install, so fresh installs and
+/// libraries without binaries never show up.
+pub fn find_stale(packages: &HashMap<String, Package>, days: u64) -> Vec<StalePackage> {
+    let now = chrono::Local::now();
+    let mut stale: Vec<StalePackage> = packages
+        .values()
+        .filter(|package| package.versions.values().any(|v| !v.bin_paths.is_empty()))
+        .filter_map(|package| {
+            let last_used = shim::last_used(&package.name).map(chrono::DateTime::<chrono::Local>::from);
+            let installed = package
+                .versions
+                .values()
+                .filter_map(|v| chrono::DateTime::parse_from_rfc3339(&v.install_date).ok())
+                .max()
+                .map(|date| date.with_timezone(&chrono::Local));
+            let idle_days = (now - last_used.or(installed)?).num_days();
+            (idle_days >= days as i64).then(|| StalePackage {
+                name: package.name.clone(),
+                last_used: last_used.map(|date| date.to_rfc3339()),
+                idle_days,
+            })
+        })
+        .collect();
+    stale.sort_by(|a, b| b.idle_days.cmp(&a.idle_days).then_with(|| a.name.cmp(&b.name)));
+    stale
+}
+
+/// Print the packages not used in the last `days` days, longest unused first.
+pub fn list_stale(days: u64) -> Result<()> {
+    let stale = find_stale(&load_packages()?, days);
+    if stale.is_empty() {
+        println!("{}", format!("No packages went unused for {} days", days).green());
+        return Ok(());
+    }
+    for package in &stale {
+        let when = match package.last_used {
+            Some(_) => format!("not used in {} days", package.idle_days),
+            None => format!("never used since it was installed {} days ago", package.idle_days),
+        };
+        println!("{} {}", package.name.yellow().bold(), when.dimmed());
+    }
+    info!("{} {}", "Remove unneeded ones with".yellow(), "`updater remove <name>`".cyan());
     Ok(())
 }
 
-pub fn list(system_only: bool, user_only: bool) -> Result<()> {
+/// Point out explicitly installed packages that went unused for a long time,
+/// which `autoremove` never removes on its own.
+fn suggest_stale(packages: &HashMap<String, Package>, skip: &[String]) {
+    let stale: Vec<String> = find_stale(packages, STALE_DAYS)
+        .into_iter()
+        .filter(|package| !skip.contains(&package.name))
+        .map(|package| package.name)
+        .collect();
+    if !stale.is_empty() {
+        info!("{} {} {}", format!("Not used in over {} days:", STALE_DAYS).yellow(), stale.join(", "), "(see `updater list --stale`)".dimmed());
+    }
+}
+
+/// Remove every package that was only installed as a dependency of packages
+/// that are gone now.
+pub fn autoremove(dry_run: bool) -> Result<()> {
     let packages = load_packages()?;
-    
-    if packages.is_empty() {
-        println!("{}", "No packages installed".yellow());
+    let orphans = find_orphans(&packages);
+    suggest_stale(&packages, &orphans);
+    if orphans.is_empty() {
+        info!("{}", "No packages to remove".green());
         return Ok(());
     }
     
-    let mut count = 0;
-    for (name, package) in packages {
-        // Filter based on package type
-        if (system_only && !package.system) || (user_only && package.system) {
-            continue;
-        }
-        
-        count += 1;
-        let pkg_type = if package.system { "system" } else { "user" };
-        println!("{} {} ({})", name.green().bold(), pkg_type.cyan(), package.versions.len().to_string().yellow());
-        
-        for (version, pkg_version) in &package.versions {
-            let active_marker = if Some(version) == package.active_version.as_ref() {
-                "* ".green().bold()
-            } else {
-                "  ".normal()
-            };
-            
-            println!("{}v{} - installed on {}", 
-                active_marker,
-                version.cyan(),
-                pkg_version.install_date.yellow());
-        }
-        println!();
+    info!("{} {}", "No longer needed:".yellow(), orphans.join(", "));
+    if !dry_run && !prompt::confirm(&format!("Remove {} package(s)?", orphans.len()))? {
+        return Err(Error::Aborted("Aborted autoremove".to_string()).into());
     }
-    
-    if count == 0 {
-        if system_only {
-            println!("{}", "No system packages installed".yellow());
-        } else if user_only {
-            println!("{}", "No user packages installed".yellow());
-        }
+    // Dependents go before what they depend on, so nothing is removed from under another package
+    let mut remaining = orphans;
+    while !remaining.is_empty() {
+        let packages = load_packages()?;
+        let next = remaining
+            .iter()
+            .position(|name| !reverse_dependencies(name, &packages).iter().any(|(dependent, _)| remaining.contains(dependent)))
+            .unwrap_or(0);
+        let name = remaining.remove(next);
+        remove_package(&name, None, dry_run, true, false)?;
     }
-    
     Ok(())
 }
 
-pub fn search(query: &str) -> Result<()> {
-    // Get available package managers
-    let package_managers = system::get_available_package_managers()?;
-    let mut found = false;
+/// Report what `remove` would delete and change in the database.
+fn preview_remove(name: &str, package: &Package, version: Option<&str>, packages: &HashMap<String, Package>) -> Result<()> {
+    let versions: Vec<(&String, &PackageVersion)> = match version {
+        Some(ver) => match package.versions.get_key_value(ver) {
+            Some(entry) => vec![entry],
+            None => return Err(Error::VersionNotFound { package: name.to_string(), version: ver.to_string() }.into()),
+        },
+        None => package.versions.iter().collect(),
+    };
     
-    for pm in package_managers {
-        println!("{} {}", "Searching with".green(), pm.get_name().cyan());
-        let results = pm.search(query)?;
-        
-        if !results.is_empty() {
-            found = true;
-            for result in results {
-                println!("{} - {} [{}]", 
-                    result.name.green().bold(),
-                    result.description.normal(),
-                    pm.get_name().cyan());
+    // What is left once the removal is done decides which outside files go too
+    let mut remaining = packages.clone();
+    match version {
+        Some(ver) => {
+            if let Some(package) = remaining.get_mut(name) {
+                package.versions.remove(ver);
             }
         }
+        None => {
+            remaining.remove(name);
+        }
     }
     
-    if !found {
-        println!("{} {}", "No packages found matching:".yellow(), query);
+    for (_, pkg_version) in &versions {
+        if pkg_version.local_file.is_some() {
+            let manager = pkg_version.package_manager.as_deref().unwrap_or("its package manager");
+            info!("{} {} {}", "Would uninstall".yellow(), name.yellow().bold(), format!("with {}", manager).normal());
+        }
+        info!("{} {}", "Would delete".yellow(), pkg_version.install_path.display());
+        for file in stray_files(pkg_version, &remaining) {
+            info!("{} {}", "Would delete".yellow(), file.display());
+        }
     }
     
-    Ok(())
-}
+    match version {
+        Some(ver) => {
+            info!("{} {} {}", "Would remove".yellow(), format!("{} {}", name, ver).cyan(), "from the package database");
+            let next = package.versions.keys().find(|v| v.as_str() != ver);
+            if package.active_version.as_deref() == Some(ver) {
+                match next {
+                    Some(next) => info!("{} {} {}", "Would set".yellow(), next.cyan(), "as the active version"),
+                    None => info!("{}", "Would leave the package without an active version".yellow()),
+                }
+            }
+        }
+        None => info!("{} {} {}", "Would remove".yellow(), name.yellow().bold(), "from the package database"),
+    }
+    Ok(())
+}
+
+/// Update one package or all of them. When several packages are outdated and
+/// neither `all` nor yes is set, ask which ones to update first if there is a
+/// terminal to ask on.
+/// `changelog` prints upstream release notes before each versioned update.
+pub fn update(name: Option<&str>, all: bool, dry_run: bool, changelog: bool) -> Result<()> {
+    let mut packages = load_packages()?;
+    let mut failed = 0;
+    if !dry_run {
+        fs_snapshot::before(&format!("update {}", name.unwrap_or("all")))?;
+    }
+    
+    match name {
+        Some(package_name) => {
+            if !packages.contains_key(package_name) {
+                return Err(package_not_found(package_name, &packages));
+            }
+            update_package(&mut packages, package_name, dry_run, changelog)?;
+        },
+        None => {
+            // Update all packages
+            let mut names: Vec<String> = packages.keys().cloned().collect();
+            names.sort();
+            names.retain(|name| match packages[name].held_back() {
+                Some(reason) => {
+                    info!("{} {} {}", "Skipping".yellow(), name.yellow().bold(), format!("because {}", reason).normal());
+                    false
+                }
+                None => true,
+            });
+            if !all && !dry_run && !prompt::assume_yes() && io::stdin().is_terminal() {
+                let skipped = deselected_updates(&packages)?;
+                names.retain(|name| !skipped.contains(name));
+            }
+            if !dry_run {
+                // Only in-place updates fetch a version known up front; URL installs refetch `latest` anyway
+                prefetch(names.iter().filter_map(|name| {
+                    let package = &packages[name];
+                    if package.channel.is_some() || package.constraint.is_some() || !package.skipped_versions.is_empty() {
+                        return None;
+                    }
+                    let active = package.active_version.as_deref()?;
+                    let info = package.versions.get(active).filter(|info| info.local_file.is_none())?;
+                    Some((info.package_manager.as_deref().filter(|pm| *pm != "url")?, name.as_str(), Some(active), None))
+                }))?;
+            }
+            for name in names {
+                if let Err(e) = update_package(&mut packages, &name, dry_run, changelog) {
+                    error!("{} {}: {}", "Failed to update".red(), name.yellow(), e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    
+    if !dry_run {
+        save_packages(&packages)?;
+    }
+    if failed > 0 {
+        return Err(Error::UpdatesFailed(failed).into());
+    }
+    Ok(())
+}
+
+fn update_package(packages: &mut HashMap<String, Package>, name: &str, dry_run: bool, changelog: bool) -> Result<()> {
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Ok(()),
+    };
+    let active_version = match &package.active_version {
+        Some(active_version) => active_version.clone(),
+        None => return Ok(()),
+    };
+    let version_info = match package.versions.get(&active_version) {
+        Some(version_info) => version_info,
+        None => return Ok(()),
+    };
+    
+    if version_info.local_file.is_some() {
+        warn!("{} {}", name.yellow().bold(), "was installed from a local file, install a newer file to update it".yellow());
+        return Ok(());
+    }
+    let pm_name = match &version_info.package_manager {
+        Some(pm_name) => pm_name.clone(),
+        None => return Ok(()),
+    };
+    
+    let renamed = package.renamed_to.clone().or_else(|| backends::renamed(&pm_name, name));
+    if let Some(new) = renamed.filter(|new| new != name) {
+        package.renamed_to = Some(new.clone());
+        warn!("{} {} {}", name.yellow().bold(), "was renamed upstream to".yellow(), new.yellow().bold());
+        if dry_run {
+            info!("{} {} {} {}", "Would offer to migrate".yellow(), name.yellow().bold(), "to".yellow(), new.yellow().bold());
+            return Ok(());
+        }
+        if prompt::assume_yes() || io::stdin().is_terminal() {
+            if prompt::confirm(&format!("Migrate {} to {}?", name, new))? {
+                return migrate_package(packages, name, &new);
+            }
+        } else {
+            info!("{} {}", "Migrate it with".yellow(), format!("`updater migrate {}`", name).cyan());
+        }
+        // Otherwise keep updating under the old name for as long as it is published
+    }
+    let package = packages.get_mut(name).unwrap();
+    let version_info = &package.versions[&active_version];
+    let pm = package_manager_for(version_info, &pm_name)?;
+    sandbox::check_supported(pm.get_name())?;
+    
+    // Updating in place lets the backend pick the version, which could be a skipped one
+    if package.channel.is_some() || package.constraint.is_some() || !package.skipped_versions.is_empty() {
+        return update_to_latest_match(package, &*pm, &active_version, dry_run, changelog);
+    }
+    
+    if dry_run {
+        info!("{} {} {} {}", "Would update".yellow(), name.yellow().bold(), format!("in place with {} at", pm.get_name()).normal(), version_info.install_path.display());
+        return Ok(());
+    }
+    let install_path = version_info.install_path.clone();
+    let hook_context = HookContext {
+        package: name,
+        version: Some(&active_version),
+        previous_version: Some(&active_version),
+        install_path: Some(&install_path),
+        backend: Some(&pm_name),
+    };
+    let previous = version_info.exact_version(&active_version).map(str::to_string);
+    hooks::run(Hook::PreUpdate, &hook_context)?;
+    pm.update(name, Some(&active_version), &install_path, !package.system)?;
+    // The backend replaced the files in place, so what was recorded about them is stale
+    if let Some(version_info) = package.versions.get_mut(&active_version) {
+        version_info.files = file_manifest(&install_path, &version_info.bin_paths);
+        version_info.checksums = file_checksums(&install_path, &version_info.files);
+        version_info.size = Some(du::dir_size(&install_path));
+        describe(version_info, name, &active_version, &pm_name);
+        // Only now is it known which release the backend picked
+        if let (true, Some(from), Some(to)) = (changelog, previous, version_info.exact_version(&active_version)) {
+            if from != to {
+                changelog::show(name, &pm_name, version_info, &from, to);
+            }
+        }
+    }
+    package.last_updated = Some(chrono::Local::now().to_rfc3339());
+    info!("{} {}", "Updated package".green(), name.yellow().bold());
+    hooks::run(Hook::PostUpdate, &hook_context)
+}
+
+/// Install the newest release matching the package's channel and constraint
+/// next to the active version and switch to it.
+fn update_to_latest_match(package: &mut Package, pm: &dyn PackageManager, active_version: &str, dry_run: bool, changelog: bool) -> Result<()> {
+    let active_info = &package.versions[active_version];
+    let target = resolve_version(&package.name, pm, active_info.source.as_ref(), package.channel, package.constraint.as_deref(), &package.skipped_versions)?;
+    if target == active_version && !rolling_tag_moved(&package.name, pm.get_name(), active_info, &target) {
+        info!("{} {}", package.name.yellow().bold(), "is already on the newest matching version".green());
+        return Ok(());
+    }
+    if dry_run {
+        let install_dir = active_info.install_path.with_file_name(&target);
+        info!("{} {} {} {}", "Would update".yellow(), package.name.yellow().bold(), active_version.cyan(), format!("-> {}", target.cyan()));
+        info!("{} {}", "Would create".yellow(), install_dir.display());
+        info!("{} {} {}", "Would record".yellow(), format!("{} {}", package.name, target).cyan(), "in the package database as the active version");
+        return Ok(());
+    }
+    
+    install_update(package, pm, active_version, &target, changelog)
+}
+
+/// Whether the rolling tag `version` points at a newer build than the one
+/// installed under it, assuming it does when the backend can't tell.
+fn rolling_tag_moved(name: &str, pm_name: &str, installed: &PackageVersion, version: &str) -> bool {
+    if !version::is_rolling(version) {
+        return false;
+    }
+    let upstream = backends::get_version_source(pm_name, installed.source.as_ref())
+        .and_then(|source| source.revision(name, version).ok().flatten());
+    match (upstream, &installed.revision) {
+        (Some(upstream), Some(recorded)) => upstream != *recorded,
+        (Some(_), None) => true,
+        (None, _) => {
+            info!("{} {} {}", "Can't tell whether".yellow(), version.cyan(), format!("moved for {}, reinstalling it", name).yellow());
+            true
+        }
+    }
+}
+
+/// Install `target` next to the active version and make it the active one.
+fn install_update(package: &mut Package, pm: &dyn PackageManager, active_version: &str, target: &str, changelog: bool) -> Result<()> {
+    let active_info = &package.versions[active_version];
+    if changelog {
+        changelog::show(&package.name, pm.get_name(), active_info, active_version, target);
+    }
+    let install_dir = active_info.install_path.parent()
+        .context("Install path has no parent directory")?
+        .join(target);
+    let name = package.name.clone();
+    let hook_context = HookContext {
+        package: &name,
+        version: Some(target),
+        previous_version: Some(active_version),
+        install_path: Some(&install_dir),
+        backend: Some(pm.get_name()),
+    };
+    hooks::run(Hook::PreUpdate, &hook_context)?;
+    let staged = StagedInstall::begin(&install_dir, backends::is_relocatable(pm.get_name()))?;
+    // Versions installed for another architecture are updated for it too
+    let arch = active_info.arch.clone();
+    let (bin_paths, provenance) = compat::with_target_arch(arch.as_deref(), || {
+        let bin_paths = pm.install(&name, Some(target), staged.dir(), !package.system)?;
+        let provenance = verify::take_provenance(&name)?;
+        compat::confirm_runnable(&name, &bin_paths)?;
+        Ok((bin_paths, provenance))
+    })?;
+    let bin_paths = staged.commit(bin_paths)?;
+    
+    let files = file_manifest(&install_dir, &bin_paths);
+    let dependencies = backends::dependencies(pm.get_name(), &name, target);
+    let installed = load_packages()?;
+    for dependency in &dependencies {
+        if !installed.contains_key(dependency) {
+            warn!("{} {} {} {}", name.yellow().bold(), target.cyan(), "needs".yellow(),
+                format!("{}, install it with `updater install {}`", dependency, dependency).yellow());
+        }
+    }
+    let mut package_version = PackageVersion {
+        install_path: install_dir.clone(),
+        install_date: chrono::Local::now().to_rfc3339(),
+        bin_paths,
+        package_manager: Some(pm.get_name().to_string()),
+        local_file: None,
+        source: active_info.source.clone(),
+        url: active_info.url.clone(),
+        image: active_info.image.clone(),
+        arch: arch.or_else(|| Some(compat::target_arch().to_string())),
+        provenance,
+        license: backends::license(pm.get_name(), &name, target),
+        metadata: Metadata::default(),
+        source_url: None,
+        size: Some(du::dir_size(&install_dir)),
+        checksums: file_checksums(&install_dir, &files),
+        files,
+        dependencies,
+        resolved_version: None,
+        revision: None,
+    };
+    describe(&mut package_version, &name, target, pm.get_name());
+    changelog::record(&name, pm.get_name(), &package_version, target);
+    package.versions.insert(target.to_string(), package_version);
+    package.active_version = Some(target.to_string());
+    package.last_updated = Some(chrono::Local::now().to_rfc3339());
+    
+    info!("{} {} {} {}", "Updated package".green(), package.name.yellow().bold(), "to version".green(), target.cyan());
+    hooks::run(Hook::PostUpdate, &hook_context)
+}
+
+/// Move a package that was renamed upstream over to its new name, `to` or the
+/// one its backend reports.
+pub fn migrate(name: &str, to: Option<&str>) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    let new = match to {
+        Some(to) => to.to_string(),
+        None => {
+            let pm_name = package.active_version.as_ref().and_then(|v| package.versions.get(v)).and_then(|info| info.package_manager.clone());
+            package.renamed_to.clone()
+                .or_else(|| pm_name.and_then(|pm_name| backends::renamed(&pm_name, name)))
+                .with_context(|| format!("{} was not renamed upstream as far as its backend knows; pass the new name", name))?
+        }
+    };
+    if new == name {
+        bail!("{} already has that name", name);
+    }
+    if !prompt::confirm(&format!("Migrate {} to {}?", name, new))? {
+        return Err(Error::Aborted(format!("Aborted migrating {}", name)).into());
+    }
+    fs_snapshot::before(&format!("migrate {} {}", name, new))?;
+    migrate_package(&mut packages, name, &new)
+}
+
+/// Install `new` with the backend `old` came from, carry settings, history
+/// and dependents over to it, then remove `old`.
+fn migrate_package(packages: &mut HashMap<String, Package>, old: &str, new: &str) -> Result<()> {
+    if packages.contains_key(new) {
+        bail!("{} is already installed; remove {} once you have moved over to it", new, old);
+    }
+    let package = &packages[old];
+    let active_info = package.active_version.as_ref().and_then(|v| package.versions.get(v))
+        .with_context(|| format!("{} has no active version", old))?;
+    let options = InstallOptions {
+        user: !package.system,
+        backend: active_info.package_manager.clone(),
+        source: active_info.source.clone(),
+        url: active_info.url.clone(),
+        image: active_info.image.clone(),
+        arch: active_info.arch.clone(),
+        channel: package.channel,
+        ..Default::default()
+    };
+    
+    // Installing and removing read and write the database themselves
+    save_packages(packages)?;
+    let version = install(new, options)?;
+    let mut installed = load_packages()?;
+    let previous = installed[old].clone();
+    let migrated = installed.get_mut(new).context("Migrated package is missing from the database")?;
+    migrated.reason = previous.reason;
+    migrated.env = previous.env;
+    migrated.priority = previous.priority;
+    migrated.renamed_bins = previous.renamed_bins;
+    migrated.policy = previous.policy;
+    migrated.max_frequency = previous.max_frequency;
+    migrated.auto_update = previous.auto_update;
+    migrated.previous_names = previous.previous_names;
+    migrated.previous_names.push(old.to_string());
+    for package in installed.values_mut() {
+        for info in package.versions.values_mut() {
+            for dependency in info.dependencies.iter_mut().filter(|dependency| *dependency == old) {
+                *dependency = new.to_string();
+            }
+        }
+    }
+    save_packages(&installed)?;
+    changelog::rename(old, new);
+    
+    remove_package(old, None, false, false, false)?;
+    *packages = load_packages()?;
+    info!("{} {} {} {} {}", "Migrated".green(), old.yellow().bold(), "to".green(), new.yellow().bold(), version.cyan());
+    Ok(())
+}
+
+/// A package whose active version is behind the newest matching upstream version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutdatedPackage {
+    pub name: String,
+    pub current: String,
+    pub latest: String,
+}
+
+/// Check every package with a comparable active version against upstream.
+/// Packages whose backend cannot list versions are skipped.
+pub fn find_outdated(packages: &HashMap<String, Package>) -> Vec<OutdatedPackage> {
+    let mut outdated = Vec::new();
+    
+    for (name, package) in packages {
+        let current = match &package.active_version {
+            Some(current) if current != "latest" => current,
+            _ => continue,
+        };
+        let version_info = match package.versions.get(current) {
+            Some(version_info) if version_info.local_file.is_none() => version_info,
+            _ => continue,
+        };
+        let pm_name = match &version_info.package_manager {
+            Some(pm_name) => pm_name,
+            None => continue,
+        };
+        
+        if let Ok(latest) = latest_matching(name, pm_name, version_info.source.as_ref(), package.channel, package.constraint.as_deref(), &package.skipped_versions) {
+            if version::compare(&latest, current).is_gt() {
+                outdated.push(OutdatedPackage { name: name.clone(), current: current.clone(), latest });
+            }
+        }
+    }
+    
+    outdated.sort_by(|a, b| a.name.cmp(&b.name));
+    outdated
+}
+
+/// Update a package to a specific upstream version found by `find_outdated`.
+pub fn apply_update(packages: &mut HashMap<String, Package>, name: &str, target: &str) -> Result<()> {
+    let package = packages.get_mut(name).with_context(|| format!("Package not found: {}", name))?;
+    let active_version = package.active_version.clone().with_context(|| format!("{} has no active version", name))?;
+    let version_info = &package.versions[&active_version];
+    let pm_name = version_info.package_manager.clone().with_context(|| format!("{} has no recorded package manager", name))?;
+    let pm = package_manager_for(version_info, &pm_name)?;
+    
+    install_update(package, &*pm, &active_version, target, false)
+}
+
+/// Fields available to `outdated --format`.
+pub const OUTDATED_FIELDS: &[&str] = &["name", "current", "latest"];
+
+pub fn outdated(format: Option<&str>) -> Result<()> {
+    let template = format.map(|format| Template::parse(format, OUTDATED_FIELDS)).transpose()?;
+    let packages = load_packages()?;
+    let outdated = find_outdated(&packages);
+    prompt_status::store(&outdated);
+    let default_level = config::load_config()?.daemon.auto_update;
+    
+    if let Some(template) = template {
+        for pkg in &outdated {
+            println!("{}", template.render(|field| match field {
+                "name" => pkg.name.clone(),
+                "current" => pkg.current.clone(),
+                _ => pkg.latest.clone(),
+            }));
+        }
+        return Ok(());
+    }
+    if outdated.is_empty() {
+        println!("{}", "All packages are up to date".green());
+        return Ok(());
+    }
+    
+    for pkg in &outdated {
+        // Unattended updates never take a major step the package doesn't allow
+        let level = packages[&pkg.name].auto_update.unwrap_or(default_level);
+        let approval = if !UpdateLevel::Minor.allows(&pkg.current, &pkg.latest) && level != UpdateLevel::Major {
+            format!(" {}", "(needs manual approval)".yellow())
+        } else {
+            String::new()
+        };
+        println!("{} {} -> {}{}", pkg.name.green().bold(), pkg.current.yellow(), pkg.latest.cyan(), approval);
+    }
+    println!();
+    println!("{} {}", outdated.len().to_string().yellow().bold(), "packages can be updated".yellow());
+    
+    notify::outdated(&outdated.iter().map(|pkg| pkg.name.clone()).collect::<Vec<_>>())?;
+    Ok(())
+}
+
+/// Installed packages sorted by name, optionally only system or only user ones.
+pub fn installed(system_only: bool, user_only: bool) -> Result<Vec<Package>> {
+    let mut installed: Vec<Package> = load_packages()?
+        .into_values()
+        .filter(|package| (!system_only || package.system) && (!user_only || !package.system))
+        .collect();
+    installed.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(installed)
+}
+
+/// Change why a package is considered installed, e.g. to keep a dependency
+/// around after everything needing it is gone.
+pub fn mark(name: &str, reason: InstallReason) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    package.reason = reason;
+    save_packages(&packages)?;
+    info!("{} {} {} {}", "Marked".green(), name.yellow().bold(), "as".green(), reason.to_string().cyan());
+    Ok(())
+}
+
+/// Never pick `version` of a package when resolving updates, or pick it
+/// again with `undo`.
+pub fn skip(name: &str, version: &str, undo: bool) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    if undo {
+        if !package.skipped_versions.remove(version) {
+            info!("{} {} {}", name.yellow().bold(), version.cyan(), "was not skipped".yellow());
+            return Ok(());
+        }
+        save_packages(&packages)?;
+        info!("{} {} {}", "No longer skipping".green(), name.yellow().bold(), version.cyan());
+        return Ok(());
+    }
+    
+    let pm_name = package.active_version.as_ref()
+        .and_then(|active| package.versions.get(active))
+        .and_then(|info| info.package_manager.clone().map(|pm_name| (pm_name, info.source.clone())));
+    if let Some((pm_name, source)) = pm_name {
+        if backends::get_version_source(&pm_name, source.as_ref()).is_none() {
+            bail!("{} cannot list upstream versions, so it always updates to whatever is newest", pm_name);
+        }
+    }
+    if package.active_version.as_deref() == Some(version) {
+        warn!("{} {} {}", name.yellow().bold(), version.cyan(), "is the active version; it stays installed until the next update".yellow());
+    }
+    package.skipped_versions.insert(version.to_string());
+    save_packages(&packages)?;
+    info!("{} {} {}", "Updates of".green(), name.yellow().bold(), format!("will pass over {}", version.cyan()).green());
+    Ok(())
+}
+
+fn policy_summary(package: &Package) -> String {
+    let mut summary = package.policy.to_string();
+    if let Some(frequency) = package.max_frequency {
+        summary.push_str(&format!(", at most {}", frequency));
+    }
+    if let Some(level) = package.auto_update {
+        summary.push_str(&format!(", unattended {} updates", format!("{:?}", level).to_lowercase()));
+    }
+    summary
+}
+
+/// Set how updates that don't name a package treat it. `max_frequency` and
+/// `auto_update` of `Some(None)` go back to no limit and the daemon default,
+/// and `None` leaves them as they are.
+pub fn set_policy(name: &str, policy: Option<UpdatePolicy>, max_frequency: Option<Option<Frequency>>, auto_update: Option<Option<UpdateLevel>>) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    if let Some(policy) = policy {
+        package.policy = policy;
+    }
+    if let Some(max_frequency) = max_frequency {
+        package.max_frequency = max_frequency;
+    }
+    if let Some(auto_update) = auto_update {
+        package.auto_update = auto_update;
+    }
+    let summary = policy_summary(package);
+    save_packages(&packages)?;
+    info!("{} {} {}", "Updates of".green(), name.yellow().bold(), format!("are {}", summary.cyan()).green());
+    Ok(())
+}
+
+/// Move a package that was installed under the user prefix for lack of root
+/// into the system prefix, where it was meant to go.
+pub fn promote(name: &str) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    if package.system {
+        info!("{} {}", name.yellow().bold(), "is already system-wide".green());
+        return Ok(());
+    }
+    if !package.pending_system {
+        bail!("{} was installed for the user on purpose; reinstall it without --user to make it system-wide", name);
+    }
+    // Backends that bake their prefix into what they install can't be moved
+    if let Some((version, info)) = package.versions.iter().find(|(_, info)| {
+        info.package_manager.as_deref().is_some_and(|pm| !backends::is_relocatable(pm))
+    }) {
+        bail!(
+            "{} {} was installed by {}, which can't be moved; remove it and install it again with root",
+            name,
+            version,
+            info.package_manager.as_deref().unwrap_or_default()
+        );
+    }
+
+    let package_dir = base_install_path(false).join(name);
+    privilege::ensure_writable(&package_dir)?;
+    for (version, info) in package.versions.iter_mut() {
+        let dest = package_dir.join(version);
+        let source = info.install_path.clone();
+        // The tree is fixed up while it is still the user's, then moved as a whole
+        bundle::relocate(info, &source, &dest)?;
+        if let Err(e) = privilege::place(&source, &dest) {
+            transaction::relocate_tree(&source, &dest, &source).ok();
+            return Err(e).with_context(|| format!("Failed to move {} {} into {}", name, version, package_dir.display()));
+        }
+        info!("{} {} {} {}", "Moved".green(), name.yellow().bold(), version.cyan(), format!("to {}", dest.display()));
+    }
+    fs::remove_dir(base_install_path(true).join(name)).ok();
+
+    package.system = true;
+    package.pending_system = false;
+    save_packages(&packages)?;
+    info!("{} {} {}", "Promoted".green(), name.yellow().bold(), "to a system package".green());
+    Ok(())
+}
+
+/// Print everything the database records about a package.
+pub fn info(name: &str) -> Result<()> {
+    let packages = load_packages()?;
+    let package = match packages.get(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    
+    println!("{} {}", "Name:".bold(), package.name.green().bold());
+    let active = package.active_version.as_ref().and_then(|v| package.versions.get(v));
+    if let Some(metadata) = active.map(|v| &v.metadata) {
+        if let Some(description) = &metadata.description {
+            println!("{} {}", "Description:".bold(), description);
+        }
+        if let Some(homepage) = &metadata.homepage {
+            println!("{} {}", "Homepage:".bold(), homepage);
+        }
+        if let Some(repository) = &metadata.repository {
+            println!("{} {}", "Repository:".bold(), repository);
+        }
+    }
+    let kind = match (package.system, package.pending_system) {
+        (true, _) => "system",
+        (false, true) => "user, system once promoted",
+        (false, false) => "user",
+    };
+    println!("{} {}", "Type:".bold(), kind);
+    println!("{} {}", "Reason:".bold(), package.reason);
+    println!("{} {}", "Update policy:".bold(), policy_summary(package));
+    if !package.aliases.is_empty() {
+        let aliases: Vec<String> = package.aliases.iter().map(|(alias, target)| match &target.version {
+            Some(version) => format!("{} -> {} {}", alias, target.bin, version),
+            None => format!("{} -> {}", alias, target.bin),
+        }).collect();
+        println!("{} {}", "Aliases:".bold(), aliases.join(", "));
+    }
+    if let Some(renamed_to) = &package.renamed_to {
+        println!("{} {} {}", "Renamed to:".bold(), renamed_to.yellow(), format!("(updater migrate {})", name).dimmed());
+    }
+    if !package.previous_names.is_empty() {
+        println!("{} {}", "Previously named:".bold(), package.previous_names.join(", "));
+    }
+    if !package.skipped_versions.is_empty() {
+        println!("{} {}", "Skipped versions:".bold(), package.skipped_versions.iter().cloned().collect::<Vec<_>>().join(", "));
+    }
+    println!("{} {}", "Active version:".bold(), package.active_version.as_deref().unwrap_or("none").cyan());
+    if let Some(channel) = package.channel {
+        println!("{} {}", "Channel:".bold(), channel);
+    }
+    if let Some(constraint) = &package.constraint {
+        println!("{} {}", "Constraint:".bold(), constraint);
+    }
+    let dependents = reverse_dependencies(name, &packages);
+    if !dependents.is_empty() {
+        let dependents: Vec<String> = dependents.into_iter().map(|(dependent, version)| format!("{} {}", dependent, version)).collect();
+        println!("{} {}", "Required by:".bold(), dependents.join(", "));
+    }
+    
+    let mut versions: Vec<&String> = package.versions.keys().collect();
+    versions.sort_by(|a, b| version::compare(a, b));
+    for version in versions {
+        let pkg_version = &package.versions[version];
+        println!();
+        println!("{} {}", version.cyan().bold(), if package.active_version.as_ref() == Some(version) { "(active)" } else { "" });
+        println!("  {} {}", "Backend:".bold(), pkg_version.package_manager.as_deref().unwrap_or("unknown"));
+        println!("  {} {}", "Installed:".bold(), pkg_version.install_date);
+        println!("  {} {}", "Path:".bold(), pkg_version.install_path.display());
+        if let Some(size) = pkg_version.size {
+            println!("  {} {}", "Size:".bold(), HumanBytes(size));
+        }
+        if let Some(license) = &pkg_version.license {
+            println!("  {} {}", "License:".bold(), license);
+        }
+        if let Some(source_url) = &pkg_version.source_url {
+            println!("  {} {}", "Source:".bold(), source_url);
+        }
+        if let Some(provenance) = &pkg_version.provenance {
+            let status = match provenance.status {
+                ProvenanceStatus::Verified => provenance.status.to_string().green(),
+                _ => provenance.status.to_string().yellow(),
+            };
+            println!("  {} {} ({})", "Provenance:".bold(), status, provenance.repository);
+        }
+        if !pkg_version.dependencies.is_empty() {
+            println!("  {} {}", "Depends on:".bold(), pkg_version.dependencies.join(", "));
+        }
+        let bins: Vec<String> = pkg_version.bin_paths.iter().filter_map(|p| p.file_name()).map(|f| f.to_string_lossy().into_owned()).collect();
+        if !bins.is_empty() {
+            println!("  {} {}", "Binaries:".bold(), bins.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Installed package versions that depend on `name`, as `(name, version)`.
+pub fn reverse_dependencies(name: &str, packages: &HashMap<String, Package>) -> Vec<(String, String)> {
+    let mut dependents: Vec<(String, String)> = packages
+        .values()
+        .filter(|package| package.name != name)
+        .flat_map(|package| {
+            package
+                .versions
+                .iter()
+                .filter(|(_, pkg_version)| pkg_version.dependencies.iter().any(|dependency| dependency == name))
+                .map(|(version, _)| (package.name.clone(), version.clone()))
+        })
+        .collect();
+    dependents.sort();
+    dependents
+}
+
+pub fn rdeps(name: &str) -> Result<()> {
+    let packages = load_packages()?;
+    let dependents = reverse_dependencies(name, &packages);
+    if dependents.is_empty() {
+        if !packages.contains_key(name) {
+            return Err(package_not_found(name, &packages));
+        }
+        println!("Nothing depends on {}", name);
+        return Ok(());
+    }
+    for (dependent, version) in dependents {
+        println!("{} {}", dependent.yellow().bold(), version.cyan());
+    }
+    Ok(())
+}
+
+/// Package versions that installed `path`, as `(name, version)`. Versions
+/// recorded before file manifests were kept match anything under their
+/// install path.
+pub fn find_owners(path: &Path) -> Result<Vec<(String, String)>> {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut owners = Vec::new();
+    for (name, package) in load_packages()? {
+        for (version, pkg_version) in package.versions {
+            let owned = if pkg_version.files.is_empty() {
+                canonical.starts_with(&pkg_version.install_path)
+            } else {
+                pkg_version.files.iter().any(|file| file == path || *file == canonical)
+            };
+            if owned {
+                owners.push((name.clone(), version));
+            }
+        }
+    }
+    owners.sort();
+    Ok(owners)
+}
+
+pub fn owns(path: &Path) -> Result<()> {
+    let owners = find_owners(path)?;
+    if owners.is_empty() {
+        bail!("{} is not owned by any installed package", path.display());
+    }
+    for (name, version) in owners {
+        println!("{} {} {} {}", path.display(), "is owned by".dimmed(), name.yellow().bold(), version.cyan());
+    }
+    Ok(())
+}
+
+/// What changed in one installed version since it was installed.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub modified: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+    pub extra: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Compare a version's files with its recorded manifest and checksums.
+pub fn verify_version(pkg_version: &PackageVersion) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    for file in &pkg_version.files {
+        if fs::symlink_metadata(file).is_err() {
+            report.missing.push(file.clone());
+        } else if let Some(expected) = pkg_version.checksums.get(file) {
+            if download::sha256_file(file).ok().as_ref() != Some(expected) {
+                report.modified.push(file.clone());
+            }
+        }
+    }
+
+    let recorded: HashSet<&PathBuf> = pkg_version.files.iter().collect();
+    let mut present = Vec::new();
+    collect_files(&pkg_version.install_path, &mut present);
+    report.extra = present.into_iter().filter(|file| !recorded.contains(file)).collect();
+    report.extra.sort();
+    report
+}
+
+/// Report modified, missing and extra files of every installed version, or of
+/// one package's, failing if anything changed.
+pub fn verify(name: Option<&str>) -> Result<()> {
+    let packages = load_packages()?;
+    let mut names: Vec<&String> = match name {
+        Some(name) if !packages.contains_key(name) => return Err(package_not_found(name, &packages)),
+        Some(name) => packages.keys().filter(|n| *n == name).collect(),
+        None => packages.keys().collect(),
+    };
+    names.sort();
+
+    let mut changed = 0;
+    for name in names {
+        let package = &packages[name];
+        let mut versions: Vec<&String> = package.versions.keys().collect();
+        versions.sort_by(|a, b| version::compare(a, b));
+        for version in versions {
+            let pkg_version = &package.versions[version];
+            if pkg_version.files.is_empty() {
+                println!("{} {} {}", name.yellow().bold(), version.cyan(), "has no file manifest, skipped".dimmed());
+                continue;
+            }
+            let report = verify_version(pkg_version);
+            if report.is_clean() {
+                println!("{} {} {}", name.yellow().bold(), version.cyan(), "OK".green());
+                continue;
+            }
+
+            changed += 1;
+            println!("{} {}", name.yellow().bold(), version.cyan());
+            for file in &report.modified {
+                println!("  {} {}", "modified".red(), file.display());
+            }
+            for file in &report.missing {
+                println!("  {} {}", "missing ".red(), file.display());
+            }
+            for file in &report.extra {
+                println!("  {} {}", "extra   ".yellow(), file.display());
+            }
+            if pkg_version.checksums.is_empty() {
+                println!("  {}", "No checksums recorded, modified files can't be detected".dimmed());
+            }
+        }
+    }
+
+    if changed > 0 {
+        return Err(Error::Verification(format!(
+            "{} installed version(s) changed since they were installed; `updater reinstall <name> --version <version>` restores them",
+            changed
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Fetch a recorded version again from the backend, source or file it was
+/// installed from and replace its install dir, e.g. after `verify` found it
+/// changed. Defaults to the active version.
+pub fn reinstall(name: &str, version: Option<&str>) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    let version = match version.or(package.active_version.as_deref()) {
+        Some(version) => version.to_string(),
+        None => bail!("{} has no active version, pass --version", name),
+    };
+    let pkg_version = package.versions.get(&version).ok_or_else(|| Error::VersionNotFound {
+        package: name.to_string(),
+        version: version.clone(),
+    })?;
+    let pm_name = pkg_version.package_manager.clone().with_context(|| format!("{} has no recorded package manager", name))?;
+    let install_dir = pkg_version.install_path.clone();
+    let hook_context = HookContext {
+        package: name,
+        version: Some(&version),
+        install_path: Some(&install_dir),
+        backend: Some(&pm_name),
+        ..Default::default()
+    };
+    hooks::run(Hook::PreInstall, &hook_context)?;
+
+    let mut provenance = None;
+    let bin_paths = if let Some(file) = pkg_version.local_file.clone() {
+        if !file.is_file() {
+            bail!("{} is gone, install {} again from its package file", file.display(), name);
+        }
+        let local = local_package::inspect(&file)?;
+        local_package::install(&local, &file)?
+    } else {
+        let pm = package_manager_for(pkg_version, &pm_name)?;
+        if version == "latest" {
+            warn!("{} {}", name.yellow().bold(), "was recorded as latest, so the current latest release is fetched".yellow());
+        }
+        let relocatable = backends::is_relocatable(&pm_name);
+        // Backends that install in place would otherwise keep the damaged files
+        if !relocatable && install_dir.exists() {
+            privilege::remove_dir_all(&install_dir).with_context(|| format!("Failed to clear {}", install_dir.display()))?;
+        }
+        info!("{} {} {} {}", "Fetching".green(), name.yellow().bold(), version.cyan(), format!("with {}", pm.get_name()).normal());
+        let staged = StagedInstall::begin(&install_dir, relocatable)?;
+        let requested = Some(version.as_str()).filter(|v| *v != "latest");
+        // Held to the same checks as the first install, for the same architecture
+        let bin_paths = compat::with_target_arch(pkg_version.arch.as_deref(), || {
+            let bin_paths = pm.install(name, requested, staged.dir(), !package.system)?;
+            provenance = verify::take_provenance(name)?;
+            compat::confirm_runnable(name, &bin_paths)?;
+            verify::check_expected_artifact(name, staged.dir())?;
+            Ok(bin_paths)
+        })?;
+        staged.commit(bin_paths)?
+    };
+
+    let pkg_version = package.versions.get_mut(&version).unwrap();
+    let outside: Vec<PathBuf> = match pkg_version.local_file {
+        Some(_) => pkg_version.files.iter().filter(|file| !file.starts_with(&install_dir)).cloned().collect(),
+        None => bin_paths.clone(),
+    };
+    pkg_version.files = file_manifest(&install_dir, &outside);
+    pkg_version.checksums = file_checksums(&install_dir, &pkg_version.files);
+    pkg_version.size = Some(du::dir_size(&install_dir));
+    pkg_version.install_date = chrono::Local::now().to_rfc3339();
+    pkg_version.bin_paths = bin_paths;
+    if pkg_version.local_file.is_none() {
+        pkg_version.provenance = provenance;
+        describe(pkg_version, name, &version, &pm_name);
+    }
+
+    save_packages(&packages)?;
+    info!("{} {} {}", "Reinstalled".green(), name.yellow().bold(), version.cyan());
+    hooks::run(Hook::PostInstall, &hook_context)
+}
+
+/// Fields available to `list --format`.
+pub const LIST_FIELDS: &[&str] = &["name", "active_version", "type", "reason", "versions", "backend", "install_path", "install_date", "description", "homepage", "repository", "license", "size"];
+
+pub fn list(system_only: bool, user_only: bool, long: bool, format: Option<&str>) -> Result<()> {
+    let template = format.map(|format| Template::parse(format, LIST_FIELDS)).transpose()?;
+    let packages = installed(system_only, user_only)?;
+    
+    if let Some(template) = template {
+        for package in &packages {
+            let active = package.active_version.as_ref().and_then(|v| package.versions.get(v));
+            println!("{}", template.render(|field| match field {
+                "name" => package.name.clone(),
+                "active_version" => package.active_version.clone().unwrap_or_default(),
+                "type" => if package.system { "system" } else { "user" }.to_string(),
+                "reason" => package.reason.to_string(),
+                "versions" => {
+                    let mut versions: Vec<String> = package.versions.keys().cloned().collect();
+                    version::sort(&mut versions);
+                    versions.join(",")
+                }
+                "backend" => active.and_then(|v| v.package_manager.clone()).unwrap_or_default(),
+                "install_path" => active.map(|v| v.install_path.display().to_string()).unwrap_or_default(),
+                "description" => active.and_then(|v| v.metadata.description.clone()).unwrap_or_default(),
+                "homepage" => active.and_then(|v| v.metadata.homepage.clone()).unwrap_or_default(),
+                "repository" => active.and_then(|v| v.metadata.repository.clone()).unwrap_or_default(),
+                "license" => active.and_then(|v| v.license.clone()).unwrap_or_default(),
+                "size" => active.and_then(|v| v.size).map(|size| size.to_string()).unwrap_or_default(),
+                _ => active.map(|v| v.install_date.clone()).unwrap_or_default(),
+            }));
+        }
+        return Ok(());
+    }
+    
+    if packages.is_empty() {
+        if system_only {
+            println!("{}", "No system packages installed".yellow());
+        } else if user_only {
+            println!("{}", "No user packages installed".yellow());
+        } else {
+            println!("{}", "No packages installed".yellow());
+        }
+        return Ok(());
+    }
+    
+    for package in packages {
+        let pkg_type = if package.system { "system" } else { "user" };
+        let reason = match package.reason {
+            InstallReason::Explicit => String::new(),
+            reason => format!(" {}", reason.to_string().dimmed()),
+        };
+        println!("{} {}{} ({})", package.name.green().bold(), pkg_type.cyan(), reason, package.versions.len().to_string().yellow());
+        if long {
+            let active = package.active_version.as_ref().and_then(|v| package.versions.get(v));
+            if let Some(active) = active {
+                let metadata = &active.metadata;
+                let links: Vec<&str> = [&metadata.homepage, &metadata.repository].into_iter().flatten().map(String::as_str).collect();
+                if let Some(description) = &metadata.description {
+                    println!("  {}", description);
+                }
+                if let Some(license) = &active.license {
+                    println!("  {} {}", "License:".dimmed(), license);
+                }
+                if !links.is_empty() {
+                    println!("  {}", links.join("  ").dimmed());
+                }
+            }
+        }
+        
+        for (version, pkg_version) in &package.versions {
+            let active_marker = if Some(version) == package.active_version.as_ref() {
+                "* ".green().bold()
+            } else {
+                "  ".normal()
+            };
+            
+            let size = pkg_version.size.map(|size| format!(", {}", HumanBytes(size))).unwrap_or_default();
+            let arch = pkg_version.arch.as_deref().filter(|arch| *arch != std::env::consts::ARCH).map(|arch| format!(" [{}]", arch)).unwrap_or_default();
+            println!("{}v{}{} - installed on {}{}", 
+                active_marker,
+                version.cyan(),
+                arch.magenta(),
+                pkg_version.install_date.yellow(),
+                size.dimmed());
+        }
+        println!();
+    }
+    
+    Ok(())
+}
+
+/// A package found by `find_packages`, with the backend that provides it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub name: String,
+    pub description: String,
+    pub backend: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    /// Best matches first
+    pub hits: Vec<SearchHit>,
+    /// Whether some hits came from search index entries older than the configured TTL
+    pub stale: bool,
+}
+
+/// Search every backend, answering from the search index when possible;
+/// `refresh` skips the index and queries every backend directly. Results are
+/// ranked by how closely they match, so typos still find the package.
+pub fn find_packages(query: &str, refresh: bool) -> Result<SearchResults> {
+    // Get available package managers
+    let package_managers = backends::get_available_package_managers()?;
+    let ttl_hours = config::load_config()?.search.cache_ttl_hours;
+    let mut index = search_index::load_index()?;
+    let mut stale = false;
+    // (backend, result, whether the backend itself returned it for this query)
+    let mut candidates: Vec<(String, CachedResult, bool)> = Vec::new();
+    
+    for pm in package_managers {
+        let results = match index.get(pm.get_name(), query).filter(|_| !refresh) {
+            Some(cached) => {
+                stale |= cached.is_stale(ttl_hours);
+                cached.results.clone()
+            }
+            None => {
+                info!("{} {}", "Searching with".green(), pm.get_name().cyan());
+                let results = pm.search(query)?;
+                index.insert(pm.get_name(), query, results)
+            }
+        };
+        candidates.extend(results.into_iter().map(|result| (pm.get_name().to_string(), result, true)));
+    }
+    search_index::save_index(&index)?;
+    
+    // Backends only match their own spelling, so also look for typos among
+    // names seen in earlier searches and installed packages
+    candidates.extend(index.known().into_iter().map(|(pm_name, result)| (pm_name, result, false)));
+    for package in load_packages()?.into_values() {
+        let pm_name = package.versions.values().find_map(|v| v.package_manager.clone()).unwrap_or_default();
+        candidates.push((pm_name, CachedResult { name: package.name, description: "installed".to_string() }, false));
+    }
+    
+    let mut seen = HashSet::new();
+    let mut ranked: Vec<(f64, SearchHit)> = candidates
+        .into_iter()
+        .filter(|(pm_name, result, _)| seen.insert((pm_name.clone(), result.name.clone())))
+        .filter_map(|(pm_name, result, direct)| {
+            // Backends may match on fields we never see, so keep their own hits last
+            let score = fuzzy::score(query, &result.name, &result.description).or(direct.then_some(0.0))?;
+            Some((score, SearchHit { name: result.name, description: result.description, backend: pm_name }))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    
+    Ok(SearchResults { hits: ranked.into_iter().map(|(_, hit)| hit).collect(), stale })
+}
+
+/// Fields available to `search --format`.
+pub const SEARCH_FIELDS: &[&str] = &["name", "description", "backend"];
+
+/// Print the results of `find_packages`. Stale entries are still shown and
+/// refreshed in the background.
+pub fn search(query: &str, refresh: bool, format: Option<&str>) -> Result<()> {
+    let template = format.map(|format| Template::parse(format, SEARCH_FIELDS)).transpose()?;
+    let results = find_packages(query, refresh)?;
+    
+    for hit in &results.hits {
+        match &template {
+            Some(template) => println!("{}", template.render(|field| match field {
+                "name" => hit.name.clone(),
+                "description" => hit.description.clone(),
+                _ => hit.backend.clone(),
+            })),
+            None => println!("{} - {} [{}]", 
+                hit.name.green().bold(),
+                hit.description.normal(),
+                hit.backend.cyan()),
+        }
+    }
+    
+    if results.hits.is_empty() && template.is_none() {
+        println!("{} {}", "No packages found matching:".yellow(), query);
+    }
+    if results.stale {
+        info!("{}", "Some results are from the search cache; refreshing it in the background".yellow());
+        search_index::refresh_in_background(query)?;
+    }
+    
+    Ok(())
+}
+
+/// Versions of a package one backend has upstream, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamVersions {
+    pub backend: String,
+    pub versions: Vec<String>,
+}
+
+/// Ask the backends the package was installed with, or every available one,
+/// which versions they have. Backends that fail are logged and skipped.
+pub fn upstream_versions(name: &str) -> Result<Vec<UpstreamVersions>> {
+    let packages = load_packages()?;
+    
+    let mut sources: Vec<(String, Option<SourceSpec>)> = match packages.get(name) {
+        Some(package) => package.versions.values()
+            .filter_map(|v| v.package_manager.clone().map(|pm| (pm, v.source.clone())))
+            .collect(),
+        None => backends::get_available_package_managers()?
+            .iter()
+            .map(|pm| (pm.get_name().to_string(), None))
+            .collect(),
+    };
+    sources.sort_by(|a, b| a.0.cmp(&b.0));
+    sources.dedup_by(|a, b| a.0 == b.0);
+    
+    let mut found = Vec::new();
+    for (pm_name, source) in sources {
+        let version_source = match backends::get_version_source(&pm_name, source.as_ref()) {
+            Some(version_source) => version_source,
+            None => continue,
+        };
+        
+        let mut available = match version_source.available_versions(name) {
+            Ok(available) => available,
+            Err(e) => {
+                error!("{} {}: {}", "Failed to list versions from".red(), pm_name.cyan(), e);
+                continue;
+            }
+        };
+        if available.is_empty() {
+            continue;
+        }
+        
+        version::sort(&mut available);
+        found.push(UpstreamVersions { backend: pm_name, versions: available });
+    }
+    
+    Ok(found)
+}
+
+pub fn versions(name: &str) -> Result<()> {
+    let packages = load_packages()?;
+    let installed = packages.get(name);
+    let upstream = upstream_versions(name)?;
+    
+    for source in &upstream {
+        println!("{} {} {}", name.green().bold(), "versions from".green(), source.backend.cyan());
+        for v in &source.versions {
+            let active = installed.and_then(|p| p.active_version.as_ref()) == Some(v);
+            let is_installed = installed.is_some_and(|p| p.versions.contains_key(v));
+            let marker = if active { "* ".green().bold() } else { "  ".normal() };
+            let status = if active {
+                " (active)".green().to_string()
+            } else if is_installed {
+                " (installed)".yellow().to_string()
+            } else {
+                String::new()
+            };
+            println!("{}{}{}", marker, v.cyan(), status);
+        }
+        println!();
+    }
+    
+    if upstream.is_empty() {
+        println!("{} {}", "No upstream versions found for".yellow(), name);
+    }
+    
+    Ok(())
+}
 
 pub fn switch(name: &str, version: &str) -> Result<()> {
     let mut packages = load_packages()?;
@@ -279,22 +2393,96 @@ pub fn switch(name: &str, version: &str) -> Result<()> {
         if package.versions.contains_key(version) {
             package.active_version = Some(version.to_string());
             save_packages(&packages)?;
-            println!("{} {} {} {}", 
+            info!("{} {} {} {}", 
                 "Switched".green(), 
                 name.yellow().bold(),
                 "to version".green(),
                 version.cyan());
+            shim::warn_conflicts(&packages, name);
         } else {
-            println!("{} {} {}", 
-                "Version".red(), 
-                version.yellow(),
-                "not found for package".red());
+            return Err(Error::VersionNotFound { package: name.to_string(), version: version.to_string() }.into());
         }
     } else {
-        println!("{} {}", 
-            "Package not found:".red(), 
-            name.yellow());
+        return Err(package_not_found(name, &packages));
     }
     
     Ok(())
 }
+
+/// The newest of `available` before `active` that `package` would take: on
+/// its channel and not skipped.
+fn previous_release(package: &Package, available: Vec<String>, active: &str) -> Option<String> {
+    let channel = package.channel.unwrap_or_default();
+    available
+        .into_iter()
+        .filter(|v| version::compare(v, active).is_lt())
+        .filter(|v| channel.includes(v))
+        .filter(|v| !package.skipped_versions.iter().any(|skip| version::compare(v, skip).is_eq()))
+        .max_by(|a, b| version::compare(a, b))
+}
+
+/// Install an older upstream version next to the active one and switch to
+/// it: `to`, or the newest release before the active version.
+pub fn downgrade(name: &str, to: Option<&str>) -> Result<()> {
+    let mut packages = load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package_not_found(name, &packages)),
+    };
+    let active_version = package.active_version.clone().with_context(|| format!("{} has no active version", name))?;
+    let version_info = &package.versions[&active_version];
+    if version_info.local_file.is_some() {
+        bail!("{} was installed from a local file, install the older file instead", name);
+    }
+    let pm_name = version_info.package_manager.clone().with_context(|| format!("{} has no recorded backend", name))?;
+    
+    let target = match to {
+        Some(to) => to.to_string(),
+        None => {
+            if active_version == "latest" {
+                bail!("{} is installed as latest, pass --to with the version to go back to", name);
+            }
+            let version_source = backends::get_version_source(&pm_name, version_info.source.as_ref())
+                .with_context(|| format!("{} cannot list upstream versions, pass --to with the version to go back to", pm_name))?;
+            previous_release(package, version_source.available_versions(name)?, &active_version)
+                .with_context(|| format!("No release of {} on the {} channel is older than {}", name, package.channel.unwrap_or_default(), active_version))?
+        }
+    };
+    if target == active_version {
+        info!("{} {} {}", name.yellow().bold(), "is already on".green(), target.cyan());
+        return Ok(());
+    }
+    if package.versions.contains_key(&target) {
+        return switch(name, &target);
+    }
+    
+    fs_snapshot::before(&format!("downgrade {}", name))?;
+    let pm = package_manager_for(version_info, &pm_name)?;
+    install_update(package, &*pm, &active_version, &target, false)?;
+    save_packages(&packages)?;
+    shim::warn_conflicts(&packages, name);
+    info!("{} {}", "`updater update` will move it forward again;".yellow(), format!("`updater skip {} <version>` passes over bad releases", name).yellow());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(channel: Option<Channel>, skipped: &[&str]) -> Package {
+        let mut package: Package =
+            serde_json::from_value(serde_json::json!({ "name": "tool", "versions": {}, "active_version": "2.0.0", "system": false })).unwrap();
+        package.channel = channel;
+        package.skipped_versions = skipped.iter().map(|v| v.to_string()).collect();
+        package
+    }
+
+    #[test]
+    fn downgrades_stay_on_the_channel() {
+        let available: Vec<String> = ["1.8.0", "1.9.0", "2.0.0-rc.1", "2.0.0-dev", "nightly", "2.0.0", "2.1.0"].map(String::from).to_vec();
+        assert_eq!(previous_release(&package(None, &[]), available.clone(), "2.0.0").as_deref(), Some("1.9.0"));
+        assert_eq!(previous_release(&package(None, &["1.9.0"]), available.clone(), "2.0.0").as_deref(), Some("1.8.0"));
+        assert_eq!(previous_release(&package(Some(Channel::Beta), &[]), available.clone(), "2.0.0").as_deref(), Some("2.0.0-rc.1"));
+        assert_eq!(previous_release(&package(None, &[]), available, "1.8.0"), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Write access to the system install prefix without running all of updater as root.
+//!
+//! Installs are staged somewhere the user can write and only the final move
+//! into the prefix runs through sudo, leaving the prefix owned by root.
+
+use anyhow::{Context, Result};
+use dialoguer::Confirm;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::info;
+
+use crate::backends::{is_root, run_command};
+use crate::error::Error;
+
+/// Whether the user agreed to sudo for this run, so they are asked only once.
+static APPROVED: AtomicBool = AtomicBool::new(false);
+
+/// Whether this process can create files in `dir`, creating it if needed.
+pub fn is_writable(dir: &Path) -> bool {
+    if fs::create_dir_all(dir).is_err() {
+        return false;
+    }
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    match File::create(&probe) {
+        Ok(_) => {
+            fs::remove_file(&probe).ok();
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether changes under `dir` have to go through sudo. The first time they
+/// do, ask the user; `--yes` doesn't answer this, since it runs commands as
+/// root. Without sudo or a terminal to ask on, fail up front with what to
+/// run instead.
+pub fn needs_sudo(dir: &Path) -> Result<bool> {
+    if is_root() || is_writable(dir) {
+        return Ok(false);
+    }
+    if APPROVED.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+
+    let guidance = format!("{} is not writable. Rerun with sudo or install with --user", dir.display());
+    if which::which("sudo").is_err() || !io::stdin().is_terminal() {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, guidance).into());
+    }
+    let message = format!("{} is owned by root. Move installed files there with sudo?", dir.display());
+    let approved = Confirm::new().with_prompt(message).default(false).interact().context("Failed to read confirmation")?;
+    if !approved {
+        return Err(Error::Aborted(guidance).into());
+    }
+    APPROVED.store(true, Ordering::Relaxed);
+    Ok(true)
+}
+
+/// Find out before an install starts whether `dir` can be written, asking
+/// for sudo now rather than halfway through.
+pub fn ensure_writable(dir: &Path) -> Result<()> {
+    needs_sudo(dir).map(drop)
+}
+
+/// Move the tree at `from` to `to`, replacing what is there. Under a prefix
+/// the user can't write this runs through sudo and leaves `to` owned by root.
+pub fn place(from: &Path, to: &Path) -> Result<()> {
+    let parent = to.parent().context("Install path has no parent directory")?;
+    let previous = to.with_file_name(format!(".{}.previous-{}", to.file_name().unwrap_or_default().to_string_lossy(), std::process::id()));
+
+    if !needs_sudo(parent)? {
+        fs::create_dir_all(parent)?;
+        if to.exists() {
+            fs::rename(to, &previous).context("Failed to move previous install aside")?;
+        }
+        // The staging dir is often on another filesystem, where rename fails
+        if fs::rename(from, to).is_err() {
+            if let Err(e) = run_command(Command::new("cp").arg("-a").arg(from).arg(to)) {
+                fs::remove_dir_all(to).ok();
+                fs::rename(&previous, to).ok();
+                return Err(e).with_context(|| format!("Failed to move {} into {}", from.display(), to.display()));
+            }
+            fs::remove_dir_all(from).ok();
+        }
+        fs::remove_dir_all(&previous).ok();
+        return Ok(());
+    }
+
+    info!("Moving {} into place with sudo", to.display());
+    let sudo = |program: &str| {
+        let mut command = Command::new("sudo");
+        command.arg(program);
+        command
+    };
+    run_command(sudo("mkdir").arg("-p").arg(parent)).context("Failed to create install directory with sudo")?;
+    let replacing = to.exists();
+    if replacing {
+        run_command(sudo("mv").arg("--").arg(to).arg(&previous)).context("Failed to move previous install aside with sudo")?;
+    }
+    if let Err(e) = run_command(sudo("mv").arg("--").arg(from).arg(to)) {
+        if replacing {
+            run_command(sudo("mv").arg("--").arg(&previous).arg(to)).ok();
+        }
+        return Err(e).context("Failed to move install into place with sudo");
+    }
+    run_command(sudo("chown").arg("-R").arg("0:0").arg("--").arg(to)).context("Failed to hand install to root")?;
+    if replacing {
+        run_command(sudo("rm").arg("-rf").arg("--").arg(&previous)).ok();
+    }
+    Ok(())
+}
+
+/// Delete the tree at `dir`, through sudo when its parent belongs to root.
+pub fn remove_dir_all(dir: &Path) -> Result<()> {
+    let parent = dir.parent().context("Install path has no parent directory")?;
+    if !needs_sudo(parent)? {
+        return Ok(fs::remove_dir_all(dir)?);
+    }
+    info!("Removing {} with sudo", dir.display());
+    run_command(Command::new("sudo").arg("rm").arg("-rf").arg("--").arg(dir))
+        .with_context(|| format!("Failed to remove {} with sudo", dir.display()))?;
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Named profiles like `work` or `ctf`. Each one has its own package
+//! database, install dirs, shims and linked man pages and completions, so
+//! the tools of one context stay out of the others. The `default` profile
+//! uses the paths updater always used.
+//!
+//! A `--prefix` goes further and keeps all of that in one self-contained
+//! dir, for projects, containers and tests, regardless of the profile.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::package;
+use crate::share;
+use crate::shim;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Set by `--prefix` and baked into the shims made under a prefix.
+const PREFIX_VAR: &str = "UPDATER_PREFIX";
+
+/// Use `dir` as the prefix for the rest of this process and the commands it runs.
+pub fn set_prefix(dir: &Path) -> Result<()> {
+    let dir = std::path::absolute(dir).with_context(|| format!("Invalid prefix {}", dir.display()))?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create prefix {}", dir.display()))?;
+    std::env::set_var(PREFIX_VAR, dir);
+    Ok(())
+}
+
+/// Run `f` with `dir` as the prefix, then go back to the previous one.
+pub fn with_prefix<T>(dir: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let previous = std::env::var_os(PREFIX_VAR);
+    set_prefix(dir)?;
+    let result = f();
+    match previous {
+        Some(previous) => std::env::set_var(PREFIX_VAR, previous),
+        None => std::env::remove_var(PREFIX_VAR),
+    }
+    result
+}
+
+/// The prefix everything lives under, if one was given.
+pub fn prefix() -> Option<PathBuf> {
+    std::env::var_os(PREFIX_VAR).filter(|dir| !dir.is_empty()).map(PathBuf::from)
+}
+
+fn get_updater_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    data_dir.join("updater")
+}
+
+fn get_state_path() -> PathBuf {
+    get_updater_dir().join("profile")
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("Invalid profile name {:?}, use letters, digits, - and _", name);
+    }
+    Ok(())
+}
+
+/// The active profile: `UPDATER_PROFILE` if set, which is how shims stay
+/// with the profile that made them, otherwise the one last switched to.
+pub fn active() -> String {
+    if let Ok(name) = std::env::var("UPDATER_PROFILE") {
+        if !name.is_empty() {
+            return name;
+        }
+    }
+    fs::read_to_string(get_state_path())
+        .map(|name| name.trim().to_string())
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Where a profile keeps its database, shims and share dir, `None` for the
+/// default profile, which keeps them directly in updater's data dir.
+pub fn get_profile_dir(name: &str) -> Option<PathBuf> {
+    (name != DEFAULT_PROFILE).then(|| get_updater_dir().join("profiles").join(name))
+}
+
+/// Where the database, shims and share dir live when it isn't updater's
+/// data dir: the prefix, or else the active profile's dir.
+pub fn active_dir() -> Option<PathBuf> {
+    prefix().or_else(|| get_profile_dir(&active()))
+}
+
+fn exists(name: &str) -> bool {
+    get_profile_dir(name).is_none_or(|dir| dir.is_dir())
+}
+
+fn profile_names() -> Vec<String> {
+    let mut names = vec![DEFAULT_PROFILE.to_string()];
+    let mut others: Vec<String> = fs::read_dir(get_updater_dir().join("profiles"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    others.sort();
+    names.extend(others);
+    names
+}
+
+pub fn create(name: &str) -> Result<()> {
+    check_name(name)?;
+    if exists(name) {
+        bail!("Profile {} already exists", name);
+    }
+    let dir = get_profile_dir(name).unwrap();
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    info!("{} {} {}", "Created profile".green(), name.yellow().bold(), format!("(`updater profile switch {}` to use it)", name).dimmed());
+    Ok(())
+}
+
+/// Make `name` the active profile and point the desktop entries at its
+/// packages. Shells pick up its shims the next time they run `updater env`.
+pub fn switch(name: &str) -> Result<()> {
+    check_name(name)?;
+    if !exists(name) {
+        bail!("Profile {} doesn't exist, create it with `updater profile create {}`", name, name);
+    }
+    fs::create_dir_all(get_updater_dir())?;
+    fs::write(get_state_path(), name).context("Failed to record the active profile")?;
+    // UPDATER_PROFILE would keep pointing this process at the old profile
+    std::env::set_var("UPDATER_PROFILE", name);
+
+    let packages = package::load_packages()?;
+    shim::refresh(&packages)?;
+    share::refresh(&packages)?;
+    info!("{} {}", "Switched to profile".green(), name.yellow().bold());
+    info!("Open a new shell or run `eval \"$(updater env)\"` to use its packages");
+    Ok(())
+}
+
+/// Print every profile with its package count, marking the active one.
+pub fn list() -> Result<()> {
+    let active = active();
+    for name in profile_names() {
+        let db = match get_profile_dir(&name) {
+            Some(dir) => dir.join("packages.json"),
+            None => get_updater_dir().join("packages.json"),
+        };
+        let count = fs::read_to_string(&db)
+            .ok()
+            .and_then(|data| serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&data).ok())
+            .map_or(0, |packages| packages.len());
+        let marker = if name == active { "*" } else { " " };
+        println!("{} {} {}", marker, name.yellow().bold(), format!("({} packages)", count).dimmed());
+    }
+    Ok(())
+}
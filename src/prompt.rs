@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use dialoguer::Confirm;
+use std::io::{self, IsTerminal};
+
+use crate::error::Error;
+
+/// Environment variable that answers every confirmation prompt with yes.
+/// `--yes` sets it too, so child updater processes inherit the choice.
+pub const ASSUME_YES_VAR: &str = "UPDATER_ASSUME_YES";
+
+pub fn assume_yes() -> bool {
+    std::env::var(ASSUME_YES_VAR).is_ok_and(|v| !v.is_empty() && v != "0" && v != "false")
+}
+
+pub fn set_assume_yes() {
+    std::env::set_var(ASSUME_YES_VAR, "1");
+}
+
+/// Ask before doing something destructive. Without a terminal to ask on this
+/// fails instead of guessing, unless yes was assumed.
+pub fn confirm(message: &str) -> Result<bool> {
+    if assume_yes() {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(Error::Aborted(format!(
+            "Cannot ask \"{}\" without a terminal; pass --yes or set {}=1",
+            message, ASSUME_YES_VAR
+        ))
+        .into());
+    }
+
+    Confirm::new()
+        .with_prompt(message)
+        .default(false)
+        .interact()
+        .context("Failed to read confirmation")
+}
@@ -0,0 +1,115 @@
+//! `updater prompt-status`: pending updates as a few characters for shell
+//! prompts, like `3⬆` in starship or PS1.
+//!
+//! It only ever reads a cache, so prompts never wait on the network. The
+//! daemon refreshes the cache on every check, as does `updater outdated`.
+//! When the cache is older than the daemon's check interval and no daemon is
+//! checking, `prompt-status` starts a check in the background and shows the
+//! count it has meanwhile. `updater motd` summarizes the same cache at login.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use crate::config;
+use crate::daemon;
+use crate::package::{self, OutdatedPackage};
+
+/// Minutes before a background check that never finished is started again.
+const RETRY_MINUTES: i64 = 10;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    checked: Option<String>,
+    /// When a background check was last started
+    refreshing: Option<String>,
+    outdated: Vec<OutdatedPackage>,
+}
+
+/// Next to the package database, so every profile and prefix has its own.
+fn cache_path() -> PathBuf {
+    package::get_package_db_path().with_file_name("outdated.json")
+}
+
+fn load() -> Cache {
+    fs::read_to_string(cache_path()).ok().and_then(|data| serde_json::from_str(&data).ok()).unwrap_or_default()
+}
+
+fn save(cache: &Cache) -> Result<()> {
+    fs::write(cache_path(), serde_json::to_string_pretty(cache)?).context("Failed to write outdated cache")
+}
+
+/// Whether `time` is unset or further back than `age`.
+fn older_than(time: Option<&str>, age: chrono::Duration) -> bool {
+    match time.and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok()) {
+        Some(time) => chrono::Local::now().signed_duration_since(time) >= age,
+        None => true,
+    }
+}
+
+/// Remember what a check found for the prompt to show.
+pub fn store(outdated: &[OutdatedPackage]) {
+    let cache = Cache { checked: Some(chrono::Local::now().to_rfc3339()), refreshing: None, outdated: outdated.to_vec() };
+    if let Err(e) = save(&cache) {
+        debug!("Not caching outdated packages: {:#}", e);
+    }
+}
+
+/// When the outdated packages were last checked, if ever.
+pub fn last_check() -> Option<String> {
+    load().checked
+}
+
+/// Check upstream and refresh the cache, as the background check does.
+pub fn refresh() -> Result<()> {
+    let outdated = package::find_outdated(&package::load_packages()?);
+    store(&outdated);
+    Ok(())
+}
+
+fn spawn_refresh() -> Result<()> {
+    let updater = std::env::current_exe().context("Could not determine updater executable")?;
+    Command::new(updater)
+        .args(["prompt-status", "--refresh"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .context("Failed to start background check")?;
+    Ok(())
+}
+
+/// Outdated packages as of the last check, less the ones updated since, and
+/// start a background check when that one is stale. Never waits on the network.
+pub fn pending() -> Vec<OutdatedPackage> {
+    let mut cache = load();
+    let packages = package::load_packages().unwrap_or_default();
+    let interval = config::load_config().map_or(24, |config| config.daemon.check_interval_hours).max(1);
+    let stale = older_than(cache.checked.as_deref(), chrono::Duration::hours(interval as i64));
+    if stale && !daemon::is_checking() && older_than(cache.refreshing.as_deref(), chrono::Duration::minutes(RETRY_MINUTES)) {
+        cache.refreshing = Some(chrono::Local::now().to_rfc3339());
+        if let Err(e) = save(&cache).and_then(|_| spawn_refresh()) {
+            debug!("Not refreshing outdated packages: {:#}", e);
+        }
+    }
+    // Updates applied since the check no longer count
+    cache
+        .outdated
+        .into_iter()
+        .filter(|pkg| packages.get(&pkg.name).and_then(|package| package.active_version.as_deref()) == Some(pkg.current.as_str()))
+        .collect()
+}
+
+/// Print how many updates are pending followed by `symbol`, or nothing when
+/// there are none. Problems are only logged, so a broken cache can't break the prompt.
+pub fn print(symbol: &str) {
+    let pending = pending().len();
+    if pending > 0 {
+        println!("{}{}", pending, symbol);
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::backends::{self, repo, run_command};
+use crate::config::{self, RepoKind, Repository};
+use crate::error::Error;
+use crate::tap;
+
+/// Register a repository in the config, checking that it is reachable first.
+pub fn add(repository: Repository) -> Result<()> {
+    let mut config = config::load_config()?;
+    if config.repos.iter().any(|r| r.name == repository.name) {
+        bail!("Repository {} already exists", repository.name);
+    }
+
+    match repository.kind {
+        RepoKind::Updater => {
+            let index = repo::fetch_index(&repository)?;
+            info!("{} {} packages", "Found".green(), index.packages.len());
+        }
+        RepoKind::Flatpak => {
+            let mut cmd = flatpak_remote_command("remote-add");
+            cmd.arg("--if-not-exists")
+                .arg(format!("--prio={}", repository.priority))
+                .arg(&repository.name)
+                .arg(&repository.url);
+            run_command(&mut cmd).with_context(|| format!("Failed to add flatpak remote {}", repository.name))?;
+        }
+        RepoKind::Tap => {
+            let count = tap::fetch(&repository)?;
+            info!("{} {} recipes", "Found".green(), count);
+        }
+        RepoKind::Oci => {}
+    }
+
+    info!("{} {} {}", "Added".green(), repository.kind, repository.name.yellow().bold());
+    config.repos.push(repository);
+    config::save_config(&config)
+}
+
+pub fn remove(name: &str) -> Result<()> {
+    let mut config = config::load_config()?;
+    let position = match config.repos.iter().position(|r| r.name == name) {
+        Some(position) => position,
+        None => return Err(Error::RepositoryNotFound(name.to_string()).into()),
+    };
+    let repository = config.repos.remove(position);
+
+    if repository.kind == RepoKind::Flatpak {
+        // The remote may already have been removed with flatpak itself
+        let mut cmd = flatpak_remote_command("remote-delete");
+        cmd.arg(&repository.name);
+        if let Err(e) = run_command(&mut cmd) {
+            warn!("{:#}", e);
+        }
+    }
+    if repository.kind == RepoKind::Tap {
+        tap::remove_checkout(&repository.name)?;
+    }
+
+    config::save_config(&config)?;
+    info!("{} {}", "Removed repository".green(), name.yellow().bold());
+    Ok(())
+}
+
+pub fn list() -> Result<()> {
+    let config = config::load_config()?;
+    if config.repos.is_empty() {
+        println!("{}", "No repositories configured".yellow());
+        return Ok(());
+    }
+
+    for kind in [RepoKind::Updater, RepoKind::Flatpak, RepoKind::Tap, RepoKind::Oci] {
+        for repository in config.repos_of(kind) {
+            println!(
+                "{} ({}, priority {}) {}",
+                repository.name.green().bold(),
+                kind,
+                repository.priority,
+                repository.url
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Flatpak remotes go into the user installation unless running as root.
+fn flatpak_remote_command(subcommand: &str) -> Command {
+    let mut cmd = Command::new("flatpak");
+    cmd.arg(subcommand);
+    if !backends::is_root() {
+        cmd.arg("--user");
+    }
+    cmd
+}
@@ -0,0 +1,227 @@
+//! `updater rpc`: JSON-RPC 2.0 over stdio for editors and GUI frontends.
+//!
+//! Requests and responses are one JSON object per line. Queries answer
+//! directly; `install`, `update` and `remove` answer `{"operation": <id>}` and
+//! then send `progress` notifications (`{"operation", "line"}`) followed by a
+//! `finished` notification (`{"operation", "success"}`).
+//!
+//! | method     | params                                      | result             |
+//! |------------|---------------------------------------------|--------------------|
+//! | `list`     | `{"system_only"?, "user_only"?}`            | installed packages |
+//! | `outdated` |                                             | outdated packages  |
+//! | `search`   | `{"query", "refresh"?}`                     | `{"hits", "stale"}`|
+//! | `versions` | `{"name"}`                                  | versions per backend |
+//! | `install`  | `{"name", "version"?, "user"?, "backend"?}` | `{"operation"}`    |
+//! | `update`   | `{"name"?}`                                 | `{"operation"}`    |
+//! | `remove`   | `{"name", "version"?}`                      | `{"operation"}`    |
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::debug;
+
+use crate::operation::{self, OperationStatus, Operations};
+use crate::package;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Any error from updater itself, with the message in `message`
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    /// Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ListParams {
+    system_only: bool,
+    user_only: bool,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    #[serde(default)]
+    refresh: bool,
+}
+
+#[derive(Deserialize)]
+struct NameParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct InstallParams {
+    name: String,
+    version: Option<String>,
+    #[serde(default)]
+    user: bool,
+    backend: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct UpdateParams {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoveParams {
+    name: String,
+    version: Option<String>,
+}
+
+/// Stdout shared by responses and notifications so lines never interleave.
+#[derive(Clone)]
+struct Output(Arc<Mutex<io::Stdout>>);
+
+impl Output {
+    fn send(&self, message: Value) {
+        let mut stdout = self.0.lock().unwrap();
+        writeln!(stdout, "{}", message).and_then(|_| stdout.flush()).ok();
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+}
+
+/// What a request answers with: a value, or an operation whose progress follows the response.
+enum Reply {
+    Value(Value),
+    Operation(usize),
+}
+
+type RpcResult = std::result::Result<Reply, (i64, String)>;
+
+/// Serve requests from stdin until it closes. Each request runs on its own
+/// thread, so a slow search doesn't hold up progress from an install.
+/// Operations still running when stdin closes are waited for.
+pub fn run() -> Result<()> {
+    let output = Output(Arc::new(Mutex::new(io::stdout())));
+    let operations = Operations::default();
+    let mut handlers = Vec::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        debug!("rpc request: {}", line);
+
+        let request: RpcRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let code = if serde_json::from_str::<Value>(&line).is_ok() { INVALID_REQUEST } else { PARSE_ERROR };
+                output.send(error_response(Value::Null, code, e.to_string()));
+                continue;
+            }
+        };
+
+        let output = output.clone();
+        let operations = operations.clone();
+        handlers.push(thread::spawn(move || {
+            let result = if request.jsonrpc == "2.0" {
+                dispatch(&request.method, request.params, &operations)
+            } else {
+                Err((INVALID_REQUEST, "Only JSON-RPC 2.0 is supported".to_string()))
+            };
+            if let Some(id) = request.id {
+                output.send(match &result {
+                    Ok(Reply::Value(value)) => json!({ "jsonrpc": "2.0", "id": id, "result": value }),
+                    Ok(Reply::Operation(operation)) => json!({ "jsonrpc": "2.0", "id": id, "result": { "operation": operation } }),
+                    Err((code, message)) => error_response(id, *code, message.clone()),
+                });
+            }
+            if let Ok(Reply::Operation(operation)) = result {
+                follow(operation, &operations, &output);
+            }
+        }));
+    }
+    for handler in handlers {
+        handler.join().ok();
+    }
+    Ok(())
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn dispatch(method: &str, params: Value, operations: &Operations) -> RpcResult {
+    match method {
+        "list" => {
+            let params: ListParams = parse(params)?;
+            query(package::installed(params.system_only, params.user_only))
+        }
+        "outdated" => query(package::load_packages().map(|packages| package::find_outdated(&packages))),
+        "search" => {
+            let params: SearchParams = parse(params)?;
+            let results = package::find_packages(&params.query, params.refresh).map_err(server_error)?;
+            Ok(Reply::Value(json!({ "hits": results.hits, "stale": results.stale })))
+        }
+        "versions" => {
+            let params: NameParams = parse(params)?;
+            query(package::upstream_versions(&params.name))
+        }
+        "install" => {
+            let params: InstallParams = parse(params)?;
+            Ok(Reply::Operation(operations.start(operation::install_args(params.name, params.version, params.user, params.backend).map_err(invalid_params)?)))
+        }
+        "update" => {
+            let params: UpdateParams = parse(params)?;
+            Ok(Reply::Operation(operations.start(operation::update_args(params.name).map_err(invalid_params)?)))
+        }
+        "remove" => {
+            let params: RemoveParams = parse(params)?;
+            Ok(Reply::Operation(operations.start(operation::remove_args(params.name, params.version).map_err(invalid_params)?)))
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    }
+}
+
+/// Parse params, treating missing ones as `{}`.
+fn parse<T: DeserializeOwned>(params: Value) -> std::result::Result<T, (i64, String)> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+fn invalid_params(e: anyhow::Error) -> (i64, String) {
+    (INVALID_PARAMS, format!("{:#}", e))
+}
+
+fn query<T: serde::Serialize>(result: Result<T>) -> RpcResult {
+    Ok(Reply::Value(json!(result.map_err(server_error)?)))
+}
+
+fn server_error(e: anyhow::Error) -> (i64, String) {
+    (SERVER_ERROR, format!("{:#}", e))
+}
+
+/// Forward an operation's output as notifications until it finishes.
+fn follow(id: usize, operations: &Operations, output: &Output) {
+    let mut seen = 0;
+    while let Some((lines, status)) = operations.wait(id, seen) {
+        seen += lines.len();
+        for line in lines {
+            output.notify("progress", json!({ "operation": id, "line": line }));
+        }
+        if status != OperationStatus::Running {
+            output.notify("finished", json!({ "operation": id, "success": status == OperationStatus::Succeeded }));
+            return;
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Confining backend install and build steps with bubblewrap, so a hostile
+//! package can only write to its own build and install dirs.
+
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+use crate::backends::plugin;
+use crate::config::{self, SandboxPolicy};
+
+/// Built-in backends whose steps go through `confine`; plugins' do too.
+const CONFINED_BACKENDS: &[&str] = &["source", "gem"];
+
+/// The sandbox policy configured for a backend, if its steps are confined.
+pub fn policy(backend: &str) -> Result<Option<SandboxPolicy>> {
+    let config = config::load_config()?;
+    Ok(config.sandbox.backends.get(backend).filter(|policy| policy.enabled).cloned())
+}
+
+/// Fail before installing with `backend` when it has a sandbox policy it
+/// would ignore, since running unconfined is not what was asked for.
+pub fn check_supported(backend: &str) -> Result<()> {
+    if policy(backend)?.is_none() || CONFINED_BACKENDS.contains(&backend) || plugin::find(backend)?.is_some() {
+        return Ok(());
+    }
+    bail!(
+        "The {} backend can't be sandboxed, only {} and plugin backends can; remove [sandbox.backends.{}] from the config to install with it unconfined",
+        backend,
+        CONFINED_BACKENDS.join(", "),
+        backend
+    )
+}
+
+/// `cmd` rewritten to run inside bubblewrap when `backend` is sandboxed,
+/// otherwise a copy of it. The filesystem is visible read-only except for the
+/// home dir, which is hidden along with the tokens and keys kept there, with
+/// the policy's readable paths visible again; `writable`, the policy's
+/// writable paths and a private `/tmp` can be written. The network is cut off
+/// unless the policy allows it. Once sandboxing is configured this fails
+/// rather than running unconfined.
+pub fn confine(backend: &str, cmd: &Command, writable: &[&Path]) -> Result<Command> {
+    let policy = match policy(backend)? {
+        Some(policy) => policy,
+        None => return Ok(copy(cmd, Command::new(cmd.get_program()))),
+    };
+    if which::which("bwrap").is_err() {
+        bail!("The {} backend is configured to run sandboxed, but bwrap is not installed", backend);
+    }
+
+    let mut bwrap = Command::new("bwrap");
+    bwrap.args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"]);
+    if let Some(home) = dirs::home_dir() {
+        bwrap.arg("--tmpfs").arg(home);
+    }
+    // The program itself may live under the home dir, like plugins do
+    let program = Path::new(cmd.get_program());
+    let readable = program.is_absolute().then_some(program);
+    for path in readable.into_iter().chain(policy.readable.iter().map(|p| p.as_path())) {
+        bwrap.arg("--ro-bind-try").arg(path).arg(path);
+    }
+    for path in writable.iter().copied().chain(policy.writable.iter().map(|p| p.as_path())) {
+        // bwrap can only bind paths that exist
+        std::fs::create_dir_all(path).ok();
+        bwrap.arg("--bind").arg(path).arg(path);
+    }
+    if !policy.network {
+        bwrap.arg("--unshare-net");
+    }
+    bwrap.args(["--unshare-ipc", "--unshare-pid", "--unshare-uts", "--die-with-parent", "--new-session"]);
+    if let Some(dir) = cmd.get_current_dir() {
+        bwrap.arg("--chdir").arg(dir);
+    }
+    bwrap.arg("--").arg(cmd.get_program());
+    debug!("Sandboxing {:?} for the {} backend", cmd.get_program(), backend);
+    Ok(copy(cmd, bwrap))
+}
+
+/// Carry the arguments, environment and working dir of `from` over to `to`.
+fn copy(from: &Command, mut to: Command) -> Command {
+    to.args(from.get_args());
+    for (key, value) in from.get_envs() {
+        match value {
+            Some(value) => to.env(key, value),
+            None => to.env_remove(key),
+        };
+    }
+    if let Some(dir) = from.get_current_dir() {
+        to.current_dir(dir);
+    }
+    to
+}
@@ -0,0 +1,254 @@
+//! Software bills of materials for everything updater tracks.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::backends::repo;
+use crate::download;
+use crate::package::{self, PackageVersion};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    Cyclonedx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+/// One installed package version as it appears in the bill of materials.
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub name: String,
+    pub version: String,
+    pub backend: String,
+    pub purl: String,
+    pub download_url: Option<String>,
+    pub sha256: Option<String>,
+    pub license: Option<String>,
+}
+
+/// Package URL type for a backend, `generic` for ones without a registered type.
+fn purl_type(pm_name: &str) -> &'static str {
+    match pm_name {
+        "cargo" => "cargo",
+        "pip" => "pypi",
+        "npm" => "npm",
+        "gem" => "gem",
+        "go" => "golang",
+        "conda" => "conda",
+        "apt" | "deb" => "deb",
+        "dnf" | "yum" | "zypper" | "rpm" => "rpm",
+        "pacman" => "alpm",
+        "apk" => "apk",
+        _ => "generic",
+    }
+}
+
+fn purl(name: &str, version: &str, pm_name: &str) -> String {
+    // Scoped npm names and Go module paths keep their slashes as namespaces
+    let name = name.replace('@', "%40");
+    format!("pkg:{}/{}@{}", purl_type(pm_name), name, version)
+}
+
+/// Where a version was downloaded from, as far as updater can tell.
+pub(crate) fn download_url(name: &str, version: &str, info: &PackageVersion, pm_name: &str) -> Option<String> {
+    if let Some(spec) = &info.url {
+        return Some(spec.url.replace("{version}", version.trim_start_matches('v')));
+    }
+    if let Some(spec) = &info.source {
+        return Some(spec.repo.clone());
+    }
+    match pm_name {
+        "cargo" => Some(format!("https://crates.io/api/v1/crates/{}/{}/download", name, version)),
+        "pip" => Some(format!("https://pypi.org/project/{}/{}/", name, version)),
+        "npm" => Some(format!("https://registry.npmjs.org/{}/-/{}-{}.tgz", name, name.rsplit('/').next().unwrap_or(name), version)),
+        "gem" => Some(format!("https://rubygems.org/downloads/{}-{}.gem", name, version)),
+        "go" => Some(format!("https://proxy.golang.org/{}/@v/{}.zip", name.to_lowercase(), version)),
+        "repo" => match repo::resolve(name, Some(version)) {
+            Ok(asset) => Some(asset.url),
+            Err(e) => {
+                warn!("Could not look up the download URL of {} {}: {:#}", name, version, e);
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+/// SHA-256 of the file a version was installed from, when updater kept it.
+pub(crate) fn artifact_sha256(info: &PackageVersion) -> Option<String> {
+    if let Some(file) = &info.local_file {
+        return download::sha256_file(file).ok();
+    }
+    kept_artifact_sha256(&info.install_path)
+}
+
+/// SHA-256 of the artifact a backend kept in `install_dir`, if it kept one.
+pub(crate) fn kept_artifact_sha256(install_dir: &Path) -> Option<String> {
+    let kept = fs::read_dir(install_dir.join(".artifact")).ok()?.flatten().find(|entry| entry.path().is_file())?;
+    download::sha256_file(&kept.path()).ok()
+}
+
+/// Every tracked package version, sorted by name and version.
+pub fn components() -> Result<Vec<Component>> {
+    let mut components = Vec::new();
+    for package in package::installed(false, false)? {
+        for (recorded, info) in &package.versions {
+            let version = match info.exact_version(recorded) {
+                Some(version) => version,
+                None => {
+                    warn!("Leaving out {}, installed as latest at an unknown release", package.name);
+                    continue;
+                }
+            };
+            let backend = info.package_manager.clone().unwrap_or_default();
+            components.push(Component {
+                name: package.name.clone(),
+                version: version.to_string(),
+                purl: purl(&package.name, version, &backend),
+                download_url: info.source_url.clone().or_else(|| download_url(&package.name, version, info, &backend)),
+                sha256: artifact_sha256(info),
+                license: info.license.clone(),
+                backend,
+            });
+        }
+    }
+    components.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    Ok(components)
+}
+
+/// Print a bill of materials for every tracked package in `format`.
+pub fn sbom(format: SbomFormat) -> Result<()> {
+    let components = components()?;
+    let document = match format {
+        SbomFormat::Cyclonedx => cyclonedx(&components),
+        SbomFormat::Spdx => spdx(&components),
+    };
+    println!("{}", serde_json::to_string_pretty(&document)?);
+    Ok(())
+}
+
+/// A random-looking but reproducible UUID for the document, from its contents.
+fn document_uuid(components: &[Component], timestamp: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(timestamp);
+    for component in components {
+        hasher.update(&component.purl);
+    }
+    let mut bytes: [u8; 16] = hasher.finalize()[..16].try_into().unwrap();
+    // Mark it as a version 4, RFC 4122 variant UUID
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn cyclonedx(components: &[Component]) -> Value {
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let entries: Vec<Value> = components
+        .iter()
+        .map(|component| {
+            let mut entry = json!({
+                "type": "application",
+                "bom-ref": component.purl,
+                "name": component.name,
+                "version": component.version,
+                "purl": component.purl,
+                "properties": [{ "name": "updater:backend", "value": component.backend }],
+            });
+            if let Some(sha256) = &component.sha256 {
+                entry["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+            }
+            if let Some(license) = &component.license {
+                entry["licenses"] = json!([{ "expression": license }]);
+            }
+            if let Some(url) = &component.download_url {
+                entry["externalReferences"] = json!([{ "type": "distribution", "url": url }]);
+            }
+            entry
+        })
+        .collect();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", document_uuid(components, &timestamp)),
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp,
+            "tools": {
+                "components": [{ "type": "application", "name": "updater", "version": env!("CARGO_PKG_VERSION") }],
+            },
+        },
+        "components": entries,
+    })
+}
+
+/// SPDX identifiers only allow letters, digits, `.` and `-`.
+fn spdx_id(component: &Component) -> String {
+    let id: String = format!("{}-{}", component.name, component.version)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '-' })
+        .collect();
+    format!("SPDXRef-Package-{}", id)
+}
+
+fn spdx(components: &[Component]) -> Value {
+    let timestamp = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let packages: Vec<Value> = components
+        .iter()
+        .map(|component| {
+            let mut package = json!({
+                "SPDXID": spdx_id(component),
+                "name": component.name,
+                "versionInfo": component.version,
+                "downloadLocation": component.download_url.as_deref().unwrap_or("NOASSERTION"),
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": component.license.as_deref().unwrap_or("NOASSERTION"),
+                "supplier": "NOASSERTION",
+                "comment": format!("Installed with the {} backend", component.backend),
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": component.purl,
+                }],
+            });
+            if let Some(sha256) = &component.sha256 {
+                package["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": sha256 }]);
+            }
+            package
+        })
+        .collect();
+    let relationships: Vec<Value> = components
+        .iter()
+        .map(|component| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": spdx_id(component),
+            })
+        })
+        .collect();
+    let host = fs::read_to_string("/etc/hostname").map(|h| h.trim().to_string()).unwrap_or_else(|_| "localhost".to_string());
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("updater-{}", host),
+        "documentNamespace": format!("https://spdx.org/spdxdocs/updater-{}-{}", host, document_uuid(components, &timestamp)),
+        "creationInfo": {
+            "created": timestamp,
+            "creators": [format!("Tool: updater-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
@@ -0,0 +1,167 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::info;
+
+use crate::backends::{is_root, run_command};
+
+const SERVICE_NAME: &str = "updater-update.service";
+const TIMER_NAME: &str = "updater-update.timer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.on_calendar())
+    }
+}
+
+impl Frequency {
+    /// Time between two runs at this frequency.
+    pub fn interval(&self) -> chrono::Duration {
+        match self {
+            Frequency::Hourly => chrono::Duration::hours(1),
+            Frequency::Daily => chrono::Duration::days(1),
+            Frequency::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+
+    fn on_calendar(&self) -> &'static str {
+        match self {
+            Frequency::Hourly => "hourly",
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+        }
+    }
+}
+
+fn unit_dir(system: bool) -> PathBuf {
+    if system {
+        PathBuf::from("/etc/systemd/system")
+    } else {
+        let config_dir = dirs::config_dir().expect("Could not determine config directory");
+        config_dir.join("systemd").join("user")
+    }
+}
+
+fn systemctl(system: bool) -> Command {
+    let mut cmd = Command::new("systemctl");
+    if !system {
+        cmd.arg("--user");
+    }
+    cmd
+}
+
+fn check_scope(system: bool) -> Result<()> {
+    if system && !is_root() {
+        bail!("Managing system units requires root, rerun with sudo");
+    }
+    Ok(())
+}
+
+/// Write the service and timer units and enable the timer.
+pub fn install(frequency: Frequency, system: bool) -> Result<()> {
+    check_scope(system)?;
+    let updater = std::env::current_exe().context("Could not determine updater executable")?;
+    let dir = unit_dir(system);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let service = format!(
+        "[Unit]\n\
+         Description=Update packages managed by updater\n\
+         After=network-online.target\n\
+         Wants=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={} update --all --yes\n",
+        updater.display()
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run updater update {}\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={}\n\
+         Persistent=true\n\
+         RandomizedDelaySec=15min\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        frequency.on_calendar(),
+        frequency.on_calendar()
+    );
+
+    fs::write(dir.join(SERVICE_NAME), service).context("Failed to write service unit")?;
+    fs::write(dir.join(TIMER_NAME), timer).context("Failed to write timer unit")?;
+
+    run_command(systemctl(system).arg("daemon-reload"))?;
+    run_command(systemctl(system).args(["enable", "--now", TIMER_NAME]))?;
+
+    info!("{} {} {}", "Scheduled".green(), "updater update".yellow().bold(), frequency.on_calendar().cyan());
+    info!("Units written to {}", dir.display());
+    Ok(())
+}
+
+pub fn remove(system: bool) -> Result<()> {
+    check_scope(system)?;
+    let dir = unit_dir(system);
+    let timer_path = dir.join(TIMER_NAME);
+    if !timer_path.exists() {
+        println!("{}", "No updater schedule installed".yellow());
+        return Ok(());
+    }
+
+    // The timer may already be disabled by hand, that is fine
+    run_command(systemctl(system).args(["disable", "--now", TIMER_NAME])).ok();
+    fs::remove_file(&timer_path)?;
+    let service_path = dir.join(SERVICE_NAME);
+    if service_path.exists() {
+        fs::remove_file(service_path)?;
+    }
+    run_command(systemctl(system).arg("daemon-reload"))?;
+
+    info!("{}", "Removed updater schedule".green());
+    Ok(())
+}
+
+pub fn status(system: bool) -> Result<()> {
+    let dir = unit_dir(system);
+    if !dir.join(TIMER_NAME).exists() {
+        println!("{}", "No updater schedule installed".yellow());
+        return Ok(());
+    }
+
+    // is-enabled / is-active exit non-zero for disabled or inactive units, so read stdout only
+    let query = |arg: &str| -> String {
+        systemctl(system)
+            .args([arg, TIMER_NAME])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    };
+    let enabled = query("is-enabled");
+    let active = query("is-active");
+    let next_run = run_command(systemctl(system).args(["show", TIMER_NAME, "--property=NextElapseUSecRealtime", "--value"]))
+        .map(|out| out.trim().to_string())
+        .unwrap_or_default();
+
+    println!("Timer: {}", dir.join(TIMER_NAME).display());
+    println!("Enabled: {}", if enabled == "enabled" { enabled.green() } else { enabled.red() });
+    println!("Active: {}", if active == "active" { active.green() } else { active.red() });
+    if !next_run.is_empty() {
+        println!("Next run: {}", next_run.cyan());
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::system::SearchResult;
+
+/// Last search results of every backend, keyed by backend and then by query.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    backends: BTreeMap<String, BTreeMap<String, CachedSearch>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedSearch {
+    pub updated: String,
+    pub results: Vec<CachedResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResult {
+    pub name: String,
+    pub description: String,
+}
+
+impl SearchIndex {
+    /// Cached results of `backend` for `query`, whether fresh or stale.
+    pub fn get(&self, backend: &str, query: &str) -> Option<&CachedSearch> {
+        self.backends.get(backend)?.get(&normalize(query))
+    }
+
+    /// Whether anything `backend` found is cached.
+    pub fn has_results(&self, backend: &str) -> bool {
+        self.backends.get(backend).is_some_and(|queries| queries.values().any(|search| !search.results.is_empty()))
+    }
+
+    /// Every result cached for any query, with the backend it came from.
+    pub fn known(&self) -> Vec<(String, CachedResult)> {
+        self.backends
+            .iter()
+            .flat_map(|(backend, queries)| {
+                queries.values().flat_map(move |search| search.results.iter().map(move |r| (backend.clone(), r.clone())))
+            })
+            .collect()
+    }
+
+    pub fn insert(&mut self, backend: &str, query: &str, results: Vec<SearchResult>) -> Vec<CachedResult> {
+        let results: Vec<CachedResult> = results
+            .into_iter()
+            .map(|r| CachedResult {
+                name: r.name,
+                description: r.description,
+            })
+            .collect();
+        self.backends.entry(backend.to_string()).or_default().insert(
+            normalize(query),
+            CachedSearch {
+                updated: chrono::Local::now().to_rfc3339(),
+                results: results.clone(),
+            },
+        );
+        results
+    }
+}
+
+impl CachedSearch {
+    pub fn is_stale(&self, ttl_hours: u64) -> bool {
+        match chrono::DateTime::parse_from_rfc3339(&self.updated) {
+            Ok(updated) => chrono::Local::now().signed_duration_since(updated) >= chrono::Duration::hours(ttl_hours as i64),
+            Err(_) => true,
+        }
+    }
+}
+
+fn normalize(query: &str) -> String {
+    query.trim().to_lowercase()
+}
+
+pub fn get_index_path() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    let updater_dir = data_dir.join("updater");
+    fs::create_dir_all(&updater_dir).expect("Failed to create data directory");
+    updater_dir.join("search-index.json")
+}
+
+pub fn load_index() -> Result<SearchIndex> {
+    let index_path = get_index_path();
+    if !index_path.exists() {
+        return Ok(SearchIndex::default());
+    }
+
+    let data = fs::read_to_string(&index_path).context("Failed to read search index")?;
+    // A corrupt cache is not worth failing a search over
+    Ok(serde_json::from_str(&data).unwrap_or_default())
+}
+
+pub fn save_index(index: &SearchIndex) -> Result<()> {
+    let data = serde_json::to_string(index).context("Failed to serialize search index")?;
+    fs::write(get_index_path(), data).context("Failed to write search index")?;
+    Ok(())
+}
+
+/// Re-run `query` against every backend in a detached `updater search --refresh`.
+pub fn refresh_in_background(query: &str) -> Result<()> {
+    let updater = std::env::current_exe().context("Could not determine updater executable")?;
+    Command::new(updater)
+        .args(["search", "--refresh", query])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .context("Failed to start search index refresh")?;
+    Ok(())
+}
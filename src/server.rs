@@ -0,0 +1,271 @@
+//! `updater serve`: a small JSON API for dashboards and remote tooling.
+//!
+//! - `GET /packages` – installed packages
+//! - `GET /outdated` – packages with a newer upstream version
+//! - `GET /search?q=<query>` – search every backend
+//! - `POST /install` `{"name", "version"?, "user"?, "backend"?}` – start an install
+//! - `POST /update` `{"name"?}` – start updating one package, or all of them
+//! - `POST /remove` `{"name", "version"?}` – start a removal
+//! - `GET /operations`, `GET /operations/<id>` – started operations and their output
+//! - `GET /operations/<id>/events` – follow an operation as server-sent events
+//! - `GET /metrics` – Prometheus metrics, see `metrics.rs`
+//!
+//! Starting an operation answers `202 Accepted` with `{"id": <id>}` right away.
+//! Bodies must be sent as `application/json`, and requests a browser sends
+//! from another site's page, which carry its `Origin`, are refused.
+
+use anyhow::{anyhow, Result};
+use colored::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{debug, info, warn};
+
+use crate::metrics;
+use crate::operation::{self, OperationStatus, Operations};
+use crate::package;
+
+/// Environment variable holding the bearer token clients must send, like `--token`.
+pub const TOKEN_VAR: &str = "UPDATER_SERVE_TOKEN";
+
+#[derive(Deserialize)]
+struct InstallRequest {
+    name: String,
+    version: Option<String>,
+    #[serde(default)]
+    user: bool,
+    backend: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpdateRequest {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoveRequest {
+    name: String,
+    version: Option<String>,
+}
+
+/// An error answered as `{"error": ...}` with an HTTP status.
+struct ApiError(u16, String);
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError(500, format!("{:#}", e))
+    }
+}
+
+type Reply = std::result::Result<(u16, Value), ApiError>;
+
+/// The token clients must send, from `--token` or the environment, warning
+/// when there is none and the address is reachable from other machines.
+pub(crate) fn resolve_token(listen: SocketAddr, token: Option<String>) -> Option<String> {
+    let token = token.or_else(|| std::env::var(TOKEN_VAR).ok());
+    if token.is_none() && !listen.ip().is_loopback() {
+        warn!("{}", format!("Listening on {} without a token lets anyone who can reach it install packages", listen).yellow());
+    }
+    token
+}
+
+pub fn serve(listen: SocketAddr, token: Option<String>) -> Result<()> {
+    let token = resolve_token(listen, token);
+
+    let server = Server::http(listen).map_err(|e| anyhow!("Failed to listen on {}: {}", listen, e))?;
+    info!("{} {}", "Serving the updater API on".green(), format!("http://{}", listen).cyan());
+
+    let operations = Operations::default();
+    for request in server.incoming_requests() {
+        let operations = operations.clone();
+        let token = token.clone();
+        // Event streams stay open until their operation finishes
+        thread::spawn(move || handle(request, &operations, token.as_deref()));
+    }
+    Ok(())
+}
+
+fn handle(mut request: Request, operations: &Operations, token: Option<&str>) {
+    debug!("{} {}", request.method(), request.url());
+    if let Some(token) = token {
+        let expected = format!("Bearer {}", token);
+        let authorized = request
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Authorization") && h.value.as_str() == expected);
+        if !authorized {
+            respond(request, Err(ApiError(401, "Missing or wrong bearer token".to_string())));
+            return;
+        }
+    }
+
+    // Browsers send an Origin with cross-site requests; no page of ours makes them
+    if let Some(origin) = header(&request, "Origin") {
+        let own = header(&request, "Host").map(|host| format!("http://{}", host));
+        if own.as_deref() != Some(origin.as_str()) {
+            respond(request, Err(ApiError(403, format!("Requests from {} are not allowed", origin))));
+            return;
+        }
+    }
+
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let reply = match (request.method(), segments.as_slice()) {
+        (Method::Get, ["packages"]) => package::installed(false, false).map(|p| (200, json!(p))).map_err(ApiError::from),
+        (Method::Get, ["outdated"]) => package::load_packages()
+            .map(|packages| (200, json!(package::find_outdated(&packages))))
+            .map_err(ApiError::from),
+        (Method::Get, ["search"]) => match query_param(query, "q") {
+            Some(q) => package::find_packages(&q, false).map(|r| (200, json!(r.hits))).map_err(ApiError::from),
+            None => Err(ApiError(400, "Missing query parameter q".to_string())),
+        },
+        (Method::Post, ["install"]) => body::<InstallRequest>(&mut request)
+            .and_then(|install| invalid(operation::install_args(install.name, install.version, install.user, install.backend)))
+            .map(|args| started(operations.start(args))),
+        (Method::Post, ["update"]) => body::<UpdateRequest>(&mut request)
+            .and_then(|update| invalid(operation::update_args(update.name)))
+            .map(|args| started(operations.start(args))),
+        (Method::Post, ["remove"]) => body::<RemoveRequest>(&mut request)
+            .and_then(|remove| invalid(operation::remove_args(remove.name, remove.version)))
+            .map(|args| started(operations.start(args))),
+        (Method::Get, ["operations"]) => Ok((200, json!(operations.list()))),
+        (Method::Get, ["operations", id]) => {
+            operation_id(id, operations).map(|id| (200, json!(operations.get(id))))
+        }
+        (Method::Get, ["metrics"]) => {
+            if let Err(e) = request.respond(metrics::response()) {
+                debug!("Failed to send response: {}", e);
+            }
+            return;
+        }
+        (Method::Get, ["operations", id, "events"]) => match operation_id(id, operations) {
+            Ok(id) => {
+                stream_events(request, operations, id);
+                return;
+            }
+            Err(e) => Err(e),
+        },
+        (_, ["packages" | "outdated" | "search" | "install" | "update" | "remove" | "operations" | "metrics", ..]) => {
+            Err(ApiError(405, format!("{} is not supported here", request.method())))
+        }
+        _ => Err(ApiError(404, format!("No such endpoint: {}", path))),
+    };
+    respond(request, reply);
+}
+
+fn started(id: usize) -> (u16, Value) {
+    (202, json!({ "id": id }))
+}
+
+fn operation_id(id: &str, operations: &Operations) -> std::result::Result<usize, ApiError> {
+    id.parse()
+        .ok()
+        .filter(|id| operations.get(*id).is_some())
+        .ok_or_else(|| ApiError(404, format!("No such operation: {}", id)))
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str().to_string())
+}
+
+/// Answer arguments rejected for a request with `400 Bad Request`.
+fn invalid(args: Result<Vec<String>>) -> std::result::Result<Vec<String>, ApiError> {
+    args.map_err(|e| ApiError(400, format!("{:#}", e)))
+}
+
+/// Parse a JSON request body, treating an empty body as `{}`. Other content
+/// types are refused, since browsers send `text/plain` cross-site without asking.
+fn body<T: DeserializeOwned>(request: &mut Request) -> std::result::Result<T, ApiError> {
+    let is_json = header(request, "Content-Type").is_some_and(|t| t.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case("application/json"));
+    if !is_json {
+        return Err(ApiError(415, "Send request bodies as application/json".to_string()));
+    }
+    let mut data = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut data)
+        .map_err(|e| ApiError(400, format!("Failed to read request body: {}", e)))?;
+    if data.trim().is_empty() {
+        data = "{}".to_string();
+    }
+    serde_json::from_str(&data).map_err(|e| ApiError(400, format!("Invalid request body: {}", e)))
+}
+
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=')?;
+        (k == key).then(|| percent_decode(v))
+    })
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn respond(request: Request, reply: Reply) {
+    let (status, value) = match reply {
+        Ok(reply) => reply,
+        Err(ApiError(status, message)) => (status, json!({ "error": message })),
+    };
+    let response = Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    if let Err(e) = request.respond(response) {
+        debug!("Failed to send response: {}", e);
+    }
+}
+
+/// Send each line an operation prints as a `data:` event, then a `finished`
+/// event carrying its status. The body is chunked by hand so every event goes
+/// out as soon as it happens.
+fn stream_events(request: Request, operations: &Operations, id: usize) {
+    let mut writer = request.into_writer();
+    let header = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\r\n";
+    if writer.write_all(header.as_bytes()).is_err() {
+        return;
+    }
+    let mut send = |chunk: &str| {
+        write!(writer, "{:x}\r\n{}\r\n", chunk.len(), chunk).and_then(|_| writer.flush())
+    };
+
+    let mut seen = 0;
+    while let Some((lines, status)) = operations.wait(id, seen) {
+        seen += lines.len();
+        let events: String = lines.iter().map(|line| format!("data: {}\n\n", line)).collect();
+        if !events.is_empty() && send(&events).is_err() {
+            return;
+        }
+        if status != OperationStatus::Running {
+            let status = serde_json::to_value(status).unwrap_or_default();
+            let finished = format!("event: finished\ndata: {}\n\n", status.as_str().unwrap_or_default());
+            // An empty chunk ends the body
+            send(&finished).and_then(|_| send("")).ok();
+            return;
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! A managed `share` tree linking the man pages and shell completions of
+//! every active version, laid out like `/usr/share` so `updater env` can put
+//! it on MANPATH and the shells' completion paths. Desktop entries of GUI
+//! apps go straight to `~/.local/share/applications`, where launchers look.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::package::{Package, PackageVersion};
+use crate::profile;
+use crate::shell::Shell;
+use crate::shim;
+
+/// Prefix of the desktop entries updater writes, so it only ever replaces its own.
+const DESKTOP_ENTRY_PREFIX: &str = "updater-";
+
+pub fn get_share_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    profile::active_dir().unwrap_or_else(|| data_dir.join("updater")).join("share")
+}
+
+pub fn get_man_dir() -> PathBuf {
+    get_share_dir().join("man")
+}
+
+/// Where completions for `shell` are linked, in the directory that shell's
+/// completion system loads from by command name.
+pub fn get_completion_dir(shell: Shell) -> PathBuf {
+    let share_dir = get_share_dir();
+    match shell {
+        Shell::Bash => share_dir.join("bash-completion").join("completions"),
+        Shell::Zsh => share_dir.join("zsh").join("site-functions"),
+        Shell::Fish => share_dir.join("fish").join("vendor_completions.d"),
+    }
+}
+
+/// Rebuild the share tree from the active versions, so it follows installs,
+/// removals and `switch`. Earlier package names win when two packages ship
+/// a file with the same name.
+pub fn refresh(packages: &HashMap<String, Package>) -> Result<()> {
+    let share_dir = get_share_dir();
+    if share_dir.exists() {
+        fs::remove_dir_all(&share_dir).context("Failed to clear the shared files directory")?;
+    }
+
+    let mut names: Vec<&String> = packages.keys().collect();
+    names.sort();
+    for name in names {
+        let package = &packages[name];
+        let active = match package.active_version.as_ref().and_then(|v| package.versions.get(v)) {
+            Some(active) => active,
+            None => continue,
+        };
+        for file in files_under(&active.install_path) {
+            if let Some(link) = link_for(&file) {
+                fs::create_dir_all(link.parent().unwrap())?;
+                if !link.exists() {
+                    debug!("Linking {}", file.display());
+                    std::os::unix::fs::symlink(&file, &link)
+                        .with_context(|| format!("Failed to link {}", link.display()))?;
+                }
+            }
+        }
+    }
+    // Desktop entries are shared by the whole session, so a prefix keeps out of them
+    if profile::prefix().is_some() {
+        return Ok(());
+    }
+    refresh_desktop_entries(packages)
+}
+
+pub fn get_applications_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    data_dir.join("applications")
+}
+
+/// Install the desktop entries of every active version as
+/// `updater-<package>-<entry>.desktop`, and remove the ones of packages that
+/// are gone.
+fn refresh_desktop_entries(packages: &HashMap<String, Package>) -> Result<()> {
+    let applications_dir = get_applications_dir();
+    for entry in fs::read_dir(&applications_dir).into_iter().flatten().flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with(DESKTOP_ENTRY_PREFIX) && file_name.ends_with(".desktop") {
+            fs::remove_file(entry.path()).with_context(|| format!("Failed to remove {}", entry.path().display()))?;
+        }
+    }
+
+    for (name, package) in packages {
+        let active = match package.active_version.as_ref().and_then(|v| package.versions.get(v)) {
+            Some(active) => active,
+            None => continue,
+        };
+        let files = files_under(&active.install_path);
+        for file in files.iter().filter(|file| file.extension().is_some_and(|ext| ext == "desktop")) {
+            let contents = match fs::read_to_string(file) {
+                Ok(contents) if contents.lines().any(|line| line.trim() == "Type=Application") => contents,
+                _ => continue,
+            };
+            let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+            let target = applications_dir.join(format!("{}{}-{}.desktop", DESKTOP_ENTRY_PREFIX, name.replace('/', "-"), stem));
+            debug!("Installing desktop entry {}", target.display());
+            fs::create_dir_all(&applications_dir)?;
+            fs::write(&target, rewrite_desktop_entry(&contents, package, active, &files))
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Point a packaged desktop entry's `Exec` and `TryExec` at updater's shims,
+/// and its `Icon` at the icon file shipped next to it.
+fn rewrite_desktop_entry(contents: &str, package: &Package, active: &PackageVersion, files: &[PathBuf]) -> String {
+    let bins: Vec<&str> = active.bin_paths.iter().filter_map(|bin| bin.file_name()?.to_str()).collect();
+    // Entries from AppImages launch `AppRun` or the app's internal name, which
+    // for a single-binary package can only mean that binary
+    let shim_for = |command: &str| {
+        let command_name = command.rsplit('/').next().unwrap_or(command);
+        let bin = bins.iter().find(|bin| **bin == command_name).or(if bins.len() == 1 { bins.first() } else { None })?;
+        Some(shim::get_shim_dir().join(shim::exposed_name(package, bin)))
+    };
+
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let rewritten = match line.split_once('=') {
+            Some((key, value)) if key.trim() == "Exec" || key.trim() == "TryExec" => {
+                let value = value.trim();
+                let (command, args) = match value.strip_prefix('"').and_then(|rest| rest.split_once('"')) {
+                    Some((command, args)) => (command, args),
+                    None => value.split_at(value.find(' ').unwrap_or(value.len())),
+                };
+                shim_for(command).map(|shim| format!("{}=\"{}\"{}", key.trim(), shim.display(), args))
+            }
+            Some((key, value)) if key.trim() == "Icon" && !value.trim().starts_with('/') => {
+                find_icon(value.trim(), files).map(|icon| format!("Icon={}", icon.display()))
+            }
+            _ => None,
+        };
+        lines.push(rewritten.unwrap_or_else(|| line.to_string()));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The best file for an icon name: a scalable one, otherwise the largest.
+fn find_icon<'a>(icon: &str, files: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let candidates = files.iter().filter(|file| {
+        file.file_stem().is_some_and(|stem| stem == icon)
+            && file.extension().is_some_and(|ext| ext == "png" || ext == "svg" || ext == "xpm")
+    });
+    candidates.max_by_key(|file| {
+        let scalable = file.extension().is_some_and(|ext| ext == "svg");
+        (scalable, fs::metadata(file).map(|m| m.len()).unwrap_or(0))
+    })
+}
+
+/// Regular files under an install dir, skipping the kept artifact and other hidden dirs.
+fn files_under(install_dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![install_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') => {
+                    dirs.push(entry.path())
+                }
+                Ok(file_type) if file_type.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Where in the share tree a packaged file belongs, if it is a man page or a completion.
+fn link_for(file: &Path) -> Option<PathBuf> {
+    let file_name = file.file_name()?.to_str()?;
+    if let Some(section) = man_section(file_name) {
+        return Some(get_man_dir().join(format!("man{}", section)).join(file_name));
+    }
+    let (shell, command) = completion(file)?;
+    let link_name = match shell {
+        Shell::Bash => command.to_string(),
+        Shell::Zsh => format!("_{}", command),
+        Shell::Fish => format!("{}.fish", command),
+    };
+    Some(get_completion_dir(shell).join(link_name))
+}
+
+/// The section of a man page file name like `rg.1` or `git-log.1.gz`.
+fn man_section(file_name: &str) -> Option<char> {
+    let name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+    let (stem, extension) = name.rsplit_once('.')?;
+    let mut chars = extension.chars();
+    match (chars.next(), chars.next()) {
+        (Some(section @ '1'..='9'), None) if !stem.is_empty() => Some(section),
+        _ => None,
+    }
+}
+
+/// The shell and command a completion file is for. Only files in a
+/// completion directory count, e.g. `complete/rg.bash`, `complete/_rg`,
+/// `completions/rg.fish` or `share/bash-completion/completions/rg`.
+fn completion(file: &Path) -> Option<(Shell, &str)> {
+    let parent = file.parent()?;
+    let in_completion_dir = parent.components().any(|component| {
+        let component = component.as_os_str().to_string_lossy().to_lowercase();
+        component.contains("complet") || component == "site-functions"
+    });
+    if !in_completion_dir {
+        return None;
+    }
+
+    let file_name = file.file_name()?.to_str()?;
+    let parent_name = parent.to_string_lossy().to_lowercase();
+    if let Some(command) = file_name.strip_suffix(".fish") {
+        Some((Shell::Fish, command))
+    } else if let Some(command) = file_name.strip_suffix(".bash") {
+        Some((Shell::Bash, command))
+    } else if let Some(command) = file_name.strip_prefix('_').map(|f| f.strip_suffix(".zsh").unwrap_or(f)) {
+        Some((Shell::Zsh, command))
+    } else if parent_name.contains("bash") && !file_name.contains('.') {
+        Some((Shell::Bash, file_name))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use colored::*;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::backends::sh_quote;
+use crate::package;
+use crate::share;
+use crate::shim;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Guess the user's shell from `$SHELL`.
+    pub fn detect() -> Result<Self> {
+        let shell = std::env::var("SHELL").unwrap_or_default();
+        match Path::new(&shell).file_name().and_then(|name| name.to_str()) {
+            Some("bash") => Ok(Shell::Bash),
+            Some("zsh") => Ok(Shell::Zsh),
+            Some("fish") => Ok(Shell::Fish),
+            _ => bail!("Could not detect shell from $SHELL, pass one of bash, zsh or fish"),
+        }
+    }
+
+    /// `value` as a single-quoted word the shell takes literally.
+    fn quote(&self, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => sh_quote(value),
+            Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+        }
+    }
+
+    fn quote_path(&self, path: &Path) -> String {
+        self.quote(&path.to_string_lossy())
+    }
+
+    fn export(&self, key: &str, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export {}={}", key, self.quote(value)),
+            Shell::Fish => format!("set -gx {} {}", key, self.quote(value)),
+        }
+    }
+
+    fn prepend_path(&self, key: &str, dir: &Path) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export {}={}:\"${}\"", key, self.quote_path(dir), key),
+            Shell::Fish => format!("set -gx {} {} ${}", key, self.quote_path(dir), key),
+        }
+    }
+
+    /// Statements making the shell's completion system find the managed completions.
+    fn add_completions(&self, dir: &Path) -> String {
+        match self {
+            Shell::Bash => {
+                // bash-completion loads completions on demand from XDG_DATA_DIRS/bash-completion/completions
+                let share_dir = dir.parent().and_then(Path::parent).unwrap_or(dir);
+                format!("export XDG_DATA_DIRS={}:\"${{XDG_DATA_DIRS:-/usr/local/share:/usr/share}}\"", self.quote_path(share_dir))
+            }
+            Shell::Zsh => format!("fpath=({} $fpath)", self.quote_path(dir)),
+            Shell::Fish => format!("set -gx fish_complete_path {} $fish_complete_path", self.quote_path(dir)),
+        }
+    }
+
+    /// The startup file `init --write` appends to.
+    fn rc_file(&self) -> Result<PathBuf> {
+        let home = dirs::home_dir().context("Could not determine home directory")?;
+        Ok(match self {
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => std::env::var_os("ZDOTDIR").map(PathBuf::from).unwrap_or(home).join(".zshrc"),
+            Shell::Fish => dirs::config_dir()
+                .context("Could not determine config directory")?
+                .join("fish")
+                .join("conf.d")
+                .join("updater.fish"),
+        })
+    }
+
+    /// Like `prepend_path`, but keeping the empty entry that makes `man`
+    /// still search the system pages when MANPATH was unset.
+    fn prepend_manpath(&self, dir: &Path) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => self.prepend_path("MANPATH", dir),
+            Shell::Fish => format!("set -q MANPATH; or set -gx MANPATH \"\"\n{}", self.prepend_path("MANPATH", dir)),
+        }
+    }
+}
+
+/// Shell statements that put the shims on PATH, the man pages on MANPATH and
+/// the completions where the shell looks, and export per-package variables.
+pub fn env_script(shell: Shell) -> Result<String> {
+    let packages = package::load_packages()?;
+    let mut lines = vec![shell.prepend_path("PATH", &shim::get_shim_dir())];
+    let man_dir = share::get_man_dir();
+    if man_dir.is_dir() {
+        lines.push(shell.prepend_manpath(&man_dir));
+    }
+    let completion_dir = share::get_completion_dir(shell);
+    if completion_dir.is_dir() {
+        lines.push(shell.add_completions(&completion_dir));
+    }
+
+    let mut vars: BTreeMap<&str, String> = BTreeMap::new();
+    for package in packages.values() {
+        let install_dir = package.active_version.as_ref().and_then(|v| package.versions.get(v)).map(|info| info.install_path.to_string_lossy());
+        for (key, value) in &package.env {
+            if !is_var_name(key) {
+                warn!("Not exporting {} for {}, it is not a valid variable name", key, package.name);
+                continue;
+            }
+            let value = match &install_dir {
+                Some(install_dir) => value.replace("{install_dir}", install_dir),
+                None if value.contains("{install_dir}") => continue,
+                None => value.clone(),
+            };
+            vars.insert(key, value);
+        }
+    }
+    for (key, value) in vars {
+        lines.push(shell.export(key, &value));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn is_var_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A `KEY=VALUE` pair as given to `install --env`.
+pub fn parse_var(entry: &str) -> Result<(String, String)> {
+    let (key, value) = entry.split_once('=').with_context(|| format!("{} is not KEY=VALUE", entry))?;
+    if !is_var_name(key) {
+        bail!("{} is not a valid variable name", key);
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+pub fn print_env(shell: Option<Shell>) -> Result<()> {
+    let shell = match shell {
+        Some(shell) => shell,
+        None => Shell::detect()?,
+    };
+    println!("{}", env_script(shell)?);
+    Ok(())
+}
+
+/// First line of the init snippet, used to tell whether it was already added.
+const INIT_MARKER: &str = "# Added by `updater init`";
+
+/// Startup file snippet that evaluates `updater env` on every shell start, so
+/// packages installed later are picked up, and loads the completions.
+pub fn init_script(shell: Shell) -> Result<String> {
+    let exe = std::env::current_exe().context("Could not determine the updater executable")?;
+    let lines = match shell {
+        Shell::Bash => vec![
+            format!("eval \"$({} env bash)\"", shell.quote_path(&exe)),
+            // Completions are only loaded on demand when bash-completion is set up
+            "if [ -z \"${BASH_COMPLETION_VERSINFO-}\" ] && [ -r /usr/share/bash-completion/bash_completion ]; then".to_string(),
+            "    . /usr/share/bash-completion/bash_completion".to_string(),
+            "fi".to_string(),
+        ],
+        Shell::Zsh => vec![
+            format!("eval \"$({} env zsh)\"", shell.quote_path(&exe)),
+            // compinit has to run after fpath is set to see the managed completions
+            "autoload -Uz compinit && compinit".to_string(),
+        ],
+        Shell::Fish => vec![format!("{} env fish | source", shell.quote_path(&exe))],
+    };
+    Ok(format!("{}\n{}\n", INIT_MARKER, lines.join("\n")))
+}
+
+/// Print the init snippet, or append it to the shell's startup file with `write`.
+pub fn init(shell: Option<Shell>, write: bool) -> Result<()> {
+    let shell = match shell {
+        Some(shell) => shell,
+        None => Shell::detect()?,
+    };
+    let script = init_script(shell)?;
+    if !write {
+        print!("{}", script);
+        return Ok(());
+    }
+
+    let rc_file = shell.rc_file()?;
+    let existing = fs::read_to_string(&rc_file).unwrap_or_default();
+    if existing.contains(INIT_MARKER) {
+        println!("{} {}", "Already set up in".yellow(), rc_file.display());
+        return Ok(());
+    }
+    if let Some(parent) = rc_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&rc_file)
+        .with_context(|| format!("Failed to open {}", rc_file.display()))?;
+    // Keep a blank line between the snippet and whatever is already there
+    let separator = match existing.chars().last() {
+        None => "",
+        Some('\n') => "\n",
+        Some(_) => "\n\n",
+    };
+    write!(file, "{}{}", separator, script).with_context(|| format!("Failed to write {}", rc_file.display()))?;
+    println!("{} {}", "Added updater to".green(), rc_file.display());
+    println!("Restart your shell or run: source {}", rc_file.display());
+    Ok(())
+}
@@ -0,0 +1,449 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+use tracing::{debug, info, warn};
+
+use crate::error::Error;
+use crate::package::{self, BinAlias, Package, PackageVersion};
+use crate::profile;
+
+/// Name of the per-project file mapping package names to versions.
+pub const VERSIONS_FILE: &str = ".updater-versions";
+
+/// Marker written into every shim so stale ones can be told apart from other files.
+const SHIM_MARKER: &str = "# updater shim";
+
+pub fn get_shim_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    profile::active_dir().unwrap_or_else(|| data_dir.join("updater")).join("shims")
+}
+
+/// Marker file whose mtime is when a shim last ran one of the package's binaries.
+fn usage_marker(name: &str) -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    let dir = profile::active_dir().unwrap_or_else(|| data_dir.join("updater")).join("used");
+    dir.join(name.replace('/', "%2F"))
+}
+
+/// Note that a shim is about to run `name`. Only touches the marker, so every
+/// invocation can afford it, and failures never keep the binary from running.
+fn record_use(name: &str) {
+    let marker = usage_marker(name);
+    let touched = marker
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::OpenOptions::new().create(true).truncate(false).write(true).open(&marker))
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(e) = touched {
+        debug!("Could not record use of {}: {}", name, e);
+    }
+}
+
+/// When a shim last ran one of the package's binaries, if one ever did.
+pub fn last_used(name: &str) -> Option<SystemTime> {
+    fs::metadata(usage_marker(name)).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Rewrite the shim directory so it has exactly one shim per managed binary.
+pub fn refresh(packages: &HashMap<String, Package>) -> Result<()> {
+    let shim_dir = get_shim_dir();
+    fs::create_dir_all(&shim_dir).context("Failed to create shim directory")?;
+
+    let wanted: BTreeMap<String, &str> = binaries(packages)
+        .into_iter()
+        .map(|(bin, owners)| (bin, owners[0]))
+        .collect();
+
+    // Drop shims for binaries that are no longer provided by any package
+    for entry in fs::read_dir(&shim_dir)?.flatten() {
+        let bin = entry.file_name().to_string_lossy().into_owned();
+        if !wanted.contains_key(&bin) && is_shim(&entry.path()) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    let updater = std::env::current_exe().context("Could not determine updater executable")?;
+    // Shims on an old PATH keep running the profile or prefix they were made for
+    let scope = match profile::prefix() {
+        Some(prefix) => format!("UPDATER_PREFIX=\"{}\"", prefix.display()),
+        None => format!("UPDATER_PROFILE=\"{}\"", profile::active()),
+    };
+    for bin in wanted.keys() {
+        let script = format!(
+            "#!/bin/sh\n{}\n{} exec \"{}\" shim-exec \"{}\" -- \"$@\"\n",
+            SHIM_MARKER,
+            scope,
+            updater.display(),
+            bin
+        );
+        let path = shim_dir.join(bin);
+        fs::write(&path, script).with_context(|| format!("Failed to write shim {}", path.display()))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
+
+/// Map every exposed binary name to the packages providing it, the one whose
+/// shim wins first: highest priority, then package name.
+pub fn binaries(packages: &HashMap<String, Package>) -> BTreeMap<String, Vec<&str>> {
+    let mut binaries: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (name, package) in packages {
+        for pkg_version in package.versions.values() {
+            for bin_path in &pkg_version.bin_paths {
+                if let Some(bin) = bin_path.file_name().and_then(|f| f.to_str()) {
+                    let owners = binaries.entry(exposed_name(package, bin).to_string()).or_default();
+                    if !owners.contains(&name.as_str()) {
+                        owners.push(name);
+                    }
+                }
+            }
+        }
+        for alias in package.aliases.keys() {
+            let owners = binaries.entry(alias.clone()).or_default();
+            if !owners.contains(&name.as_str()) {
+                owners.push(name);
+            }
+        }
+    }
+    for owners in binaries.values_mut() {
+        owners.sort_by_key(|owner| (std::cmp::Reverse(packages[*owner].priority), *owner));
+    }
+    binaries
+}
+
+/// The shim name a package's binary is exposed under.
+pub fn exposed_name<'a>(package: &'a Package, bin: &'a str) -> &'a str {
+    package.renamed_bins.get(bin).map(String::as_str).unwrap_or(bin)
+}
+
+/// Binary names more than one package wants to expose, winner first.
+pub fn conflicts(packages: &HashMap<String, Package>) -> BTreeMap<String, Vec<&str>> {
+    binaries(packages).into_iter().filter(|(_, owners)| owners.len() > 1).collect()
+}
+
+/// Warn about every conflict `name` is part of, saying which package wins
+/// and how to change that.
+pub fn warn_conflicts(packages: &HashMap<String, Package>, name: &str) {
+    for (bin, owners) in conflicts(packages) {
+        if !owners.contains(&name) {
+            continue;
+        }
+        let others: Vec<&str> = owners.iter().copied().filter(|owner| *owner != name).collect();
+        let package = &packages[name];
+        let own_bin = package
+            .versions
+            .values()
+            .flat_map(|v| &v.bin_paths)
+            .filter_map(|path| path.file_name().and_then(|f| f.to_str()))
+            .find(|file| exposed_name(package, file) == bin)
+            .unwrap_or(&bin);
+        warn!(
+            "{} {} {} {} {}",
+            bin.yellow().bold(),
+            "is provided by both".yellow(),
+            name.yellow(),
+            format!("and {};", others.join(", ")).yellow(),
+            format!("the shim runs {}'s", owners[0]).yellow()
+        );
+        info!(
+            "Change it with `updater prefer {}` or `updater rename-bin {} {} <new name>`",
+            if owners[0] == name { others[0] } else { name },
+            name,
+            own_bin
+        );
+    }
+}
+
+/// List every binary name conflict and which package wins it.
+pub fn print_conflicts() -> Result<()> {
+    let packages = package::load_packages()?;
+    let conflicts = conflicts(&packages);
+    if conflicts.is_empty() {
+        println!("{}", "No binary name conflicts".green());
+        return Ok(());
+    }
+    for (bin, owners) in conflicts {
+        println!("{} {} {}", bin.yellow().bold(), owners[0].green(), format!("(shadows {})", owners[1..].join(", ")).dimmed());
+    }
+    Ok(())
+}
+
+/// Make `name` win every binary name conflict it is part of.
+pub fn prefer(name: &str) -> Result<()> {
+    let mut packages = package::load_packages()?;
+    if !packages.contains_key(name) {
+        return Err(package::package_not_found(name, &packages));
+    }
+    let rivals: Vec<String> = conflicts(&packages)
+        .into_values()
+        .filter(|owners| owners.contains(&name))
+        .flatten()
+        .filter(|owner| *owner != name)
+        .map(str::to_string)
+        .collect();
+    let highest = rivals.iter().map(|rival| packages[rival].priority).max().unwrap_or(0);
+
+    let package = packages.get_mut(name).unwrap();
+    package.priority = package.priority.max(highest + 1);
+    package::save_packages(&packages)?;
+    info!("{} {} {}", "Shims now prefer".green(), name.yellow().bold(), format!("(priority {})", packages[name].priority).dimmed());
+    Ok(())
+}
+
+/// Also expose one of a package's binaries as `alias`, running `version` if
+/// given and whatever version is active otherwise. `remove` drops the alias.
+pub fn alias(name: &str, version: Option<&str>, bin: &str, alias: &str, remove: bool) -> Result<()> {
+    let mut packages = package::load_packages()?;
+    let package = match packages.get_mut(name) {
+        Some(package) => package,
+        None => return Err(package::package_not_found(name, &packages)),
+    };
+    if remove {
+        if package.aliases.remove(alias).is_none() {
+            bail!("{} has no alias {}", name, alias);
+        }
+        package::save_packages(&packages)?;
+        info!("{} {}", "Removed alias".green(), alias.cyan());
+        return Ok(());
+    }
+
+    if alias.is_empty() || alias.contains('/') {
+        bail!("{:?} is not a valid binary name", alias);
+    }
+    let versions: Vec<&PackageVersion> = match version {
+        Some(version) => vec![package.versions.get(version).ok_or_else(|| Error::VersionNotFound {
+            package: name.to_string(),
+            version: version.to_string(),
+        })?],
+        None => package.versions.values().collect(),
+    };
+    let provides = versions
+        .iter()
+        .flat_map(|v| &v.bin_paths)
+        .any(|path| path.file_name().and_then(|f| f.to_str()) == Some(bin));
+    if !provides {
+        bail!("{} does not provide a binary named {}", name, bin);
+    }
+
+    package.aliases.insert(alias.to_string(), BinAlias { bin: bin.to_string(), version: version.map(str::to_string) });
+    package::save_packages(&packages)?;
+    let target = match version {
+        Some(version) => format!("{}'s {} {}", name, bin, version),
+        None => format!("{}'s {}", name, bin),
+    };
+    info!("{} {} {} {}", "Exposing".green(), target.yellow().bold(), "as".green(), alias.cyan());
+    warn_conflicts(&packages, name);
+    Ok(())
+}
+
+/// Expose one of a package's binaries under another shim name, or under its
+/// own name again when `new_name` is the binary's name.
+pub fn rename_bin(name: &str, bin: &str, new_name: &str) -> Result<()> {
+    let mut packages = package::load_packages()?;
+    if !packages.contains_key(name) {
+        return Err(package::package_not_found(name, &packages));
+    }
+    let package = packages.get_mut(name).unwrap();
+    let provides = package
+        .versions
+        .values()
+        .flat_map(|v| &v.bin_paths)
+        .any(|path| path.file_name().and_then(|f| f.to_str()) == Some(bin));
+    if !provides {
+        bail!("{} does not provide a binary named {}", name, bin);
+    }
+    if new_name.is_empty() || new_name.contains('/') {
+        bail!("{:?} is not a valid binary name", new_name);
+    }
+
+    if new_name == bin {
+        package.renamed_bins.remove(bin);
+    } else {
+        package.renamed_bins.insert(bin.to_string(), new_name.to_string());
+    }
+    package::save_packages(&packages)?;
+    info!("{} {} {} {}", "Exposing".green(), format!("{}'s {}", name, bin).yellow().bold(), "as".green(), new_name.cyan());
+    warn_conflicts(&packages, name);
+    Ok(())
+}
+
+fn is_shim(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| content.contains(SHIM_MARKER))
+}
+
+/// Find the nearest versions file from `dir` upwards.
+pub fn find_versions_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(VERSIONS_FILE))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn read_versions_file(path: &Path) -> Result<BTreeMap<String, String>> {
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Version of `name` requested by the project enclosing `dir`, if any.
+pub fn project_version(dir: &Path, name: &str) -> Result<Option<String>> {
+    match find_versions_file(dir) {
+        Some(path) => Ok(read_versions_file(&path)?.remove(name)),
+        None => Ok(None),
+    }
+}
+
+/// Pin a package version for the current directory.
+pub fn set_local(name: &str, version: &str) -> Result<()> {
+    let packages = package::load_packages()?;
+    match packages.get(name) {
+        Some(package) if !package.versions.contains_key(version) => {
+            warn!("{} {} {}", "version".yellow(), version.cyan(), "is not installed yet".yellow());
+        }
+        None => warn!("{} {}", name.yellow().bold(), "is not installed yet".yellow()),
+        _ => {}
+    }
+
+    let path = std::env::current_dir()?.join(VERSIONS_FILE);
+    let mut versions = if path.exists() { read_versions_file(&path)? } else { BTreeMap::new() };
+    versions.insert(name.to_string(), version.to_string());
+
+    let data = toml::to_string(&versions).context("Failed to serialize project versions")?;
+    fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("{} {} {} {}", "Pinned".green(), name.yellow().bold(), "to version".green(), version.cyan());
+    info!("in {}", path.display());
+    Ok(())
+}
+
+/// The installed binary a shim runs, and why that version was picked.
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub package: String,
+    pub version: String,
+    pub path: PathBuf,
+    /// Versions file that selected `version` instead of the active version
+    pub pinned_by: Option<PathBuf>,
+    /// Other packages that provide a binary with the same name
+    pub also_provided_by: Vec<String>,
+}
+
+/// Find the binary the shim for `bin` would run from `dir`.
+pub fn resolve(packages: &HashMap<String, Package>, bin: &str, dir: &Path) -> Result<Resolved> {
+    let owners = binaries(packages).remove(bin).unwrap_or_default();
+    let name = match owners.first() {
+        Some(name) => *name,
+        None => bail!("{} is not provided by any installed package", bin),
+    };
+    let package = &packages[name];
+    let alias = package.aliases.get(bin);
+
+    let versions_file = find_versions_file(dir);
+    let pinned = match &versions_file {
+        Some(path) => read_versions_file(path)?.remove(name),
+        None => None,
+    };
+    let (version, pinned_by) = match (alias.and_then(|alias| alias.version.clone()), pinned) {
+        // An alias for one version runs it wherever it is called from
+        (Some(version), _) => (version, None),
+        (None, Some(version)) if package.versions.contains_key(&version) => (version, versions_file),
+        (None, Some(version)) => bail!(
+            "{} {} is requested by {} but not installed, run `updater install {} --version {}`",
+            name,
+            version,
+            VERSIONS_FILE,
+            name,
+            version
+        ),
+        (None, None) => (package.active_version.clone().with_context(|| format!("{} has no active version", name))?, None),
+    };
+
+    let pkg_version = package.versions.get(&version).with_context(|| format!("{} {} is not installed", name, version))?;
+    let wanted = |file: &str| match alias {
+        Some(alias) => file == alias.bin,
+        None => exposed_name(package, file) == bin,
+    };
+    let path = pkg_version
+        .bin_paths
+        .iter()
+        .find(|path| path.file_name().and_then(|f| f.to_str()).is_some_and(wanted))
+        .with_context(|| format!("{} {} does not provide {}", name, version, bin))?
+        .clone();
+
+    Ok(Resolved {
+        package: name.to_string(),
+        version,
+        path,
+        pinned_by,
+        also_provided_by: owners[1..].iter().map(|owner| owner.to_string()).collect(),
+    })
+}
+
+/// Resolve the binary a shim stands for and replace this process with it.
+pub fn exec(bin: &str, args: &[String]) -> Result<()> {
+    let packages = package::load_packages()?;
+    let resolved = resolve(&packages, bin, &std::env::current_dir()?)?;
+    record_use(&resolved.package);
+    let target = resolved.path;
+
+    let err = Command::new(&target).args(args).exec();
+    Err(err).with_context(|| format!("Failed to execute {}", target.display()))
+}
+
+/// Print which package and version a binary, shim or installed file belongs
+/// to, and whether something else earlier on PATH shadows it.
+pub fn which(query: &str) -> Result<()> {
+    let packages = package::load_packages()?;
+
+    // A path names either a shim or a binary inside an install directory
+    let bin = if query.contains('/') {
+        let path = Path::new(query);
+        if !is_shim(path) {
+            let canonical = fs::canonicalize(path).with_context(|| format!("{} does not exist", query))?;
+            for (name, package) in &packages {
+                for (version, pkg_version) in &package.versions {
+                    if pkg_version.bin_paths.iter().any(|bin| fs::canonicalize(bin).is_ok_and(|bin| bin == canonical)) {
+                        println!("{} {} {}", query, "is provided by".dimmed(), format!("{} {}", name, version).yellow().bold());
+                        return Ok(());
+                    }
+                }
+            }
+            bail!("{} is not provided by any installed package", query);
+        }
+        path.file_name().and_then(|f| f.to_str()).context("Shim path has no file name")?.to_string()
+    } else {
+        query.to_string()
+    };
+
+    let resolved = resolve(&packages, &bin, &std::env::current_dir()?)?;
+    println!(
+        "{} {} {} {}",
+        bin.bold(),
+        "->".dimmed(),
+        format!("{} {}", resolved.package, resolved.version).yellow().bold(),
+        resolved.path.display()
+    );
+    if let Some(versions_file) = &resolved.pinned_by {
+        println!("  {} {}", "pinned by".dimmed(), versions_file.display());
+    }
+    if !resolved.also_provided_by.is_empty() {
+        println!("  {} {}", "also provided by".dimmed(), resolved.also_provided_by.join(", "));
+    }
+
+    let shim = get_shim_dir().join(&bin);
+    match which::which(&bin) {
+        Ok(found) if found == shim || found == resolved.path => {}
+        Ok(found) => warn!(
+            "{} {} {}",
+            found.display().to_string().yellow(),
+            "comes first on PATH and shadows the updater-managed".yellow(),
+            bin.yellow().bold()
+        ),
+        Err(_) => warn!("{} {}", get_shim_dir().display().to_string().yellow(), "is not on PATH".yellow()),
+    }
+    Ok(())
+}
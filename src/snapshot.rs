@@ -0,0 +1,214 @@
+//! Named snapshots of the package database that the installed state can be
+//! reconciled back to.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::error::Error;
+use crate::package::{self, InstallOptions, Package, PackageVersion};
+use crate::prompt;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub label: String,
+    pub created: String,
+    pub packages: BTreeMap<String, Package>,
+}
+
+/// One change `restore` makes to get back to a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Install { name: String, version: String },
+    Switch { name: String, version: String },
+    RemoveVersion { name: String, version: String },
+    RemovePackage { name: String },
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Install { name, version } => write!(f, "install {} {}", name, version),
+            Step::Switch { name, version } => write!(f, "switch {} to {}", name, version),
+            Step::RemoveVersion { name, version } => write!(f, "remove {} {}", name, version),
+            Step::RemovePackage { name } => write!(f, "remove {}", name),
+        }
+    }
+}
+
+/// Next to the package database, so every profile and prefix has its own.
+pub fn get_snapshot_dir() -> PathBuf {
+    package::get_package_db_path().with_file_name("snapshots")
+}
+
+fn snapshot_path(label: &str) -> Result<PathBuf> {
+    if label.is_empty() || label.contains('/') || label.starts_with('.') {
+        bail!("Invalid snapshot label {:?}", label);
+    }
+    Ok(get_snapshot_dir().join(format!("{}.json", label)))
+}
+
+pub fn load(label: &str) -> Result<Snapshot> {
+    let path = snapshot_path(label)?;
+    if !path.exists() {
+        bail!("Snapshot {} not found, see `updater snapshot list`", label);
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Record every package, version and active selection under `label`.
+pub fn create(label: &str) -> Result<()> {
+    let path = snapshot_path(label)?;
+    if path.exists() && !prompt::confirm(&format!("Replace existing snapshot {}?", label))? {
+        return Err(Error::Aborted(format!("Aborted creating snapshot {}", label)).into());
+    }
+    let snapshot = Snapshot {
+        label: label.to_string(),
+        created: chrono::Local::now().to_rfc3339(),
+        packages: package::load_packages()?.into_iter().collect(),
+    };
+    fs::create_dir_all(get_snapshot_dir())?;
+    fs::write(&path, serde_json::to_string_pretty(&snapshot)?).with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("{} {} {}", "Created snapshot".green(), label.yellow().bold(), format!("of {} package(s)", snapshot.packages.len()));
+    Ok(())
+}
+
+pub fn list() -> Result<()> {
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(get_snapshot_dir()).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if let Some(label) = path.file_stem().and_then(|s| s.to_str()).filter(|_| path.extension().is_some_and(|e| e == "json")) {
+            snapshots.push(load(label)?);
+        }
+    }
+    if snapshots.is_empty() {
+        println!("No snapshots");
+        return Ok(());
+    }
+    snapshots.sort_by(|a, b| a.created.cmp(&b.created));
+    for snapshot in snapshots {
+        println!("{} {} ({} packages)", snapshot.label.yellow().bold(), snapshot.created.dimmed(), snapshot.packages.len());
+    }
+    Ok(())
+}
+
+/// What it takes to get from the installed packages back to `snapshot`.
+pub fn plan(snapshot: &Snapshot) -> Result<Vec<Step>> {
+    let current = package::load_packages()?;
+    let mut steps = Vec::new();
+    for (name, wanted) in &snapshot.packages {
+        let installed = current.get(name);
+        let mut versions: Vec<&String> = wanted.versions.keys().collect();
+        versions.sort();
+        for version in versions {
+            if !installed.is_some_and(|p| p.versions.contains_key(version)) {
+                steps.push(Step::Install { name: name.clone(), version: version.clone() });
+            }
+        }
+        if let Some(active) = &wanted.active_version {
+            if installed.and_then(|p| p.active_version.as_ref()) != Some(active) {
+                steps.push(Step::Switch { name: name.clone(), version: active.clone() });
+            }
+        }
+        if let Some(installed) = installed {
+            let mut extra: Vec<&String> = installed.versions.keys().filter(|v| !wanted.versions.contains_key(*v)).collect();
+            extra.sort();
+            steps.extend(extra.into_iter().map(|version| Step::RemoveVersion { name: name.clone(), version: version.clone() }));
+        }
+    }
+    let mut gone: Vec<&String> = current.keys().filter(|name| !snapshot.packages.contains_key(*name)).collect();
+    gone.sort();
+    steps.extend(gone.into_iter().map(|name| Step::RemovePackage { name: name.clone() }));
+    Ok(steps)
+}
+
+/// Install a version the way it was installed when the snapshot was taken.
+fn install_recorded(name: &str, package: &Package, version: &str, info: &PackageVersion) -> Result<()> {
+    if let Some(file) = &info.local_file {
+        if !file.is_file() {
+            bail!("{} is gone, install {} {} again from its package file", file.display(), name, version);
+        }
+        return package::install_file(file, false);
+    }
+    let options = InstallOptions {
+        version: Some(version.to_string()).filter(|v| v != "latest"),
+        user: !package.system,
+        backend: info.package_manager.clone().filter(|_| info.source.is_none() && info.url.is_none() && info.image.is_none()),
+        source: info.source.clone(),
+        url: info.url.clone(),
+        image: info.image.clone(),
+        arch: info.arch.clone(),
+        env: package.env.clone(),
+        ..Default::default()
+    };
+    package::install(name, options).map(|_| ())
+}
+
+/// Install, switch and remove until the installed packages match the snapshot,
+/// then restore per-package settings like channels and priorities.
+pub fn restore(label: &str, dry_run: bool) -> Result<()> {
+    let snapshot = load(label)?;
+    let steps = plan(&snapshot)?;
+    if steps.is_empty() {
+        println!("{} {}", "Already matches snapshot".green(), label.yellow().bold());
+        return Ok(());
+    }
+    for step in &steps {
+        println!("  {}", step);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !prompt::confirm(&format!("Apply {} change(s) to restore snapshot {}?", steps.len(), label))? {
+        return Err(Error::Aborted(format!("Aborted restoring snapshot {}", label)).into());
+    }
+    // The steps were confirmed as a whole
+    prompt::set_assume_yes();
+
+    let mut failed = 0;
+    for step in &steps {
+        let result = match step {
+            Step::Install { name, version } => {
+                let package = &snapshot.packages[name];
+                install_recorded(name, package, version, &package.versions[version])
+            }
+            Step::Switch { name, version } => package::switch(name, version),
+            // Removing for a snapshot is deliberate, even if something still needs it
+            Step::RemoveVersion { name, version } => package::remove_package(name, Some(version.clone()), false, true, false),
+            Step::RemovePackage { name } => package::remove_package(name, None, false, true, false),
+        };
+        if let Err(e) = result {
+            warn!("{} {}: {:#}", "Failed to".red(), step, e);
+            failed += 1;
+        }
+    }
+
+    let mut packages = package::load_packages()?;
+    for (name, wanted) in &snapshot.packages {
+        if let Some(package) = packages.get_mut(name) {
+            package.reason = wanted.reason;
+            package.channel = wanted.channel;
+            package.constraint = wanted.constraint.clone();
+            package.env = wanted.env.clone();
+            package.priority = wanted.priority;
+            package.renamed_bins = wanted.renamed_bins.clone();
+            package.aliases = wanted.aliases.clone();
+            package.policy = wanted.policy;
+            package.max_frequency = wanted.max_frequency;
+            package.auto_update = wanted.auto_update;
+            package.skipped_versions = wanted.skipped_versions.clone();
+        }
+    }
+    package::save_packages(&packages)?;
+
+    if failed > 0 {
+        bail!("{} of {} step(s) failed, rerun `updater snapshot restore {}` to retry", failed, steps.len(), label);
+    }
+    info!("{} {}", "Restored snapshot".green(), label.yellow().bold());
+    Ok(())
+}
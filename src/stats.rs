@@ -0,0 +1,144 @@
+//! Summaries of the local package database and shim usage. Everything is
+//! computed from files on this machine; nothing is fetched or sent anywhere.
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use colored::*;
+use indicatif::HumanBytes;
+use std::collections::BTreeMap;
+
+use crate::du;
+use crate::package::{self, Package};
+use crate::shim;
+
+/// How many packages the most and least recently used lists show.
+const USED_LIMIT: usize = 5;
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub user: usize,
+    pub system: usize,
+    /// Packages by the backend of their active version
+    pub backends: BTreeMap<String, usize>,
+    /// Package versions installed, including by updates, in the last 30 and 365 days
+    pub installed_30_days: usize,
+    pub installed_365_days: usize,
+    /// Packages updated in the last 30 days
+    pub updated_30_days: usize,
+    /// Packages last run through a shim, most recently used first, with the days since
+    pub used: Vec<(String, i64)>,
+    /// Packages with binaries that no shim ran since tracking started
+    pub never_used: Vec<String>,
+    /// Bytes on disk today, by the month the versions holding them were installed
+    pub size_by_month: BTreeMap<String, u64>,
+}
+
+fn parse_date(date: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(date).ok().map(|date| date.with_timezone(&Local))
+}
+
+/// Gather the statistics for `packages`.
+pub fn collect(packages: &[Package]) -> Stats {
+    let now = Local::now();
+    let mut stats = Stats::default();
+    for package in packages {
+        if package.system {
+            stats.system += 1;
+        } else {
+            stats.user += 1;
+        }
+        let backend = package
+            .active_version
+            .as_ref()
+            .and_then(|v| package.versions.get(v))
+            .and_then(|v| v.package_manager.clone())
+            .unwrap_or_else(|| "unknown".to_string());
+        *stats.backends.entry(backend).or_default() += 1;
+        if package.last_updated.as_deref().and_then(parse_date).is_some_and(|date| (now - date).num_days() < 30) {
+            stats.updated_30_days += 1;
+        }
+
+        for info in package.versions.values() {
+            let installed = parse_date(&info.install_date);
+            if let Some(installed) = installed {
+                let age = (now - installed).num_days();
+                stats.installed_30_days += usize::from(age < 30);
+                stats.installed_365_days += usize::from(age < 365);
+            }
+            let month = installed.map_or_else(|| "unknown".to_string(), |date| date.format("%Y-%m").to_string());
+            let size = info.size.unwrap_or_else(|| du::dir_size(&info.install_path));
+            *stats.size_by_month.entry(month).or_default() += size;
+        }
+
+        if package.versions.values().any(|v| !v.bin_paths.is_empty()) {
+            match shim::last_used(&package.name) {
+                Some(used) => stats.used.push((package.name.clone(), (now - DateTime::<Local>::from(used)).num_days())),
+                None => stats.never_used.push(package.name.clone()),
+            }
+        }
+    }
+    stats.used.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    stats.never_used.sort();
+    stats
+}
+
+fn days_ago(days: i64) -> String {
+    match days {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+/// Print a summary of what is installed, how often it changes and what gets used.
+pub fn stats() -> Result<()> {
+    let packages = package::installed(false, false)?;
+    let stats = collect(&packages);
+
+    println!("{} {} ({} user, {} system)", "Packages:".bold(), stats.user + stats.system, stats.user, stats.system);
+    let mut backends: Vec<(&String, &usize)> = stats.backends.iter().collect();
+    backends.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    for (backend, count) in backends {
+        println!("  {:<12} {}", backend.cyan(), count);
+    }
+
+    println!();
+    println!("{}", "Updates:".bold());
+    println!("  {} versions installed in the last 30 days, {} in the last year", stats.installed_30_days, stats.installed_365_days);
+    println!("  {} packages updated in the last 30 days", stats.updated_30_days);
+
+    if !stats.used.is_empty() || !stats.never_used.is_empty() {
+        println!();
+        if !stats.used.is_empty() {
+            println!("{}", "Most recently used:".bold());
+        }
+        for (name, days) in stats.used.iter().take(USED_LIMIT) {
+            println!("  {:<24} {}", name.yellow(), days_ago(*days).dimmed());
+        }
+        // Without overlapping the most recently used ones
+        let least = stats.used.len().saturating_sub(USED_LIMIT).min(USED_LIMIT);
+        if least > 0 {
+            println!("{}", "Least recently used:".bold());
+            for (name, days) in stats.used.iter().rev().take(least) {
+                println!("  {:<24} {}", name.yellow(), days_ago(*days).dimmed());
+            }
+        }
+        if !stats.never_used.is_empty() {
+            println!("{} {}", "Never run through a shim:".bold(), stats.never_used.join(", "));
+        }
+    }
+
+    if !stats.size_by_month.is_empty() {
+        println!();
+        println!("{}", "Disk usage by install month:".bold());
+        let mut total = 0;
+        for (month, size) in &stats.size_by_month {
+            total += size;
+            println!("  {}  {:>10}  {:>10}", month, HumanBytes(*size).to_string(), HumanBytes(total).to_string().dimmed());
+        }
+    }
+
+    println!();
+    println!("{}", "Computed from local data only, nothing was sent anywhere".dimmed());
+    Ok(())
+}
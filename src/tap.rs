@@ -0,0 +1,218 @@
+//! Taps: git repositories of recipes (see `backends/recipe.rs`), so packaging
+//! knowledge can be shared without changes to updater itself.
+//!
+//! A tap is a repository of kind `tap`, checked out under the taps dir.
+//! Recipes are read from its `recipes/` directory, or from its top level when
+//! it has none. Every fetch indexes the recipes for search, and taps fetched
+//! longer than a day ago are pulled again before searching.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::backends::{recipe, run_command};
+use crate::config::{self, RepoKind, Repository};
+use crate::error::Error;
+use crate::repo;
+use crate::system::SearchResult;
+
+/// Hours after which a tap is pulled again before searching.
+const REFRESH_HOURS: i64 = 24;
+
+/// Recipes of a tap as of its last fetch.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    updated: String,
+    recipes: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    name: String,
+    description: String,
+}
+
+impl Index {
+    fn is_stale(&self) -> bool {
+        match chrono::DateTime::parse_from_rfc3339(&self.updated) {
+            Ok(updated) => chrono::Local::now().signed_duration_since(updated) >= chrono::Duration::hours(REFRESH_HOURS),
+            Err(_) => true,
+        }
+    }
+}
+
+pub fn taps_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not determine data directory");
+    data_dir.join("updater").join("taps")
+}
+
+fn checkout(name: &str) -> PathBuf {
+    taps_dir().join(name)
+}
+
+fn index_path(name: &str) -> PathBuf {
+    taps_dir().join(format!("{}.index.json", name))
+}
+
+fn recipes_in(name: &str) -> PathBuf {
+    let checkout = checkout(name);
+    let recipes = checkout.join("recipes");
+    if recipes.is_dir() {
+        recipes
+    } else {
+        checkout
+    }
+}
+
+/// The git URL for a tap given as `github.com/org/repo`, which is cloned
+/// over HTTPS. URLs with a scheme, scp-like addresses and paths stay as they are.
+pub fn clone_url(url: &str) -> String {
+    let is_path = url.starts_with('/') || url.starts_with('.');
+    if is_path || url.contains("://") || url.contains('@') {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    }
+}
+
+/// A name for the tap at `url`, from its owner and repository: `org-repo`.
+pub fn default_name(url: &str) -> String {
+    let path = url.rsplit_once("://").map_or(url, |(_, rest)| rest).trim_end_matches('/').trim_end_matches(".git");
+    let segments: Vec<&str> = path.split(['/', ':']).filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        [.., owner, repo] if segments.len() > 2 => format!("{}-{}", owner, repo),
+        [.., repo] => repo.to_string(),
+        [] => "tap".to_string(),
+    }
+}
+
+fn taps() -> Vec<Repository> {
+    config::load_config().map(|config| config.repos_of(RepoKind::Tap)).unwrap_or_default()
+}
+
+/// Recipe directories of every fetched tap, highest priority first.
+pub fn recipe_dirs() -> Vec<PathBuf> {
+    taps().iter().map(|tap| recipes_in(&tap.name)).filter(|dir| dir.is_dir()).collect()
+}
+
+fn load_index(name: &str) -> Option<Index> {
+    let data = fs::read_to_string(index_path(name)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+fn reindex(name: &str) -> Result<usize> {
+    let recipes: Vec<IndexEntry> = recipe::describe(&recipes_in(name))
+        .into_iter()
+        .map(|result| IndexEntry { name: result.name, description: result.description })
+        .collect();
+    let count = recipes.len();
+    let index = Index { updated: chrono::Local::now().to_rfc3339(), recipes };
+    fs::write(index_path(name), serde_json::to_string_pretty(&index)?).context("Failed to write tap index")?;
+    Ok(count)
+}
+
+/// Clone the tap, or bring an existing checkout in line with its remote,
+/// and index its recipes. Returns how many it has.
+pub fn fetch(tap: &Repository) -> Result<usize> {
+    if tap.name.is_empty() || tap.name.starts_with('.') || tap.name.contains('/') {
+        bail!("{} can't be used as a tap name", tap.name);
+    }
+    let checkout = checkout(&tap.name);
+    if checkout.join(".git").is_dir() {
+        // Taps aren't edited locally, so whatever the remote has wins, force pushes included
+        run_command(Command::new("git").arg("-C").arg(&checkout).args(["fetch", "--depth", "1", "origin"]))
+            .with_context(|| format!("Failed to fetch tap {}", tap.name))?;
+        run_command(Command::new("git").arg("-C").arg(&checkout).args(["reset", "--hard", "FETCH_HEAD"]))
+            .with_context(|| format!("Failed to update tap {}", tap.name))?;
+    } else {
+        fs::create_dir_all(taps_dir())?;
+        run_command(Command::new("git").args(["clone", "--depth", "1", &tap.url]).arg(&checkout))
+            .with_context(|| format!("Failed to clone tap {} from {}", tap.name, tap.url))?;
+    }
+    reindex(&tap.name)
+}
+
+/// Pull the named tap, or every tap, and reindex it.
+pub fn update(name: Option<&str>) -> Result<()> {
+    let taps: Vec<Repository> = taps().into_iter().filter(|tap| name.is_none_or(|name| tap.name == name)).collect();
+    if let Some(name) = name.filter(|_| taps.is_empty()) {
+        return Err(Error::RepositoryNotFound(name.to_string()).into());
+    }
+    let mut failed = 0;
+    for tap in &taps {
+        match fetch(tap) {
+            Ok(count) => info!("{} {} ({} recipes)", "Updated tap".green(), tap.name.yellow().bold(), count),
+            Err(e) => {
+                warn!("{:#}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} tap(s) failed to update", failed, taps.len());
+    }
+    Ok(())
+}
+
+/// Pull taps whose last fetch is older than a day, only warning when that fails.
+pub fn refresh_stale() {
+    for tap in taps() {
+        if load_index(&tap.name).is_some_and(|index| !index.is_stale()) {
+            continue;
+        }
+        if let Err(e) = fetch(&tap) {
+            warn!("Using tap {} as last fetched: {:#}", tap.name, e);
+        }
+    }
+}
+
+/// Unregister the tap `name`, leaving other kinds of repositories alone.
+pub fn remove(name: &str) -> Result<()> {
+    if !taps().iter().any(|tap| tap.name == name) {
+        return Err(Error::RepositoryNotFound(name.to_string()).into());
+    }
+    repo::remove(name)
+}
+
+/// Delete the checkout and index of a tap that was removed.
+pub fn remove_checkout(name: &str) -> Result<()> {
+    let checkout = checkout(name);
+    if checkout.exists() {
+        fs::remove_dir_all(&checkout).with_context(|| format!("Failed to remove {}", checkout.display()))?;
+    }
+    let index = index_path(name);
+    if index.exists() {
+        fs::remove_file(index)?;
+    }
+    Ok(())
+}
+
+/// Indexed recipes of every tap, highest priority first.
+pub fn indexed() -> Vec<SearchResult> {
+    taps()
+        .iter()
+        .filter_map(|tap| load_index(&tap.name))
+        .flat_map(|index| index.recipes)
+        .map(|entry| SearchResult { name: entry.name, description: entry.description })
+        .collect()
+}
+
+pub fn list() -> Result<()> {
+    let taps = taps();
+    if taps.is_empty() {
+        println!("{}", "No taps added".yellow());
+        return Ok(());
+    }
+    for tap in taps {
+        let (count, updated) = match load_index(&tap.name) {
+            Some(index) => (index.recipes.len().to_string(), index.updated),
+            None => ("?".to_string(), "never".to_string()),
+        };
+        println!("{} {} ({} recipes, fetched {})", tap.name.green().bold(), tap.url, count, updated);
+    }
+    Ok(())
+}
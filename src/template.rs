@@ -0,0 +1,105 @@
+use anyhow::{bail, Result};
+
+/// A `--format` template like `{name}\t{active_version}`. `\t`, `\n` and `\\`
+/// are unescaped so templates work inside single quotes, and `{{`/`}}` are
+/// literal braces.
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+impl Template {
+    /// Parse `spec`, rejecting placeholders that aren't in `fields`.
+    pub fn parse(spec: &str, fields: &[&str]) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = spec.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => text.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => bail!("Unclosed {{ in format {:?}", spec),
+                        }
+                    }
+                    if !fields.contains(&field.as_str()) {
+                        bail!("Unknown format field {{{}}}, expected one of: {}", field, fields.join(", "));
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => bail!("Unmatched }} in format {:?}, write }}}} for a literal brace", spec),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Fill in every placeholder with `value(field)`.
+    pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => value(field),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &[&str] = &["name", "version"];
+
+    fn render(spec: &str) -> String {
+        Template::parse(spec, FIELDS).unwrap().render(|field| format!("<{}>", field))
+    }
+
+    #[test]
+    fn fields_and_escapes_render() {
+        assert_eq!(render("{name}\\t{version}\\n"), "<name>\t<version>\n");
+        assert_eq!(render("{{{name}}} \\\\ \\x"), "{<name>} \\ \\x");
+        assert_eq!(render("plain"), "plain");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn bad_placeholders_are_rejected() {
+        for spec in ["{size}", "{name", "name}", "{}"] {
+            assert!(Template::parse(spec, FIELDS).is_err(), "{:?} accepted", spec);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File};
+use std::io::Read;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use crate::privilege;
+
+/// A package version being installed. Relocatable installs go into a staging
+/// dir next to their final location, or in the temp dir when the prefix
+/// belongs to root, and are only moved into place by `commit`; anything not
+/// committed is cleaned up when dropped, so a failed install leaves neither
+/// files nor database entries behind.
+pub struct StagedInstall {
+    target: PathBuf,
+    staging: Option<PathBuf>,
+    /// Whether `target` is ours to delete on failure
+    created_target: bool,
+    committed: bool,
+    /// Whether the prefix belongs to root, so committing moves the staging dir in with sudo
+    sudo: bool,
+}
+
+impl StagedInstall {
+    /// Start installing into `target`. Backends that bake their prefix into
+    /// binaries can't be moved afterwards and install into `target` directly.
+    pub fn begin(target: &Path, relocatable: bool) -> Result<Self> {
+        let parent = target.parent().context("Install path has no parent directory")?;
+        let sudo = privilege::needs_sudo(parent)?;
+        let name = target.file_name().context("Install path has no file name")?.to_string_lossy();
+
+        if !relocatable {
+            if sudo {
+                bail!("{} can only install straight into {}; rerun with sudo or install with --user", name, parent.display());
+            }
+            fs::create_dir_all(parent)?;
+            let created_target = !target.exists();
+            fs::create_dir_all(target)?;
+            return Ok(StagedInstall { target: target.to_path_buf(), staging: None, created_target, committed: false, sudo });
+        }
+
+        // Under a root-owned prefix only the final move goes through sudo
+        let staging_parent = if sudo { std::env::temp_dir() } else { parent.to_path_buf() };
+        let package = parent.file_name().unwrap_or_default().to_string_lossy();
+        let staging = staging_parent.join(format!(".{}-{}.staging-{}", package, name, std::process::id()));
+        fs::create_dir_all(&staging_parent)?;
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(&staging)?;
+        Ok(StagedInstall { target: target.to_path_buf(), staging: Some(staging), created_target: false, committed: false, sudo })
+    }
+
+    /// Directory the backend should install into.
+    pub fn dir(&self) -> &Path {
+        self.staging.as_deref().unwrap_or(&self.target)
+    }
+
+    /// Move the staged install into place, replacing any previous install of the
+    /// same version, and return `bin_paths` pointed at the final location.
+    pub fn commit(mut self, bin_paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let staging = match self.staging.take() {
+            Some(staging) => staging,
+            None => {
+                self.committed = true;
+                return Ok(bin_paths);
+            }
+        };
+
+        if self.sudo {
+            // The tree can't be touched once root owns it, so fix it up first
+            relocate_tree(&staging, &staging, &self.target)?;
+            if let Err(e) = privilege::place(&staging, &self.target) {
+                self.staging = Some(staging);
+                return Err(e);
+            }
+            self.committed = true;
+            return Ok(moved_bins(bin_paths, &staging, &self.target));
+        }
+
+        // Swap the old install out rather than deleting it first, so there is
+        // never a moment without a working install
+        let previous = self.target.with_file_name(format!(
+            ".{}.previous-{}",
+            self.target.file_name().unwrap_or_default().to_string_lossy(),
+            std::process::id()
+        ));
+        let replacing = self.target.exists();
+        if replacing {
+            fs::rename(&self.target, &previous).context("Failed to move previous install aside")?;
+        }
+        if let Err(e) = fs::rename(&staging, &self.target) {
+            if replacing {
+                fs::rename(&previous, &self.target).ok();
+            }
+            self.staging = Some(staging);
+            return Err(e).context("Failed to move staged install into place");
+        }
+        self.committed = true;
+        if replacing {
+            fs::remove_dir_all(&previous).ok();
+        }
+
+        relocate_tree(&self.target, &staging, &self.target)?;
+        Ok(moved_bins(bin_paths, &staging, &self.target))
+    }
+}
+
+/// `bin_paths` under `staging` pointed at `target` instead.
+fn moved_bins(bin_paths: Vec<PathBuf>, staging: &Path, target: &Path) -> Vec<PathBuf> {
+    bin_paths
+        .into_iter()
+        .map(|bin| match bin.strip_prefix(staging) {
+            Ok(rest) => target.join(rest),
+            Err(_) => bin,
+        })
+        .collect()
+}
+
+impl Drop for StagedInstall {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        if let Some(staging) = &self.staging {
+            fs::remove_dir_all(staging).ok();
+        } else if self.created_target {
+            fs::remove_dir_all(&self.target).ok();
+        }
+        // Drop the package dir too if this was its first version
+        if let Some(parent) = self.target.parent() {
+            fs::remove_dir(parent).ok();
+        }
+    }
+}
+
+/// Rewrite symlinks and `#!` scripts under `dir` that still point into
+/// `old_root` after its contents moved to `new_root`.
+pub fn relocate_tree(dir: &Path, old_root: &Path, new_root: &Path) -> Result<()> {
+    if old_root == new_root {
+        return Ok(());
+    }
+    let old_prefix = old_root.to_string_lossy().into_owned();
+    let new_prefix = new_root.to_string_lossy().into_owned();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let target = fs::read_link(&path)?;
+            if let Ok(rest) = target.strip_prefix(old_root) {
+                fs::remove_file(&path)?;
+                symlink(new_root.join(rest), &path)?;
+            }
+        } else if file_type.is_dir() {
+            relocate_tree(&path, old_root, new_root)?;
+        } else if is_script(&path) {
+            if let Ok(script) = fs::read_to_string(&path) {
+                if script.contains(&old_prefix) {
+                    fs::write(&path, script.replace(&old_prefix, &new_prefix))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_script(path: &Path) -> bool {
+    let mut magic = [0u8; 2];
+    File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && &magic == b"#!"
+}
@@ -0,0 +1,309 @@
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::package::{self, OutdatedPackage, Package};
+use crate::version;
+
+/// Lines of task output kept in the log pane.
+const LOG_LINES: usize = 200;
+
+/// Results reported back to the UI thread by background work.
+enum Message {
+    Outdated(Vec<OutdatedPackage>),
+    Log(String),
+    /// A task spawned by the UI finished; the package database may have changed
+    Finished(String, bool),
+}
+
+#[derive(PartialEq)]
+enum Focus {
+    Packages,
+    Versions,
+}
+
+struct App {
+    packages: Vec<Package>,
+    outdated: HashMap<String, OutdatedPackage>,
+    checking: bool,
+    running: Option<String>,
+    log: Vec<String>,
+    focus: Focus,
+    package_state: ListState,
+    version_state: ListState,
+    sender: Sender<Message>,
+}
+
+impl App {
+    fn new(sender: Sender<Message>) -> Result<Self> {
+        let mut app = App {
+            packages: Vec::new(),
+            outdated: HashMap::new(),
+            checking: false,
+            running: None,
+            log: Vec::new(),
+            focus: Focus::Packages,
+            package_state: ListState::default(),
+            version_state: ListState::default(),
+            sender,
+        };
+        app.reload()?;
+        app.check_outdated();
+        Ok(app)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let mut packages: Vec<Package> = package::load_packages()?.into_values().collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        self.packages = packages;
+
+        let selected = self.package_state.selected().unwrap_or(0);
+        self.package_state.select((!self.packages.is_empty()).then(|| selected.min(self.packages.len() - 1)));
+        self.version_state.select(Some(0));
+        Ok(())
+    }
+
+    fn selected_package(&self) -> Option<&Package> {
+        self.package_state.selected().and_then(|i| self.packages.get(i))
+    }
+
+    /// Versions of the selected package, newest first.
+    fn selected_versions(&self) -> Vec<String> {
+        let mut versions: Vec<String> = self.selected_package().map(|p| p.versions.keys().cloned().collect()).unwrap_or_default();
+        version::sort(&mut versions);
+        versions.reverse();
+        versions
+    }
+
+    /// Look for outdated packages in the background; backends can be slow.
+    fn check_outdated(&mut self) {
+        if self.checking {
+            return;
+        }
+        self.checking = true;
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let outdated = package::load_packages().map(|packages| package::find_outdated(&packages)).unwrap_or_default();
+            sender.send(Message::Outdated(outdated)).ok();
+        });
+    }
+
+    /// Run an updater subcommand in the background, streaming its output to the log pane.
+    fn run_task(&mut self, description: String, args: Vec<String>) {
+        if let Some(running) = &self.running {
+            self.push_log(format!("Still running: {}", running));
+            return;
+        }
+        self.push_log(format!("> {}", description));
+        self.running = Some(description.clone());
+
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let success = run_updater(&args, &sender).unwrap_or_else(|e| {
+                sender.send(Message::Log(format!("{:#}", e))).ok();
+                false
+            });
+            sender.send(Message::Finished(description, success)).ok();
+        });
+    }
+
+    fn push_log(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) -> Result<()> {
+        match message {
+            Message::Outdated(outdated) => {
+                self.checking = false;
+                self.outdated = outdated.into_iter().map(|o| (o.name.clone(), o)).collect();
+            }
+            Message::Log(line) => self.push_log(line),
+            Message::Finished(description, success) => {
+                self.running = None;
+                self.push_log(format!("{} {}", description, if success { "finished" } else { "failed" }));
+                self.reload()?;
+                self.check_outdated();
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a key press, returning false when the UI should exit.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                self.focus = if self.focus == Focus::Packages { Focus::Versions } else { Focus::Packages };
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Enter if self.focus == Focus::Versions => {
+                let version = self.version_state.selected().and_then(|i| self.selected_versions().get(i).cloned());
+                if let (Some(package), Some(version)) = (self.selected_package(), version) {
+                    let name = package.name.clone();
+                    self.run_task(format!("switch {} {}", name, version), vec!["switch".into(), name, version]);
+                }
+            }
+            KeyCode::Char('u') => {
+                if let Some(package) = self.selected_package() {
+                    let name = package.name.clone();
+                    self.run_task(format!("update {}", name), vec!["update".into(), name]);
+                }
+            }
+            KeyCode::Char('U') => self.run_task("update all".to_string(), vec!["update".into()]),
+            KeyCode::Char('r') => self.check_outdated(),
+            _ => {}
+        }
+        true
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (state, len) = match self.focus {
+            Focus::Packages => (&mut self.package_state, self.packages.len()),
+            Focus::Versions => {
+                let len = self.selected_versions().len();
+                (&mut self.version_state, len)
+            }
+        };
+        if len == 0 {
+            return;
+        }
+        let current = state.selected().unwrap_or(0) as isize;
+        state.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+        if self.focus == Focus::Packages {
+            self.version_state.select(Some(0));
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(5), Constraint::Length(10), Constraint::Length(1)])
+            .split(frame.area());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[0]);
+
+        let items: Vec<ListItem> = self
+            .packages
+            .iter()
+            .map(|package| {
+                let mut spans = vec![
+                    Span::styled(package.name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!(" {}", package.active_version.as_deref().unwrap_or("-"))),
+                ];
+                if let Some(outdated) = self.outdated.get(&package.name) {
+                    spans.push(Span::styled(format!(" -> {}", outdated.latest), Style::default().fg(Color::Yellow)));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let title = if self.checking { "Packages (checking for updates...)" } else { "Packages" };
+        let packages = List::new(items)
+            .block(pane(title, self.focus == Focus::Packages))
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        frame.render_stateful_widget(packages, columns[0], &mut self.package_state);
+
+        let active = self.selected_package().and_then(|p| p.active_version.clone());
+        let items: Vec<ListItem> = self
+            .selected_versions()
+            .into_iter()
+            .map(|version| {
+                let marker = if Some(&version) == active.as_ref() { "* " } else { "  " };
+                ListItem::new(format!("{}{}", marker, version))
+            })
+            .collect();
+        let versions = List::new(items)
+            .block(pane("Installed versions", self.focus == Focus::Versions))
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        frame.render_stateful_widget(versions, columns[1], &mut self.version_state);
+
+        // Show the tail of the log that fits in the pane
+        let height = rows[1].height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self.log[self.log.len().saturating_sub(height)..].iter().map(|l| Line::raw(l.clone())).collect();
+        let title = match &self.running {
+            Some(running) => format!("Output (running {})", running),
+            None => "Output".to_string(),
+        };
+        frame.render_widget(Paragraph::new(lines).block(pane(&title, false)).wrap(Wrap { trim: false }), rows[1]);
+
+        let help = "↑/↓ move  tab switch pane  enter activate version  u update  U update all  r recheck  q quit";
+        frame.render_widget(Paragraph::new(help).style(Style::default().fg(Color::DarkGray)), rows[2]);
+    }
+}
+
+fn pane(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused { Style::default().fg(Color::Cyan) } else { Style::default() };
+    Block::default().borders(Borders::ALL).border_style(style).title(title)
+}
+
+/// Run `updater <args>` and forward each line it prints, returning whether it succeeded.
+fn run_updater(args: &[String], sender: &Sender<Message>) -> Result<bool> {
+    let updater = std::env::current_exe().context("Could not determine updater executable")?;
+    let mut child = Command::new(updater)
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start updater")?;
+
+    let stderr = child.stderr.take().context("Failed to capture output")?;
+    let stderr_sender = sender.clone();
+    let stderr_thread = thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            stderr_sender.send(Message::Log(line)).ok();
+        }
+    });
+    let stdout = child.stdout.take().context("Failed to capture output")?;
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+        sender.send(Message::Log(line)).ok();
+    }
+    stderr_thread.join().ok();
+
+    Ok(child.wait()?.success())
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, receiver: &Receiver<Message>) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        while let Ok(message) = receiver.try_recv() {
+            app.handle_message(message)?;
+        }
+
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Interactive dashboard for browsing, switching and updating packages.
+pub fn run() -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut app = App::new(sender)?;
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, &receiver);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,600 @@
+//! Checking that downloaded artifacts are what upstream published.
+//!
+//! Artifacts that come with nothing to check them against are trusted on
+//! first use: the first download of a versioned artifact pins its SHA-256,
+//! and any later download of the same artifact has to match it. A mismatch
+//! is warned about, or fails the install in strict mode.
+//!
+//! Sources configured under `[[verify.cosign]]` have to be signed with
+//! cosign instead: artifacts by a Sigstore bundle or a `.sig` published next
+//! to them, images by a signature in their registry. The `cosign` CLI checks
+//! the signer's identity and issuer, or key, and that the signature was
+//! logged in Rekor. Sources under `[[verify.minisign]]` have to come with a
+//! minisign `.minisig`, or a signify `.sig`, made with the configured key.
+//!
+//! Artifacts from GitHub releases are checked against the SLSA provenance
+//! GitHub's artifact attestations publish for them, proving which repository's
+//! workflow built them. The outcome is recorded with the installed version,
+//! and `--require-provenance` refuses anything without verified provenance.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::backends::run_command;
+use crate::config::{self, Config, CosignRule, MinisignRule};
+use crate::download;
+use crate::error::Error;
+use crate::net::{self, SendRetrying};
+use crate::package;
+use crate::sbom;
+
+/// Set by `--strict`, and inherited by child updater processes.
+pub const STRICT_VAR: &str = "UPDATER_STRICT";
+
+/// Set by `--require-provenance`, and inherited by child updater processes.
+pub const REQUIRE_PROVENANCE_VAR: &str = "UPDATER_REQUIRE_PROVENANCE";
+
+/// Signer of GitHub Actions' keyless signatures.
+const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// Serializes changes to the pins between threads of one process.
+static PINS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Provenance checked by backends, by package, until the install records it.
+static CHECKED: Mutex<BTreeMap<String, Provenance>> = Mutex::new(BTreeMap::new());
+
+/// Artifact digests `apply --locked` holds installs to, by package.
+static EXPECTED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+pub fn is_strict() -> bool {
+    std::env::var(STRICT_VAR).is_ok_and(|v| !v.is_empty() && v != "0" && v != "false")
+}
+
+pub fn set_strict() {
+    std::env::set_var(STRICT_VAR, "1");
+}
+
+pub fn requires_provenance() -> bool {
+    std::env::var(REQUIRE_PROVENANCE_VAR).is_ok_and(|v| !v.is_empty() && v != "0" && v != "false")
+}
+
+pub fn set_require_provenance() {
+    std::env::set_var(REQUIRE_PROVENANCE_VAR, "1");
+}
+
+/// The checksum an artifact had when it was first downloaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Pin {
+    sha256: String,
+    package: String,
+    version: String,
+    first_seen: String,
+}
+
+/// Next to the package database, so every profile and prefix pins its own downloads.
+fn pins_path() -> PathBuf {
+    package::get_package_db_path().with_file_name("pins.json")
+}
+
+fn load_pins() -> Result<BTreeMap<String, Pin>> {
+    let path = pins_path();
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let data = fs::read_to_string(&path).context("Failed to read checksum pins")?;
+    serde_json::from_str(&data).context("Failed to parse checksum pins")
+}
+
+fn save_pins(pins: &BTreeMap<String, Pin>) -> Result<()> {
+    let path = pins_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(pins)?).context("Failed to write checksum pins")
+}
+
+/// Pin the checksum of `artifact`, downloaded from `url` for `package`
+/// `version`, or check it against the pin from its first download.
+/// Pins are kept under `upstream`, the URL before mirrors rewrote it, so
+/// switching mirrors doesn't start over. Only versioned URLs should be
+/// pinned, ones that always serve the same file.
+pub fn trust_on_first_use(upstream: &str, url: &str, artifact: &Path, package: &str, version: &str) -> Result<()> {
+    let checksum = download::sha256_file(artifact)?;
+    let _guard = PINS_LOCK.lock().unwrap();
+    let mut pins = load_pins()?;
+    let pin = match pins.get(upstream) {
+        Some(pin) => pin,
+        None => {
+            debug!("Pinning {} to sha256 {}", upstream, checksum);
+            pins.insert(
+                upstream.to_string(),
+                Pin { sha256: checksum, package: package.to_string(), version: version.to_string(), first_seen: chrono::Local::now().to_rfc3339() },
+            );
+            return save_pins(&pins);
+        }
+    };
+    if pin.sha256.eq_ignore_ascii_case(&checksum) {
+        return Ok(());
+    }
+
+    let message = format!(
+        "{} {} from {} changed since it was first downloaded on {}: sha256 was {}, now {}",
+        pin.package, pin.version, upstream, pin.first_seen, pin.sha256, checksum
+    );
+    // The changed download mustn't be served from the cache next time
+    download::forget(url)?;
+    if is_strict() {
+        return Err(Error::Verification(message).into());
+    }
+    warn!("{} {}", "WARNING:".red().bold(), message.red());
+    warn!(
+        "{}",
+        format!("This may be a tampered download. If upstream republished it on purpose, remove its entry from {}", pins_path().display()).red()
+    );
+    Ok(())
+}
+
+fn cosign_rule(location: &str) -> Result<Option<CosignRule>> {
+    Ok(config::load_config()?.cosign_rule(location).cloned())
+}
+
+/// Arguments constraining who may have signed, for `cosign verify` and `verify-blob`.
+fn signer_args(rule: &CosignRule) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    if let Some(key) = &rule.key {
+        args.push("--key".to_string());
+        args.push(key.display().to_string());
+    } else {
+        match (&rule.identity, &rule.identity_regexp) {
+            (Some(identity), _) => args.extend(["--certificate-identity".to_string(), identity.clone()]),
+            (None, Some(regexp)) => args.extend(["--certificate-identity-regexp".to_string(), regexp.clone()]),
+            (None, None) => bail!("The cosign rule for {} needs a key, identity or identity_regexp", rule.source),
+        }
+        match (&rule.issuer, &rule.issuer_regexp) {
+            (Some(issuer), _) => args.extend(["--certificate-oidc-issuer".to_string(), issuer.clone()]),
+            (None, Some(regexp)) => args.extend(["--certificate-oidc-issuer-regexp".to_string(), regexp.clone()]),
+            (None, None) => bail!("The cosign rule for {} needs an issuer or issuer_regexp", rule.source),
+        }
+    }
+    if rule.skip_tlog {
+        args.push("--insecure-ignore-tlog".to_string());
+    }
+    Ok(args)
+}
+
+/// Run a signature checker, `tool`, turning a rejected signature into a verification error.
+fn run_verifier(tool: &str, cmd: &mut Command, subject: &str) -> Result<()> {
+    if which::which(cmd.get_program()).is_err() {
+        return Err(Error::Verification(format!("{} has to be verified with {}, which isn't installed", subject, tool)).into());
+    }
+    run_command(cmd).map_err(|e| Error::Verification(format!("{} signature of {} didn't verify: {:#}", tool, subject, e)))?;
+    info!("{} {} {}", "Verified".green(), tool, format!("signature of {}", subject).green());
+    Ok(())
+}
+
+/// A file next to `artifact` named after it, for signatures and keys that go with it.
+fn side_file(artifact: &Path, suffix: &str) -> PathBuf {
+    artifact.with_file_name(format!("{}{}", artifact.file_name().unwrap_or_default().to_string_lossy(), suffix))
+}
+
+/// Download `url` to `dest`, `false` when there's nothing there.
+fn fetch_optional(url: &str, dest: &Path) -> Result<bool> {
+    let response = net::get(url).send_retrying().with_context(|| format!("Failed to request {}", url))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    let mut response = response.error_for_status().with_context(|| format!("Failed to fetch {}", url))?;
+    let mut file = File::create(dest)?;
+    io::copy(&mut response, &mut file).with_context(|| format!("Failed to download {}", url))?;
+    Ok(true)
+}
+
+/// The body at `url`, `None` when there's nothing there.
+fn fetch_text_optional(url: &str) -> Result<Option<String>> {
+    let response = net::get(url).send_retrying().with_context(|| format!("Failed to request {}", url))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let response = response.error_for_status().with_context(|| format!("Failed to fetch {}", url))?;
+    Ok(Some(response.text().with_context(|| format!("Failed to read {}", url))?))
+}
+
+/// The SHA-256 upstream published for the artifact at `url`, in a
+/// `<artifact>.sha256` next to it or a `SHA256SUMS` in the same directory.
+fn published_sha256(url: &str) -> Result<Option<String>> {
+    if let Some(text) = fetch_text_optional(&format!("{}.sha256", url))? {
+        return Ok(text.split_whitespace().next().map(str::to_lowercase));
+    }
+    let (dir, file_name) = match url.rsplit_once('/') {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+    let sums = fetch_text_optional(&format!("{}/SHA256SUMS", dir))?.unwrap_or_default();
+    Ok(sums.lines().find_map(|line| {
+        let (sha256, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file_name).then(|| sha256.to_lowercase())
+    }))
+}
+
+/// The SHA-256 the artifact `package` downloads from `url` must have: the
+/// locked one, the one pinned on first use, or the one upstream published.
+pub fn expected_sha256(package: &str, url: &str) -> Result<Option<String>> {
+    if let Some(expected) = EXPECTED.lock().unwrap().get(package) {
+        return Ok(Some(expected.clone()));
+    }
+    if let Some(pin) = load_pins()?.remove(url) {
+        return Ok(Some(pin.sha256));
+    }
+    published_sha256(url)
+}
+
+/// Check `artifact`, downloaded from `url`, against the cosign signature
+/// published next to it, when `rule` says it has to be signed.
+fn cosign_artifact(rule: Option<CosignRule>, url: &str, artifact: &Path) -> Result<()> {
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return Ok(()),
+    };
+    let mut cmd = Command::new("cosign");
+    cmd.arg("verify-blob").args(signer_args(&rule)?);
+
+    // Bundles carry the signature, certificate and Rekor entry in one file
+    let bundle = side_file(artifact, ".sigstore.json");
+    let legacy_bundle = side_file(artifact, ".bundle");
+    let signature = side_file(artifact, ".sig");
+    if fetch_optional(&format!("{}.sigstore.json", url), &bundle)? {
+        cmd.arg("--new-bundle-format").arg("--bundle").arg(&bundle);
+    } else if fetch_optional(&format!("{}.bundle", url), &legacy_bundle)? {
+        cmd.arg("--bundle").arg(&legacy_bundle);
+    } else if fetch_optional(&format!("{}.sig", url), &signature)? {
+        cmd.arg("--signature").arg(&signature);
+        let certificate = side_file(artifact, ".pem");
+        if rule.key.is_none() {
+            if !fetch_optional(&format!("{}.pem", url), &certificate)? {
+                return Err(Error::Verification(format!("{} has a signature but no certificate at {}.pem", url, url)).into());
+            }
+            cmd.arg("--certificate").arg(&certificate);
+        }
+    } else {
+        return Err(Error::Verification(format!("{} has to be signed with cosign, but no signature was published next to it", url)).into());
+    }
+    cmd.arg(artifact);
+    run_verifier("cosign", &mut cmd, url)
+}
+
+/// Decode standard, padded base64.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// The key number a minisign or signify signature or public key names, from
+/// the file's contents or just its base64 line.
+fn key_id(text: &str) -> Option<[u8; 8]> {
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))?;
+    let bytes = decode_base64(line)?;
+    // Both formats start with the algorithm, `Ed`, or `ED` for prehashed
+    // minisign signatures, followed by the key number
+    match bytes.get(..10)? {
+        [b'E', b'd' | b'D', id @ ..] => id.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// Check `artifact`, downloaded from `url`, against the minisign or signify
+/// signature published next to it, when `rule` says it has to be signed.
+fn minisign_artifact(rule: Option<MinisignRule>, url: &str, artifact: &Path) -> Result<()> {
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return Ok(()),
+    };
+    let (tool, suffix) = if rule.signify { ("signify", ".sig") } else { ("minisign", ".minisig") };
+    let signature = side_file(artifact, suffix);
+    if !fetch_optional(&format!("{}{}", url, suffix), &signature)? {
+        return Err(Error::Verification(format!("{} has to be signed with {}, but no {} was published next to it", url, tool, suffix)).into());
+    }
+    let key = match (&rule.key, &rule.public_key) {
+        (Some(key), _) => key.clone(),
+        (None, Some(public_key)) => {
+            // signify only reads keys from files
+            let key = side_file(artifact, ".pub");
+            fs::write(&key, format!("untrusted comment: public key for {}\n{}\n", rule.source, public_key.trim()))?;
+            key
+        }
+        (None, None) => bail!("The minisign rule for {} needs a public_key or key", rule.source),
+    };
+    // The tools only report that verification failed, so name the common case
+    let signed_with = fs::read_to_string(&signature).ok().as_deref().and_then(key_id);
+    if let (Some(expected), Some(actual)) = (fs::read_to_string(&key).ok().as_deref().and_then(key_id), signed_with) {
+        if expected != actual {
+            return Err(Error::Verification(format!("{} is signed with a different {} key than the one configured for {}", url, tool, rule.source)).into());
+        }
+    }
+    // Debian ships signify as signify-openbsd
+    let program = match tool {
+        "signify" if which::which("signify").is_err() && which::which("signify-openbsd").is_ok() => "signify-openbsd",
+        tool => tool,
+    };
+    let mut cmd = Command::new(program);
+    cmd.args(["-V", "-q", "-p"]).arg(&key).arg("-x").arg(&signature).arg("-m").arg(artifact);
+    run_verifier(tool, &mut cmd, url)
+}
+
+/// Check `artifact`, downloaded from `url`, against every signature its
+/// source is configured to need. Rules are matched against `upstream`, the
+/// URL before mirrors rewrote it, so a mirror can't opt out of them; the
+/// signatures are fetched from next to `url`.
+pub fn signatures(upstream: &str, url: &str, artifact: &Path) -> Result<()> {
+    check_signatures(&config::load_config()?, upstream, url, artifact)
+}
+
+fn check_signatures(config: &Config, upstream: &str, url: &str, artifact: &Path) -> Result<()> {
+    cosign_artifact(config.cosign_rule(upstream).cloned(), url, artifact)?;
+    minisign_artifact(config.minisign_rule(upstream).cloned(), url, artifact)
+}
+
+/// Check the image or artifact `reference`, pinned by digest as
+/// `registry/repository@sha256:…`, against its signature in the registry
+/// when a `[[verify.cosign]]` rule covers it.
+pub fn cosign_image(reference: &str) -> Result<()> {
+    let rule = match cosign_rule(reference)? {
+        Some(rule) => rule,
+        None => return Ok(()),
+    };
+    let mut cmd = Command::new("cosign");
+    cmd.arg("verify").args(signer_args(&rule)?).arg(reference);
+    run_verifier("cosign", &mut cmd, reference)
+}
+
+/// How an installed version's build provenance checked out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub status: ProvenanceStatus,
+    /// Repository whose workflow the provenance has to come from
+    pub repository: String,
+    pub checked: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProvenanceStatus {
+    Verified,
+    /// Upstream published no attestation for the artifact
+    Missing,
+    /// Attestations were published, but cosign wasn't there to check them
+    Unchecked,
+}
+
+impl fmt::Display for ProvenanceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProvenanceStatus::Verified => write!(f, "verified"),
+            ProvenanceStatus::Missing => write!(f, "none published"),
+            ProvenanceStatus::Unchecked => write!(f, "not checked, cosign is missing"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Attestations {
+    #[serde(default)]
+    attestations: Vec<Attestation>,
+}
+
+#[derive(Deserialize)]
+struct Attestation {
+    /// Sigstore bundle with the signed provenance statement
+    bundle: serde_json::Value,
+}
+
+/// The `owner/repo` a GitHub release download URL belongs to.
+pub fn github_release_repo(url: &str) -> Option<String> {
+    let path = url.strip_prefix("https://github.com/")?;
+    let mut segments = path.split('/');
+    let (owner, repo) = (segments.next()?, segments.next()?);
+    (segments.next() == Some("releases") && segments.next() == Some("download")).then(|| format!("{}/{}", owner, repo))
+}
+
+/// Check `artifact` against the SLSA provenance GitHub attests for it, which
+/// has to come from a workflow of `repository`, and keep the outcome for
+/// `package`'s install to record. Fails when attestations exist but none verifies.
+pub fn check_provenance(package: &str, repository: &str, artifact: &Path) -> Result<()> {
+    let digest = download::sha256_file(artifact)?;
+    let url = format!("https://api.github.com/repos/{}/attestations/sha256:{}", repository, digest);
+    let response = net::get(&config::mirror_url("github", &url)?).send_retrying().with_context(|| format!("Failed to request {}", url))?;
+    let attestations = if response.status() == reqwest::StatusCode::NOT_FOUND {
+        Vec::new()
+    } else {
+        let list: Attestations = response.error_for_status().with_context(|| format!("Failed to fetch {}", url))?.json().context("Failed to parse attestations")?;
+        list.attestations
+    };
+
+    let status = if attestations.is_empty() {
+        debug!("{} publishes no provenance for {}", repository, artifact.display());
+        ProvenanceStatus::Missing
+    } else if which::which("cosign").is_err() {
+        warn!("{}", format!("Install cosign to check the provenance {} publishes for {}", repository, package).yellow());
+        ProvenanceStatus::Unchecked
+    } else {
+        let identity = format!("^https://github\\.com/{}/", repository.replace('.', "\\."));
+        let verified = attestations.iter().enumerate().any(|(i, attestation)| {
+            let bundle = side_file(artifact, &format!(".attestation{}.json", i));
+            let result = fs::write(&bundle, attestation.bundle.to_string()).map_err(anyhow::Error::from).and_then(|_| {
+                run_command(
+                    Command::new("cosign")
+                        .args(["verify-blob-attestation", "--new-bundle-format", "--type", "slsaprovenance1"])
+                        .arg("--bundle")
+                        .arg(&bundle)
+                        .args(["--certificate-identity-regexp", &identity, "--certificate-oidc-issuer", GITHUB_ACTIONS_ISSUER])
+                        .arg(artifact),
+                )
+            });
+            fs::remove_file(&bundle).ok();
+            // Attestations of other kinds, like SBOMs, fail to verify as provenance
+            result.map_err(|e| debug!("Attestation {} of {} didn't verify: {:#}", i, package, e)).is_ok()
+        });
+        if !verified {
+            return Err(Error::Verification(format!("None of the provenance attestations for {} verified as built by {}", package, repository)).into());
+        }
+        info!("{} {} {}", "Verified provenance of".green(), package, format!("(built by {})", repository).normal());
+        ProvenanceStatus::Verified
+    };
+    let provenance = Provenance { status, repository: repository.to_string(), checked: chrono::Local::now().to_rfc3339() };
+    CHECKED.lock().unwrap().insert(package.to_string(), provenance);
+    Ok(())
+}
+
+/// The provenance checked while installing `package`, if its backend could
+/// check any. With `--require-provenance`, anything short of verified fails.
+pub fn take_provenance(package: &str) -> Result<Option<Provenance>> {
+    let provenance = CHECKED.lock().unwrap().remove(package);
+    if !requires_provenance() {
+        return Ok(provenance);
+    }
+    let reason = match &provenance {
+        Some(provenance) if provenance.status == ProvenanceStatus::Verified => return Ok(Some(provenance.clone())),
+        Some(provenance) => format!("has provenance from {}: {}", provenance.repository, provenance.status),
+        None => "isn't from a source publishing provenance".to_string(),
+    };
+    Err(Error::Verification(format!("{} {}, and --require-provenance is set", package, reason)).into())
+}
+
+/// Hold installs of `package` in this process to an artifact with this SHA-256.
+pub fn expect_artifact(package: &str, sha256: &str) {
+    EXPECTED.lock().unwrap().insert(package.to_string(), sha256.to_string());
+}
+
+/// Fail before a staged install of `package` in `install_dir` is moved into
+/// place when its artifact isn't the expected one. Backends that keep no
+/// artifact are only checked file by file afterwards.
+pub fn check_expected_artifact(package: &str, install_dir: &Path) -> Result<()> {
+    let expected = match EXPECTED.lock().unwrap().get(package) {
+        Some(expected) => expected.clone(),
+        None => return Ok(()),
+    };
+    match sbom::kept_artifact_sha256(install_dir) {
+        Some(actual) if actual != expected => Err(Error::Verification(format!(
+            "{} downloaded an artifact with SHA-256 {}, but {} was locked",
+            package, actual, expected
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server answering every request with 404, standing in for a mirror
+    /// that doesn't carry signatures.
+    fn empty_mirror() -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                request.respond(tiny_http::Response::empty(404)).ok();
+            }
+        });
+        format!("http://{}/", address)
+    }
+
+    #[test]
+    fn mirrored_downloads_still_need_their_signatures() {
+        let mirror = empty_mirror();
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [[mirrors]]
+            from = "https://github.com/"
+            to = "{}"
+
+            [[verify.cosign]]
+            source = "https://github.com/org/"
+            identity = "release@org.example"
+            issuer = "https://token.actions.githubusercontent.com"
+            "#,
+            mirror
+        ))
+        .unwrap();
+        let upstream = "https://github.com/org/tool/releases/download/v1.0/tool.tar.gz";
+        let url = config.mirror_url("url", upstream);
+        assert!(url.starts_with(&mirror));
+
+        let artifact = std::env::temp_dir().join(format!("updater-mirrored-{}.tar.gz", std::process::id()));
+        fs::write(&artifact, "tool").unwrap();
+        let error = check_signatures(&config, upstream, &url, &artifact).unwrap_err();
+        assert!(error.to_string().contains("has to be signed with cosign"), "{:#}", error);
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [[mirrors]]
+            from = "https://github.com/"
+            to = "{}"
+
+            [[verify.minisign]]
+            source = "https://github.com/org/"
+            public_key = "{}"
+            "#,
+            mirror, PUBLIC_KEY
+        ))
+        .unwrap();
+        let error = check_signatures(&config, upstream, &url, &artifact).unwrap_err();
+        assert!(error.to_string().contains("has to be signed with minisign"), "{:#}", error);
+        fs::remove_file(&artifact).ok();
+    }
+
+    /// minisign's example public key, key ID E7620F1842B4E81F
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+
+    #[test]
+    fn key_ids_come_from_keys_and_signatures() {
+        let id = [0x1f, 0xe8, 0xb4, 0x42, 0x18, 0x0f, 0x62, 0xe7];
+        assert_eq!(key_id(PUBLIC_KEY), Some(id));
+        let key_file = format!("untrusted comment: minisign public key E7620F1842B4E81F\n{}\n", PUBLIC_KEY);
+        assert_eq!(key_id(&key_file), Some(id));
+
+        let signature = "untrusted comment: signature from minisign secret key\n\
+            RWQf6LRCGA9i5wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\n\
+            trusted comment: timestamp:1700000000\n";
+        assert_eq!(key_id(signature), Some(id));
+        let prehashed = "RUQBAgMEBQYHCAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+        assert_eq!(key_id(prehashed), Some([1, 2, 3, 4, 5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn malformed_signatures_have_no_key_id() {
+        assert_eq!(key_id(""), None);
+        assert_eq!(key_id("untrusted comment: nothing else\n"), None);
+        assert_eq!(key_id("not base64!"), None);
+        assert_eq!(key_id("RWQf6LRC"), None);
+        // Base64 of `XX` and a key number: not an ed25519 key
+        assert_eq!(key_id("WFgf6LRCGA9i5w=="), None);
+        assert_eq!(decode_base64("aGVsbG8="), Some(b"hello".to_vec()));
+    }
+}
@@ -0,0 +1,244 @@
+use clap::ValueEnum;
+use anyhow::{Context, Result};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Release channel a package is subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Final releases only
+    #[default]
+    Stable,
+    /// Final releases plus alpha, beta and release candidates
+    Beta,
+    /// Everything, including nightly and development builds
+    Nightly,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::Stable => "stable",
+            Channel::Beta => "beta",
+            Channel::Nightly => "nightly",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ReleaseKind {
+    Stable,
+    Prerelease,
+    Nightly,
+}
+
+impl Channel {
+    /// Whether a version string belongs on this channel.
+    pub fn includes(&self, version: &str) -> bool {
+        let max_kind = match self {
+            Channel::Stable => ReleaseKind::Stable,
+            Channel::Beta => ReleaseKind::Prerelease,
+            Channel::Nightly => ReleaseKind::Nightly,
+        };
+        release_kind(version) <= max_kind
+    }
+}
+
+fn release_kind(version: &str) -> ReleaseKind {
+    let lower = version.to_lowercase();
+    if ["nightly", "dev", "snapshot", "canary"].iter().any(|tag| lower.contains(tag)) {
+        return ReleaseKind::Nightly;
+    }
+
+    let prerelease = match parse(version) {
+        Some(v) => !v.pre.is_empty(),
+        None => ["alpha", "beta", "rc", "pre"].iter().any(|tag| lower.contains(tag)),
+    };
+    if prerelease {
+        ReleaseKind::Prerelease
+    } else {
+        ReleaseKind::Stable
+    }
+}
+
+/// Leniently parse a version string as semver, accepting `v` prefixes,
+/// distro epochs and missing minor/patch components.
+pub fn parse(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split_once(':').map_or(version, |(_, rest)| rest);
+
+    if let Ok(v) = Version::parse(version) {
+        return Some(v);
+    }
+
+    // Pad `1` and `1.2` out to a full `major.minor.patch`
+    let (core, rest) = match version.find(['-', '+']) {
+        Some(idx) => version.split_at(idx),
+        None => (version, ""),
+    };
+    let parts = core.split('.').count();
+    if parts == 0 || parts > 2 || !core.split('.').all(|part| part.chars().all(|c| c.is_ascii_digit()) && !part.is_empty()) {
+        return None;
+    }
+    let padded = format!("{}{}{}", core, ".0".repeat(3 - parts), rest);
+    Version::parse(&padded).ok()
+}
+
+/// Order two version strings, falling back to comparing numeric segments
+/// when they are not semver.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (parse(a), parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => {
+            let segments = |v: &str| -> Vec<u64> {
+                v.split(|c: char| !c.is_ascii_digit())
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| s.parse().ok())
+                    .collect()
+            };
+            segments(a).cmp(&segments(b)).then_with(|| a.cmp(b))
+        }
+    }
+}
+
+/// Sort versions in place from oldest to newest.
+pub fn sort(versions: &mut [String]) {
+    versions.sort_by(|a, b| compare(a, b));
+}
+
+/// Pick the newest version available on a channel.
+pub fn latest(versions: &[String], channel: Channel) -> Option<String> {
+    // Projects publishing a rolling `nightly` tag expect it to be used as-is
+    if channel == Channel::Nightly {
+        if let Some(tag) = versions.iter().find(|v| v.eq_ignore_ascii_case("nightly")) {
+            return Some(tag.clone());
+        }
+    }
+
+    versions
+        .iter()
+        .filter(|v| channel.includes(v))
+        .max_by(|a, b| compare(a, b))
+        .cloned()
+}
+
+/// Whether `version` is a tag like `nightly` that is moved to every new build
+/// rather than naming one release.
+pub fn is_rolling(version: &str) -> bool {
+    release_kind(version) == ReleaseKind::Nightly && !version.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Whether a requested version is a constraint such as `^1.4`, `~1.4` or
+/// `>=1, <2` rather than an exact version.
+pub fn is_constraint(spec: &str) -> bool {
+    let spec = spec.trim();
+    spec.starts_with(['^', '~', '>', '<', '=', '*']) || spec.contains(',')
+}
+
+pub fn parse_constraint(spec: &str) -> Result<VersionReq> {
+    VersionReq::parse(spec.trim()).with_context(|| format!("Invalid version constraint: {}", spec))
+}
+
+/// Pick the newest version on a channel that satisfies a constraint.
+pub fn best_match(versions: &[String], constraint: &VersionReq, channel: Channel) -> Option<String> {
+    let matching: Vec<String> = versions
+        .iter()
+        .filter(|v| parse(v).is_some_and(|parsed| constraint.matches(&parsed)))
+        .cloned()
+        .collect();
+    latest(&matching, channel)
+}
+
+/// How far an unattended update may move a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateLevel {
+    #[default]
+    Off,
+    Patch,
+    Minor,
+    Major,
+}
+
+impl UpdateLevel {
+    /// Whether moving from `current` to `target` stays within this level.
+    pub fn allows(&self, current: &str, target: &str) -> bool {
+        let (current, target) = match (parse(current), parse(target)) {
+            (Some(current), Some(target)) => (current, target),
+            _ => return *self == UpdateLevel::Major,
+        };
+
+        match self {
+            UpdateLevel::Off => false,
+            UpdateLevel::Patch => current.major == target.major && current.minor == target.minor,
+            UpdateLevel::Minor => current.major == target.major,
+            UpdateLevel::Major => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(list: &[&str]) -> Vec<String> {
+        list.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn parsing_is_lenient() {
+        assert_eq!(parse("v1.2"), Version::parse("1.2.0").ok());
+        assert_eq!(parse("2:1.4.3-1"), Version::parse("1.4.3-1").ok());
+        assert_eq!(parse("7"), Version::parse("7.0.0").ok());
+        assert_eq!(parse("1.2-rc1"), Version::parse("1.2.0-rc1").ok());
+        assert_eq!(parse("nightly"), None);
+        assert_eq!(parse("1.2.3.4"), None);
+        assert_eq!(compare("1.10.0", "1.9.0"), Ordering::Greater);
+        assert_eq!(compare("r12", "r9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn channels_filter_releases() {
+        let all = versions(&["1.0.0", "1.1.0-beta.1", "1.2.0-dev", "nightly"]);
+        assert_eq!(latest(&all, Channel::Stable).as_deref(), Some("1.0.0"));
+        assert_eq!(latest(&all, Channel::Beta).as_deref(), Some("1.1.0-beta.1"));
+        assert_eq!(latest(&all, Channel::Nightly).as_deref(), Some("nightly"));
+        assert_eq!(latest(&all[..3], Channel::Nightly).as_deref(), Some("1.2.0-dev"));
+        assert!(Channel::Beta.includes("2.0rc1"));
+        assert!(!Channel::Stable.includes("2.0-rc.1"));
+        assert!(is_rolling("nightly") && is_rolling("Canary"));
+        assert!(!is_rolling("nightly-2024-05-01") && !is_rolling("1.0.0"));
+    }
+
+    #[test]
+    fn constraints_pick_the_newest_match() {
+        let all = versions(&["v1.3.9", "1.4.0", "1.4.7", "1.5.0-rc.1", "2.0.0"]);
+        let best = |spec: &str, channel| best_match(&all, &parse_constraint(spec).unwrap(), channel);
+        assert_eq!(best("~1.4", Channel::Stable).as_deref(), Some("1.4.7"));
+        assert_eq!(best("^1", Channel::Stable).as_deref(), Some("1.4.7"));
+        assert_eq!(best(">=1.5.0-rc.1, <2", Channel::Beta).as_deref(), Some("1.5.0-rc.1"));
+        assert_eq!(best(">=3", Channel::Nightly), None);
+        assert!(is_constraint("^1.4") && is_constraint(">=1, <2") && is_constraint("*"));
+        assert!(!is_constraint("1.4.0") && !is_constraint("latest"));
+        assert!(parse_constraint("not a constraint").is_err());
+    }
+
+    #[test]
+    fn update_levels_bound_the_move() {
+        assert!(UpdateLevel::Patch.allows("1.4.0", "1.4.9"));
+        assert!(!UpdateLevel::Patch.allows("1.4.0", "1.5.0"));
+        assert!(UpdateLevel::Minor.allows("1.4.0", "1.9.0"));
+        assert!(!UpdateLevel::Minor.allows("1.4.0", "2.0.0"));
+        assert!(UpdateLevel::Major.allows("1.4.0", "2.0.0"));
+        assert!(!UpdateLevel::Off.allows("1.4.0", "1.4.1"));
+        // Nothing but major updates may move between versions that aren't semver
+        assert!(!UpdateLevel::Minor.allows("nightly", "1.0.0"));
+        assert!(UpdateLevel::Major.allows("nightly", "1.0.0"));
+    }
+}